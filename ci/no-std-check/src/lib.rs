@@ -0,0 +1,55 @@
+//! Compile check of the default (non-`concurrent`) build on a `no_std` MCU target
+//!
+//! CI runs `cargo check --target thumbv7em-none-eabihf` on this crate, so code generated
+//! by `state_machine!` that needs atomics the target lacks (e.g., 64-bit ones on
+//! Cortex-M) fails the build. Each directive with its own statics is declared once.
+
+#![no_std]
+
+use typed_fsm::{state_machine, Bookkeeping, Transition};
+
+/// Stand-in for the Cortex-M `DWT` cycle counter
+fn cycles() -> u64 {
+    0
+}
+
+pub struct Context {
+    pub fsm: Bookkeeping,
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Tick,
+    Fault,
+}
+
+state_machine! {
+    Name: Controller,
+    Context: Context,
+    Event: Event,
+    Metrics: events,
+    MaxLatency: cycles,
+    TransitionTable: [
+        Running + Fault => Halted,
+    ],
+    CountTransitions: true,
+    Watchdog: 100,
+    OnStuck: |_ctx, _state_name| {},
+    Bookkeeping: fsm,
+
+    States: {
+        Running => {
+            retry: { max: 3, on_exhausted: Halted },
+            process: |_ctx, evt| {
+                match evt {
+                    Event::Tick => Transition::Retry,
+                    Event::Fault => Transition::To(Controller::Halted),
+                }
+            }
+        },
+
+        Halted => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
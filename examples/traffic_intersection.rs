@@ -15,6 +15,7 @@
 //!   - Shared state: `Arc<Mutex<IntersectionState>>`
 //!   - Event distribution: `mpsc::channel`
 //!   - Safety coordination: prevents conflicting green lights
+//!   - Safety invariant: `Invariant` asserts it after every transition
 //!
 //! ## Key Concepts
 //!
@@ -93,6 +94,12 @@ state_machine! {
     Context: NSLightContext,
     Event: TrafficEvent,
 
+    // Checked after every transition: the two directions are never green together
+    Invariant: |ctx, _state_name| -> bool {
+        let state = ctx.intersection.lock().unwrap();
+        !(state.ns_is_green && state.ew_is_green)
+    },
+
     States: {
         Red => {
             entry: |ctx| {
@@ -181,6 +188,12 @@ state_machine! {
     Context: EWLightContext,
     Event: TrafficEvent,
 
+    // Checked after every transition: the two directions are never green together
+    Invariant: |ctx, _state_name| -> bool {
+        let state = ctx.intersection.lock().unwrap();
+        !(state.ns_is_green && state.ew_is_green)
+    },
+
     States: {
         Red => {
             entry: |ctx| {
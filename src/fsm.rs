@@ -23,929 +23,8062 @@
 //! ```
 
 // Logging support (optional) - Internal macro for code generation
+//
+// Call sites pass the `LogLevels` list and describe *what* happened (`init`, `reentry`,
+// `entry`, `exit`, `transition`, `stayed`); each backend decides how to format it. If both
+// `logging` and `defmt` are enabled, `logging` takes precedence.
+//
+// With `concurrent`, no call site may sit inside a `critical_section::with` block: those
+// only touch the queue and counters, so formatting never runs with interrupts masked
+// and a logger may enter a critical section of its own.
 #[cfg(feature = "logging")]
 #[macro_export]
 #[doc(hidden)]
 macro_rules! __fsm_log {
-    ($($arg:tt)*) => {
-        log::info!($($arg)*);
+    (enabled) => {
+        true
+    };
+    ([$($levels:tt)*] init: $name:ident, $state:expr) => {
+        $crate::__fsm_log_at!(log entry [$($levels)*] (
+            "[{}] init() -> {:?}",
+            stringify!($name),
+            $state
+        ));
+    };
+    ([$($levels:tt)*] reentry: $name:ident, $state:expr) => {
+        $crate::__fsm_log_at!(log entry [$($levels)*] (
+            "[{}] reentry() -> {:?}",
+            stringify!($name),
+            $state
+        ));
+    };
+    ([$($levels:tt)*] entry: $name:ident, $state:expr) => {
+        $crate::__fsm_log_at!(log entry [$($levels)*] (
+            "[{}] {:?}.entry()",
+            stringify!($name),
+            $state
+        ));
+    };
+    ([$($levels:tt)*] exit: $name:ident, $state:expr) => {
+        $crate::__fsm_log_at!(log exit [$($levels)*] (
+            "[{}] {:?}.exit()",
+            stringify!($name),
+            $state
+        ));
+    };
+    ([$($levels:tt)*] transition: $name:ident, $from:expr, $event:expr, $to:expr) => {
+        $crate::__fsm_log_at!(log transition [$($levels)*] (
+            "[{}] {:?} + {:?} -> {:?}",
+            stringify!($name),
+            $from,
+            $event,
+            $to
+        ));
+    };
+    ([$($levels:tt)*] stayed: $name:ident, $state:expr, $event:expr, $kind:literal) => {
+        $crate::__fsm_log_at!(log process [$($levels)*] (
+            concat!("[{}] {:?} + {:?} -> ", $kind, " (stayed)"),
+            stringify!($name),
+            $state,
+            $event
+        ));
     };
 }
 
-#[cfg(not(feature = "logging"))]
+// defmt backend: states and events don't implement `defmt::Format`, so only
+// state names (`current_state_name()`) are logged.
+#[cfg(all(feature = "defmt", not(feature = "logging")))]
 #[macro_export]
 #[doc(hidden)]
 macro_rules! __fsm_log {
-    ($($arg:tt)*) => {
-        // When logging feature disabled, generate no code at all (true zero-cost)
+    (enabled) => {
+        true
+    };
+    ([$($levels:tt)*] init: $name:ident, $state:expr) => {
+        $crate::__fsm_log_at!(defmt entry [$($levels)*] (
+            "[{=str}] init() -> {=str}",
+            stringify!($name),
+            $state.current_state_name()
+        ));
+    };
+    ([$($levels:tt)*] reentry: $name:ident, $state:expr) => {
+        $crate::__fsm_log_at!(defmt entry [$($levels)*] (
+            "[{=str}] reentry() -> {=str}",
+            stringify!($name),
+            $state.current_state_name()
+        ));
+    };
+    ([$($levels:tt)*] entry: $name:ident, $state:expr) => {
+        $crate::__fsm_log_at!(defmt entry [$($levels)*] (
+            "[{=str}] {=str}.entry()",
+            stringify!($name),
+            $state.current_state_name()
+        ));
+    };
+    ([$($levels:tt)*] exit: $name:ident, $state:expr) => {
+        $crate::__fsm_log_at!(defmt exit [$($levels)*] (
+            "[{=str}] {=str}.exit()",
+            stringify!($name),
+            $state.current_state_name()
+        ));
+    };
+    ([$($levels:tt)*] transition: $name:ident, $from:expr, $event:expr, $to:expr) => {
+        $crate::__fsm_log_at!(defmt transition [$($levels)*] (
+            "[{=str}] {=str} -> {=str}",
+            stringify!($name),
+            $from.current_state_name(),
+            $to.current_state_name()
+        ));
+    };
+    ([$($levels:tt)*] stayed: $name:ident, $state:expr, $event:expr, $kind:literal) => {
+        $crate::__fsm_log_at!(defmt process [$($levels)*] (
+            "[{=str}] {=str} -> {=str} (stayed)",
+            stringify!($name),
+            $state.current_state_name(),
+            $kind
+        ));
     };
 }
 
-/// Represents the result of a state processing step.
-///
-/// This enum guides the state machine on whether to stay or switch states.
-/// **Every `process` closure must return a `Transition`.**
-///
-/// # Type Parameters
-///
-/// * `S` - The state machine enum type
-///
-/// # When to Use Each Variant
-///
-/// ## `Transition::None`
-/// Use when an event should be processed but doesn't require changing states:
-/// - Event updates context but state logic remains the same
-/// - Event should be ignored in the current state
-/// - Handling events that don't affect state flow
-///
-/// ## `Transition::To(State)`
-/// Use when an event should trigger a state change:
-/// - Event triggers a state transition
-/// - Conditions are met for moving to another state
-/// - Need to execute `exit` and `entry` hooks
-/// - Even for self-transitions (same state to same state)
-///
-/// # Examples
-///
-/// ```rust
-/// use typed_fsm::Transition;
-///
-/// #[derive(Debug)]
-/// enum MyState {
-///     Idle,
-///     Active { speed: u32 },
-/// }
-///
-/// // Stay in current state - no hooks execute
-/// let no_change: Transition<MyState> = Transition::None;
-///
-/// // Transition to a new state - exit + entry execute
-/// let change = Transition::To(MyState::Idle);
-///
-/// // Transition with state data
-/// let with_data = Transition::To(MyState::Active { speed: 100 });
-/// ```
-///
-/// # Common Pattern in `process` Hook
-///
-/// ```rust
-/// # use typed_fsm::{state_machine, Transition};
-/// # struct Context { data: u32 }
-/// # #[derive(Debug, Clone)]
-/// # enum Event { Update(u32), Activate, Ignore }
-/// # state_machine! {
-/// #     Name: FSM,
-/// #     Context: Context,
-/// #     Event: Event,
-/// #     States: {
-/// #         Idle => {
-/// process: |ctx, evt| {
-///     match evt {
-///         Event::Update(value) => {
-///             ctx.data = *value;        // Update context
-///             Transition::None          // Stay in same state
-///         },
-///         Event::Activate => {
-///             Transition::To(FSM::Active { speed: 100 })  // Change state
-///         },
-///         Event::Ignore => Transition::None  // Do nothing
-///     }
-/// }
-/// #         },
-/// #         Active { speed: u32 } => {
-/// #             process: |ctx, evt| { Transition::None }
-/// #         }
-/// #     }
-/// # }
-/// ```
-///
-/// # Performance
-///
-/// Creating a `Transition` has zero runtime overhead. The enum is optimized
-/// by the compiler and typically doesn't allocate any heap memory.
-///
-/// # Thread Safety
-///
-/// `Transition` is `Send` and `Sync` if the state type `S` is `Send` and `Sync`.
-pub enum Transition<S> {
-    /// Stay in the current state (no action required).
-    ///
-    /// Use this when an event should be handled but doesn't trigger a state change.
-    ///
-    /// # When to Use
-    ///
-    /// - Event updates context but state remains the same
-    /// - Event should be ignored in this state
-    /// - Processing an event that doesn't affect state flow
-    ///
-    /// # Lifecycle Impact
-    ///
-    /// - `process` executes
-    /// - `exit` does NOT execute (no state change)
-    /// - `entry` does NOT execute (no state change)
-    /// - State remains unchanged
-    ///
-    /// # Example
-    ///
-    /// ```rust
-    /// # use typed_fsm::{state_machine, Transition};
-    /// # struct Context { counter: u32 }
-    /// # #[derive(Debug, Clone)]
-    /// # enum Event { Increment }
-    /// # state_machine! {
-    /// #     Name: FSM,
-    /// #     Context: Context,
-    /// #     Event: Event,
-    /// #     States: {
-    /// #         Active => {
-    /// process: |ctx, evt| {
-    ///     match evt {
-    ///         Event::Increment => {
-    ///             ctx.counter += 1;   // Update context
-    ///             Transition::None    // Stay in Active state
-    ///         }
-    ///     }
-    /// }
-    /// #         }
-    /// #     }
-    /// # }
-    /// ```
-    None,
+// Declared - Internal macro that tells whether an optional part of the declaration (a
+// hook, a directive) is present: `[]` expands to `false`, anything else to `true`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_declared {
+    ([]) => {
+        false
+    };
+    ([$($declared:tt)+]) => {
+        true
+    };
+}
 
-    /// Transition to a new state.
-    ///
-    /// This will trigger the full state transition lifecycle:
-    /// `exit` (old state) → `entry` (new state) → update state
-    ///
-    /// # When to Use
-    ///
-    /// - Event triggers a state change
-    /// - Conditions are met for transitioning
-    /// - Need to move to a different state
-    /// - Self-transitions (same state, but re-execute entry/exit)
-    ///
-    /// # Arguments
-    ///
-    /// * `0` - The new state instance. Can carry data (payloads).
-    ///
-    /// # Lifecycle Impact
-    ///
-    /// 1. `process` executes and returns new state
-    /// 2. Current state's `exit` executes (if defined)
-    /// 3. New state's `entry` executes (if defined)
-    /// 4. State updates to the new state
-    ///
-    /// # Example
-    ///
-    /// ```rust
-    /// # use typed_fsm::{state_machine, Transition};
-    /// # struct Context { }
-    /// # #[derive(Debug, Clone)]
-    /// # enum Event { Start, Stop }
-    /// # state_machine! {
-    /// #     Name: FSM,
-    /// #     Context: Context,
-    /// #     Event: Event,
-    /// #     States: {
-    /// #         Idle => {
-    /// process: |ctx, evt| {
-    ///     match evt {
-    ///         Event::Start => {
-    ///             // Transition to Running state with data
-    ///             Transition::To(FSM::Running { speed: 100 })
-    ///         },
-    ///         Event::Stop => Transition::None
-    ///     }
-    /// }
-    /// #         },
-    /// #         Running { speed: u32 } => {
-    /// #             process: |ctx, evt| { Transition::None }
-    /// #         }
-    /// #     }
-    /// # }
-    /// ```
-    ///
-    /// # Performance
-    ///
-    /// State transitions use move semantics, making them extremely fast
-    /// (typically just a few CPU instructions).
-    To(S),
+// Bookkeeping - Internal macro that reaches the context field named by the optional
+// `Bookkeeping: field,` directive (`get_mut`: `None` without it), and rejects
+// `retry:` budgets (`require`, given each state's `on_exhausted`) and the `Watchdog`
+// (`require_watchdog`, given its limit) when it is missing.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_bookkeeping {
+    (get_mut [] $ctx:ident) => {
+        None
+    };
+    (get_mut [$field:ident] $ctx:ident) => {
+        Some(&mut $ctx.$field)
+    };
+    (require [] $first:ident $($rest:ident)*) => {
+        compile_error!(
+            "`retry:` counts attempts in the context: add a `Bookkeeping: field,` directive \
+             naming a `typed_fsm::Bookkeeping` context field"
+        );
+    };
+    (require [$($field:ident)?] $($retry_fail:ident)*) => {};
+    (require_watchdog [] $limit:expr) => {
+        compile_error!(
+            "`Watchdog` counts stayed dispatches in the context: add a `Bookkeeping: field,` \
+             directive naming a `typed_fsm::Bookkeeping` context field"
+        );
+    };
+    (require_watchdog [$($field:ident)?] $($limit:expr)?) => {};
 }
 
-/// Generates the State Machine Enum and its implementation.
-///
-/// This macro creates a `pub enum` with the specified name and implements
-/// the necessary logic for state transitions, entry/exit actions, and event processing.
-///
-/// # Macro Parameters
-///
-/// - **Name**: The identifier for the generated state machine enum
-/// - **Context**: The type of shared state accessible to all states
-/// - **Event**: The type of events that drive the state machine
-/// - **States**: Block defining all possible states and their behavior
-///
-/// # State Definition
-///
-/// Each state can have:
-/// - **entry** (optional): Closure executed once when entering the state
-/// - **process** (required): Closure that handles events and returns `Transition<S>`
-/// - **exit** (optional): Closure executed once when leaving the state
-///
-/// States can carry data by adding fields: `StateName { field: Type }`
-///
-/// # Complete Example
-///
-/// ```rust
-/// use typed_fsm::{state_machine, Transition};
-///
-/// struct MyContext {
-///     counter: u32,
-/// }
-///
-/// #[derive(Debug, Clone)]
-/// enum MyEvent {
-///     Start,
-///     Stop,
-/// }
-///
-/// state_machine! {
-///     Name: MyMachine,
-///     Context: MyContext,
-///     Event: MyEvent,
-///     States: {
-///         Idle => {
-///             entry: |ctx| {
-///                 println!("Entering Idle");
-///                 ctx.counter = 0;
-///             }
-///
-///             process: |_ctx, evt| {
-///                 match evt {
-///                     MyEvent::Start => Transition::To(MyMachine::Active { id: 1 }),
-///                     _ => Transition::None
-///                 }
-///             }
-///         },
-///
-///         Active { id: u32 } => {
-///             entry: |ctx| {
-///                 println!("Entering Active with id: {}", id);
-///                 ctx.counter += 1;
-///             }
-///
-///             process: |_ctx, evt| {
-///                 match evt {
-///                     MyEvent::Stop => Transition::To(MyMachine::Idle),
-///                     _ => Transition::None
-///                 }
-///             }
-///
-///             exit: |_ctx| {
-///                 println!("Leaving Active");
-///             }
-///         }
-///     }
-/// }
-/// ```
-///
-/// # Usage
-///
-/// ```rust
-/// # use typed_fsm::{state_machine, Transition};
-/// # struct MyContext { counter: u32 }
-/// # #[derive(Debug, Clone)]
-/// # enum MyEvent { Start, Stop }
-/// # state_machine! {
-/// #     Name: MyMachine,
-/// #     Context: MyContext,
-/// #     Event: MyEvent,
-/// #     States: {
-/// #         Idle => {
-/// #             process: |_ctx, evt| {
-/// #                 match evt {
-/// #                     MyEvent::Start => Transition::To(MyMachine::Active { id: 1 }),
-/// #                     _ => Transition::None
-/// #                 }
-/// #             }
-/// #         },
-/// #         Active { id: u32 } => {
-/// #             process: |_ctx, evt| {
-/// #                 match evt {
-/// #                     MyEvent::Stop => Transition::To(MyMachine::Idle),
-/// #                     _ => Transition::None
-/// #                 }
-/// #             }
-/// #         }
-/// #     }
-/// # }
-/// let mut ctx = MyContext { counter: 0 };
-/// let mut fsm = MyMachine::Idle;
-///
-/// // Initialize (calls entry action of initial state)
-/// fsm.init(&mut ctx);
-///
-/// // Dispatch events
-/// fsm.dispatch(&mut ctx, &MyEvent::Start);
-/// fsm.dispatch(&mut ctx, &MyEvent::Stop);
-/// ```
+// Transition counters - Internal macro that generates the per-edge counters of the
+// `CountTransitions: true` directive (`api`, given the atomics module of the build) and
+// the call counting an applied transition (`count`, through `increment`). Nothing is
+// generated or counted without the directive.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_transition_counters {
+    ([] $($rest:tt)*) => {};
+    ([false] $($rest:tt)*) => {};
+    ([true] count $from_index:expr, $to_index:expr) => {
+        Self::count_edge($from_index, $to_index);
+    };
+    ([true] api $enum_name:ident, $($atomic:ident)::+) => {
+        /// Returns how many times the `edge_index`-th edge of `TRANSITIONS` fired (a
+        /// `Transition::To` between its `from` and `to` states), or `0` if out of range.
+        ///
+        /// Edges sharing the same `from` and `to` (different events) can't be told
+        /// apart by `dispatch`: the first one in table order is counted. The counters
+        /// are shared by all values of this state machine type and wrap on overflow.
+        pub fn transition_count(edge_index: usize) -> u32 {
+            Self::edge_counters()
+                .get(edge_index)
+                .map_or(0, |count| count.load($($atomic)::+::Ordering::Relaxed))
+        }
 
-// ============================================================================
-// IMPLEMENTATION WITHOUT CONCURRENCY PROTECTION (default)
-// ============================================================================
-#[cfg(not(feature = "concurrent"))]
+        /// Snapshot of `transition_count()` for every edge, indexed like `TRANSITIONS`.
+        pub fn transition_counts() -> [u32; $enum_name::TRANSITION_COUNT] {
+            let mut counts = [0; $enum_name::TRANSITION_COUNT];
+            for (count, counter) in counts.iter_mut().zip(Self::edge_counters()) {
+                *count = counter.load($($atomic)::+::Ordering::Relaxed);
+            }
+            counts
+        }
+
+        /// Internal: The per-edge counters behind `transition_count()`.
+        fn edge_counters() -> &'static [$($atomic)::+::AtomicU32; $enum_name::TRANSITION_COUNT] {
+            #[allow(clippy::declare_interior_mutable_const)]
+            const ZERO: $($atomic)::+::AtomicU32 = $($atomic)::+::AtomicU32::new(0);
+            static EDGES: [$($atomic)::+::AtomicU32; $enum_name::TRANSITION_COUNT] =
+                [ZERO; $enum_name::TRANSITION_COUNT];
+            &EDGES
+        }
+
+        /// Internal: Counts a `Transition::To` against the first edge of `TRANSITIONS`
+        /// between the states at `from_index` and `to_index`, looked up in a table built
+        /// at compile time.
+        #[inline(always)]
+        fn count_edge(from_index: usize, to_index: usize) {
+            const EDGE_OF: [u16; $enum_name::STATE_COUNT * $enum_name::STATE_COUNT] =
+                $crate::edge_lookup($enum_name::STATE_NAMES, $enum_name::TRANSITIONS);
+            let edge = EDGE_OF[from_index * $enum_name::STATE_COUNT + to_index];
+            // `0` marks a pair of states without an edge
+            let counter = (edge as usize).checked_sub(1).and_then(|i| Self::edge_counters().get(i));
+            if let Some(counter) = counter {
+                $crate::__fsm_transition_counters!(increment counter, $($atomic)::+);
+            }
+        }
+    };
+    // Instances of the type may dispatch on several threads at once (e.g., two
+    // `FsmCell`s): increment atomically wherever the target can. Targets without
+    // atomic read-modify-write (e.g., Cortex-M0) have no threads
+    (increment $counter:ident, portable_atomic) => {
+        $counter.fetch_add(1, portable_atomic::Ordering::Relaxed);
+    };
+    (increment $counter:ident, $($atomic:ident)::+) => {
+        #[cfg(target_has_atomic = "32")]
+        $counter.fetch_add(1, $($atomic)::+::Ordering::Relaxed);
+        #[cfg(not(target_has_atomic = "32"))]
+        {
+            let count = $counter.load($($atomic)::+::Ordering::Relaxed);
+            $counter.store(count.wrapping_add(1), $($atomic)::+::Ordering::Relaxed);
+        }
+    };
+    ([$other:tt] count $($rest:tt)*) => {};
+    ([$other:tt] api $($rest:tt)*) => {
+        compile_error!(concat!(
+            "unknown `CountTransitions: ", stringify!($other), "` (expected `true` or `false`)"
+        ));
+    };
+}
+
+// Generation - Internal macro that generates `generation()` and `dispatch_gen()` when the
+// `Bookkeeping` directive names the context field holding the generation. Kept out of
+// the `Bookkeeping` repetition, which can't hold the optional parameters; `$bounds` is
+// the `where` clause of the concurrent build.
 #[macro_export]
-macro_rules! state_machine {
+#[doc(hidden)]
+macro_rules! __fsm_generation {
+    ([] $($rest:tt)*) => {};
     (
-        Name: $enum_name:ident,
-        Context: $ctx_type:ty,
-        Event: $event_type:ty,
-        States: {
-            $(
-                // Captures the State Name and optional fields (e.g., Running { speed: u32 })
-                $state_name:ident $( { $($field_name:ident : $field_type:ty),* } )? => {
+        [$field:ident] $ctx_type:ty, $event_type:ty, [$($shared_type:ty)?] [$($instr_type:ty)?]
+        [$($bounds:tt)*]
+    ) => {
+        /// Returns the generation of this state machine, as recorded in `ctx`'s
+        /// `Bookkeeping` field. Every `init()` (and so `start_in()`, `start()` and
+        /// `reset()`) assigns a new one, never issued before to a machine of this type
+        /// (until it wraps around after `u32::MAX` starts).
+        ///
+        /// Hand it out with references to the machine, and dispatch through
+        /// `dispatch_gen()` to refuse events from holders of a recreated machine.
+        #[must_use]
+        pub fn generation(&self, ctx: &$ctx_type) -> u32 {
+            ctx.$field.generation
+        }
+
+        /// Dispatches `event` like `dispatch()` if `expected_gen` is the current
+        /// `generation()`. Otherwise the caller holds a stale reference from before the
+        /// last `init()` (hot reload, reconnection): nothing runs (nor is queued) and
+        /// `StaleGen` is returned. The generation is checked on entry, as
+        /// `dispatch_before()` checks its deadline.
+        pub fn dispatch_gen(
+            &mut self,
+            ctx: &mut $ctx_type
+            $(, shared: &$shared_type)?
+            $(, instrumentation: &mut $instr_type)?,
+            event: &$event_type,
+            expected_gen: u32,
+        ) -> Result<$crate::DispatchOutcome, $crate::StaleGen>
+        $($bounds)*
+        {
+            let current = ctx.$field.generation;
+            if expected_gen != current {
+                return Err($crate::StaleGen { expected: expected_gen, current });
+            }
+            Ok(self.dispatch_ref(
+                ctx,
+                $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                event,
+            ))
+        }
+    };
+}
+
+// Log level selection - Internal macro that picks the level a hook logs at from the
+// `LogLevels` list (`entry: debug, ...`), or `info` when the hook isn't listed.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_log_at {
+    ($backend:ident $hook:ident [] ($($args:tt)*)) => {
+        $backend::info!($($args)*)
+    };
+    ($backend:ident entry [entry: $level:ident $(, $($rest:tt)*)?] ($($args:tt)*)) => {
+        $backend::$level!($($args)*)
+    };
+    ($backend:ident exit [exit: $level:ident $(, $($rest:tt)*)?] ($($args:tt)*)) => {
+        $backend::$level!($($args)*)
+    };
+    ($backend:ident transition [transition: $level:ident $(, $($rest:tt)*)?] ($($args:tt)*)) => {
+        $backend::$level!($($args)*)
+    };
+    ($backend:ident process [process: $level:ident $(, $($rest:tt)*)?] ($($args:tt)*)) => {
+        $backend::$level!($($args)*)
+    };
+    // A known hook configured for something else: keep looking
+    (
+        $backend:ident $hook:ident
+        [$key:ident: $level:ident $(, $($rest:tt)*)?] $args:tt
+    ) => {
+        $crate::__fsm_log_at!($backend $hook [$key] ());
+        $crate::__fsm_log_at!($backend $hook [$($($rest)*)?] $args)
+    };
+    // Only reached through the arm above: validates the skipped key
+    ($backend:ident $hook:ident [entry] ()) => {};
+    ($backend:ident $hook:ident [exit] ()) => {};
+    ($backend:ident $hook:ident [transition] ()) => {};
+    ($backend:ident $hook:ident [process] ()) => {};
+    ($backend:ident $hook:ident [$key:ident] ()) => {
+        compile_error!(concat!(
+            "unknown LogLevels key `",
+            stringify!($key),
+            "`: expected `entry`, `exit`, `transition` or `process`"
+        ));
+    };
+}
+
+#[cfg(not(any(feature = "logging", feature = "defmt")))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_log {
+    (enabled) => {
+        false
+    };
+    ($($arg:tt)*) => {
+        // When logging feature disabled, generate no code at all (true zero-cost)
+    };
+}
+
+// Readonly states - Internal macro that reborrows the context immutably.
+// Only the `readonly` keyword is accepted, so typos fail to compile.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_readonly {
+    (readonly $ctx:ident) => {
+        &*$ctx
+    };
+}
+
+// Code size - Internal macro that picks the inlining attributes of the hot paths
+// (`dispatch*` and `on_process`) according to the optional `CodeSize` directive
+// (and forwards `MustUse` to the `dispatch*` ones).
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_inline {
+    // Default: inline dispatch into callers (jump-table codegen)
+    ([] hot [$($must_use:tt)?] $($item:tt)*) => {
+        $crate::__fsm_must_use! { [$($must_use)?]
+            #[inline(always)]
+            $($item)*
+        }
+    };
+    ([] process [$($strict:tt)?] $($item:tt)*) => {
+        $crate::__fsm_strict_lints! { [$($strict)?] $($item)* }
+    };
+    // CodeSize: small - one out-of-line copy of dispatch and process
+    ([small] hot [$($must_use:tt)?] $($item:tt)*) => {
+        $crate::__fsm_must_use! { [$($must_use)?] $($item)* }
+    };
+    ([small] process [$($strict:tt)?] $($item:tt)*) => {
+        $crate::__fsm_strict_lints! { [$($strict)?]
+            #[inline(never)]
+            $($item)*
+        }
+    };
+    ([$other:ident] $($rest:tt)*) => {
+        compile_error!(concat!(
+            "unknown `CodeSize: ", stringify!($other), "` (expected `CodeSize: small`)"
+        ));
+    };
+}
+
+// Must use - Internal macro that marks the dispatch methods `#[must_use]` with the
+// optional `MustUse: true` directive, so a dropped `DispatchOutcome` warns.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_must_use {
+    ([] $($item:tt)*) => {
+        $($item)*
+    };
+    ([false] $($item:tt)*) => {
+        $($item)*
+    };
+    ([true] $($item:tt)*) => {
+        #[must_use = "the `DispatchOutcome` tells whether the event was processed, queued or dropped"]
+        $($item)*
+    };
+    ([$other:tt] $($item:tt)*) => {
+        compile_error!(concat!(
+            "unknown `MustUse: ", stringify!($other), "` (expected `true` or `false`)"
+        ));
+    };
+}
+
+// Strict lints - Internal macro that scopes the `unused_variables` allowance of a
+// generated function running user blocks. By default it covers the whole function,
+// user code included; with `StrictLints: true` only the generated bindings keep their
+// own `#[allow]`, so warnings in the user's blocks surface.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_strict_lints {
+    ([] $($item:tt)*) => {
+        #[allow(unused_variables)]
+        $($item)*
+    };
+    ([false] $($item:tt)*) => {
+        #[allow(unused_variables)]
+        $($item)*
+    };
+    ([true] $($item:tt)*) => {
+        $($item)*
+    };
+    ([$other:tt] $($item:tt)*) => {
+        compile_error!(concat!(
+            "unknown `StrictLints: ", stringify!($other), "` (expected `true` or `false`)"
+        ));
+    };
+}
+
+// Mixins - Internal macro that generates one zero-sized type per `Mixin`, holding its
+// shared `process` arms. Kept out of the `Mixin` repetition so that `StrictLints`
+// applies to every fragment.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_mixins {
+    (
+        $strict:tt $enum_name:ident, $ctx_type:ty, $event_type:ty;
+        $( $mixin:ident |$mixin_ctx:ident, $mixin_evt:ident| $mixin_block:block )*
+    ) => {
+        $(
+            struct $mixin;
+
+            impl $mixin {
+                // `$strict` is the bracketed `StrictLints` value, a single token tree so
+                // that it can repeat along with the mixins
+                $crate::__fsm_strict_lints! { $strict
+                    /// Internal: The mixin's arms; `Transition::Unhandled` defers to the state.
+                    #[inline(always)]
+                    fn process_fragment(
+                        arg_ctx: &mut $ctx_type,
+                        arg_evt: &$event_type,
+                    ) -> $crate::Transition<$enum_name, $event_type> {
+                        #[allow(unused_variables)]
+                        let $mixin_ctx = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $mixin_evt = arg_evt;
+                        $crate::IntoTransition::into_transition($mixin_block)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+// Fieldless check - Internal macro that rejects states carrying fields when a
+// directive (e.g., `Repr: u8`) only makes sense for fieldless state machines.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_require_fieldless {
+    ([] $($states:tt)*) => {};
+    ([$directive:ident : $value:tt] $( $( #[$cfg:meta] )* $state:ident $( { $($fields:tt)* } )? ; )*) => {
+        $($(
+            $( #[$cfg] )*
+            compile_error!(concat!(
+                "`", stringify!($directive), ": ", stringify!($value),
+                "` requires all states to be fieldless, but state `", stringify!($state),
+                "` carries fields { ", stringify!($($fields)*), " }"
+            ));
+        )?)*
+    };
+}
+
+// Fieldless value - Internal macro that yields `Some(Self::State)` for a fieldless
+// state and `None` for a state carrying fields (which can't be built without data).
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_fieldless_value {
+    ($state:ident) => {
+        Some(Self::$state)
+    };
+    ($state:ident { $($fields:tt)* }) => {
+        None
+    };
+}
+
+// Cycling - Internal macro that generates `cycle_next()` when every state is fieldless.
+// A state carrying fields can't be built from its index, so nothing is generated then
+// and calling `cycle_next()` fails to compile.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_cycle_next {
+    ($( $( #[$cfg:meta] )* $state:ident ; )*) => {
+        /// Returns the state declared after the current one, wrapping from the last state
+        /// back to the first, e.g., to step a demo or a property test through every state
+        /// without defining events. No hook runs: this only computes the value.
+        ///
+        /// Only generated when all states are fieldless.
+        #[must_use]
+        pub fn cycle_next(&self) -> Self {
+            let next = self.state_index() + 1;
+            let next = if next == Self::STATE_COUNT { 0 } else { next };
+            match Self::from_index(next) {
+                Some(state) => state,
+                None => unreachable!("fieldless states always round-trip"),
+            }
+        }
+    };
+    ($($states:tt)*) => {};
+}
+
+// Initial boot states - Internal macro that generates `start_default()` when an
+// `Initial` list is declared. Kept out of the `Initial` repetition so the public
+// signature can splice the (possibly empty) context parameter.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_start_default {
+    ($enum_name:ident [$($ctx_param:tt)*] $ctx_arg:expr; []) => {};
+    ($enum_name:ident [$($ctx_param:tt)*] $ctx_arg:expr; [$($initial:ident),+]) => {
+        /// Creates the state machine in the `which`-th declared `Initial` state
+        /// (see `INITIAL_STATES`) and runs `init()` on it.
+        ///
+        /// # Panics
+        ///
+        /// If `which >= INITIAL_STATES.len()`.
+        pub fn start_default(which: usize $($ctx_param)*) -> Self {
+            let Some(state) = [$( Self::$initial ),+].into_iter().nth(which) else {
+                panic!(
+                    "[{}] start_default({}): only {} Initial states declared",
+                    stringify!($enum_name),
+                    which,
+                    Self::INITIAL_STATES.len()
+                );
+            };
+            Self::boot(state, $ctx_arg)
+        }
+    };
+}
+
+// Debug output - Internal macro that emits the generated enum (`$enum_item`) with the
+// `Debug` implementation chosen by the optional `DebugMode` directive.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_debug {
+    // Default (or `Full`): derived, with field values
+    ([$(Full)?] $enum_name:ident; $($enum_item:tt)*) => {
+        #[derive(Debug)]
+        $($enum_item)*
+    };
+    ([NamesOnly] $enum_name:ident; $($enum_item:tt)*) => {
+        $($enum_item)*
+
+        impl core::fmt::Debug for $enum_name {
+            /// Writes the state name only (see `current_state_name()`), never field values.
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str(self.current_state_name())
+            }
+        }
+    };
+    ([$other:ident] $($rest:tt)*) => {
+        compile_error!(concat!(
+            "unknown `DebugMode: ", stringify!($other), "` (expected `Full` or `NamesOnly`)"
+        ));
+    };
+}
+
+// Initial field values - Internal macro that generates `initial_state()`, `start()` and
+// `reset()` from the `InitialValues` directive. The state is built with struct syntax,
+// so rustc rejects unknown states and missing or unknown fields.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_initial_values {
+    ($enum_name:ident [$($ctx_param:tt)*] $ctx_arg:expr; []) => {};
+    (
+        $enum_name:ident [$(, $ctx:ident : $ctx_type:ty)?] $ctx_arg:expr; [$($initial:tt)+]
+    ) => {
+        /// Builds the state declared by `InitialValues`, with its declared field values.
+        /// No hook runs.
+        pub fn initial_state() -> Self {
+            $enum_name::$($initial)+
+        }
+
+        /// Creates the state machine in `initial_state()` and runs `init()` on it.
+        pub fn start($( $ctx: $ctx_type )?) -> Self {
+            Self::boot(Self::initial_state(), $ctx_arg)
+        }
+
+        /// Replaces the current state with a fresh `initial_state()` and runs its `entry`
+        /// hook as `init()` does. The current state's `exit` hook does not run, as after
+        /// a power cycle: use `Transition::To` for an orderly return instead.
+        pub fn reset(&mut self $(, $ctx: $ctx_type )?) {
+            *self = Self::boot(Self::initial_state(), $ctx_arg);
+        }
+    };
+}
 
-                    // Optional Entry Block: entry: |ctx| { ... }
-                    $( entry: |$entry_ctx:ident| $entry_block:block )?
+// Context-free machines - Internal macro that prepends a unit context argument to
+// every hook closure (`entry: || { ... }` becomes `entry: |__fsm_ctx| { ... }`), then
+// hands the rewritten definition (prefixed with `$out`) back to `state_machine!`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_context_free {
+    // States: rewrites the hooks of one state at a time, after moving its leading
+    // `cost: N,`, `retry: { ... },` and `use: [...],` (which are not hooks) to `$meta`
+    ([$($out:tt)*] States: { $($states:tt)* }) => {
+        $crate::__fsm_context_free! { @states [$($out)*] [] $($states)* }
+    };
+    (
+        @states [$($out:tt)*] [$($done:tt)*]
+        $( #[$($state_attr:tt)*] )*
+        $state:ident $( { $($fields:tt)* } )? => $( $readonly:ident )? { $($body:tt)* }
+        $(, $($rest:tt)*)?
+    ) => {
+        $crate::__fsm_context_free! {
+            @state [$($out)*] [$($done)*]
+            [ $( #[$($state_attr)*] )* $state $( { $($fields)* } )? => $( $readonly )? ]
+            [] [$($body)*] [$($($rest)*)?]
+        }
+    };
+    (@state $out:tt $done:tt $head:tt [$($meta:tt)*] [cost: $cost:expr, $($body:tt)*] $rest:tt) => {
+        $crate::__fsm_context_free! {
+            @state $out $done $head [$($meta)* cost: $cost,] [$($body)*] $rest
+        }
+    };
+    (@state $out:tt $done:tt $head:tt [$($meta:tt)*] [retry: $retry:tt, $($body:tt)*] $rest:tt) => {
+        $crate::__fsm_context_free! {
+            @state $out $done $head [$($meta)* retry: $retry,] [$($body)*] $rest
+        }
+    };
+    (@state $out:tt $done:tt $head:tt [$($meta:tt)*] [use: $mixins:tt, $($body:tt)*] $rest:tt) => {
+        $crate::__fsm_context_free! {
+            @state $out $done $head [$($meta)* use: $mixins,] [$($body)*] $rest
+        }
+    };
+    (
+        @state [$($out:tt)*] [$($done:tt)*] [$($head:tt)*] [$($meta:tt)*]
+        [ $( $hook:ident : $(||)? $(| $($arg:ident),* |)? { $($hook_body:tt)* } )* ]
+        [$($rest:tt)*]
+    ) => {
+        $crate::__fsm_context_free! {
+            @states [$($out)*]
+            [
+                $($done)*
+                $($head)* {
+                    $($meta)*
+                    $( $hook: |__fsm_ctx $($(, $arg)*)?| { $($hook_body)* } )*
+                },
+            ]
+            $($rest)*
+        }
+    };
+    // Malformed state: leave it as is for `state_machine!` to report
+    (@state [$($out:tt)*] [$($done:tt)*] [$($head:tt)*] [$($meta:tt)*] [$($body:tt)*] [$($rest:tt)*]) => {
+        $crate::state_machine! {
+            $($out)*
+            States: { $($done)* $($head)* { $($meta)* $($body)* }, $($rest)* }
+        }
+    };
+    // All states rewritten, or a malformed one: let `state_machine!` take over
+    (@states [$($out:tt)*] [$($done:tt)*] $($rest:tt)*) => {
+        $crate::state_machine! {
+            $($out)*
+            States: { $($done)* $($rest)* }
+        }
+    };
+    // Mixins: `process_fragment: |evt| { ... }` gets the unit context like `process`
+    (
+        [$($out:tt)*]
+        Mixin: $mixin:ident => { process_fragment: | $($arg:ident),* | $body:block $(,)? },
+        $($rest:tt)*
+    ) => {
+        $crate::__fsm_context_free! {
+            [$($out)* Mixin: $mixin => { process_fragment: |__fsm_ctx, $($arg),*| $body },]
+            $($rest)*
+        }
+    };
+    // Directive hooks (e.g., `OnUnhandled: |state_name, evt| { ... },`)
+    ([$($out:tt)*] $hook:ident : || $body:block, $($rest:tt)*) => {
+        $crate::__fsm_context_free! { [$($out)* $hook: |__fsm_ctx| $body,] $($rest)* }
+    };
+    ([$($out:tt)*] $hook:ident : | $($arg:ident),* | $body:block, $($rest:tt)*) => {
+        $crate::__fsm_context_free! { [$($out)* $hook: |__fsm_ctx, $($arg),*| $body,] $($rest)* }
+    };
+    ([$($out:tt)*] $hook:ident : | $($arg:ident),* | -> $ret:ty $body:block, $($rest:tt)*) => {
+        $crate::__fsm_context_free! { [$($out)* $hook: |__fsm_ctx, $($arg),*| -> $ret $body,] $($rest)* }
+    };
+    // Everything else is copied through unchanged
+    ([$($out:tt)*] $token:tt $($rest:tt)*) => {
+        $crate::__fsm_context_free! { [$($out)* $token] $($rest)* }
+    };
+    // Malformed input: let `state_machine!` report the error
+    ([$($out:tt)*]) => {
+        $crate::state_machine! { $($out)* }
+    };
+}
+
+// Re-entrancy guard - Internal macro that makes a re-entrant `dispatch` (a hook
+// dispatching to a machine of its own type on the same thread) panic in debug builds
+// of the default (non-concurrent) implementation. Needs `std` for a thread-local flag;
+// without it (or in release builds) no code is generated.
+#[cfg(feature = "std")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_reentrancy_guard {
+    (api $enum_name:ident) => {
+        /// Internal: Marks a dispatch of this state machine type as active on this
+        /// thread until the returned guard is dropped.
+        #[cfg(debug_assertions)]
+        fn enter_dispatch() -> $crate::DispatchGuard {
+            $crate::__thread_local! {
+                static ACTIVE: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+            }
+            $crate::DispatchGuard::enter(&ACTIVE, stringify!($enum_name))
+        }
+    };
+    (enter) => {
+        #[cfg(debug_assertions)]
+        let _reentrancy_guard = Self::enter_dispatch();
+    };
+}
+
+#[cfg(not(feature = "std"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_reentrancy_guard {
+    ($($tokens:tt)*) => {};
+}
+
+// Test transition log - Internal macro that records applied transitions per thread when
+// the *user's* crate is built with `cfg(test)`, so tests can assert the exact sequence
+// without a logging backend. Needs `std` for the thread-local log; without it (or outside
+// test builds) no code is generated.
+#[cfg(feature = "std")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_test_log {
+    (api) => {
+        /// Transitions applied on the current thread so far, as `(from, to)` state
+        /// names in order. Only generated in `cfg(test)` builds (with the `std` feature).
+        ///
+        /// Every `Transition::To` and `Transition::ToFn` is recorded, not `Reenter`.
+        /// The log is shared by all instances of the state machine type on the thread.
+        #[cfg(test)]
+        pub fn test_transitions() -> $crate::__Vec<(&'static str, &'static str)> {
+            Self::with_test_log(|log| log.clone())
+        }
+
+        /// Empties the log returned by `test_transitions()` (`cfg(test)` builds only).
+        #[cfg(test)]
+        pub fn clear_test_transitions() {
+            Self::with_test_log(|log| log.clear());
+        }
+
+        /// Internal: Runs `f` on this thread's transition log.
+        #[cfg(test)]
+        fn with_test_log<R>(
+            f: impl FnOnce(&mut $crate::__Vec<(&'static str, &'static str)>) -> R,
+        ) -> R {
+            $crate::__thread_local! {
+                static LOG: core::cell::RefCell<$crate::__Vec<(&'static str, &'static str)>> =
+                    const { core::cell::RefCell::new($crate::__Vec::new()) };
+            }
+            LOG.with(|log| f(&mut log.borrow_mut()))
+        }
+    };
+    (record $from:expr, $to:expr) => {
+        #[cfg(test)]
+        Self::with_test_log(|log| log.push(($from, $to)));
+    };
+}
+
+#[cfg(not(feature = "std"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_test_log {
+    ($($tokens:tt)*) => {};
+}
+
+// Exhaustive events - Internal macro that implements `ExhaustiveEvents: true`: every
+// `process` block must be a single `match` whose arms name event variants, so adding
+// a variant breaks the build in each state that doesn't handle it. A catch-all arm
+// (`_` or a bare binding without guard) is rejected with the offending state's name.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_exhaustive_events {
+    ([] $($states:tt)*) => {};
+    ([false] $($states:tt)*) => {};
+    ([true] $( $state:ident $evt:ident { $($body:tt)* } )*) => {
+        $( $crate::__fsm_exhaustive_events! { @body $state $evt $($body)* } )*
+    };
+    (@body $state:ident $evt:ident match $(*)? $scrutinee:ident { $($arms:tt)* }) => {
+        $crate::__fsm_exhaustive_events! { @arm $state $($arms)* }
+    };
+    (@body $state:ident $evt:ident $($other:tt)*) => {
+        compile_error!(concat!(
+            "`ExhaustiveEvents: true` requires the `process` block of state `",
+            stringify!($state), "` to be a single `match ", stringify!($evt), " { ... }`"
+        ));
+    };
+    // Arm by arm: reject catch-alls, then skip the arm in one step when possible. A
+    // guarded `_ if ... =>` or `binding if ... =>` is not a catch-all
+    (@arm $state:ident) => {};
+    (@arm $state:ident , $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @arm $state $($rest)* }
+    };
+    (@arm $state:ident _ => $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @catch_all $state _ }
+    };
+    (@arm $state:ident $binding:ident => $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @catch_all $state $binding }
+    };
+    (@arm $state:ident ref $binding:ident => $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @catch_all $state $binding }
+    };
+    (@arm $state:ident mut $binding:ident => $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @catch_all $state $binding }
+    };
+    (@arm $state:ident $pat:pat $(if $guard:expr)? => { $($block:tt)* } $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @arm $state $($rest)* }
+    };
+    (@arm $state:ident $pat:pat $(if $guard:expr)? => $value:expr, $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @arm $state $($rest)* }
+    };
+    (@arm $state:ident $pat:pat $(if $guard:expr)? => $value:expr) => {};
+    // Block-like arm values without a trailing comma (e.g., `if`): skip token by token
+    (@arm $state:ident $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @skip $state $($rest)* }
+    };
+    (@skip $state:ident) => {};
+    (@skip $state:ident , $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @arm $state $($rest)* }
+    };
+    (@skip $state:ident $token:tt $($rest:tt)*) => {
+        $crate::__fsm_exhaustive_events! { @skip $state $($rest)* }
+    };
+    (@catch_all $state:ident $arm:tt) => {
+        compile_error!(concat!(
+            "`ExhaustiveEvents: true` forbids the catch-all arm `", stringify!($arm),
+            " =>` in the `process` block of state `", stringify!($state),
+            "`: handle every event variant explicitly"
+        ));
+    };
+}
+
+// Missing process - Internal macro that names each state declared without its mandatory
+// `process:` block. Such a state doesn't match the `state_machine!` grammar, whose own
+// "no rules expected" error doesn't say what is missing. Valid definitions expand to
+// nothing, and malformed states are left to `state_machine!` to report.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_missing_process {
+    (@states) => {};
+    (
+        @states
+        $( #[$state_attr:meta] )*
+        $state:ident $( { $($fields:tt)* } )? => $( $readonly:ident )? { $($body:tt)* }
+        $(, $($rest:tt)*)?
+    ) => {
+        $crate::__fsm_missing_process! { @body $state $($body)* }
+        $crate::__fsm_missing_process! { @states $($($rest)*)? }
+    };
+    (@states $($malformed:tt)*) => {};
+    // Hooks are `name: |args| { ... }`: only the top level of the state body is scanned
+    (@body $state:ident process : $($rest:tt)*) => {};
+    (@body $state:ident $token:tt $($rest:tt)*) => {
+        $crate::__fsm_missing_process! { @body $state $($rest)* }
+    };
+    (@body $state:ident) => {
+        compile_error!(concat!(
+            "state `", stringify!($state), "` is missing a required `process:` block"
+        ));
+    };
+    // Directives: skip them up to `States`, a whole directive at a time when possible
+    (States: { $($states:tt)* } $($rest:tt)*) => {
+        $crate::__fsm_missing_process! { @states $($states)* }
+    };
+    ($key:ident : $value:tt, $($rest:tt)*) => {
+        $crate::__fsm_missing_process! { $($rest)* }
+    };
+    ($key:ident : |$($arg:ident),*| $block:tt, $($rest:tt)*) => {
+        $crate::__fsm_missing_process! { $($rest)* }
+    };
+    ($token:tt $($rest:tt)*) => {
+        $crate::__fsm_missing_process! { $($rest)* }
+    };
+    () => {};
+}
+
+// Shared reference - Internal macro that resolves the optional `SharedRef` directive:
+// the type of the read-only `shared` argument threaded to `process` (`()` when absent)
+// and the value passed for it.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_shared {
+    (type []) => {
+        ()
+    };
+    (type [$shared_type:ty]) => {
+        $shared_type
+    };
+    (arg [] $shared:ident) => {
+        &()
+    };
+    (arg [$shared_type:ty] $shared:ident) => {
+        $shared
+    };
+    // `Dispatch` and `Fsm` take neither a `shared` nor an `instrumentation` argument, so
+    // they are only implemented without `SharedRef` and `Instrumentation`
+    (
+        dispatch_impl [] [] $enum_name:ident $ctx_type:ty, $event_type:ty,
+        [$($bound:tt)*]
+    ) => {
+        impl $crate::Dispatch<$ctx_type, $event_type> for $enum_name {
+            fn dispatch_event(
+                &mut self,
+                ctx: &mut $ctx_type,
+                event: $event_type,
+            ) -> $crate::DispatchOutcome {
+                self.dispatch_value(ctx, &(), &mut (), event)
+            }
+        }
+
+        impl $crate::Fsm<$ctx_type, $event_type> for $enum_name $($bound)* {
+            fn init(&mut self, ctx: &mut $ctx_type) {
+                self.do_init(ctx);
+            }
+
+            fn dispatch(&mut self, ctx: &mut $ctx_type, event: &$event_type) -> $crate::DispatchOutcome {
+                self.dispatch_ref(ctx, &(), &mut (), event)
+            }
+        }
+    };
+    (
+        dispatch_impl [$($shared_type:ty)?] [$($instr_type:ty)?] $enum_name:ident
+        $ctx_type:ty, $event_type:ty, [$($bound:tt)*]
+    ) => {};
+}
+
+// Instrumentation - Internal macro that resolves the optional `Instrumentation` directive:
+// the type of the `&mut` argument threaded to `process` (`()` when absent) and the value
+// passed for it.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_instrumentation {
+    (type []) => {
+        ()
+    };
+    (type [$instr_type:ty]) => {
+        $instr_type
+    };
+    (arg [] $instr:ident) => {
+        &mut ()
+    };
+    (arg [$instr_type:ty] $instr:ident) => {
+        $instr
+    };
+}
+
+// Event recording - Internal macro that generates the `Record: N` API (`recorded_events()`,
+// `clear_recorded_events()`, `replay()`) and records each dispatched event.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_record {
+    // No Record directive: zero-cost
+    ([] $($rest:tt)*) => {};
+    (
+        [$capacity:expr] api $event_type:ty,
+        [$($ctx_param:tt)*] $ctx_arg:expr, [$($shared_type:ty)?] [$($instr_type:ty)?]
+    ) => {
+        /// Returns the events dispatched so far (see `Record`), oldest first.
+        ///
+        /// Only events passed to a dispatch method are recorded, not events posted from
+        /// hooks, which replaying re-posts anyway. Recording stops once `Record` events
+        /// are stored. The log is shared by all instances of the state machine type.
+        pub fn recorded_events() -> heapless::Vec<$event_type, { $capacity }> {
+            Self::event_recorder().snapshot()
+        }
+
+        /// Discards the recorded events, e.g., to start a new recording after `init()`.
+        pub fn clear_recorded_events() {
+            Self::event_recorder().clear();
+        }
+
+        /// Dispatches every recorded event to `self`, in recording order, and returns
+        /// how many were dispatched.
+        ///
+        /// Call it on a freshly initialized machine and context to reproduce a recorded
+        /// run. The replayed events are not recorded again.
+        pub fn replay(
+            &mut self $($ctx_param)* $(, shared: &$shared_type)?
+            $(, instrumentation: &mut $instr_type)?
+        ) -> usize {
+            let events = Self::event_recorder().snapshot();
+            Self::event_recorder().pause(true);
+            for event in events.iter() {
+                self.dispatch_ref(
+                    $ctx_arg,
+                    $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                    $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                    event,
+                );
+            }
+            Self::event_recorder().pause(false);
+            events.len()
+        }
+
+        /// Internal: The `Record` log of this state machine type.
+        fn event_recorder() -> &'static $crate::EventRecorder<$event_type, { $capacity }> {
+            static RECORDER: $crate::EventRecorder<$event_type, { $capacity }> =
+                $crate::EventRecorder::new();
+            &RECORDER
+        }
+    };
+    ([$capacity:expr] record $event:expr) => {
+        Self::event_recorder().record($event);
+    };
+}
+
+// Overflow policy - Internal macro that resolves the optional `OverflowPolicy` directive
+// (default `DropNewest`).
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_overflow_policy {
+    () => {
+        $crate::OverflowPolicy::DropNewest
+    };
+    ($policy:ident) => {
+        $crate::OverflowPolicy::$policy
+    };
+}
+
+// Dropped buffer - Internal macro that generates the `DroppedBuffer: N` store of events
+// dropped by a full concurrent queue (`drained_dropped_events()`) and records them. The
+// store is a per-type `heapless::Deque` behind the same critical section as the queue.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_dropped_buffer {
+    // No DroppedBuffer directive: zero-cost, and the event is never cloned
+    ([] api $event_type:ty) => {};
+    ([] bytes $event_type:ty) => {
+        0
+    };
+    ([] record $cs:ident, $event:expr) => {};
+    ([$capacity:expr] api $event_type:ty) => {
+        /// Takes the events dropped by the full queue since startup or the last call,
+        /// oldest first, leaving the store empty (see `DroppedBuffer`).
+        ///
+        /// Only the `DroppedBuffer` most recent drops are kept; `dropped_events_count()`
+        /// still counts every one of them. The store is shared by all instances of the
+        /// state machine type.
+        pub fn drained_dropped_events() -> heapless::Deque<$event_type, { $capacity }> {
+            critical_section::with(|cs| {
+                core::mem::replace(
+                    &mut *Self::dropped_buffer().borrow(cs).borrow_mut(),
+                    heapless::Deque::new(),
+                )
+            })
+        }
+
+        /// Internal: The `DroppedBuffer` store of this state machine type.
+        fn dropped_buffer() -> &'static critical_section::Mutex<
+            core::cell::RefCell<heapless::Deque<$event_type, { $capacity }>>,
+        > {
+            static DROPPED: critical_section::Mutex<
+                core::cell::RefCell<heapless::Deque<$event_type, { $capacity }>>,
+            > = critical_section::Mutex::new(core::cell::RefCell::new(heapless::Deque::new()));
+            &DROPPED
+        }
+    };
+    ([$capacity:expr] bytes $event_type:ty) => {
+        core::mem::size_of::<
+            critical_section::Mutex<
+                core::cell::RefCell<heapless::Deque<$event_type, { $capacity }>>,
+            >,
+        >()
+    };
+    ([$capacity:expr] record $cs:ident, $event:expr) => {{
+        let mut dropped = Self::dropped_buffer().borrow($cs).borrow_mut();
+        // Full: forget the oldest drop to keep the most recent ones
+        if dropped.is_full() {
+            let _ = dropped.pop_front();
+        }
+        let _ = dropped.push_back($event);
+    }};
+}
+
+// Embedded context - Internal macro that generates the `EmbedContext: Name,` wrapper
+// owning both the state and the context. `[$($bound)*]` is the `where` clause `dispatch()`
+// needs in this build (`Event: Clone` with the `concurrent` feature).
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_embed_context {
+    // No EmbedContext directive: nothing is generated
+    ([] $($rest:tt)*) => {};
+    (
+        [$embed:ident] [$($shared_type:ty)?] [$($instr_type:ty)?]
+        $enum_name:ident, $ctx_type:ty, $event_type:ty, [$($bound:tt)*]
+    ) => {
+        /// A
+        #[doc = concat!("[`", stringify!($enum_name), "`]")]
+        /// state machine that owns its context (generated by `EmbedContext`).
+        ///
+        /// Hooks see the embedded context exactly as with a separate `ctx` argument;
+        /// `dispatch()` just passes it internally.
+        pub struct $embed {
+            state: $enum_name,
+            context: $ctx_type,
+        }
+
+        impl $embed {
+            /// Wraps `state` and `context`. Call `init()` before the first `dispatch()`.
+            pub fn new(state: $enum_name, context: $ctx_type) -> Self {
+                Self { state, context }
+            }
+
+            /// Runs the entry action of the current state on the embedded context.
+            pub fn init(&mut self) {
+                self.state.do_init(&mut self.context);
+            }
+
+            /// Dispatches `event`, with the embedded context passed to the hooks.
+            pub fn dispatch(
+                &mut self $(, shared: &$shared_type)?
+                $(, instrumentation: &mut $instr_type)?,
+                event: &$event_type,
+            ) -> $crate::DispatchOutcome
+            $($bound)*
+            {
+                self.state.dispatch_ref(
+                    &mut self.context,
+                    $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                    $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                    event,
+                )
+            }
+
+            /// The current state.
+            pub fn state(&self) -> &$enum_name {
+                &self.state
+            }
+
+            /// The embedded context.
+            pub fn context(&self) -> &$ctx_type {
+                &self.context
+            }
+
+            /// The embedded context, mutably (e.g., to feed inputs between events).
+            pub fn context_mut(&mut self) -> &mut $ctx_type {
+                &mut self.context
+            }
+
+            /// Unwraps the state and the context.
+            pub fn into_parts(self) -> ($enum_name, $ctx_type) {
+                (self.state, self.context)
+            }
+        }
+    };
+}
+
+// Snapshot - Internal macro that generates the `Snapshot: Name { fields: [...] }` struct
+// and the `snapshot()` / `restore()` pair.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_snapshot {
+    // No Snapshot directive: nothing is generated
+    ([] $($rest:tt)*) => {};
+    (
+        [$snap:ident { fields: [ $( $field:ident : $field_ty:ty ),* ] }]
+        $enum_name:ident, $ctx_type:ty
+    ) => {
+        /// The state of a
+        #[doc = concat!("[`", stringify!($enum_name), "`]")]
+        /// state machine plus the context fields nominated by its `Snapshot` directive.
+        pub struct $snap {
+            /// The state when the snapshot was taken.
+            pub state: $enum_name,
+            $(
+                #[doc = concat!("The context's `", stringify!($field), "` when the snapshot was taken.")]
+                pub $field: $field_ty,
+            )*
+        }
+
+        impl $enum_name {
+            /// Captures the current state and the nominated context fields.
+            ///
+            /// Requires the generated enum to implement `Clone` (e.g., `Derives: [Clone],`).
+            pub fn snapshot(&self, ctx: &$ctx_type) -> $snap
+            where
+                for<'a> $enum_name: Clone,
+            {
+                $snap {
+                    state: self.clone(),
+                    $( $field: ctx.$field.clone(), )*
+                }
+            }
+
+            /// Rolls the state machine and the nominated context fields back to `snapshot`.
+            ///
+            /// Plain assignment: no `exit` or `entry` hook runs, and context fields that
+            /// weren't nominated keep their current values.
+            pub fn restore(&mut self, ctx: &mut $ctx_type, snapshot: $snap) {
+                *self = snapshot.state;
+                $( ctx.$field = snapshot.$field; )*
+            }
+        }
+    };
+}
+
+// Metrics - Internal macro that generates the `Metrics: events` counter of processed
+// events. `[$($atomic)*]` is the atomics module of the build (`core::sync::atomic`, or
+// `portable_atomic` with the `concurrent` feature).
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_metrics {
+    // No Metrics directive: zero-cost
+    ([] $($rest:tt)*) => {};
+    ([events] api [$($atomic:tt)*]) => {
+        /// Returns the number of events processed by this state machine type since
+        /// startup (or the last `reset_events_processed()`).
+        ///
+        /// Every event handled by a `process` block counts once, whether it caused a
+        /// transition or not, including events drained from the queue. The counter is
+        /// shared by all instances of the state machine type.
+        pub fn events_processed() -> u64 {
+            Self::events_counter().load($($atomic)*::Ordering::Relaxed)
+        }
+
+        /// Resets the processed events counter to zero.
+        pub fn reset_events_processed() {
+            Self::events_counter().store(0, $($atomic)*::Ordering::Relaxed);
+        }
+
+        /// Internal: The processed events counter of this state machine type.
+        fn events_counter() -> &'static $($atomic)*::AtomicU64 {
+            static EVENTS: $($atomic)*::AtomicU64 = $($atomic)*::AtomicU64::new(0);
+            &EVENTS
+        }
+    };
+    ([events] count [$($atomic:tt)*]) => {
+        Self::events_counter().fetch_add(1, $($atomic)*::Ordering::Relaxed);
+    };
+    ([$other:ident] api $($rest:tt)*) => {
+        compile_error!(concat!(
+            "unknown `Metrics: ", stringify!($other), "` (expected `Metrics: events`)"
+        ));
+    };
+    ([$other:ident] count $($rest:tt)*) => {};
+}
+
+// Max latency - Internal macro that generates the `MaxLatency: now_fn` worst-case
+// dispatch time and times each event. `[$($atomic)*]` is the atomics module of the build,
+// as for `__fsm_metrics!`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_max_latency {
+    // No MaxLatency directive: zero-cost
+    ([] api $($rest:tt)*) => {};
+    ([] time [$($atomic:tt)*] $dispatch:expr) => {
+        $dispatch
+    };
+    ([$now:path] api [$($atomic:tt)*]) => {
+        /// Returns the longest time a single event took to process (its `process`, `exit`
+        /// and `entry` hooks) since startup or the last `reset_max_dispatch_cycles()`, in
+        /// the unit of the `MaxLatency` clock.
+        ///
+        /// The maximum is shared by all instances of the state machine type. Events queued
+        /// behind others are timed on their own.
+        pub fn max_dispatch_cycles() -> u64 {
+            Self::max_latency_cell().load($($atomic)*::Ordering::Relaxed)
+        }
+
+        /// Resets the worst-case dispatch time to zero (e.g., after the startup phase).
+        pub fn reset_max_dispatch_cycles() {
+            Self::max_latency_cell().store(0, $($atomic)*::Ordering::Relaxed);
+        }
+
+        /// Internal: The worst-case dispatch time of this state machine type.
+        fn max_latency_cell() -> &'static $($atomic)*::AtomicU64 {
+            static MAX_LATENCY: $($atomic)*::AtomicU64 = $($atomic)*::AtomicU64::new(0);
+            &MAX_LATENCY
+        }
+    };
+    ([$now:path] time [$($atomic:tt)*] $dispatch:expr) => {{
+        let now: fn() -> u64 = $now;
+        let started = now();
+        let outcome = $dispatch;
+        // Wrapping: a free-running counter may overflow between the two samples
+        let elapsed = now().wrapping_sub(started);
+        Self::max_latency_cell().fetch_max(elapsed, $($atomic)*::Ordering::Relaxed);
+        outcome
+    }};
+}
+
+// Test internals - Internal macro that exposes the concurrent queue and dispatch flag of
+// a state machine (feature `test-internals`), so the queueing, overflow and drain logic
+// can be tested deterministically: single-threaded, and under Miri. Like `__fsm_log!`,
+// the feature is checked here, in this crate, not in the crate declaring the machine.
+#[cfg(feature = "test-internals")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_test_internals {
+    ($enum_name:ident, $event_type:ty) => {
+        /// Test hook (feature `test-internals`): marks a dispatch as active (`true`), so
+        /// `dispatch()` queues its event as if called from an ISR or another thread, or
+        /// as idle again (`false`). Drain the queue afterwards with `run_to_idle()`.
+        pub fn __test_set_dispatch_active(active: bool) {
+            paste::paste! {
+                [<DISPATCH_ACTIVE_ $enum_name:upper>]
+                    .store(active, portable_atomic::Ordering::Release);
+            }
+        }
+
+        /// Test hook (feature `test-internals`): queues `event` exactly as a `dispatch()`
+        /// finding another dispatch active would, applying the `OverflowPolicy`.
+        /// Returns the event back if it was dropped.
+        pub fn __test_enqueue(event: $event_type) -> Result<(), $event_type> {
+            Self::enqueue_pending(event)
+        }
+
+        /// Test hook (feature `test-internals`): removes the next queued event without
+        /// processing it. `None` if the queue is empty.
+        pub fn __test_pop() -> Option<$event_type> {
+            paste::paste! {
+                critical_section::with(|cs| {
+                    [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow_mut().pop_front()
+                })
+            }
+        }
+
+        /// Test hook (feature `test-internals`): the number of queued events.
+        pub fn __test_queue_len() -> usize {
+            paste::paste! {
+                critical_section::with(|cs| {
+                    [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow().len()
+                })
+            }
+        }
+
+        /// Test hook (feature `test-internals`): restores the per-type statics to their
+        /// initial values (empty queue, no active dispatch, no dropped events, neither
+        /// frozen nor paused), so that tests sharing a machine type start clean.
+        pub fn __test_reset() {
+            paste::paste! {
+                use portable_atomic::Ordering;
+
+                critical_section::with(|cs| {
+                    [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow_mut().clear();
+                });
+                [<DISPATCH_ACTIVE_ $enum_name:upper>].store(false, Ordering::Release);
+                [<DROPPED_EVENTS_ $enum_name:upper>].store(0, Ordering::Relaxed);
+                [<FROZEN_ $enum_name:upper>].store(false, Ordering::Release);
+                [<QUEUE_PAUSED_ $enum_name:upper>].store(false, Ordering::Release);
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "test-internals"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_test_internals {
+    ($($arg:tt)*) => {
+        // Without the feature, the queue is only reachable through the public API
+    };
+}
+
+// Path trace - Internal macro that generates the `PathTrace: N` transition path
+// (`path_trace()`) and records entered states into it.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __fsm_path_trace {
+    // No PathTrace directive: zero-cost
+    ([] $($rest:tt)*) => {};
+    ([$capacity:expr] api) => {
+        /// Returns the recent path of entered states, oldest first, separated by `>`
+        /// (e.g., `"Idle>Connecting>Retrying"`), for crash dumps and field debugging.
+        ///
+        /// `init()` and every `Transition::To` append the entered state. When the
+        /// `PathTrace` capacity (in bytes) is exceeded, the oldest states are dropped.
+        /// The path is shared by all instances of the state machine type.
+        pub fn path_trace() -> heapless::String<{ $capacity }> {
+            Self::path_trace_buffer().snapshot()
+        }
+
+        /// Internal: The `PathTrace` buffer of this state machine type.
+        fn path_trace_buffer() -> &'static $crate::PathTrace<{ $capacity }> {
+            static TRACE: $crate::PathTrace<{ $capacity }> = $crate::PathTrace::new();
+            &TRACE
+        }
+    };
+    ([$capacity:expr] record $state:expr) => {
+        Self::path_trace_buffer().record($state);
+    };
+}
+
+/// Represents the result of a state processing step.
+///
+/// This enum guides the state machine on whether to stay or switch states.
+/// **Every `process` closure must return a `Transition`** (or, as shorthand, a bare
+/// state for `Transition::To` or `()` for `Transition::None`).
+///
+/// # Type Parameters
+///
+/// * `S` - The state machine enum type
+/// * `E` - The event type, carried by `Transition::ToAndPost` (inferred in `process`
+///   blocks; defaults to `()` so standalone transitions can be written `Transition<S>`)
+///
+/// # When to Use Each Variant
+///
+/// ## `Transition::None`
+/// Use when an event should be processed but doesn't require changing states:
+/// - Event updates context but state logic remains the same
+/// - Event should be ignored in the current state
+/// - Handling events that don't affect state flow
+///
+/// ## `Transition::Unhandled`
+/// Use when the current state has no handler for the event:
+/// - Same effect on the state as `Transition::None`
+/// - Additionally invokes the `OnUnhandled` hook (if declared)
+///
+/// ## `Transition::Reenter`
+/// Use to restart the current state's lifecycle without changing its data:
+/// - Runs `exit` then `entry` on the current state (e.g., to reset a timer)
+/// - Carried fields are preserved, no need to restate them
+///
+/// ## `Transition::To(State)`
+/// Use when an event should trigger a state change:
+/// - Event triggers a state transition
+/// - Conditions are met for moving to another state
+/// - Need to execute `exit` and `entry` hooks
+/// - Even for self-transitions (same state to same state)
+///
+/// ## `Transition::ToAndPost(State, Event)`
+/// Use when the new state needs a follow-up event right away:
+/// - Same lifecycle as `Transition::To`
+/// - Then the new state processes the event before `dispatch` returns
+///
+/// ## `Transition::Retry`
+/// Use when the current state's attempt failed and should be made again:
+/// - Re-enters the state like `Transition::Reenter`
+/// - Moves to the state's `on_exhausted` state once its `retry: { max: N, ... }` is spent
+///
+/// ## `Transition::Await(token)`
+/// Use when the state started an operation that completes through a callback:
+/// - Stays in the current state like `Transition::None`, parked on `token`
+/// - The callback later resumes it with `complete(token, event)`
+///
+/// # Examples
+///
+/// ```rust
+/// use typed_fsm::Transition;
+///
+/// #[derive(Debug)]
+/// enum MyState {
+///     Idle,
+///     Active { speed: u32 },
+/// }
+///
+/// // Stay in current state - no hooks execute
+/// let no_change: Transition<MyState> = Transition::None;
+///
+/// // Transition to a new state - exit + entry execute
+/// let change: Transition<MyState> = Transition::To(MyState::Idle);
+///
+/// // Transition with state data
+/// let with_data: Transition<MyState> = Transition::To(MyState::Active { speed: 100 });
+/// ```
+///
+/// # Common Pattern in `process` Hook
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context { data: u32 }
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Update(u32), Activate, Ignore }
+/// # state_machine! {
+/// #     Name: FSM,
+/// #     Context: Context,
+/// #     Event: Event,
+/// #     States: {
+/// #         Idle => {
+/// process: |ctx, evt| {
+///     match evt {
+///         Event::Update(value) => {
+///             ctx.data = *value;        // Update context
+///             Transition::None          // Stay in same state
+///         },
+///         Event::Activate => {
+///             Transition::To(FSM::Active { speed: 100 })  // Change state
+///         },
+///         Event::Ignore => Transition::None  // Do nothing
+///     }
+/// }
+/// #         },
+/// #         Active { speed: u32 } => {
+/// #             process: |ctx, evt| { Transition::None }
+/// #         }
+/// #     }
+/// # }
+/// ```
+///
+/// # Performance
+///
+/// Creating a `Transition` has zero runtime overhead. The enum is optimized
+/// by the compiler and typically doesn't allocate any heap memory.
+///
+/// # Thread Safety
+///
+/// `Transition` is `Send` and `Sync` if the state type `S` is `Send` and `Sync`.
+pub enum Transition<S, E = ()> {
+    /// Stay in the current state (no action required).
+    ///
+    /// Use this when an event should be handled but doesn't trigger a state change.
+    ///
+    /// # When to Use
+    ///
+    /// - Event updates context but state remains the same
+    /// - Event should be ignored in this state
+    /// - Processing an event that doesn't affect state flow
+    ///
+    /// # Lifecycle Impact
+    ///
+    /// - `process` executes
+    /// - `exit` does NOT execute (no state change)
+    /// - `entry` does NOT execute (no state change)
+    /// - State remains unchanged
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use typed_fsm::{state_machine, Transition};
+    /// # struct Context { counter: u32 }
+    /// # #[derive(Debug, Clone)]
+    /// # enum Event { Increment }
+    /// # state_machine! {
+    /// #     Name: FSM,
+    /// #     Context: Context,
+    /// #     Event: Event,
+    /// #     States: {
+    /// #         Active => {
+    /// process: |ctx, evt| {
+    ///     match evt {
+    ///         Event::Increment => {
+    ///             ctx.counter += 1;   // Update context
+    ///             Transition::None    // Stay in Active state
+    ///         }
+    ///     }
+    /// }
+    /// #         }
+    /// #     }
+    /// # }
+    /// ```
+    None,
+
+    /// Stay in the current state because the event has no handler here.
+    ///
+    /// Behaves exactly like [`Transition::None`] as far as the state is concerned,
+    /// but additionally invokes the machine-level `OnUnhandled` hook (if declared).
+    /// This makes it possible to tell "I deliberately ignored this event" apart from
+    /// "this state has no arm for this event".
+    ///
+    /// # Lifecycle Impact
+    ///
+    /// - `process` executes
+    /// - `OnUnhandled` executes (if defined)
+    /// - `exit` and `entry` do NOT execute
+    /// - State remains unchanged
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use typed_fsm::{state_machine, Transition};
+    /// # struct Context { unhandled: u32 }
+    /// # #[derive(Debug, Clone)]
+    /// # enum Event { Start, Reset }
+    /// # state_machine! {
+    /// #     Name: FSM,
+    /// #     Context: Context,
+    /// #     Event: Event,
+    /// #     OnUnhandled: |ctx, _state, _evt| { ctx.unhandled += 1; },
+    /// #     States: {
+    /// #         Idle => {
+    /// process: |ctx, evt| {
+    ///     match evt {
+    ///         Event::Start => Transition::None,  // Deliberately ignored
+    ///         _ => Transition::Unhandled         // No handler in this state
+    ///     }
+    /// }
+    /// #         }
+    /// #     }
+    /// # }
+    /// ```
+    Unhandled,
+
+    /// Re-enter the current state: run its `exit` then `entry` actions.
+    ///
+    /// Equivalent to a self-transition (`Transition::To` with the same variant),
+    /// except that the current state value, including its fields, is kept unchanged.
+    /// Both hooks see the current state's own name as the counterpart state.
+    ///
+    /// ```rust
+    /// # use typed_fsm::{state_machine, Transition};
+    /// # struct Context { elapsed_ms: u32 }
+    /// # #[derive(Debug, Clone)]
+    /// # enum Event { Activity, Tick(u32) }
+    /// # state_machine! {
+    /// #     Name: FSM,
+    /// #     Context: Context,
+    /// #     Event: Event,
+    /// #     States: {
+    /// #         Active { session: u32 } => {
+    /// #             entry: |ctx| { ctx.elapsed_ms = 0; }
+    /// process: |ctx, evt| {
+    ///     match evt {
+    ///         Event::Activity => Transition::Reenter,  // Restart the inactivity timer
+    ///         Event::Tick(ms) => { ctx.elapsed_ms += ms; Transition::None }
+    ///     }
+    /// }
+    /// #         }
+    /// #     }
+    /// # }
+    /// ```
+    Reenter,
+
+    /// Transition to a new state.
+    ///
+    /// This will trigger the full state transition lifecycle:
+    /// `exit` (old state) → `entry` (new state) → update state
+    ///
+    /// # When to Use
+    ///
+    /// - Event triggers a state change
+    /// - Conditions are met for transitioning
+    /// - Need to move to a different state
+    /// - Self-transitions (same state, but re-execute entry/exit)
+    ///
+    /// # Arguments
+    ///
+    /// * `0` - The new state instance. Can carry data (payloads).
+    ///
+    /// # Lifecycle Impact
+    ///
+    /// 1. `process` executes and returns new state
+    /// 2. Current state's `exit` executes (if defined)
+    /// 3. New state's `entry` executes (if defined)
+    /// 4. State updates to the new state
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use typed_fsm::{state_machine, Transition};
+    /// # struct Context { }
+    /// # #[derive(Debug, Clone)]
+    /// # enum Event { Start, Stop }
+    /// # state_machine! {
+    /// #     Name: FSM,
+    /// #     Context: Context,
+    /// #     Event: Event,
+    /// #     States: {
+    /// #         Idle => {
+    /// process: |ctx, evt| {
+    ///     match evt {
+    ///         Event::Start => {
+    ///             // Transition to Running state with data
+    ///             Transition::To(FSM::Running { speed: 100 })
+    ///         },
+    ///         Event::Stop => Transition::None
+    ///     }
+    /// }
+    /// #         },
+    /// #         Running { speed: u32 } => {
+    /// #             process: |ctx, evt| { Transition::None }
+    /// #         }
+    /// #     }
+    /// # }
+    /// ```
+    ///
+    /// # Performance
+    ///
+    /// State transitions use move semantics, making them extremely fast
+    /// (typically just a few CPU instructions).
+    To(S),
+
+    /// Transition to a state that is only built once the transition is applied.
+    ///
+    /// `dispatch` calls the function exactly once, after the current state's
+    /// `exit` hook has run and right before the new state's `entry`. If the
+    /// transition is never applied (e.g. a locked `StrictTerminal` state), the
+    /// function is never called. Useful when building the target is expensive
+    /// or must observe side effects of `exit`.
+    ///
+    /// The function must be a plain `fn` or a non-capturing closure. Since the
+    /// target does not exist yet when `exit` runs, `exit` receives `""` as the
+    /// name of the next state.
+    ///
+    /// ```rust
+    /// # use typed_fsm::{state_machine, Transition};
+    /// # struct Context { }
+    /// # #[derive(Debug, Clone)]
+    /// # enum Event { Load }
+    /// # state_machine! {
+    /// #     Name: FSM,
+    /// #     Context: Context,
+    /// #     Event: Event,
+    /// #     States: {
+    /// #         Idle => {
+    /// process: |ctx, evt| {
+    ///     Transition::ToFn(|| FSM::Loaded { table: [0; 64] })
+    /// }
+    /// #         },
+    /// #         Loaded { table: [u8; 64] } => {
+    /// #             process: |ctx, evt| { Transition::None }
+    /// #         }
+    /// #     }
+    /// # }
+    /// ```
+    ToFn(fn() -> S),
+
+    /// Transition to a new state, then hand it a follow-up event.
+    ///
+    /// Applies the transition exactly like [`Transition::To`], then the new state
+    /// processes the event before `dispatch` returns, ahead of any event queued with
+    /// `post()`. The transition completes first (run-to-completion), so the
+    /// follow-up sees the new state's `entry` effects. Typical use: "go to `B`, then
+    /// feed `B` its initialization event", without a second explicit dispatch.
+    ///
+    /// `dispatch` still reports the outcome of the original event
+    /// (`DispatchOutcome::Transitioned`).
+    ///
+    /// ```rust
+    /// # use typed_fsm::{state_machine, Transition};
+    /// # struct Context { configured: bool }
+    /// # #[derive(Debug, Clone)]
+    /// # enum Event { Connect, Configure }
+    /// # state_machine! {
+    /// #     Name: FSM,
+    /// #     Context: Context,
+    /// #     Event: Event,
+    /// #     States: {
+    /// #         Idle => {
+    /// process: |ctx, evt| {
+    ///     match evt {
+    ///         Event::Connect => Transition::ToAndPost(FSM::Connected, Event::Configure),
+    ///         Event::Configure => Transition::None
+    ///     }
+    /// }
+    /// #         },
+    /// #         Connected => {
+    /// #             process: |ctx, evt| { ctx.configured = true; Transition::None }
+    /// #         }
+    /// #     }
+    /// # }
+    /// ```
+    ToAndPost(S, E),
+
+    /// Retry the current state: re-enter it, or give up once its attempts are spent.
+    ///
+    /// With a `retry: { max: N, on_exhausted: Failed },` block on the state, each
+    /// `Retry` counts one failed attempt (entering the state was the first). The first
+    /// `N - 1` act like [`Transition::Reenter`]; the `N`th transitions to the
+    /// `on_exhausted` state like [`Transition::To`]. Without a `retry:` block, `Retry`
+    /// always re-enters. The attempts are counted in the context's [`Bookkeeping`]
+    /// field, which `retry:` requires. `retry_count(ctx)` reports the retries made so
+    /// far; the count resets on `init()` and whenever a transition leaves the state.
+    ///
+    /// ```rust
+    /// # use typed_fsm::{state_machine, Bookkeeping, Transition};
+    /// # struct Context { link_up: bool, fsm: Bookkeeping }
+    /// # #[derive(Debug, Clone)]
+    /// # enum Event { Timeout }
+    /// # state_machine! {
+    /// #     Name: FSM,
+    /// #     Context: Context,
+    /// #     Event: Event,
+    /// #     Bookkeeping: fsm,
+    /// #     States: {
+    /// #         Connecting => {
+    /// #             retry: { max: 3, on_exhausted: Offline },
+    /// process: |ctx, evt| {
+    ///     if ctx.link_up { Transition::To(FSM::Online) } else { Transition::Retry }
+    /// }
+    /// #         },
+    /// #         Online => { process: |ctx, evt| { Transition::None } },
+    /// #         Offline => { process: |ctx, evt| { Transition::None } }
+    /// #     }
+    /// # }
+    /// ```
+    Retry,
+
+    /// Stay in the current state, parked until `complete(token, event)` resumes it.
+    ///
+    /// A lightweight continuation for callback-driven I/O without an async runtime:
+    /// the state starts an operation, hands `token` to its completion callback and
+    /// returns `Await(token)`. When the callback fires, the generated
+    /// `complete(ctx, token, event)` dispatches `event` to the state, provided that it
+    /// is still the current state and still awaiting `token`. A stale completion (the
+    /// machine left the state, or awaited another token since) is dropped and
+    /// `complete()` returns `DispatchOutcome::Stale`. A completion that is not
+    /// processed (the machine is frozen, or the `EventFilter` rejects it) leaves the
+    /// token pending, so the callback can deliver it again.
+    ///
+    /// Parking does not block other events: a `Cancel` or `Timeout` that leaves the
+    /// state simply discards the pending completion. Otherwise, `Await` behaves like
+    /// [`Transition::None`] (no hook runs, `dispatch` returns `Stayed`).
+    ///
+    /// The pending token is kept in the context's [`Bookkeeping`] field, so each machine
+    /// awaits its own completions. Without the `Bookkeeping` directive, debug builds
+    /// panic on `Await` and release builds treat it as `Transition::Unhandled`.
+    ///
+    /// ```rust
+    /// # use typed_fsm::{state_machine, Bookkeeping, Transition, DispatchOutcome};
+    /// # struct Context { next_token: usize, fsm: Bookkeeping }
+    /// # #[derive(Debug, Clone)]
+    /// # enum Event { Read, Done(u8) }
+    /// # state_machine! {
+    /// #     Name: FSM,
+    /// #     Context: Context,
+    /// #     Event: Event,
+    /// #     Bookkeeping: fsm,
+    /// #     States: {
+    /// #         Idle => {
+    /// process: |ctx, evt| {
+    ///     match evt {
+    ///         Event::Read => {
+    ///             ctx.next_token += 1;
+    ///             // start_read(ctx.next_token, callback) ...
+    ///             Transition::Await(ctx.next_token)
+    ///         }
+    ///         Event::Done(byte) => Transition::To(FSM::Received { byte: *byte }),
+    ///     }
+    /// }
+    /// #         },
+    /// #         Received { byte: u8 } => { process: |ctx, evt| { Transition::None } }
+    /// #     }
+    /// # }
+    /// let mut ctx = Context { next_token: 0, fsm: Bookkeeping::new() };
+    /// let mut fsm = FSM::start_in(FSM::Idle, &mut ctx);
+    ///
+    /// fsm.dispatch(&mut ctx, &Event::Read);
+    /// assert_eq!(fsm.pending_token(&ctx), Some(1));
+    ///
+    /// // ... later, from the completion callback
+    /// assert_eq!(fsm.complete(&mut ctx, 1, &Event::Done(7)), DispatchOutcome::Transitioned);
+    /// assert!(matches!(fsm, FSM::Received { byte: 7 }));
+    /// ```
+    Await(usize),
+}
+
+impl<S, E> Transition<S, E> {
+    /// Returns `Transition::To(state)` if `cond` is true, otherwise `Transition::None`.
+    ///
+    /// Shorthand for the guard pattern `if cond { To(state) } else { None }`.
+    ///
+    /// ```rust
+    /// use typed_fsm::Transition;
+    ///
+    /// # #[derive(Debug, PartialEq)]
+    /// enum Door { Open, Closed }
+    ///
+    /// let pin_ok = true;
+    /// let granted: Transition<Door> = Transition::to_if(pin_ok, Door::Open);
+    /// assert!(matches!(granted, Transition::To(Door::Open)));
+    /// let denied: Transition<Door> = Transition::to_if(!pin_ok, Door::Open);
+    /// assert!(matches!(denied, Transition::None));
+    /// ```
+    #[inline(always)]
+    pub fn to_if(cond: bool, state: S) -> Self {
+        if cond {
+            Transition::To(state)
+        } else {
+            Transition::None
+        }
+    }
+
+    /// Returns `Transition::To(if_true)` if `cond` is true, otherwise `Transition::To(if_false)`.
+    ///
+    /// The two-way branch of a guard: unlike `to_if`, it always transitions. Both states
+    /// are constructed before the choice is made, and the other one is simply dropped;
+    /// use a plain `if` when building a state is expensive.
+    ///
+    /// ```rust
+    /// use typed_fsm::Transition;
+    ///
+    /// # #[derive(Debug, PartialEq)]
+    /// enum Atm { Menu, Locked }
+    ///
+    /// let attempts_left = 0;
+    /// let next: Transition<Atm> = Transition::to_or(attempts_left > 0, Atm::Menu, Atm::Locked);
+    /// assert!(matches!(next, Transition::To(Atm::Locked)));
+    /// ```
+    #[doc(alias = "branch")]
+    #[inline(always)]
+    pub fn to_or(cond: bool, if_true: S, if_false: S) -> Self {
+        if cond {
+            Transition::To(if_true)
+        } else {
+            Transition::To(if_false)
+        }
+    }
+}
+
+/// Conversion of a `process` block's value into a `Transition`.
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can name it. A `process` block may evaluate to a `Transition`,
+/// to a bare state (`Transition::To`), or to `()` (`Transition::None`).
+#[doc(hidden)]
+pub trait IntoTransition<S, E = ()> {
+    fn into_transition(self) -> Transition<S, E>;
+}
+
+impl<S, E> IntoTransition<S, E> for Transition<S, E> {
+    #[inline(always)]
+    fn into_transition(self) -> Transition<S, E> {
+        self
+    }
+}
+
+impl<S, E> IntoTransition<S, E> for () {
+    #[inline(always)]
+    fn into_transition(self) -> Transition<S, E> {
+        Transition::None
+    }
+}
+
+/// What a call to the generated `dispatch()` did with its event.
+///
+/// Returned by `dispatch()`, `dispatch_owned()` and `dispatch_then()`. Events
+/// processed afterwards from the `post()` / concurrent queue do not affect it:
+/// it only describes the event passed in.
+///
+/// # Example
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition, DispatchOutcome};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Toggle, Ignore }
+/// # state_machine! {
+/// #     Name: Switch,
+/// #     Context: Context,
+/// #     Event: Event,
+/// #     States: {
+/// #         Off => { process: |_ctx, evt| { Transition::to_if(matches!(evt, Event::Toggle), Switch::On) } },
+/// #         On => { process: |_ctx, evt| { Transition::to_if(matches!(evt, Event::Toggle), Switch::Off) } }
+/// #     }
+/// # }
+/// let mut ctx = Context {};
+/// let mut fsm = Switch::start_in(Switch::Off, &mut ctx);
+///
+/// assert_eq!(fsm.dispatch(&mut ctx, &Event::Toggle), DispatchOutcome::Transitioned);
+/// assert_eq!(fsm.dispatch(&mut ctx, &Event::Ignore), DispatchOutcome::Stayed);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DispatchOutcome {
+    /// The event was processed and applied a `Transition::To`.
+    Transitioned,
+    /// The event was processed without a `Transition::To` (`None`, `Reenter`, `Unhandled`
+    /// or `Await`).
+    Stayed,
+    /// Another dispatch was active, so the event was queued for it (feature `concurrent`).
+    Queued,
+    /// The state machine is frozen (see `freeze()`): the event was ignored and no hook ran.
+    Frozen,
+    /// The event was past its deadline (see `dispatch_before()`): it was dropped and no
+    /// hook ran.
+    Expired,
+    /// The `EventFilter` predicate rejected the event: it was dropped and no hook ran.
+    Filtered,
+    /// The completion's token is not awaited by the current state (see
+    /// `Transition::Await`): the event was dropped and no hook ran.
+    Stale,
+}
+
+/// Error of the generated `FromStr` / `TryFrom<&str>` implementations, which build a
+/// state from its declared name (e.g., `"Idle".parse::<Machine>()`).
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition, ParseStateError};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Tick }
+/// # state_machine! {
+/// #     Name: Pump,
+/// #     Context: Context,
+/// #     Event: Event,
+/// #     States: {
+/// #         Idle => { process: |_ctx, _evt| { Transition::None } },
+/// #         Running { rpm: u32 } => { process: |_ctx, _evt| { Transition::None } }
+/// #     }
+/// # }
+/// assert!(matches!("Idle".parse::<Pump>(), Ok(Pump::Idle)));
+/// assert_eq!("Running".parse::<Pump>().unwrap_err(), ParseStateError::HasFields);
+/// assert_eq!("Sleeping".parse::<Pump>().unwrap_err(), ParseStateError::Unknown);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseStateError {
+    /// No state has this name (see `STATE_NAMES`; names are case-sensitive).
+    Unknown,
+    /// The state carries fields, so it can't be built from its name alone.
+    HasFields,
+}
+
+impl core::fmt::Display for ParseStateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseStateError::Unknown => f.write_str("unknown state name"),
+            ParseStateError::HasFields => {
+                f.write_str("state carries fields and can't be built from its name")
+            }
+        }
+    }
+}
+
+/// Per-instance bookkeeping of a state machine, kept in its context.
+///
+/// The state machine enum only holds the current state, so the features that track a
+/// machine over time store their counters in a context field of this type, named with
+/// the `Bookkeeping: field,` directive. Each context (and so each machine) gets its own
+/// counters. Used by:
+/// - `retry:` budgets, counting the `Transition::Retry` attempts (`retry_count()`)
+/// - `Transition::Await`, holding the token the current state is parked on
+///   (`pending_token()`, `complete()`)
+/// - the `Watchdog`, counting the consecutive dispatches that stayed in the same state
+/// - the generation assigned by every `init()`, checked by `dispatch_gen()`
+///   (`generation()`)
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Bookkeeping, Transition};
+/// struct Context {
+///     fsm: Bookkeeping,
+/// }
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Timeout }
+/// state_machine! {
+///     Name: Dialer,
+///     Context: Context,
+///     Event: Event,
+///     Bookkeeping: fsm,
+///     States: {
+///         Dialing => {
+///             retry: { max: 3, on_exhausted: Failed },
+///             process: |ctx, evt| { Transition::Retry }
+///         },
+///         Failed => { process: |ctx, evt| { Transition::None } }
+///     }
+/// }
+///
+/// let mut ctx = Context { fsm: Bookkeeping::new() };
+/// let mut dialer = Dialer::start_in(Dialer::Dialing, &mut ctx);
+/// dialer.dispatch(&mut ctx, &Event::Timeout);
+/// assert_eq!(dialer.retry_count(&ctx), 1);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Bookkeeping {
+    // Not part of the public API: only `pub` for the code generated by `state_machine!`
+    #[doc(hidden)]
+    pub retries: usize,
+    // `state_index() + 1` of the state parked by `Transition::Await`, `0` if none
+    #[doc(hidden)]
+    pub awaiting: usize,
+    #[doc(hidden)]
+    pub token: usize,
+    #[doc(hidden)]
+    pub generation: u32,
+    // Consecutive dispatches without a transition, for the `Watchdog`
+    #[doc(hidden)]
+    pub stayed: usize,
+}
+
+impl Bookkeeping {
+    /// Fresh bookkeeping, for a machine that has not started yet.
+    pub const fn new() -> Self {
+        Bookkeeping {
+            retries: 0,
+            awaiting: 0,
+            token: 0,
+            generation: 0,
+            stayed: 0,
+        }
+    }
+
+    /// Forgets the retries and the pending `Transition::Await` of the state just left.
+    #[doc(hidden)]
+    #[inline(always)]
+    pub fn leave_state(&mut self) {
+        self.retries = 0;
+        self.awaiting = 0;
+    }
+
+    /// The token the state at `state_index` is parked on, if any.
+    #[doc(hidden)]
+    #[inline]
+    pub fn pending(&self, state_index: usize) -> Option<usize> {
+        (self.awaiting == state_index + 1).then_some(self.token)
+    }
+
+    /// Parks the state at `state_index` on `token`, replacing any pending one.
+    #[doc(hidden)]
+    #[inline]
+    pub fn park(&mut self, state_index: usize, token: usize) {
+        self.awaiting = state_index + 1;
+        self.token = token;
+    }
+
+    /// Takes the pending completion if it is `token`, awaited by the state at
+    /// `state_index`, so that it resumes the state at most once.
+    #[doc(hidden)]
+    #[inline]
+    pub fn unpark(&mut self, state_index: usize, token: usize) -> bool {
+        let resumes = self.pending(state_index) == Some(token);
+        if resumes {
+            self.awaiting = 0;
+        }
+        resumes
+    }
+}
+
+/// Error of the generated `dispatch_gen()`: the caller's generation is not the state
+/// machine's current one, so it holds a stale reference from before the last `init()`
+/// (`start_in()`, `start()`, `reset()`). The event was not dispatched.
+///
+/// The generation is kept per instance in the context's [`Bookkeeping`] field, so
+/// `generation()` and `dispatch_gen()` require the `Bookkeeping` directive.
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Bookkeeping, Transition, StaleGen};
+/// # struct Context { fsm: Bookkeeping }
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Ping }
+/// # state_machine! {
+/// #     Name: Session,
+/// #     Context: Context,
+/// #     Event: Event,
+/// #     Bookkeeping: fsm,
+/// #     States: {
+/// #         Open => { process: |_ctx, _evt| { Transition::None } }
+/// #     }
+/// # }
+/// let mut ctx = Context { fsm: Bookkeeping::new() };
+/// let mut session = Session::start_in(Session::Open, &mut ctx);
+/// let handle = session.generation(&ctx);
+///
+/// // Reconnection: the session is recreated
+/// session = Session::start_in(Session::Open, &mut ctx);
+/// let err = session.dispatch_gen(&mut ctx, &Event::Ping, handle).unwrap_err();
+/// assert_eq!(err, StaleGen { expected: handle, current: session.generation(&ctx) });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaleGen {
+    /// The generation the caller passed to `dispatch_gen()`.
+    pub expected: u32,
+    /// The state machine's current generation (see `generation()`).
+    pub current: u32,
+}
+
+impl core::fmt::Display for StaleGen {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "stale state machine generation {} (current: {})",
+            self.expected, self.current
+        )
+    }
+}
+
+/// What the `concurrent` event queue does with an event that doesn't fit, selected with
+/// the `OverflowPolicy` directive.
+///
+/// Every dropped event is counted by `dropped_events_count()`. The default build's
+/// `post()` queue ignores the policy and always hands the new event back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drops the new event (the default). Debug builds panic to expose undersized
+    /// queues; release builds drop silently.
+    DropNewest,
+    /// Drops the oldest queued event to make room for the new one, e.g., when only the
+    /// latest sensor readings matter. Never panics, and `post()` always succeeds.
+    DropOldest,
+    /// Panics in every build, for systems where losing an event is a fault.
+    Panic,
+}
+
+/// Consistent snapshot of a concurrent state machine's event queue.
+///
+/// Returned by the generated `overflow_report()` method (feature `concurrent`).
+/// All fields are captured together inside one critical section, so they are
+/// always consistent with each other.
+#[cfg(feature = "concurrent")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowReport {
+    /// Number of events dropped because the queue was full (see `dropped_events_count()`).
+    pub dropped: usize,
+    /// Maximum number of events the queue can hold (the `QueueCapacity`, or `usize::MAX`
+    /// with the unbounded `alloc` queue).
+    pub capacity: usize,
+    /// Number of events currently waiting in the queue.
+    pub current_len: usize,
+}
+
+/// Pending event queue of a concurrent state machine.
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can name it. By default it is a `heapless::Deque` holding at most
+/// `N` (`QueueCapacity`) events. With the `alloc` feature it is an unbounded `VecDeque`
+/// that never rejects an event, and `N` is ignored.
+#[cfg(feature = "concurrent")]
+pub struct PendingQueue<E, const N: usize> {
+    #[cfg(not(feature = "alloc"))]
+    events: heapless::Deque<E, N>,
+    #[cfg(feature = "alloc")]
+    events: alloc::collections::VecDeque<E>,
+}
+
+#[cfg(feature = "concurrent")]
+impl<E, const N: usize> PendingQueue<E, N> {
+    /// Creates an empty queue (usable in a `static`).
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(not(feature = "alloc"))]
+            events: heapless::Deque::new(),
+            #[cfg(feature = "alloc")]
+            events: alloc::collections::VecDeque::new(),
+        }
+    }
+
+    /// Appends `event`, handing it back if the queue is full.
+    pub fn push_back(&mut self, event: E) -> Result<(), E> {
+        #[cfg(not(feature = "alloc"))]
+        {
+            self.events.push_back(event)
+        }
+        #[cfg(feature = "alloc")]
+        {
+            self.events.push_back(event);
+            Ok(())
+        }
+    }
+
+    /// Removes the oldest event.
+    pub fn pop_front(&mut self) -> Option<E> {
+        self.events.pop_front()
+    }
+
+    /// The oldest event, without removing it.
+    pub fn front(&self) -> Option<&E> {
+        self.events.front()
+    }
+
+    /// Number of queued events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// `true` if no event is queued.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Discards every queued event.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Maximum number of events: `N`, or `usize::MAX` when unbounded.
+    pub fn capacity(&self) -> usize {
+        if cfg!(feature = "alloc") {
+            usize::MAX
+        } else {
+            N
+        }
+    }
+}
+
+#[cfg(feature = "concurrent")]
+impl<E, const N: usize> Default for PendingQueue<E, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// `str` equality isn't `const`: compare the bytes
+const fn same_name(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Edge lists per state (`(event, to)` pairs, padded), plus their lengths.
+#[doc(hidden)]
+pub type TransitionsByState<const STATES: usize, const EDGES: usize> = (
+    [[(&'static str, &'static str); EDGES]; STATES],
+    [usize; STATES],
+);
+
+/// Compile-time grouping behind the generated `available_transitions()`.
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can call it. Row `i` lists the `(event, to)` pairs of the edges of
+/// `table` leaving `states[i]`, in table order, and the second array holds how many of
+/// the row's slots are used.
+#[doc(hidden)]
+pub const fn transitions_by_state<const STATES: usize, const EDGES: usize>(
+    states: &[&'static str],
+    table: &[(&'static str, &'static str, &'static str)],
+) -> TransitionsByState<STATES, EDGES> {
+    let mut rows = [[("", ""); EDGES]; STATES];
+    let mut lengths = [0; STATES];
+    let mut state = 0;
+    while state < STATES {
+        let mut edge = 0;
+        while edge < EDGES {
+            let (from, event, to) = table[edge];
+            if same_name(from, states[state]) {
+                rows[state][lengths[state]] = (event, to);
+                lengths[state] += 1;
+            }
+            edge += 1;
+        }
+        state += 1;
+    }
+    (rows, lengths)
+}
+
+/// Compile-time lookup table behind the generated transition counters
+/// (`CountTransitions`).
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can call it. Entry `from * states.len() + to` holds one more than the
+/// index in `table` of the first edge from the `from`-th to the `to`-th state of
+/// `states`, or `0` if there is none, so that `dispatch` finds an edge with a single
+/// indexing. Edges with unknown names (rejected by the macro) are skipped.
+#[doc(hidden)]
+pub const fn edge_lookup<const CELLS: usize>(
+    states: &[&'static str],
+    table: &[(&'static str, &'static str, &'static str)],
+) -> [u16; CELLS] {
+    const fn index_of(states: &[&'static str], name: &str) -> usize {
+        let mut state = 0;
+        while state < states.len() {
+            if same_name(states[state], name) {
+                return state;
+            }
+            state += 1;
+        }
+        usize::MAX
+    }
+
+    assert!(
+        table.len() < u16::MAX as usize,
+        "too many TransitionTable edges to count"
+    );
+    let mut lookup = [0; CELLS];
+    // Backwards, so that the first of several edges between two states wins
+    let mut edge = table.len();
+    while edge > 0 {
+        edge -= 1;
+        let (from, _, to) = table[edge];
+        let (from, to) = (index_of(states, from), index_of(states, to));
+        if from != usize::MAX && to != usize::MAX {
+            lookup[from * states.len() + to] = edge as u16 + 1;
+        }
+    }
+    lookup
+}
+
+/// Runtime check behind the generated `validate_transition_table()`.
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can call it. Panics, listing every unknown name once, if a
+/// source or target state of `table` is missing from `states`.
+#[doc(hidden)]
+pub fn validate_transition_table(machine: &str, states: &[&str], table: &[(&str, &str, &str)]) {
+    // Every `from`/`to` name of the table, in order
+    fn endpoints<'a>(table: &'a [(&'a str, &'a str, &'a str)]) -> impl Iterator<Item = &'a str> {
+        table.iter().flat_map(|&(from, _, to)| [from, to])
+    }
+
+    struct Unknown<'a> {
+        states: &'a [&'a str],
+        table: &'a [(&'a str, &'a str, &'a str)],
+    }
+
+    impl core::fmt::Display for Unknown<'_> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            let mut separator = "";
+            for (i, name) in endpoints(self.table).enumerate() {
+                let repeated = endpoints(self.table).take(i).any(|seen| seen == name);
+                if !repeated && !self.states.contains(&name) {
+                    write!(f, "{}{}", separator, name)?;
+                    separator = ", ";
+                }
+            }
+            Ok(())
+        }
+    }
+
+    if endpoints(table).all(|name| states.contains(&name)) {
+        return;
+    }
+
+    panic!(
+        "[{}] TransitionTable names unknown states: {} (declared states: {:?})",
+        machine,
+        Unknown { states, table },
+        states
+    );
+}
+
+/// Dispatching implemented by every generated state machine without `SharedRef` or
+/// `Instrumentation`.
+///
+/// Lets generic code such as `FsmCell` drive any state machine. `C` is the `Context`
+/// type (`()` for context-free machines) and `E` the `Event` type. They are type
+/// parameters rather than associated types so that a public machine may keep private
+/// context and event types.
+pub trait Dispatch<C, E> {
+    /// Dispatches `event`, exactly like the generated `dispatch_owned()`.
+    fn dispatch_event(&mut self, ctx: &mut C, event: E) -> DispatchOutcome;
+}
+
+/// The lifecycle of every generated state machine without `SharedRef` or
+/// `Instrumentation`, for generic drivers and schedulers that accept any state machine.
+///
+/// `C` is the `Context` type (`()` for context-free machines) and `E` the `Event`
+/// type, as in `Dispatch`. With the `concurrent` feature, it is only implemented for
+/// `Clone` events.
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Fsm, Transition};
+/// #[derive(Debug, Clone)]
+/// enum Event { Toggle }
+/// # state_machine! {
+/// #     Name: Lamp,
+/// #     Event: Event,
+/// #     States: {
+/// #         Off => { process: |_evt| { Lamp::On } },
+/// #         On => { process: |_evt| { Lamp::Off } }
+/// #     }
+/// # }
+///
+/// // Runs any state machine over a script of events
+/// fn run<C, E, F: Fsm<C, E>>(fsm: &mut F, ctx: &mut C, script: &[E]) {
+///     fsm.init(ctx);
+///     for event in script {
+///         fsm.dispatch(ctx, event);
+///     }
+/// }
+///
+/// let mut lamp = Lamp::Off;
+/// run(&mut lamp, &mut (), &[Event::Toggle]);
+/// assert!(matches!(lamp, Lamp::On));
+/// ```
+pub trait Fsm<C, E>: Dispatch<C, E> {
+    /// Enters the current state, exactly like the generated `init()`.
+    fn init(&mut self, ctx: &mut C);
+
+    /// Dispatches `event`, exactly like the generated `dispatch()`.
+    fn dispatch(&mut self, ctx: &mut C, event: &E) -> DispatchOutcome;
+}
+
+/// A state machine and its context behind a single `std::sync::Mutex` (feature `std`).
+///
+/// Sharing a machine between threads as `Arc<Mutex<Fsm>>` plus `Arc<Mutex<Context>>`
+/// means taking two locks for every dispatch, and two threads locking them in
+/// different orders deadlock. `FsmCell` keeps both under one lock instead.
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition, FsmCell};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// struct Context { presses: u32 }
+/// #[derive(Debug, Clone)]
+/// enum Event { Press }
+/// # state_machine! {
+/// #     Name: Button,
+/// #     Context: Context,
+/// #     Event: Event,
+/// #     States: {
+/// #         Up => { process: |ctx, _evt| { ctx.presses += 1; Transition::None } }
+/// #     }
+/// # }
+///
+/// let mut ctx = Context { presses: 0 };
+/// let fsm = Button::start_in(Button::Up, &mut ctx);
+/// let cell = Arc::new(FsmCell::new(fsm, ctx));
+///
+/// let worker = Arc::clone(&cell);
+/// thread::spawn(move || worker.dispatch(Event::Press)).join().unwrap();
+/// cell.dispatch(Event::Press);
+///
+/// assert_eq!(cell.with(|_fsm, ctx| ctx.presses), 2);
+/// ```
+///
+/// # Lock Poisoning
+///
+/// A hook that panics poisons a plain `Mutex`, and every later `.lock().unwrap()` panics
+/// too, spreading one thread's failure to all of them. `FsmCell` recovers the lock
+/// instead, so the machine stays usable. As the panic may have struck mid-transition,
+/// `on_error_transition()` can also replace the machine with a known safe state, built
+/// by the next caller to take the lock. With the `concurrent` feature, a panic inside
+/// `dispatch()` also leaves the machine type's dispatch lock taken, so later events are
+/// only queued: recovery covers the `FsmCell` lock, not that one. Call the machine's
+/// `force_release_lock()` from the `on_error_transition()` function to release it too.
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition, FsmCell};
+/// # struct Context { faults: u32 }
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Glitch }
+/// # state_machine! {
+/// #     Name: Pump,
+/// #     Context: Context,
+/// #     Event: Event,
+/// #     States: {
+/// #         Running => {
+/// #             process: |ctx, _evt| { assert!(ctx.faults > 0, "sensor fault"); Transition::None }
+/// #         },
+/// #         SafeMode => {
+/// #             entry: |ctx| { ctx.faults += 1; }
+/// #             process: |_ctx, _evt| { Transition::None }
+/// #         }
+/// #     }
+/// # }
+/// let mut ctx = Context { faults: 0 };
+/// let fsm = Pump::start_in(Pump::Running, &mut ctx);
+/// let cell = FsmCell::new(fsm, ctx)
+///     .on_error_transition(|ctx| Pump::start_in(Pump::SafeMode, ctx));
+///
+/// let crash = std::panic::catch_unwind(|| cell.dispatch(Event::Glitch));
+/// assert!(crash.is_err());
+///
+/// cell.with(|fsm, ctx| {
+///     assert!(matches!(fsm, Pump::SafeMode));
+///     assert_eq!(ctx.faults, 1);
+/// });
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct FsmCell<F, C> {
+    inner: std::sync::Mutex<(F, C)>,
+    /// Set when a panic unwinds while the lock is held, until the next caller recovers.
+    panicked: core::sync::atomic::AtomicBool,
+    on_error: Option<fn(&mut C) -> F>,
+}
+
+#[cfg(feature = "std")]
+impl<F, C> FsmCell<F, C> {
+    /// Wraps an (already initialized) state machine and its context.
+    pub fn new(fsm: F, ctx: C) -> Self {
+        Self {
+            inner: std::sync::Mutex::new((fsm, ctx)),
+            panicked: core::sync::atomic::AtomicBool::new(false),
+            on_error: None,
+        }
+    }
+
+    /// Replaces the state machine with `recover(ctx)` after a panic under the lock (see
+    /// [Lock Poisoning](#lock-poisoning)).
+    ///
+    /// `recover` runs once per panic, under the lock, before the next `with()` or
+    /// `dispatch()` proceeds (or in `into_inner()`). The interrupted state gets no
+    /// `exit`; use `start_in(SafeState, ctx)` to run the safe state's `entry`.
+    pub fn on_error_transition(mut self, recover: fn(&mut C) -> F) -> Self {
+        self.on_error = Some(recover);
+        self
+    }
+
+    /// Runs `f` with the state machine and its context, under the lock.
+    ///
+    /// If a previous holder of the lock panicked, the lock is recovered rather than
+    /// propagating the poisoning (see [Lock Poisoning](#lock-poisoning)).
+    pub fn with<R>(&self, f: impl FnOnce(&mut F, &mut C) -> R) -> R {
+        let mut guard = self
+            .inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let (fsm, ctx) = &mut *guard;
+        if self
+            .panicked
+            .swap(false, core::sync::atomic::Ordering::Relaxed)
+        {
+            self.recover(fsm, ctx);
+        }
+
+        // Dropped before the guard: flags a panic in `f` while the lock is still held
+        let _sentinel = PanicSentinel(&self.panicked);
+        f(fsm, ctx)
+    }
+
+    /// Consumes the cell, returning the state machine and its context.
+    pub fn into_inner(self) -> (F, C) {
+        let (mut fsm, mut ctx) = self
+            .inner
+            .into_inner()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if self.panicked.into_inner() {
+            if let Some(recover) = self.on_error {
+                fsm = recover(&mut ctx);
+            }
+        }
+        (fsm, ctx)
+    }
+
+    /// Internal: Applies `on_error_transition()`, if one was set.
+    fn recover(&self, fsm: &mut F, ctx: &mut C) {
+        if let Some(recover) = self.on_error {
+            *fsm = recover(ctx);
+        }
+    }
+
+    /// Dispatches `event` under the lock (see `Dispatch::dispatch_event`).
+    pub fn dispatch<E>(&self, event: E) -> DispatchOutcome
+    where
+        F: Dispatch<C, E>,
+    {
+        self.with(|fsm, ctx| fsm.dispatch_event(ctx, event))
+    }
+}
+
+/// Raises `FsmCell`'s `panicked` flag when dropped during a panic.
+#[cfg(feature = "std")]
+struct PanicSentinel<'a>(&'a core::sync::atomic::AtomicBool);
+
+#[cfg(feature = "std")]
+impl Drop for PanicSentinel<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0.store(true, core::sync::atomic::Ordering::Relaxed);
+        }
+    }
+}
+
+/// Debug-build guard behind the run-to-completion check of the default `dispatch()`.
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can name it. Sets the per-type, per-thread `active` flag for as
+/// long as it lives, and panics instead if the flag is already set.
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub struct DispatchGuard {
+    active: &'static std::thread::LocalKey<core::cell::Cell<bool>>,
+}
+
+#[cfg(feature = "std")]
+impl DispatchGuard {
+    pub fn enter(
+        active: &'static std::thread::LocalKey<core::cell::Cell<bool>>,
+        machine: &str,
+    ) -> Self {
+        if active.with(|flag| flag.replace(true)) {
+            panic!(
+                "[{}] dispatch() re-entered from inside a dispatch (e.g., a hook dispatching \
+                 to a machine of the same type): post() the event instead to keep run-to-completion",
+                machine
+            );
+        }
+        Self { active }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for DispatchGuard {
+    fn drop(&mut self) {
+        self.active.with(|flag| flag.set(false));
+    }
+}
+
+/// 64-bit atomics behind the `Metrics` and `MaxLatency` statics of the default build.
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can name it. `AtomicU64` is `core`'s where the target has 64-bit
+/// atomics, and otherwise [`SplitU64`](atomic64::SplitU64), which keeps the value in two
+/// 32-bit words so that the directives also compile on 32-bit MCUs (e.g., Cortex-M).
+#[doc(hidden)]
+pub mod atomic64 {
+    #[cfg(target_has_atomic = "64")]
+    pub use core::sync::atomic::AtomicU64;
+    pub use core::sync::atomic::Ordering;
+
+    #[cfg(not(target_has_atomic = "64"))]
+    pub use SplitU64 as AtomicU64;
+
+    use core::sync::atomic::AtomicU32;
+
+    /// The subset of `AtomicU64` used by the generated code, over two `AtomicU32` words.
+    ///
+    /// Additions never lose a count where 32-bit read-modify-write atomics exist (e.g.,
+    /// Cortex-M3 and up); elsewhere (e.g., Cortex-M0) they use load/store, as the rest of
+    /// the default build does. A read racing a carry into the high word, or a `store()`,
+    /// may briefly see a mix of the old and new values. `fetch_max()` keeps its maximum in
+    /// the low word, saturating at `u32::MAX` (the range of a 32-bit cycle counter such as
+    /// the Cortex-M `DWT`'s). `Ordering` arguments are accepted for `AtomicU64`
+    /// compatibility; both words are accessed with `Relaxed`.
+    #[derive(Debug, Default)]
+    pub struct SplitU64 {
+        high: AtomicU32,
+        low: AtomicU32,
+    }
+
+    impl SplitU64 {
+        /// Creates the value (usable in `static` initializers).
+        pub const fn new(value: u64) -> Self {
+            Self {
+                high: AtomicU32::new((value >> 32) as u32),
+                low: AtomicU32::new(value as u32),
+            }
+        }
+
+        /// Reads the value, retrying if a carry into the high word happens meanwhile.
+        pub fn load(&self, _order: Ordering) -> u64 {
+            loop {
+                let high = self.high.load(Ordering::Relaxed);
+                let low = self.low.load(Ordering::Relaxed);
+                if self.high.load(Ordering::Relaxed) == high {
+                    return (u64::from(high) << 32) | u64::from(low);
+                }
+            }
+        }
+
+        /// Replaces the value.
+        pub fn store(&self, value: u64, _order: Ordering) {
+            self.high.store((value >> 32) as u32, Ordering::Relaxed);
+            self.low.store(value as u32, Ordering::Relaxed);
+        }
+
+        /// Adds `value`, wrapping around on overflow.
+        pub fn fetch_add(&self, value: u64, _order: Ordering) {
+            let (high, low) = ((value >> 32) as u32, value as u32);
+            let carry = Self::add_word(&self.low, low);
+            Self::add_word(&self.high, high.wrapping_add(u32::from(carry)));
+        }
+
+        /// Raises the value to `value` if it is larger, saturating at `u32::MAX`. Meant
+        /// for a value only ever reset to `0` with `store()`, so the high word stays `0`.
+        pub fn fetch_max(&self, value: u64, _order: Ordering) {
+            let value = u32::try_from(value).unwrap_or(u32::MAX);
+            #[cfg(target_has_atomic = "32")]
+            self.low.fetch_max(value, Ordering::Relaxed);
+            #[cfg(not(target_has_atomic = "32"))]
+            if value > self.low.load(Ordering::Relaxed) {
+                self.low.store(value, Ordering::Relaxed);
+            }
+        }
+
+        /// Adds `value` to `word`, returning `true` if it wrapped around.
+        fn add_word(word: &AtomicU32, value: u32) -> bool {
+            #[cfg(target_has_atomic = "32")]
+            let old = word.fetch_add(value, Ordering::Relaxed);
+            #[cfg(not(target_has_atomic = "32"))]
+            let old = {
+                let old = word.load(Ordering::Relaxed);
+                word.store(old.wrapping_add(value), Ordering::Relaxed);
+                old
+            };
+            old.checked_add(value).is_none()
+        }
+    }
+}
+
+/// Bounded recorder of entered state names backing the generated `path_trace()`.
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can name it. Each state machine declaring `PathTrace` gets its
+/// own `static` instance.
+///
+/// Like `DeferredQueue`, access is serialized by an atomic try-lock: a contended
+/// `record` is skipped and a contended `snapshot` returns an empty path.
+#[doc(hidden)]
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+pub struct PathTrace<const N: usize> {
+    busy: core::sync::atomic::AtomicBool,
+    path: core::cell::UnsafeCell<heapless::String<N>>,
+}
+
+// SAFETY: `path` is only touched while `busy` is held.
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+unsafe impl<const N: usize> Sync for PathTrace<N> {}
+
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+impl<const N: usize> PathTrace<N> {
+    /// Creates an empty path (usable in `static` initializers).
+    pub const fn new() -> Self {
+        Self {
+            busy: core::sync::atomic::AtomicBool::new(false),
+            path: core::cell::UnsafeCell::new(heapless::String::new()),
+        }
+    }
+
+    /// Runs `f` with exclusive access to the path, or returns `None` if contended.
+    fn locked<R>(&self, f: impl FnOnce(&mut heapless::String<N>) -> R) -> Option<R> {
+        use core::sync::atomic::Ordering;
+
+        if self.busy.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: `busy` grants exclusive access until it is released below.
+        let result = f(unsafe { &mut *self.path.get() });
+        self.busy.store(false, Ordering::Release);
+        Some(result)
+    }
+
+    /// Appends `>state` (just `state` when empty), dropping the oldest states until
+    /// it fits. A name longer than the whole buffer is truncated.
+    pub fn record(&self, state: &str) {
+        self.locked(|path| {
+            while !path.is_empty() && path.len() + 1 + state.len() > N {
+                let rest = match path.find('>') {
+                    Some(separator) => &path[separator + 1..],
+                    None => "",
+                };
+                let mut kept = heapless::String::new();
+                // `rest` is shorter than `path`, so it always fits
+                let _ = kept.push_str(rest);
+                *path = kept;
+            }
+            if !path.is_empty() {
+                let _ = path.push('>');
+            }
+            for c in state.chars() {
+                if path.push(c).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Returns a copy of the recorded path.
+    pub fn snapshot(&self) -> heapless::String<N> {
+        self.locked(|path| path.clone()).unwrap_or_default()
+    }
+}
+
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+impl<const N: usize> Default for PathTrace<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bounded log of dispatched events backing the generated `recorded_events()` and
+/// `replay()`.
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can name it. Each state machine declaring `Record` gets its own
+/// `static` instance. Once full, further events are not recorded, so the log always
+/// starts at the first recorded event and can be replayed from a fresh machine.
+///
+/// Like `PathTrace`, access is serialized by an atomic try-lock: a contended `record`
+/// is skipped and a contended `snapshot` returns no events.
+#[doc(hidden)]
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+pub struct EventRecorder<E, const N: usize> {
+    busy: core::sync::atomic::AtomicBool,
+    paused: core::sync::atomic::AtomicBool,
+    events: core::cell::UnsafeCell<heapless::Vec<E, N>>,
+}
+
+// SAFETY: `events` is only touched while `busy` is held, and events are only moved or
+// cloned across threads, which `E: Send` allows.
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+unsafe impl<E: Send, const N: usize> Sync for EventRecorder<E, N> {}
+
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+impl<E: Clone, const N: usize> EventRecorder<E, N> {
+    /// Creates an empty log (usable in `static` initializers).
+    pub const fn new() -> Self {
+        Self {
+            busy: core::sync::atomic::AtomicBool::new(false),
+            paused: core::sync::atomic::AtomicBool::new(false),
+            events: core::cell::UnsafeCell::new(heapless::Vec::new()),
+        }
+    }
+
+    /// Runs `f` with exclusive access to the log, or returns `None` if contended.
+    fn locked<R>(&self, f: impl FnOnce(&mut heapless::Vec<E, N>) -> R) -> Option<R> {
+        use core::sync::atomic::Ordering;
+
+        if self.busy.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: `busy` grants exclusive access until it is released below.
+        let result = f(unsafe { &mut *self.events.get() });
+        self.busy.store(false, Ordering::Release);
+        Some(result)
+    }
+
+    /// Appends a clone of `event`, unless the log is full or paused.
+    pub fn record(&self, event: &E) {
+        if self.paused.load(core::sync::atomic::Ordering::Relaxed) {
+            return;
+        }
+        self.locked(|events| {
+            let _ = events.push(event.clone());
+        });
+    }
+
+    /// Stops (`true`) or resumes (`false`) recording, e.g., while replaying.
+    pub fn pause(&self, paused: bool) {
+        self.paused
+            .store(paused, core::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Returns a copy of the recorded events, oldest first.
+    pub fn snapshot(&self) -> heapless::Vec<E, N> {
+        self.locked(|events| events.clone()).unwrap_or_default()
+    }
+
+    /// Discards every recorded event.
+    pub fn clear(&self) {
+        self.locked(|events| events.clear());
+    }
+}
+
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+impl<E: Clone, const N: usize> Default for EventRecorder<E, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bounded FIFO backing the generated `post()` in the non-concurrent build.
+///
+/// Not part of the public API: it is only `pub` so that code generated by
+/// `state_machine!` can name it. Each state machine declaring `QueueCapacity`
+/// gets its own `static` instance.
+///
+/// Access is serialized by an atomic try-lock instead of a critical section, so
+/// the default build stays dependency-free. The non-concurrent build is not meant
+/// to be shared with ISRs or threads (use the `concurrent` feature for that): if
+/// the lock is ever contended, `push` rejects the event (counted as dropped) and
+/// `pop` reports an empty queue rather than racing.
+#[doc(hidden)]
+#[cfg(target_has_atomic = "8")]
+pub struct DeferredQueue<E, const N: usize> {
+    busy: core::sync::atomic::AtomicBool,
+    dropped: core::sync::atomic::AtomicUsize,
+    head: core::cell::UnsafeCell<usize>,
+    len: core::cell::UnsafeCell<usize>,
+    buf: core::cell::UnsafeCell<core::mem::MaybeUninit<[E; N]>>,
+}
+
+// SAFETY: the buffer and indices are only touched while `busy` is held, so events
+// are moved between contexts one at a time (hence `E: Send`).
+#[cfg(target_has_atomic = "8")]
+unsafe impl<E: Send, const N: usize> Sync for DeferredQueue<E, N> {}
+
+#[cfg(target_has_atomic = "8")]
+impl<E, const N: usize> DeferredQueue<E, N> {
+    /// Creates an empty queue (usable in `static` initializers).
+    pub const fn new() -> Self {
+        Self {
+            busy: core::sync::atomic::AtomicBool::new(false),
+            dropped: core::sync::atomic::AtomicUsize::new(0),
+            head: core::cell::UnsafeCell::new(0),
+            len: core::cell::UnsafeCell::new(0),
+            buf: core::cell::UnsafeCell::new(core::mem::MaybeUninit::uninit()),
+        }
+    }
+
+    /// Runs `f` with exclusive access to the queue, or returns `None` if contended.
+    fn locked<R>(&self, f: impl FnOnce(&mut usize, &mut usize, *mut E) -> R) -> Option<R> {
+        use core::sync::atomic::Ordering;
+
+        if self.busy.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: `busy` grants exclusive access until it is released below.
+        let result = unsafe {
+            f(
+                &mut *self.head.get(),
+                &mut *self.len.get(),
+                (*self.buf.get()).as_mut_ptr() as *mut E,
+            )
+        };
+        self.busy.store(false, Ordering::Release);
+        Some(result)
+    }
+
+    /// Appends `event`, handing it back (and counting a drop) if the queue is full.
+    pub fn push(&self, event: E) -> Result<(), E> {
+        use core::sync::atomic::Ordering;
+
+        let mut slot = Some(event);
+        let pushed = self.locked(|head, len, buf| {
+            if *len == N {
+                return false;
+            }
+            // SAFETY: `(head + len) % N` is in bounds and currently uninitialized.
+            unsafe { buf.add((*head + *len) % N).write(slot.take().unwrap()) };
+            *len += 1;
+            true
+        });
+
+        match slot {
+            None => Ok(()),
+            Some(event) => {
+                debug_assert_ne!(pushed, Some(true));
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Err(event)
+            }
+        }
+    }
+
+    /// Removes the oldest event, if any.
+    pub fn pop(&self) -> Option<E> {
+        self.locked(|head, len, buf| {
+            if *len == 0 {
+                return None;
+            }
+            // SAFETY: `head` is in bounds and initialized while `len > 0`.
+            let event = unsafe { buf.add(*head).read() };
+            *head = (*head + 1) % N;
+            *len -= 1;
+            Some(event)
+        })
+        .flatten()
+    }
+
+    /// Number of events currently queued (0 if the queue is momentarily locked).
+    pub fn len(&self) -> usize {
+        self.locked(|_, len, _| *len).unwrap_or(0)
+    }
+
+    /// Returns `true` if no events are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of events rejected by `push` since creation or the last reset.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(core::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Resets the dropped counter to zero.
+    pub fn reset_dropped(&self) {
+        self.dropped.store(0, core::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<E, const N: usize> Default for DeferredQueue<E, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<E, const N: usize> Drop for DeferredQueue<E, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+/// Generates the State Machine Enum and its implementation.
+///
+/// This macro creates a `pub enum` with the specified name and implements
+/// the necessary logic for state transitions, entry/exit actions, and event processing.
+///
+/// # Macro Parameters
+///
+/// - **Name**: The identifier for the generated state machine enum
+/// - **Context** (optional): The type of shared state accessible to all states (see
+///   [Context-free Machines](#context-free-machines))
+/// - **Event**: The type of events that drive the state machine
+/// - **States**: Block defining all possible states and their behavior
+///
+/// # Optional Directives
+///
+/// Optional directives go between `Event` (or `QueueCapacity`) and `States`,
+/// in the order listed here:
+///
+/// - **QueueCapacity**: `QueueCapacity: 8,` - Right after `Event`. Sizes the bounded
+///   queue used by the generated `post()`. In the default build it also enables
+///   `post()`, `dropped_events_count()` and `reset_dropped_count()`; with the
+///   `concurrent` feature it sizes the ISR/thread queue (default 16), unless the `alloc`
+///   feature makes that queue unbounded.
+/// - **OverflowPolicy**: `OverflowPolicy: DropOldest,` - What the `concurrent` queue does
+///   when it is full: `DropNewest` (default) drops the new event and panics in debug
+///   builds, `DropOldest` discards the oldest queued event to keep the latest ones, and
+///   `Panic` panics in every build. Dropped events are counted either way (see
+///   `OverflowPolicy`). Ignored by the default build, whose `post()` hands the event back.
+/// - **DroppedBuffer**: `DroppedBuffer: 4,` - Keeps the 4 most recent events dropped by
+///   the full `concurrent` queue (the new event, or the evicted oldest one with
+///   `DropOldest`) in a per-type `heapless::Deque`, for post-incident analysis.
+///   `drained_dropped_events()` takes them, oldest first. Requires `Event: Clone`, since
+///   an event handed back to the caller is stored as a clone. Ignored by the default build.
+/// - **SharedRef**: `SharedRef: Config,` - Read-only data kept out of the mutable context.
+///   `dispatch()`, `dispatch_owned()` and `run_until()` take an extra `shared: &Config`
+///   argument after `ctx` (`fsm.dispatch(&mut ctx, &config, &event)`), which `process`
+///   can bind as an optional third argument: `process: |ctx, evt, config| { ... }`.
+///   `init()` and the `entry`/`exit` hooks are unchanged.
+/// - **Instrumentation**: `Instrumentation: stats: Stats,` - Mutable data for counters and
+///   traces, kept out of the domain context. The dispatch methods take an extra
+///   `instrumentation: &mut Stats` argument after `ctx` and `shared`
+///   (`fsm.dispatch(&mut ctx, &mut stats, &event)`), bound as `stats` in every `process`
+///   block and in `ProcessPrelude`. `init()` and the `entry`/`exit` hooks are unchanged.
+///   With `SharedRef` or `Instrumentation`, the machine doesn't implement `Dispatch`/`Fsm`.
+/// - **Repr**: `Repr: u8,` - Applies `#[repr(u8)]` (or any other primitive integer type)
+///   to the generated enum for compact storage and transmission. Only allowed when
+///   **no** state carries fields; otherwise compilation fails.
+/// - **MaxSize**: `MaxSize: 16,` - Compile-time budget in bytes for the generated enum (see
+///   [Size Budget](#size-budget)).
+/// - **Attrs**: `Attrs: [#[non_exhaustive], #[derive(Clone, PartialEq)]],` - Attributes
+///   spliced verbatim onto the generated `pub enum`, for state machines exposed in a public
+///   API. The enum already derives `Debug` (and gets `#[repr]` from `Repr`); avoiding
+///   conflicting or duplicate attributes is up to the caller.
+/// - **Derives**: `Derives: [Clone, Copy, PartialEq, Eq, Hash],` - Traits derived on the
+///   generated enum, e.g., to key a `HashMap<MyFSM, Config>` by state. Always possible for
+///   fieldless state machines; with stateful states, every field type must implement the
+///   derived traits (`f32` fields rule out `Eq` and `Hash`). `Debug` is already derived.
+/// - **DebugMode**: `DebugMode: NamesOnly,` - Replaces the derived `Debug` (`Full`, the
+///   default) with one that prints only the state name, e.g., `Connected` instead of
+///   `Connected { token: "..." }`, so `{:?}` never leaks secrets carried by states nor
+///   bloats logs with large payloads.
+/// - **Default**: `Default: Idle,` - Implements `Default` for the generated enum, returning
+///   the named state (e.g., for `let mut fsm = MyFSM::default();` followed by `init()`). The
+///   state must be fieldless, otherwise compilation fails. Use it instead of deriving
+///   `Default` through `Attrs`, which would need `#[default]` on a variant.
+/// - **EmbedContext**: `EmbedContext: Machine,` - Also generates `struct Machine`, which
+///   owns the state and the context: `Machine::new(state, ctx)`, then `init()` and
+///   `dispatch(&event)` without a `ctx` argument. `context()` / `context_mut()` reach the
+///   embedded context and `into_parts()` returns both. Hooks are unchanged.
+/// - **Snapshot**: `Snapshot: MachineSnapshot { fields: [count: u32, log: Vec<u8>] },` -
+///   Generates `struct MachineSnapshot` holding the state and the nominated context fields
+///   (their types are spelled out, as the macro can't see the context's declaration),
+///   plus `snapshot(&self, &ctx)` and `restore(&mut self, &mut ctx, snapshot)` for
+///   speculative dispatch with rollback. Only the nominated fields are cloned, so the
+///   context itself needn't be `Clone`; the enum must be (e.g., through `Derives`).
+///   `restore()` just assigns: no hook runs.
+/// - **CodeSize**: `CodeSize: small,` - Optimizes for flash size instead of speed (see
+///   [Code Size](#code-size)).
+/// - **MustUse**: `MustUse: true,` - Marks the methods returning a `DispatchOutcome`
+///   (`dispatch()`, `dispatch_owned()`, `dispatch_before()`, ...) `#[must_use]` (see
+///   [Ignored Outcomes](#ignored-outcomes)).
+/// - **StrictLints**: `StrictLints: true,` - Lets `rustc` and clippy warnings in the user's
+///   own blocks (`process`, hooks, mixins, ...) surface, e.g., under `-D warnings` (see
+///   [Strict Lints](#strict-lints)).
+/// - **Metrics**: `Metrics: events,` - Generates `events_processed()` and
+///   `reset_events_processed()`, a per-type `u64` counter incremented once per event handled
+///   by `process` (transitions, stays and queued events alike). On targets without 64-bit
+///   atomics (e.g., Cortex-M), the default build keeps it in two 32-bit words; with
+///   `concurrent` it uses `portable-atomic`. Nothing is generated without the directive.
+/// - **MaxLatency**: `MaxLatency: cycle_counter,` - Times every event with the given
+///   `fn() -> u64` clock (e.g., a wrapper around the Cortex-M `DWT` cycle counter), read
+///   before and after its `process`, `exit` and `entry` hooks, and keeps the worst case
+///   for WCET validation: `max_dispatch_cycles()` returns it and
+///   `reset_max_dispatch_cycles()` clears it. Per-type, as for `Metrics`: on targets
+///   without 64-bit atomics the default build keeps it in a 32-bit word, saturating at
+///   `u32::MAX` (the range of the `DWT` counter). Nothing is generated or sampled without
+///   the directive.
+/// - **PathTrace**: `PathTrace: 64,` - Generates `path_trace()`, the recent path of entered
+///   states as a `heapless::String<64>` (e.g., `"Idle>Connecting>Retrying"`) for crash
+///   dumps. `init()` and every `Transition::To` append the entered state; the oldest states
+///   are dropped when the capacity (in bytes) is exceeded. Requires the `path-trace` (or
+///   `concurrent`) feature and a `heapless` dependency.
+/// - **Record**: `Record: 32,` - Records up to 32 dispatched events (clones, so
+///   `Event: Clone`) for reproducing field bugs. `recorded_events()` returns them as a
+///   `heapless::Vec`, `clear_recorded_events()` starts over and `replay(ctx)` dispatches
+///   them again, typically on a fresh machine and context. Events posted from hooks and
+///   events ignored while frozen are not recorded. Requires the `record` (or
+///   `concurrent`) feature and a `heapless` dependency.
+/// - **LogLevels**: `LogLevels: { entry: debug, transition: info, process: trace },` -
+///   Picks the level each hook logs at with the `logging` or `defmt` feature: `entry`
+///   (also `init()` and `reentry()`), `exit`, `transition` and `process` (events that
+///   don't transition). Levels are `error`, `warn`, `info`, `debug` and `trace`; hooks not
+///   listed log at `info`, as without the directive.
+/// - **TransitionTable**: `TransitionTable: [Idle + Start => Running, ...],` - Declarative
+///   list of `From + Event => To` edges, exposed as `TRANSITIONS` and used by introspection
+///   helpers such as `successors()`, `can_handle()`, `available_transitions()` and
+///   `to_mermaid()`. It documents the machine; `process` still decides the actual
+///   transitions. Unknown state names fail to compile; event labels are free-form. In
+///   debug builds, `validate_transition_table()` checks other hand-maintained name tables
+///   against `STATE_NAMES`.
+/// - **CountTransitions**: `CountTransitions: true,` - Counts how often each
+///   `TransitionTable` edge fires: `transition_count(edge_index)` and
+///   `transition_counts()` report the per-type `u32` counters, indexed like `TRANSITIONS`.
+///   A table built at compile time maps each pair of states to its edge, so counting a
+///   transition is one lookup. Nothing is generated or counted without the directive.
+/// - **Initial**: `Initial: [Idle, SafeMode],` - The legal boot states (fieldless), exposed as
+///   `INITIAL_STATES` and selectable with `start_default(index, ctx)`. `start_in()` checks
+///   against this list in debug builds.
+/// - **InitialValues**: `InitialValues: Running { speed: 0 },` - The state, with its field
+///   values, that `start(ctx)` creates and `reset(ctx)` returns to (both run `init()`), also
+///   returned by `initial_state()`. Unlike `Initial` and `Default`, it may carry fields.
+///   An unknown state, or missing or unknown fields, fail to compile.
+/// - **Terminal / StrictTerminal**: `Terminal: [Completed, Cancelled], StrictTerminal: true,` -
+///   The "done" states of a workflow, queried with `is_terminal()`. With
+///   `StrictTerminal: true` (optional, right after `Terminal`), `dispatch` treats every
+///   event in a terminal state as `Transition::None` without calling `process`, so a done
+///   state can never be left by accident (`init()`, `reentry()` and `start_in()` still work).
+/// - **EventNames**: `EventNames: [Tick, Button],` - The variants of the `Event` enum, from
+///   which `event_name(&event)` is generated to map an event to its variant name (e.g., to
+///   bucket metrics by event kind). The list must mirror the enum: the generated exhaustive
+///   `match` fails to compile if a variant is missing or unknown.
+/// - **ExhaustiveEvents**: `ExhaustiveEvents: true,` - Every `process` block must be a single
+///   `match evt { ... }` without a catch-all arm, so a new event variant fails to compile in
+///   each state that doesn't handle it (see "Exhaustive Events" below).
+/// - **OnUnhandled**: `OnUnhandled: |ctx, state_name, evt| { ... },` - Hook invoked by
+///   `dispatch` whenever `process` returns `Transition::Unhandled`. `state_name` is the
+///   `&'static str` name of the current state.
+/// - **Watchdog / OnStuck**: `Watchdog: 100, OnStuck: |ctx, state_name| { ... },` - Liveness
+///   check: the hook fires once when 100 consecutive dispatches stay in the same state
+///   (`Transition::None` or `Transition::Unhandled`). Any `Transition::To` or
+///   `Transition::Reenter` (and `init()`) resets the count. Each machine counts in its
+///   own context, so the `Watchdog` requires the `Bookkeeping` directive.
+/// - **OnQuiescent**: `OnQuiescent: |ctx| { ... },` - Idle notification (e.g., to enter
+///   low-power sleep), run once at the end of every dispatch that processed its event,
+///   after the queue has been drained. With `concurrent` it runs while the dispatch lock is
+///   still held, right before it is released, so no other dispatch can interleave. It does
+///   not run for queued or frozen dispatches. Events posted from the hook wait for the
+///   next dispatch.
+/// - **TransitionObserver**: `TransitionObserver: observer,` - Names a context field of type
+///   `fn(&str, &str)` that `dispatch` calls with the `from` and `to` state names after every
+///   applied `Transition::To` (once the new state's `entry` has run). Unlike a hook, the
+///   function pointer can be swapped at runtime (e.g., per test) and needs no `dyn` or
+///   allocation. Requires a `Context`.
+/// - **Bookkeeping**: `Bookkeeping: fsm,` - Names a context field of type
+///   [`Bookkeeping`] holding the machine's per-instance counters: the attempts of `retry:`
+///   budgets and the count of the `Watchdog` (which require it), the token of a pending
+///   `Transition::Await` and the generation checked by `dispatch_gen()` (which requires
+///   it). Each context keeps its own, so several machines of the same type never share
+///   them. Requires a `Context`.
+/// - **AnyEntry / AnyExit**: `AnyEntry: |ctx, state_name| { ... }, AnyExit: |ctx, state_name| { ... },` -
+///   Machine-wide hooks that run for every state, for cross-cutting concerns such as
+///   logging or status LEDs. Either may be declared alone. `AnyExit` runs **before** the
+///   per-state `exit` of the state being left; `AnyEntry` runs **after** the per-state
+///   `entry` of the state being entered (including on `init()`, `reentry()` and
+///   `Transition::Reenter`). `state_name` is the name of that state.
+/// - **ProcessPrelude**: `ProcessPrelude: |ctx, evt| { ... },` - Runs before the current
+///   state's `process`, once for every event it handles, e.g., to feed a watchdog or
+///   count events without repeating that code in each state. It can't pick a transition:
+///   the block must evaluate to `()`, and `process` still decides. It gets `&mut` access to
+///   the context even before `readonly` states, and doesn't run for events ignored by
+///   `StrictTerminal`.
+/// - **EventFilter**: `EventFilter: |ctx, evt| -> bool { ... },` - A machine-wide gate
+///   consulted before anything else handles an event (`-> bool` is optional). When it
+///   returns `false`, the event is dropped: no hook runs, not even `ProcessPrelude`, and
+///   `dispatch()` returns `DispatchOutcome::Filtered`. Use it for input validation or
+///   debouncing at the machine's boundary. It gets `&mut` access to the context (e.g.,
+///   to keep a debounce timestamp) and also screens events queued with `post()`.
+/// - **Invariant / OnInvariantViolation**: `Invariant: |ctx, state_name| -> bool { ... },
+///   OnInvariantViolation: |ctx, state_name| { ... },` - A safety property (e.g., "never
+///   both lights green") that `dispatch` evaluates after every applied `Transition::To`,
+///   `ToFn` and `Reenter`, once the new state's `entry` has run. The invariant gets a
+///   shared `&Context` (`-> bool` is optional). When it returns `false`, the optional
+///   `OnInvariantViolation` hook runs (e.g., to force a safe state or log a fault);
+///   without one, a violation panics in debug builds and is ignored in release builds,
+///   like `debug_assert!`. `init()` is not checked.
+/// - **Mixin** (repeatable, last before `States`): `Mixin: CancelHandling => {
+///   process_fragment: |ctx, evt| { ... } },` - `process` arms shared by several states,
+///   which opt in with `use: [CancelHandling],`. The fragment returns a `Transition` like
+///   `process`; `Transition::Unhandled` means "not mine" and passes the event on. Each
+///   mixin becomes a private zero-sized type of that name in the calling module.
+///
+/// # Posting Events (Run-to-Completion)
+///
+/// With `QueueCapacity`, code running inside a dispatch (e.g., `entry` or `process`)
+/// can queue follow-up events with `post()`. They are processed in FIFO order once
+/// the current event has been fully handled, before `dispatch()` returns. When the
+/// queue is full, `post()` hands the event back and `dropped_events_count()` grows.
+///
+/// Calling `dispatch()` from inside a dispatch of the same state machine type instead
+/// (e.g., on a second machine reached through the context) breaks run-to-completion.
+/// With the `std` feature, debug builds of the default implementation panic when this
+/// happens on one thread; release builds skip the check. The `concurrent`
+/// implementation queues such events instead.
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context { log: Vec<&'static str> }
+/// #[derive(Debug, Clone)]
+/// enum Event { Start, Ready }
+///
+/// state_machine! {
+///     Name: Boot,
+///     Context: Context,
+///     Event: Event,
+///     QueueCapacity: 4,
+///     States: {
+///         Off => {
+///             process: |_ctx, evt| {
+///                 match evt {
+///                     Event::Start => Transition::To(Boot::Starting),
+///                     Event::Ready => Transition::None,
+///                 }
+///             }
+///         },
+///         Starting => {
+///             entry: |ctx| {
+///                 ctx.log.push("starting");
+///                 Boot::post(Event::Ready).unwrap();
+///             }
+///             process: |_ctx, evt| {
+///                 match evt {
+///                     Event::Ready => Transition::To(Boot::On),
+///                     Event::Start => Transition::None,
+///                 }
+///             }
+///         },
+///         On => { process: |_ctx, _evt| { Transition::None } }
+///     }
+/// }
+///
+/// let mut ctx = Context { log: Vec::new() };
+/// let mut fsm = Boot::Off;
+/// fsm.init(&mut ctx);
+/// fsm.dispatch(&mut ctx, &Event::Start);
+/// assert!(matches!(fsm, Boot::On));
+/// ```
+///
+/// # Transition Table
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Start, Stop }
+/// state_machine! {
+///     Name: Motor,
+///     Context: Context,
+///     Event: Event,
+///     TransitionTable: [
+///         Idle + Start => Running,
+///         Running + Stop => Idle,
+///     ],
+///     States: {
+///         Idle => {
+///             process: |_ctx, evt| {
+///                 Transition::to_if(matches!(evt, Event::Start), Motor::Running)
+///             }
+///         },
+///         Running => {
+///             process: |_ctx, evt| {
+///                 Transition::to_if(matches!(evt, Event::Stop), Motor::Idle)
+///             }
+///         }
+///     }
+/// }
+///
+/// assert_eq!(Motor::TRANSITIONS[0], ("Idle", "Start", "Running"));
+/// assert_eq!(Motor::Idle.successors().collect::<Vec<_>>(), ["Running"]);
+/// assert_eq!(Motor::Idle.available_transitions(), &[("Start", "Running")]);
+/// ```
+///
+/// # Code Size
+///
+/// By default `dispatch()` is `#[inline(always)]`, so each call site gets its own copy
+/// of the state machine flattened into a jump table (fastest, but every call site adds
+/// to `.text`). On size-constrained MCUs, `CodeSize: small,` drops `#[inline(always)]`
+/// from `dispatch()`/`dispatch_owned()` and marks the generated `process` step
+/// `#[inline(never)]`, so there is a single out-of-line copy at the cost of a call.
+/// Build with `opt-level = "s"` or `"z"` and compare `.text` before and after
+/// (e.g., `cargo size --release -- -A` from `cargo-binutils`, or `cargo bloat`).
+///
+/// Hooks cost nothing where they aren't declared: on a transition, `dispatch()` only
+/// calls the exit and entry steps of states with an `exit`/`entry` hook (or when
+/// `AnyExit`/`AnyEntry` or logging is enabled). Moving between states that only have
+/// `process` compiles to `process` plus the state update, independently of how much
+/// the optimizer inlines.
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Tick }
+/// state_machine! {
+///     Name: Blinker,
+///     Context: Context,
+///     Event: Event,
+///     CodeSize: small,
+///     States: {
+///         On => { process: |_ctx, _evt| { Transition::To(Blinker::Off) } },
+///         Off => { process: |_ctx, _evt| { Transition::To(Blinker::On) } }
+///     }
+/// }
+/// ```
+///
+/// # Ignored Outcomes
+///
+/// Side-effect-free queries (`current_state_name()`, `can_handle()`, `can_reach()`, ...)
+/// are always `#[must_use]`. Dispatching only for its side effects is common, so the
+/// dispatch methods are only `#[must_use]` with `MustUse: true,`: code that must react
+/// to `DispatchOutcome::Queued`, `Frozen`, `Expired` or `Filtered` then gets a warning wherever an
+/// outcome is dropped. Use `let _ = fsm.dispatch(...);` where ignoring it is intended.
+///
+/// ```rust,compile_fail
+/// #![deny(unused_must_use)]
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Tick }
+/// state_machine! {
+///     Name: Beacon,
+///     Context: Context,
+///     Event: Event,
+///     MustUse: true,
+///     States: {
+///         On => { process: |_ctx, _evt| { Transition::To(Beacon::Off) } },
+///         Off => { process: |_ctx, _evt| { Transition::To(Beacon::On) } }
+///     }
+/// }
+///
+/// let mut ctx = Context {};
+/// let mut beacon = Beacon::start_in(Beacon::On, &mut ctx);
+/// beacon.dispatch(&mut ctx, &Event::Tick); // error: unused `DispatchOutcome`
+/// ```
+///
+/// # Strict Lints
+///
+/// The generated code allows `unused_variables` around the user's blocks, so that hook
+/// arguments and state fields a block doesn't need never warn. That also hides dead
+/// code in the blocks themselves. With `StrictLints: true,`, the allowances only cover
+/// the bindings the macro generates (the closure arguments and the state's fields), and
+/// the user's own code is linted normally:
+///
+/// ```rust,compile_fail
+/// #![deny(unused_variables)]
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Tick }
+/// state_machine! {
+///     Name: Meter,
+///     Context: Context,
+///     Event: Event,
+///     StrictLints: true,
+///     States: {
+///         // Unused `ctx`, `evt` and `count` are fine
+///         Counting { count: u32 } => {
+///             process: |ctx, evt| {
+///                 let limit = 10; // error: unused variable `limit`
+///                 Transition::None
+///             }
+///         }
+///     }
+/// }
+/// ```
+///
+/// # Compact Representation
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Next }
+/// state_machine! {
+///     Name: Light,
+///     Context: Context,
+///     Event: Event,
+///     Repr: u8,
+///     States: {
+///         Red => { process: |_ctx, _evt| { Transition::To(Light::Green) } },
+///         Green => { process: |_ctx, _evt| { Transition::To(Light::Red) } }
+///     }
+/// }
+///
+/// assert_eq!(core::mem::size_of::<Light>(), 1);
+/// assert_eq!(Light::Green as u8, 1);
+/// ```
+///
+/// `Repr` on a state machine with stateful states fails to compile:
+///
+/// ```rust,compile_fail
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Next }
+/// state_machine! {
+///     Name: Light,
+///     Context: Context,
+///     Event: Event,
+///     Repr: u8,
+///     States: {
+///         Red => { process: |_ctx, _evt| { Transition::None } },
+///         Blinking { period_ms: u32 } => { process: |_ctx, _evt| { Transition::None } }
+///     }
+/// }
+/// ```
+///
+/// # Size Budget
+///
+/// `MaxSize: N,` asserts at compile time that `size_of::<Enum>() <= N`, so RAM
+/// budgets hold as stateful variants are added. A new field that pushes the enum
+/// over budget (e.g., a buffer) fails the build:
+///
+/// ```rust,compile_fail
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Next }
+/// state_machine! {
+///     Name: Sensor,
+///     Context: Context,
+///     Event: Event,
+///     MaxSize: 8,
+///     States: {
+///         Idle => { process: |_ctx, _evt| { Transition::None } },
+///         Sampling { buffer: [u8; 64] } => { process: |_ctx, _evt| { Transition::None } }
+///     }
+/// }
+/// ```
+///
+/// # Exhaustive Events
+///
+/// A `_ => Transition::None` arm silently swallows event variants added later.
+/// With `ExhaustiveEvents: true,` each `process` block must be a single
+/// `match evt { ... }` whose arms name event variants: a `_` arm or a bare binding
+/// (`other => ...`) is rejected with the state's name, so the compiler reports every
+/// state that misses a variant. Group variants with `|` where they share a handler,
+/// and write them with their path (`Event::Tick`), since a bare imported variant
+/// can't be told apart from a binding. Guarded arms (`Event::Tick if ctx.armed =>`,
+/// even `_ if ... =>`) are accepted, since they don't cover every variant.
+///
+/// ```rust,compile_fail
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Start, Stop, Pause }
+/// state_machine! {
+///     Name: Player,
+///     Context: Context,
+///     Event: Event,
+///     ExhaustiveEvents: true,
+///     States: {
+///         Stopped => {
+///             process: |_ctx, evt| {
+///                 match evt {
+///                     Event::Start => Transition::To(Player::Playing),
+///                     _ => Transition::None, // ERROR: catch-all arm in state `Stopped`
+///                 }
+///             }
+///         },
+///         Playing => {
+///             process: |_ctx, evt| {
+///                 match evt {
+///                     Event::Stop => Transition::To(Player::Stopped),
+///                     Event::Start | Event::Pause => Transition::None,
+///                 }
+///             }
+///         }
+///     }
+/// }
+/// ```
+///
+/// # State Definition
+///
+/// Each state can have:
+/// - **entry** (optional): Closure executed once when entering the state
+/// - **process** (required): Closure that handles events and returns `Transition<S>`
+/// - **exit** (optional): Closure executed once when leaving the state
+/// - **cost** (optional, first): `cost: 120,` - Worst-case execution cost of the state
+///   (in the unit of your choice) for external schedulers, reported by `state_cost()`
+///   (`0` when omitted)
+/// - **retry** (optional, after `cost`): `retry: { max: 3, on_exhausted: Failed },` - Retry
+///   budget for `Transition::Retry`: the state is re-entered until `max` attempts failed,
+///   then the machine moves to the (fieldless) `on_exhausted` state. Requires the
+///   `Bookkeeping` directive, where the attempts are counted
+/// - **use** (optional, after `retry`): `use: [CancelHandling, Diagnostics],` - Mixins
+///   whose `process_fragment` runs before the state's `process`, in list order. The first
+///   one not returning `Transition::Unhandled` decides the transition and the state's own
+///   `process` doesn't run. Fragments see the state's context but not its fields, with
+///   `&mut` access even in `readonly` states.
+///
+/// States can carry data by adding fields: `StateName { field: Type }`
+///
+/// To handle the same event differently depending on the context, without an extra
+/// state, use match guards in `process`. A guard may read the context and the state's
+/// fields, or call `&mut` methods on them:
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context { armed: bool }
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Trigger }
+/// state_machine! {
+///     Name: Alarm,
+///     Context: Context,
+///     Event: Event,
+///     States: {
+///         Quiet => {
+///             process: |ctx, evt| {
+///                 match evt {
+///                     Event::Trigger if ctx.armed => Transition::To(Alarm::Ringing),
+///                     Event::Trigger => Transition::None,
+///                 }
+///             }
+///         },
+///         Ringing => { process: |_ctx, _evt| { Transition::None } }
+///     }
+/// }
+/// ```
+///
+/// A state without `process` fails to compile with an error naming it, e.g.,
+/// "state `Open` is missing a required `process:` block":
+///
+/// ```rust,compile_fail
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Open }
+/// state_machine! {
+///     Name: Door,
+///     Context: Context,
+///     Event: Event,
+///     States: {
+///         Closed => { process: |_ctx, _evt| { Transition::To(Door::Open) } },
+///         Open => { entry: |_ctx| { } } // ERROR: state `Open` has no `process`
+///     }
+/// }
+/// ```
+///
+/// As shorthand, `process` may evaluate to a bare state instead of
+/// `Transition::To(state)`, or to `()` (e.g., a block ending in `;`) instead of
+/// `Transition::None`. Explicit `Transition` values keep working. An early `return`
+/// always takes the explicit form, and the arms of a `match` must agree on one form.
+///
+/// Fields are bound by name in every hook of their state, whether or not the hook
+/// reads them, so the generated code allows `unused_variables` there. There is no
+/// opt-in to lint a field that no hook reads: rustc does not report unused bindings
+/// created by a macro defined in another crate, whatever the lint level.
+///
+/// `entry` and `exit` also accept a second argument with the name of the
+/// counterpart state: `entry: |ctx, from_name| { ... }` receives the state being
+/// left, and `exit: |ctx, to_name| { ... }` receives the state being entered.
+/// Both are `&'static str` (see `current_state_name()`). On `init()` there is no
+/// previous state, so `from_name` is the initial state's own name.
+///
+/// `entry` takes an optional third argument, `entry: |ctx, from_name, is_init| { ... }`:
+/// a `bool` that is `true` only when the entry runs from `init()` (or `start_in()` /
+/// `start_default()`), and `false` for transitions, `Transition::Reenter` and `reentry()`.
+/// Use it to separate one-time setup from repeat setup.
+///
+/// A state can be compiled conditionally with `#[cfg(...)]` attributes placed before
+/// its name (e.g., `#[cfg(feature = "calib")] Calibration => { ... }`). They are
+/// forwarded to the enum variant, every generated `match` arm and the name tables
+/// (`STATE_NAMES`, `state_index()`), so the machine stays exhaustive in every build.
+/// Code that names the state (other states' `process`, `TransitionTable`, `Initial`)
+/// must be guarded by the same `cfg`.
+///
+/// Doc comments placed before a state (and before its `cfg`s) document the generated
+/// enum variant, so `cargo doc` describes each state of a public machine:
+/// `/// Waiting for a coin.` on the line above `Idle => { ... }`.
+///
+/// # Context-free Machines
+///
+/// `Context` can be omitted for machines whose actions only touch statics or
+/// hardware. Every hook then drops its context argument (`entry: || { ... }`,
+/// `entry: |from_name| { ... }`, `process: |evt| { ... }`,
+/// `OnUnhandled: |state_name, evt| { ... }`), and the generated methods drop their
+/// `ctx` parameter (`init()`, `dispatch(&event)`, `start_in(state)`, ...).
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition};
+/// use core::sync::atomic::{AtomicU32, Ordering};
+///
+/// static BLINKS: AtomicU32 = AtomicU32::new(0);
+///
+/// #[derive(Debug, Clone)]
+/// enum Event { Tick }
+///
+/// state_machine! {
+///     Name: Blinker,
+///     Event: Event,
+///     States: {
+///         Off => {
+///             process: |_evt| { Transition::To(Blinker::On) }
+///         },
+///         On => {
+///             entry: || { BLINKS.fetch_add(1, Ordering::Relaxed); }
+///             process: |_evt| { Transition::To(Blinker::Off) }
+///         }
+///     }
+/// }
+///
+/// let mut fsm = Blinker::Off;
+/// fsm.init();
+/// fsm.dispatch(&Event::Tick);
+/// assert_eq!(BLINKS.load(Ordering::Relaxed), 1);
+/// ```
+///
+/// # Readonly States
+///
+/// A state marked `readonly` (`Failed => readonly { ... }`) receives a shared
+/// `&Context` in its `process` block instead of `&mut Context`, so the compiler
+/// rejects any attempt to mutate the context from it. `entry` and `exit` are unaffected.
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context { errors: u32 }
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Fail, Reset }
+/// state_machine! {
+///     Name: Job,
+///     Context: Context,
+///     Event: Event,
+///     States: {
+///         Working => {
+///             process: |ctx, evt| {
+///                 match evt {
+///                     Event::Fail => { ctx.errors += 1; Transition::To(Job::Failed) }
+///                     Event::Reset => Transition::None
+///                 }
+///             }
+///         },
+///         Failed => readonly {
+///             process: |ctx, evt| {
+///                 // `ctx` is `&Context` here
+///                 match evt {
+///                     Event::Reset if ctx.errors < 3 => Transition::To(Job::Working),
+///                     _ => Transition::None
+///                 }
+///             }
+///         }
+///     }
+/// }
+/// ```
+///
+/// Mutating the context from a `readonly` state fails to compile:
+///
+/// ```rust,compile_fail
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct Context { errors: u32 }
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Reset }
+/// state_machine! {
+///     Name: Job,
+///     Context: Context,
+///     Event: Event,
+///     States: {
+///         Failed => readonly {
+///             process: |ctx, _evt| {
+///                 ctx.errors = 0; // ERROR: cannot assign through a `&` reference
+///                 Transition::None
+///             }
+///         }
+///     }
+/// }
+/// ```
+///
+/// # Complete Example
+///
+/// ```rust
+/// use typed_fsm::{state_machine, Transition};
+///
+/// struct MyContext {
+///     counter: u32,
+/// }
+///
+/// #[derive(Debug, Clone)]
+/// enum MyEvent {
+///     Start,
+///     Stop,
+/// }
+///
+/// state_machine! {
+///     Name: MyMachine,
+///     Context: MyContext,
+///     Event: MyEvent,
+///     States: {
+///         Idle => {
+///             entry: |ctx| {
+///                 println!("Entering Idle");
+///                 ctx.counter = 0;
+///             }
+///
+///             process: |_ctx, evt| {
+///                 match evt {
+///                     MyEvent::Start => Transition::To(MyMachine::Active { id: 1 }),
+///                     _ => Transition::None
+///                 }
+///             }
+///         },
+///
+///         Active { id: u32 } => {
+///             entry: |ctx| {
+///                 println!("Entering Active with id: {}", id);
+///                 ctx.counter += 1;
+///             }
+///
+///             process: |_ctx, evt| {
+///                 match evt {
+///                     MyEvent::Stop => Transition::To(MyMachine::Idle),
+///                     _ => Transition::None
+///                 }
+///             }
+///
+///             exit: |_ctx| {
+///                 println!("Leaving Active");
+///             }
+///         }
+///     }
+/// }
+/// ```
+///
+/// # Usage
+///
+/// ```rust
+/// # use typed_fsm::{state_machine, Transition};
+/// # struct MyContext { counter: u32 }
+/// # #[derive(Debug, Clone)]
+/// # enum MyEvent { Start, Stop }
+/// # state_machine! {
+/// #     Name: MyMachine,
+/// #     Context: MyContext,
+/// #     Event: MyEvent,
+/// #     States: {
+/// #         Idle => {
+/// #             process: |_ctx, evt| {
+/// #                 match evt {
+/// #                     MyEvent::Start => Transition::To(MyMachine::Active { id: 1 }),
+/// #                     _ => Transition::None
+/// #                 }
+/// #             }
+/// #         },
+/// #         Active { id: u32 } => {
+/// #             process: |_ctx, evt| {
+/// #                 match evt {
+/// #                     MyEvent::Stop => Transition::To(MyMachine::Idle),
+/// #                     _ => Transition::None
+/// #                 }
+/// #             }
+/// #         }
+/// #     }
+/// # }
+/// let mut ctx = MyContext { counter: 0 };
+/// let mut fsm = MyMachine::Idle;
+///
+/// // Initialize (calls entry action of initial state)
+/// fsm.init(&mut ctx);
+///
+/// // Dispatch events
+/// fsm.dispatch(&mut ctx, &MyEvent::Start);
+/// fsm.dispatch(&mut ctx, &MyEvent::Stop);
+/// ```
+// ============================================================================
+// IMPLEMENTATION WITHOUT CONCURRENCY PROTECTION (default)
+// ============================================================================
+#[cfg(not(feature = "concurrent"))]
+#[macro_export]
+macro_rules! state_machine {
+    // Pattern 1: With Context
+    (
+        Name: $enum_name:ident,
+        Context: $ctx_type:ty,
+        Event: $event_type:ty,
+        $($rest:tt)*
+    ) => {
+        $crate::__fsm_missing_process! { $($rest)* }
+
+        $crate::state_machine! {
+            @internal
+            Name: $enum_name,
+            Context: $ctx_type => [, ctx: &mut $ctx_type] ctx,
+            Event: $event_type,
+            $($rest)*
+        }
+    };
+
+    // Pattern 2: Context-free (hooks take no context argument)
+    (
+        Name: $enum_name:ident,
+        Event: $event_type:ty,
+        $($rest:tt)*
+    ) => {
+        $crate::__fsm_missing_process! { $($rest)* }
+
+        $crate::__fsm_context_free! {
+            [
+                @internal
+                Name: $enum_name,
+                Context: () => [] &mut (),
+                Event: $event_type,
+            ]
+            $($rest)*
+        }
+    };
+
+    // Internal implementation (actual code generation)
+    (
+        @internal
+        Name: $enum_name:ident,
+        // Public signatures take `$ctx_param` (empty when context-free) and
+        // hand `$ctx_arg` to the internal functions
+        Context: $ctx_type:ty => [$($ctx_param:tt)*] $ctx_arg:expr,
+        Event: $event_type:ty,
+
+        // Optional Deferred Queue: QueueCapacity: 8,
+        $( QueueCapacity: $queue_capacity:expr, )?
+
+        // Optional Concurrent Queue Overflow Policy: OverflowPolicy: DropOldest,
+        $( OverflowPolicy: $overflow_policy:ident, )?
+
+        // Optional Store of Overflowed Events (concurrent queue): DroppedBuffer: 4,
+        $( DroppedBuffer: $dropped_buffer:expr, )?
+
+        // Optional Read-only Data for process: SharedRef: Config,
+        $( SharedRef: $shared_type:ty, )?
+
+        // Optional Mutable Data for process: Instrumentation: stats: Stats,
+        $( Instrumentation: $instr_var:ident : $instr_type:ty, )?
+
+        // Optional Representation: Repr: u8,
+        $( Repr: $repr:ident, )?
+
+        // Optional Size Budget: MaxSize: 16,
+        $( MaxSize: $max_size:expr, )?
+
+        // Optional Enum Attributes: Attrs: [#[non_exhaustive], #[derive(Clone)]],
+        $( Attrs: [ $( #[$attr:meta] ),* $(,)? ], )?
+
+        // Optional Enum Derives: Derives: [Clone, Copy, PartialEq, Eq, Hash],
+        $( Derives: [ $( $derive:path ),* $(,)? ], )?
+
+        // Optional Debug Output: DebugMode: NamesOnly,
+        $( DebugMode: $debug_mode:ident, )?
+
+        // Optional Default State: Default: Idle,
+        $( Default: $default_state:ident, )?
+
+        // Optional Context-Owning Wrapper: EmbedContext: MachineWithContext,
+        $( EmbedContext: $embed:ident, )?
+
+        // Optional Rollback Support: Snapshot: MachineSnapshot { fields: [count: u32] },
+        $( Snapshot: $snap:ident { fields: [ $( $snap_field:ident : $snap_ty:ty ),* $(,)? ] $(,)? }, )?
+
+        // Optional Code Size: CodeSize: small,
+        $( CodeSize: $code_size:ident, )?
+
+        // Optional Warning on Ignored Outcomes: MustUse: true,
+        $( MustUse: $must_use:tt, )?
+
+        // Optional Lints in User Blocks: StrictLints: true,
+        $( StrictLints: $strict_lints:tt, )?
+
+        // Optional Event Counter: Metrics: events,
+        $( Metrics: $metrics:ident, )?
+
+        // Optional Worst-case Dispatch Time: MaxLatency: cycle_counter, (a `fn() -> u64`)
+        $( MaxLatency: $latency_now:path, )?
+
+        // Optional Transition Path Recorder: PathTrace: 64,
+        $( PathTrace: $path_trace:expr, )?
+
+        // Optional Event Recorder: Record: 32,
+        $( Record: $record:expr, )?
+
+        // Optional Per-Hook Log Levels: LogLevels: { entry: debug, transition: info },
+        $( LogLevels: { $( $log_hook:ident : $log_level:ident ),* $(,)? }, )?
+
+        // Optional Transition Table: TransitionTable: [From + Event => To, ...],
+        $( TransitionTable: [ $( $tt_from:ident + $tt_event:ident => $tt_to:ident ),* $(,)? ], )?
+
+        // Optional Per-Edge Counters of the Transition Table: CountTransitions: true,
+        $( CountTransitions: $count_transitions:tt, )?
+
+        // Optional Legal Boot States: Initial: [Idle, SafeMode],
+        $( Initial: [ $( $initial:ident ),+ $(,)? ], )?
+
+        // Optional Construction for reset()/start(): InitialValues: Running { speed: 0 },
+        $( InitialValues: $init_state:ident $( { $($init_fields:tt)* } )?, )?
+
+        // Optional Done States: Terminal: [Completed, Cancelled], StrictTerminal: true,
+        $( Terminal: [ $( $terminal:ident ),+ $(,)? ], )?
+        $( StrictTerminal: $strict_terminal:literal, )?
+
+        // Optional Event Variant Names: EventNames: [Tick, Button],
+        $( EventNames: [ $( $event_name:ident ),+ $(,)? ], )?
+
+        // Optional Wildcard-free Process Blocks: ExhaustiveEvents: true,
+        $( ExhaustiveEvents: $exhaustive:tt, )?
+
+        // Optional Unhandled Hook: OnUnhandled: |ctx, state_name, evt| { ... },
+        $( OnUnhandled: |$unhandled_ctx:ident, $unhandled_state:ident, $unhandled_evt:ident| $unhandled_block:block, )?
+
+        // Optional Liveness Watchdog: Watchdog: N, OnStuck: |ctx, state_name| { ... },
+        $( Watchdog: $watchdog_limit:expr, OnStuck: |$stuck_ctx:ident, $stuck_state:ident| $stuck_block:block, )?
+
+        // Optional Idle Hook: OnQuiescent: |ctx| { ... },
+        $( OnQuiescent: |$quiescent_ctx:ident| $quiescent_block:block, )?
+
+        // Optional Runtime Observer (a `fn(&str, &str)` context field): TransitionObserver: observer,
+        $( TransitionObserver: $observer:ident, )?
+
+        // Optional Per-Instance Counters (a `typed_fsm::Bookkeeping` context field): Bookkeeping: fsm,
+        $( Bookkeeping: $bookkeeping:ident, )?
+
+        // Optional Machine-wide Hooks: AnyEntry: |ctx, state_name| { ... }, AnyExit: ...,
+        $( AnyEntry: |$any_entry_ctx:ident, $any_entry_state:ident| $any_entry_block:block, )?
+        $( AnyExit: |$any_exit_ctx:ident, $any_exit_state:ident| $any_exit_block:block, )?
+
+        // Optional Event Gate: EventFilter: |ctx, evt| -> bool { ... },
+        $( EventFilter: |$filter_ctx:ident, $filter_evt:ident| $( -> bool )? $filter_block:block, )?
+
+        // Optional Per-Event Prelude: ProcessPrelude: |ctx, evt| { ... },
+        $( ProcessPrelude: |$prelude_ctx:ident, $prelude_evt:ident| $prelude_block:block, )?
+
+        // Optional Safety Property: Invariant: |ctx, state_name| -> bool { ... },
+        // OnInvariantViolation: |ctx, state_name| { ... },
+        $( Invariant: |$inv_ctx:ident, $inv_state:ident| $( -> bool )? $inv_block:block, )?
+        $( OnInvariantViolation: |$violation_ctx:ident, $violation_state:ident| $violation_block:block, )?
+
+        // Optional Shared Process Arms (any number): Mixin: Name => { process_fragment: |ctx, evt| { ... } },
+        $( Mixin: $mixin:ident => { process_fragment: |$mixin_ctx:ident, $mixin_evt:ident| $mixin_block:block $(,)? }, )*
+
+        States: {
+            $(
+                // Captures the State Name and optional fields (e.g., Running { speed: u32 })
+                // plus the optional `readonly` marker (e.g., Failed => readonly { ... }),
+                // after the optional doc comments (forwarded to the variant) and `cfg`s
+                $( #[doc = $state_doc:expr] )*
+                $( #[cfg($state_cfg:meta)] )*
+                $state_name:ident $( { $($field_name:ident : $field_type:ty),* } )? => $( $readonly:ident )? {
+
+                    // Optional Worst-case Cost (metadata for schedulers): cost: 120,
+                    $( cost: $state_cost:expr, )?
+
+                    // Optional Retry Budget for Transition::Retry: retry: { max: 3, on_exhausted: Failed },
+                    $( retry: { max: $retry_max:expr, on_exhausted: $retry_fail:ident $(,)? }, )?
+
+                    // Optional Mixins, tried in order before process: use: [CancelHandling],
+                    $( use: [ $( $state_mixin:ident ),* $(,)? ], )?
+
+                    // Optional Entry Block: entry: |ctx| { ... }, entry: |ctx, from_name| { ... }
+                    // or entry: |ctx, from_name, is_init| { ... }
+                    $( entry: |$entry_ctx:ident $(, $entry_from:ident $(, $entry_is_init:ident )? )?| $entry_block:block )?
+
+                    // Mandatory Process Block: process: |ctx, evt| { ... }
+                    // (or |ctx, evt, shared| { ... } with SharedRef)
+                    process: |$ctx_var:ident, $evt_var:ident $(, $shared_var:ident )?| { $($process_body:tt)* }
+
+                    // Optional Exit Block: exit: |ctx| { ... } or exit: |ctx, to_name| { ... }
+                    $( exit: |$exit_ctx:ident $(, $exit_to:ident )?| $exit_block:block )?
+                }
+            ),* $(,)?
+        }
+    ) => {
+        $crate::__fsm_debug! {
+            [$( $debug_mode )?] $enum_name;
+            /// Auto-generated State Machine Enum.
+            /// Holds the current state and its internal data.
+            $( #[repr($repr)] )?
+            $($( #[$attr] )*)?
+            $( #[derive($($derive),*)] )?
+            pub enum $enum_name {
+                $(
+                    $( #[doc = $state_doc] )*
+                    $( #[cfg($state_cfg)] )*
+                    $state_name $( { $($field_name : $field_type),* } )?,
+                )*
+            }
+        }
+
+        // `Repr` is only valid for fieldless state machines
+        // `retry:` budgets count their attempts in the `Bookkeeping` field
+        $crate::__fsm_bookkeeping! {
+            require [$( $bookkeeping )?] $($( $retry_fail )?)*
+        }
+        $crate::__fsm_bookkeeping! {
+            require_watchdog [$( $bookkeeping )?] $( $watchdog_limit )?
+        }
+
+        $crate::__fsm_require_fieldless! {
+            [$( Repr: $repr )?]
+            $( $( #[cfg($state_cfg)] )* $state_name $( { $($field_name : $field_type),* } )? ; )*
+        }
+
+        // `ExhaustiveEvents: true` rejects catch-all arms in `process`
+        $crate::__fsm_exhaustive_events! {
+            [$( $exhaustive )?]
+            $( $state_name $evt_var { $($process_body)* } )*
+        }
+
+        // `MaxSize` is a compile-time budget for the size of the enum
+        $(
+            const _: () = assert!(
+                core::mem::size_of::<$enum_name>() <= $max_size,
+                concat!(
+                    "`", stringify!($enum_name), "` exceeds its `MaxSize: ",
+                    stringify!($max_size), "` byte budget"
+                )
+            );
+        )?
+
+        // `Default` names a fieldless state (a state with fields is not a value)
+        $(
+            impl core::default::Default for $enum_name {
+                fn default() -> Self {
+                    $enum_name::$default_state
+                }
+            }
+        )?
+
+        // Parsing a state name (the reverse of `current_state_name()`). `Self::Err` and
+        // `Self::Error` are avoided: they would be ambiguous with a state named `Error`.
+        impl core::str::FromStr for $enum_name {
+            type Err = $crate::ParseStateError;
+
+            fn from_str(name: &str) -> Result<Self, $crate::ParseStateError> {
+                let index = Self::STATE_NAMES
+                    .iter()
+                    .position(|state| *state == name)
+                    .ok_or($crate::ParseStateError::Unknown)?;
+                Self::from_index(index).ok_or($crate::ParseStateError::HasFields)
+            }
+        }
+
+        impl core::convert::TryFrom<&str> for $enum_name {
+            type Error = $crate::ParseStateError;
+
+            fn try_from(name: &str) -> Result<Self, $crate::ParseStateError> {
+                name.parse()
+            }
+        }
+
+        // Every state named in the TransitionTable must exist
+        $(
+            #[allow(unreachable_patterns)]
+            const _: fn(&$enum_name) = |state| match state {
+                $( $enum_name::$tt_from { .. } | $enum_name::$tt_to { .. } => {} )*
+                _ => {}
+            };
+        )?
+
+        // `process` may evaluate to a bare state (sugar for `Transition::To`)
+        impl $crate::IntoTransition<$enum_name, $event_type> for $enum_name {
+            #[inline(always)]
+            fn into_transition(self) -> $crate::Transition<$enum_name, $event_type> {
+                $crate::Transition::To(self)
+            }
+        }
+
+        // Generic dispatch (e.g., through `FsmCell`)
+        $crate::__fsm_shared! {
+            dispatch_impl [$( $shared_type )?] [$( $instr_type )?] $enum_name $ctx_type, $event_type,
+            []
+        }
+
+        $crate::__fsm_embed_context! {
+            [$( $embed )?] [$( $shared_type )?] [$( $instr_type )?]
+            $enum_name, $ctx_type, $event_type, []
+        }
+
+        $crate::__fsm_snapshot! {
+            [$( $snap { fields: [ $( $snap_field: $snap_ty ),* ] } )?]
+            $enum_name, $ctx_type
+        }
+
+        // One zero-sized type per `Mixin`, holding its shared `process` arms
+        $crate::__fsm_mixins! {
+            [$( $strict_lints )?] $enum_name, $ctx_type, $event_type;
+            $( $mixin |$mixin_ctx, $mixin_evt| $mixin_block )*
+        }
+
+        // `OverflowPolicy` only applies to the concurrent queue, but must still name a policy
+        $( const _: $crate::OverflowPolicy = $crate::OverflowPolicy::$overflow_policy; )?
+
+        // So does `DroppedBuffer`, which must still be a capacity
+        $( const _: usize = $dropped_buffer; )?
+
+        impl $enum_name {
+            /// Initializes the state machine by executing the entry action of the initial state.
+            ///
+            /// # CRITICAL: Must be called before the event loop!
+            ///
+            /// **Forgetting to call `init()` will cause silent failures:**
+            /// - The `entry` action of the initial state will NEVER execute
+            /// - State machine will still process events, but initialization is skipped
+            /// - This can lead to incorrect behavior that is difficult to debug
+            ///
+            /// # Correct Usage
+            ///
+            /// ```rust
+            /// # use typed_fsm::{state_machine, Transition};
+            /// # struct Context { count: u32 }
+            /// # #[derive(Debug, Clone)]
+            /// # enum Event { Tick }
+            /// # state_machine! {
+            /// #     Name: FSM,
+            /// #     Context: Context,
+            /// #     Event: Event,
+            /// #     States: {
+            /// #         Idle => {
+            /// #             entry: |ctx| { ctx.count = 0; }
+            /// #             process: |_ctx, _evt| { Transition::None }
+            /// #         }
+            /// #     }
+            /// # }
+            /// let mut ctx = Context { count: 0 };
+            /// let mut fsm = FSM::Idle;
+            ///
+            /// // CORRECT: Call init() before event loop
+            /// fsm.init(&mut ctx);
+            ///
+            /// // Now safe to dispatch events
+            /// fsm.dispatch(&mut ctx, &Event::Tick);
+            /// ```
+            ///
+            /// # Incorrect Usage (Common Mistake)
+            ///
+            /// ```rust,no_run
+            /// # use typed_fsm::{state_machine, Transition};
+            /// # struct Context { count: u32 }
+            /// # #[derive(Debug, Clone)]
+            /// # enum Event { Tick }
+            /// # state_machine! {
+            /// #     Name: FSM,
+            /// #     Context: Context,
+            /// #     Event: Event,
+            /// #     States: {
+            /// #         Idle => {
+            /// #             entry: |ctx| { ctx.count = 0; }
+            /// #             process: |_ctx, _evt| { Transition::None }
+            /// #         }
+            /// #     }
+            /// # }
+            /// let mut ctx = Context { count: 0 };
+            /// let mut fsm = FSM::Idle;
+            ///
+            /// // WRONG: Forgot to call init()!
+            /// // The entry action will NEVER execute!
+            /// fsm.dispatch(&mut ctx, &Event::Tick);
+            /// ```
+            ///
+            /// # When to Call
+            ///
+            /// - Call exactly **once** after creating the state machine
+            /// - Call **before** entering the event loop
+            /// - Call **before** the first `dispatch()`
+            pub fn init(&mut self $($ctx_param)*) {
+                self.do_init($ctx_arg);
+            }
+
+            /// Internal: `init()` body, shared with `start_in()`.
+            #[allow(unused_variables)]
+            fn do_init(&mut self, ctx: &mut $ctx_type) {
+                $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] init: $enum_name, self);
+                // No predecessor on init: the initial state is its own `from_name`
+                let from = self.current_state_name();
+                self.on_entry(ctx, from, true);
+                $crate::__fsm_path_trace!([$( $path_trace )?] record from);
+
+                // A fresh start resets the watchdog, the retries and any `Await`, and
+                // makes the previous generation stale
+                self.watchdog_step(ctx, true);
+                if let Some(book) = Self::bookkeeping_mut(ctx) {
+                    book.leave_state();
+                    book.generation = Self::next_generation();
+                }
+            }
+
+            /// Re-runs the entry action of the **current** state against `ctx`.
+            ///
+            /// Use this after swapping the whole context (hot reload, tests) to
+            /// re-establish the current state's invariants on the new context
+            /// without a reset cycle: no exit action runs, no transition happens,
+            /// and the state (including its fields) is left untouched.
+            ///
+            /// Mechanically identical to `init()`; the separate name documents intent.
+            /// As with `init()`, `from_name` in a two-argument `entry` is the current
+            /// state's own name.
+            ///
+            /// ```rust
+            /// # use typed_fsm::{state_machine, Transition};
+            /// # struct Context { ready: bool }
+            /// # #[derive(Debug, Clone)]
+            /// # enum Event { Tick }
+            /// # state_machine! {
+            /// #     Name: FSM,
+            /// #     Context: Context,
+            /// #     Event: Event,
+            /// #     States: {
+            /// #         Idle => {
+            /// #             entry: |ctx| { ctx.ready = true; }
+            /// #             process: |_ctx, _evt| { Transition::None }
+            /// #         }
+            /// #     }
+            /// # }
+            /// let mut fsm = FSM::Idle;
+            /// let mut ctx = Context { ready: false };
+            /// fsm.init(&mut ctx);
+            ///
+            /// // Swap in a fresh context and re-apply the current state's entry
+            /// let mut new_ctx = Context { ready: false };
+            /// fsm.reentry(&mut new_ctx);
+            /// assert!(new_ctx.ready);
+            /// ```
+            #[allow(unused_variables)]
+            pub fn reentry(&mut self $($ctx_param)*) {
+                let ctx: &mut $ctx_type = $ctx_arg;
+                $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] reentry: $enum_name, self);
+                let from = self.current_state_name();
+                self.on_entry(ctx, from, false);
+            }
+
+            /// Creates a state machine in `state` and runs `init()` on it.
+            ///
+            /// Shorthand for `let mut fsm = state; fsm.init(ctx);`. If an `Initial` list is
+            /// declared, debug builds assert that `state` is one of the legal boot states.
+            ///
+            /// ```rust
+            /// # use typed_fsm::{state_machine, Transition};
+            /// # struct Context { booted: bool }
+            /// # #[derive(Debug, Clone)]
+            /// # enum Event { Tick }
+            /// # state_machine! {
+            /// #     Name: FSM,
+            /// #     Context: Context,
+            /// #     Event: Event,
+            /// #     States: {
+            /// #         Idle => {
+            /// #             entry: |ctx| { ctx.booted = true; }
+            /// #             process: |_ctx, _evt| { Transition::None }
+            /// #         }
+            /// #     }
+            /// # }
+            /// let mut ctx = Context { booted: false };
+            /// let fsm = FSM::start_in(FSM::Idle, &mut ctx);
+            /// assert!(ctx.booted);
+            /// ```
+            #[must_use]
+            pub fn start_in(state: Self $($ctx_param)*) -> Self {
+                Self::boot(state, $ctx_arg)
+            }
+
+            /// Internal: `start_in()` body, shared with `start_default()`.
+            fn boot(state: Self, ctx: &mut $ctx_type) -> Self {
+                $(
+                    debug_assert!(
+                        matches!(state, $( Self::$initial { .. } )|+),
+                        "[{}] start_in({}): not a declared Initial state {:?}",
+                        stringify!($enum_name),
+                        state.current_state_name(),
+                        Self::INITIAL_STATES
+                    );
+                )?
+                let mut state = state;
+                state.do_init(ctx);
+                state
+            }
+
+            /// Names of the declared `Initial` states, in declaration order.
+            /// Empty if no `Initial` list was declared.
+            pub const INITIAL_STATES: &'static [&'static str] = &[
+                $($( stringify!($initial), )+)?
+            ];
+
+            $(
+                /// Returns the name of the variant of `event`, as listed in `EventNames`.
+                ///
+                /// Useful to bucket logs or metrics by event kind. The list must name every
+                /// variant of the event enum: a missing or unknown name fails to compile.
+                pub fn event_name(event: &$event_type) -> &'static str {
+                    // Variant paths need a path, not a type fragment
+                    type Event = $event_type;
+                    match event {
+                        $( Event::$event_name { .. } => stringify!($event_name), )+
+                    }
+                }
+            )?
+
+            $crate::__fsm_start_default! {
+                $enum_name [$($ctx_param)*] $ctx_arg; [$( $($initial),+ )?]
+            }
+
+            $crate::__fsm_initial_values! {
+                $enum_name [$($ctx_param)*] $ctx_arg;
+                [$( $init_state $( { $($init_fields)* } )? )?]
+            }
+
+            /// Returns the name of the current state, exactly as declared in the macro.
+            ///
+            /// Field values are not included (`Running { speed: 10 }` yields `"Running"`).
+            #[must_use]
+            pub fn current_state_name(&self) -> &'static str {
+                match self {
+                    $(
+                        $( #[cfg($state_cfg)] )*
+                        Self::$state_name { .. } => stringify!($state_name),
+                    )*
+                }
+            }
+
+            /// Returns the worst-case execution cost declared for the current state with
+            /// `cost: N,` (e.g., in microseconds or cycles), or `0` if none was declared.
+            ///
+            /// The value is metadata for external schedulers and WCET budgets; the state
+            /// machine itself never reads it.
+            pub fn state_cost(&self) -> u32 {
+                match self {
+                    $(
+                        $( #[cfg($state_cfg)] )*
+                        Self::$state_name { .. } => 0 $( + ($state_cost) )?,
+                    )*
+                }
+            }
+
+            $(
+                /// Returns how many times the current state was retried with
+                /// `Transition::Retry` since it was entered (`0` after `init()` or a
+                /// transition), as counted in `ctx`'s `Bookkeeping` field.
+                #[must_use]
+                pub fn retry_count(&self, ctx: &$ctx_type) -> usize {
+                    ctx.$bookkeeping.retries
+                }
+            )?
+
+            /// Internal: Resolves a `Transition::Retry` of the current state: `Reenter`
+            /// while attempts remain, `To` its `on_exhausted` state once `max` is reached.
+            fn retry<E>(&self, ctx: &mut $ctx_type) -> Transition<Self, E> {
+                // Without `Bookkeeping` no state has a budget (see `require`): re-enter
+                let Some(book) = Self::bookkeeping_mut(ctx) else {
+                    return Transition::Reenter;
+                };
+                let retries = book.retries;
+                match self {
+                    $(
+                        $( #[cfg($state_cfg)] )*
+                        Self::$state_name { .. } => {
+                            $(
+                                // `max` attempts, the first being the entry into the state
+                                if retries + 1 >= $retry_max {
+                                    return Transition::To($enum_name::$retry_fail);
+                                }
+                            )?
+                        }
+                    )*
+                }
+                book.retries = retries + 1;
+                Transition::Reenter
+            }
+
+            /// Internal: The context's `Bookkeeping` field, `None` without the directive.
+            #[allow(unused_variables)]
+            #[inline(always)]
+            fn bookkeeping_mut(ctx: &mut $ctx_type) -> Option<&mut $crate::Bookkeeping> {
+                $crate::__fsm_bookkeeping!(get_mut [$( $bookkeeping )?] ctx)
+            }
+
+            $(
+                /// Returns the token the current state is parked on with
+                /// `Transition::Await`, as recorded in `ctx`'s `Bookkeeping` field, or `None`
+                /// if no completion is pending.
+                #[must_use]
+                pub fn pending_token(&self, ctx: &$ctx_type) -> Option<usize> {
+                    ctx.$bookkeeping.pending(self.state_index())
+                }
+            )?
+
+            /// Internal: Parks the current state on `token` (`Transition::Await`), in the
+            /// `Bookkeeping` field. Returns `false` if there is none to hold the token.
+            fn park(&self, ctx: &mut $ctx_type, token: usize) -> bool {
+                let state_index = self.state_index();
+                match Self::bookkeeping_mut(ctx) {
+                    Some(book) => {
+                        book.park(state_index, token);
+                        true
+                    }
+                    None => {
+                        // Without it the completion could never be delivered
+                        #[cfg(debug_assertions)]
+                        panic!(
+                            "[{}] Transition::Await in state {} needs the `Bookkeeping` directive",
+                            stringify!($enum_name),
+                            self.current_state_name()
+                        );
+                        #[cfg(not(debug_assertions))]
+                        false
+                    }
+                }
+            }
+
+            /// Internal: Issues the `generation()` of a starting machine, distinct from
+            /// every one issued before, even when machines of this type start on several
+            /// threads (e.g., two `FsmCell`s). Targets without atomic read-modify-write
+            /// (e.g., Cortex-M0) fall back to load/store: they have no threads. `0` is
+            /// never issued, so a fresh `Bookkeeping` matches no started machine.
+            fn next_generation() -> u32 {
+                use core::sync::atomic::{AtomicU32, Ordering};
+
+                static LAST_ISSUED: AtomicU32 = AtomicU32::new(0);
+                #[cfg(target_has_atomic = "32")]
+                let issue = || LAST_ISSUED.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+                #[cfg(not(target_has_atomic = "32"))]
+                let issue = || {
+                    let issued = LAST_ISSUED.load(Ordering::Relaxed).wrapping_add(1);
+                    LAST_ISSUED.store(issued, Ordering::Relaxed);
+                    issued
+                };
+                match issue() {
+                    // Wrapped around: skip `0`
+                    0 => issue(),
+                    issued => issued,
+                }
+            }
+
+            /// Returns `true` if `self` and `other` are the same state variant.
+            ///
+            /// Only the variants are compared; carried field values are ignored, so
+            /// `Running { speed: 1 }` and `Running { speed: 2 }` are the same variant.
+            /// No derives are required on the generated enum.
+            #[must_use]
+            pub fn same_variant(&self, other: &Self) -> bool {
+                core::mem::discriminant(self) == core::mem::discriminant(other)
+            }
+
+            /// Returns the position of the current state in declaration order (its index
+            /// in `STATE_NAMES`). Field values are ignored.
+            #[must_use]
+            pub fn state_index(&self) -> usize {
+                // One variant per state, numbered in declaration order
+                #[allow(non_camel_case_types)]
+                enum StateIndex { $( $( #[cfg($state_cfg)] )* $state_name, )* }
+
+                match self {
+                    $(
+                        $( #[cfg($state_cfg)] )*
+                        Self::$state_name { .. } => StateIndex::$state_name as usize,
+                    )*
+                }
+            }
+
+            /// Reverse of `state_index()`: rebuilds the `index`-th declared state, e.g.,
+            /// from a byte received over the wire.
+            ///
+            /// Returns `None` if `index` is out of range, or if that state carries fields
+            /// (their values are unknown). Round-trips every state only when all states
+            /// are fieldless. No hook runs: call `init()` on the result as usual.
+            #[must_use]
+            pub fn from_index(index: usize) -> Option<Self> {
+                #[allow(non_camel_case_types)]
+                enum StateIndex { $( $( #[cfg($state_cfg)] )* $state_name, )* }
+
+                $(
+                    $( #[cfg($state_cfg)] )*
+                    if index == StateIndex::$state_name as usize {
+                        return $crate::__fsm_fieldless_value!(
+                            $state_name $( { $($field_name : $field_type),* } )?
+                        );
+                    }
+                )*
+                None
+            }
+
+            /// `state_index()` as a single byte, e.g., to log or persist the current state
+            /// cheaply. Machines with more than 256 states fail to compile.
+            pub fn state_byte(&self) -> u8 {
+                const _: () = assert!(
+                    $enum_name::STATE_NAMES.len() <= 256,
+                    "state_byte() needs at most 256 states"
+                );
+                self.state_index() as u8
+            }
+
+            /// Reverse of `state_byte()`, with the same rules as `from_index()`: `None` if
+            /// `byte` is out of range or names a state with fields.
+            #[must_use]
+            pub fn from_byte(byte: u8) -> Option<Self> {
+                Self::from_index(byte as usize)
+            }
+
+            /// The declared name of the `index`-th state (`STATE_NAMES[index]`), or `None`
+            /// if `index` is out of range. Pairs with `state_index()` and `state_byte()` for
+            /// tools that speak integer state IDs.
+            #[must_use]
+            pub fn name_of_index(index: usize) -> Option<&'static str> {
+                Self::STATE_NAMES.get(index).copied()
+            }
+
+            /// Reverse of `name_of_index()`: the index of the state declared as `name`, or
+            /// `None` if no state has that name.
+            #[must_use]
+            pub fn index_of_name(name: &str) -> Option<usize> {
+                Self::STATE_NAMES.iter().position(|declared| *declared == name)
+            }
+
+            /// Yields every fieldless state once, in declaration order, skipping states
+            /// with fields. Handy for table-driven tests that start the machine in each
+            /// state. No hook runs: call `init()` on each value as usual.
+            pub fn all_fieldless_states() -> impl Iterator<Item = Self> {
+                (0..Self::STATE_NAMES.len()).filter_map(Self::from_index)
+            }
+
+            $crate::__fsm_cycle_next! {
+                $( $( #[cfg($state_cfg)] )* $state_name $( { $($field_name : $field_type),* } )? ; )*
+            }
+
+            /// The declared `TransitionTable` as `(from, event, to)` name triples, in
+            /// declaration order. Empty if no `TransitionTable` was declared.
+            pub const TRANSITIONS: &'static [(&'static str, &'static str, &'static str)] = &[
+                $($(
+                    (stringify!($tt_from), stringify!($tt_event), stringify!($tt_to)),
+                )*)?
+            ];
+
+            /// Returns `true` if the current state is listed in `Terminal` (a "done" state
+            /// of a workflow). Always `false` without a `Terminal` directive.
+            #[allow(unreachable_patterns)]
+            #[must_use]
+            pub fn is_terminal(&self) -> bool {
+                match self {
+                    $($( Self::$terminal { .. } => true, )+)?
+                    _ => false,
+                }
+            }
+
+            /// Internal: `true` in a terminal state with `StrictTerminal: true`, where
+            /// `dispatch` skips `process` (the state can't be left).
+            #[inline(always)]
+            fn is_locked_terminal(&self) -> bool {
+                false $( || ($strict_terminal && self.is_terminal()) )?
+            }
+
+            /// Names of all declared states, in declaration order.
+            pub const STATE_NAMES: &'static [&'static str] = &[
+                $( $( #[cfg($state_cfg)] )* stringify!($state_name), )*
+            ];
+
+            /// Number of declared states (the length of `STATE_NAMES`).
+            pub const STATE_COUNT: usize = $enum_name::STATE_NAMES.len();
+
+            /// Debug-build self-check of the declared `TransitionTable`: panics if a source
+            /// or target name is not one of `STATE_NAMES`.
+            ///
+            /// Call it from a test or at startup. Only available with `debug_assertions`.
+            #[cfg(debug_assertions)]
+            pub fn validate() {
+                Self::validate_transition_table(Self::TRANSITIONS);
+            }
+
+            /// Checks any `(from, event, to)` name table against `STATE_NAMES` (e.g., a
+            /// hand-written copy kept in docs or tooling), which the compiler cannot
+            /// cross-reference. Panics with the list of unknown names.
+            ///
+            /// Only available with `debug_assertions`.
+            #[cfg(debug_assertions)]
+            pub fn validate_transition_table(table: &[(&str, &str, &str)]) {
+                $crate::validate_transition_table(stringify!($enum_name), Self::STATE_NAMES, table);
+            }
+
+            /// Mermaid `stateDiagram-v2` diagram built from the declared `Initial` states
+            /// and `TransitionTable`, for GitHub READMEs and other Markdown docs.
+            ///
+            /// Assembled at compile time with `concat!` (no runtime cost): one
+            /// `[*] --> State` line per `Initial` state, then one
+            /// `From --> To : Event` line per table entry, in declaration order.
+            pub fn to_mermaid() -> &'static str {
+                concat!(
+                    "stateDiagram-v2\n",
+                    $($( "    [*] --> ", stringify!($initial), "\n", )+)?
+                    $($(
+                        "    ", stringify!($tt_from), " --> ", stringify!($tt_to),
+                        " : ", stringify!($tt_event), "\n",
+                    )*)?
+                )
+            }
+
+            /// Names of the states reachable in one transition from the current state,
+            /// according to `TRANSITIONS`.
+            ///
+            /// Each target is yielded once, in table order, even if several events lead
+            /// to it. Allocation-free: this is a filtered iterator over `TRANSITIONS`.
+            #[must_use]
+            pub fn successors(&self) -> impl Iterator<Item = &'static str> {
+                let current = self.current_state_name();
+                Self::TRANSITIONS
+                    .iter()
+                    .enumerate()
+                    .filter(move |(i, (from, _, to))| {
+                        *from == current
+                            && !Self::TRANSITIONS[..*i]
+                                .iter()
+                                .any(|(f, _, t)| *f == current && t == to)
+                    })
+                    .map(|(_, (_, _, to))| *to)
+            }
+
+            /// The `(event, target)` name pairs that `TRANSITIONS` declares out of the
+            /// current state, in table order, e.g., to build help text ("Start: go to
+            /// Running") or to validate input against the current state.
+            ///
+            /// A slice grouped at compile time: no filtering or allocation at runtime.
+            /// Empty without a `TransitionTable`.
+            #[must_use]
+            pub fn available_transitions(&self) -> &'static [(&'static str, &'static str)] {
+                const STATES: usize = $enum_name::STATE_NAMES.len();
+                const EDGES: usize = $enum_name::TRANSITIONS.len();
+                static BY_STATE: $crate::TransitionsByState<STATES, EDGES> =
+                    $crate::transitions_by_state($enum_name::STATE_NAMES, $enum_name::TRANSITIONS);
+
+                let index = self.state_index();
+                &BY_STATE.0[index][..BY_STATE.1[index]]
+            }
+
+            /// `true` if `TRANSITIONS` declares an edge for `event_name` (a `TransitionTable`
+            /// event name, e.g., as returned by `event_name()`) out of the current state.
+            ///
+            /// Nothing is dispatched, so it can drive UIs (e.g., graying out buttons whose
+            /// event would be a no-op). Always `false` without a `TransitionTable`.
+            #[must_use]
+            pub fn can_handle(&self, event_name: &str) -> bool {
+                let current = self.current_state_name();
+                Self::TRANSITIONS
+                    .iter()
+                    .any(|(from, event, _)| *from == current && *event == event_name)
+            }
+
+            /// `true` if `TRANSITIONS` declares a path, over any number of edges, from the
+            /// state named `from` to the state named `to`. A state always reaches itself.
+            ///
+            /// Useful in tests to check that every terminal state can be reached from the
+            /// initial one. `false` if either name is not one of `STATE_NAMES`. A
+            /// breadth-first search with fixed-size arrays (one slot per state), so it
+            /// doesn't allocate.
+            #[must_use]
+            pub fn can_reach(from: &str, to: &str) -> bool {
+                const STATES: usize = $enum_name::STATE_NAMES.len();
+                let index_of = |name: &str| Self::STATE_NAMES.iter().position(|s| *s == name);
+                let (Some(start), Some(target)) = (index_of(from), index_of(to)) else {
+                    return false;
+                };
+
+                let mut visited = [false; STATES];
+                let mut pending = [0usize; STATES];
+                visited[start] = true;
+                pending[0] = start;
+                let (mut head, mut tail) = (0, 1);
+                while head < tail {
+                    let state = pending[head];
+                    head += 1;
+                    if state == target {
+                        return true;
+                    }
+                    for (edge_from, _, edge_to) in Self::TRANSITIONS {
+                        if *edge_from != Self::STATE_NAMES[state] {
+                            continue;
+                        }
+                        if let Some(next) = index_of(edge_to) {
+                            if !visited[next] {
+                                visited[next] = true;
+                                pending[tail] = next;
+                                tail += 1;
+                            }
+                        }
+                    }
+                }
+                false
+            }
+
+            /// Number of edges in `TRANSITIONS`.
+            pub const TRANSITION_COUNT: usize = $enum_name::TRANSITIONS.len();
+
+            $crate::__fsm_transition_counters! {
+                [$( $count_transitions )?] api $enum_name, core::sync::atomic
+            }
+
+            /// Internal: Bookkeeping once a `Transition::To` / `ToFn` has been applied
+            /// (edge counters, `PathTrace`, `TransitionObserver`, watchdog, retries,
+            /// `Invariant`).
+            #[inline(always)]
+            fn record_transition(&mut self, ctx: &mut $ctx_type, from: &'static str, from_index: usize) {
+                if let Some(book) = Self::bookkeeping_mut(ctx) {
+                    book.leave_state();
+                }
+                $crate::__fsm_transition_counters!([$( $count_transitions )?] count from_index, self.state_index());
+                $crate::__fsm_test_log!(record from, self.current_state_name());
+                $crate::__fsm_path_trace!([$( $path_trace )?] record self.current_state_name());
+                $( (ctx.$observer)(from, self.current_state_name()); )?
+                self.watchdog_step(ctx, true);
+                self.check_invariant(ctx);
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: The `EventFilter` predicate (`true` when none was declared).
+                #[inline(always)]
+                fn accepts(
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_evt: &$event_type,
+                ) -> bool {
+                    true $( && {
+                        // Rename the filter arguments to what the user chose
+                        #[allow(unused_variables)]
+                        let $filter_ctx = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $filter_evt = arg_evt;
+                        $filter_block
+                    } )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the `OnUnhandled` hook (if the user declared one).
+                fn on_unhandled(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_evt: &$event_type,
+                ) {
+                    $(
+                        // Rename the hook arguments to what the user chose
+                        #[allow(unused_variables)]
+                        let $unhandled_ctx = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $unhandled_state = self.current_state_name();
+                        #[allow(unused_variables)]
+                        let $unhandled_evt = arg_evt;
+
+                        $unhandled_block
+                    )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Evaluates the `Invariant` (if the user declared one) after a
+                /// transition. A violation runs `OnInvariantViolation`, or panics in debug
+                /// builds when no hook was declared.
+                #[inline(always)]
+                fn check_invariant(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                ) {
+                    $(
+                        let holds = {
+                            // Read-only: the invariant observes the context, it can't fix it
+                            #[allow(unused_variables)]
+                            let $inv_ctx: &$ctx_type = arg_ctx;
+                            #[allow(unused_variables)]
+                            let $inv_state = self.current_state_name();
+
+                            $inv_block
+                        };
+                        if !holds {
+                            self.on_invariant_violation(arg_ctx);
+                        }
+                    )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the `OnInvariantViolation` hook, or panics in debug
+                /// builds if the user declared none.
+                #[allow(dead_code)]
+                #[cold]
+                fn on_invariant_violation(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                ) {
+                    $(
+                        #[allow(unused_variables)]
+                        let $violation_ctx = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $violation_state = self.current_state_name();
+
+                        $violation_block
+                    )?
+                    #[cfg(debug_assertions)]
+                    if !$crate::__fsm_declared!([$( $violation_ctx )?]) {
+                        panic!(
+                            "[{}] Invariant violated in state {}",
+                            stringify!($enum_name),
+                            self.current_state_name()
+                        );
+                    }
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the `OnQuiescent` hook (if the user declared one).
+                #[inline(always)]
+                fn on_quiescent(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                ) {
+                    $(
+                        #[allow(unused_variables)]
+                        let $quiescent_ctx = arg_ctx;
+
+                        $quiescent_block
+                    )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Advances the `Watchdog` (if declared) after a dispatch step.
+                ///
+                /// `transitioned == true` resets the count; otherwise the `OnStuck` hook runs
+                /// when the count of consecutive stayed dispatches reaches the limit.
+                #[inline(always)]
+                fn watchdog_step(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    transitioned: bool,
+                ) {
+                    $(
+                        // Consecutive dispatches without a transition, counted per instance
+                        // (the `Watchdog` requires `Bookkeeping`)
+                        let Some(book) = Self::bookkeeping_mut(arg_ctx) else {
+                            return;
+                        };
+                        if transitioned {
+                            book.stayed = 0;
+                            return;
+                        }
+                        book.stayed = book.stayed.saturating_add(1);
+
+                        if book.stayed == $watchdog_limit {
+                            #[allow(unused_variables)]
+                            let $stuck_ctx = arg_ctx;
+                            #[allow(unused_variables)]
+                            let $stuck_state = self.current_state_name();
+
+                            $stuck_block
+                        }
+                    )?
+                }
+            }
+
+            /// Internal: `false` when entering the current state runs no code at all (no
+            /// `entry` hook, no `AnyEntry`, no logging), so `dispatch` skips the call.
+            #[inline(always)]
+            fn has_entry_hook(&self) -> bool {
+                $crate::__fsm_log!(enabled)
+                    || $crate::__fsm_declared!([$( $any_entry_ctx )?])
+                    || match self {
+                        $(
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name { .. } => $crate::__fsm_declared!([$( $entry_ctx )?]),
+                        )*
+                    }
+            }
+
+            /// Internal: `false` when leaving the current state runs no code at all (no
+            /// `exit` hook, no `AnyExit`, no logging), so `dispatch` skips the call.
+            #[inline(always)]
+            fn has_exit_hook(&self) -> bool {
+                $crate::__fsm_log!(enabled)
+                    || $crate::__fsm_declared!([$( $any_exit_ctx )?])
+                    || match self {
+                        $(
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name { .. } => $crate::__fsm_declared!([$( $exit_ctx )?]),
+                        )*
+                    }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the entry action for the current state.
+                fn on_entry(
+                    &mut self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_from: &'static str,
+                    #[allow(unused_variables)] arg_is_init: bool,
+                ) {
+                    $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] entry: $enum_name, self);
+                    match self {
+                        $(
+                            // Matches the current state and captures its fields (if any)
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name $( { $($field_name),* } )? => {
+                                // Fields the hook may not use
+                                $( $( let _ = &$field_name; )* )?
+
+                                // Only expands if the user defined an entry block
+                                $(
+                                    // Rename the context variable to what the user chose (e.g., |ctx|)
+                                    #[allow(unused_variables)]
+                                    let $entry_ctx: &mut $ctx_type = arg_ctx;
+                                    $(
+                                        #[allow(unused_variables)]
+                                        let $entry_from: &'static str = arg_from;
+                                        $(
+                                            #[allow(unused_variables)]
+                                            let $entry_is_init: bool = arg_is_init;
+                                        )?
+                                    )?
+
+                                    // Execute user code
+                                    $entry_block
+                                )?
+                            }
+                        )*
+                    }
+
+                    // Machine-wide entry hook, after the state's own entry
+                    $(
+                        #[allow(unused_variables)]
+                        let $any_entry_ctx = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $any_entry_state = self.current_state_name();
+                        $any_entry_block
+                    )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the exit action for the current state.
+                fn on_exit(
+                    &mut self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_to: &'static str,
+                ) {
+                    $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] exit: $enum_name, self);
+
+                    // Machine-wide exit hook, before the state's own exit
+                    $(
+                        #[allow(unused_variables)]
+                        let $any_exit_ctx: &mut $ctx_type = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $any_exit_state = self.current_state_name();
+                        $any_exit_block
+                    )?
+
+                    match self {
+                        $(
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name $( { $($field_name),* } )? => {
+                                // Fields the hook may not use
+                                $( $( let _ = &$field_name; )* )?
+                                $(
+                                    #[allow(unused_variables)]
+                                    let $exit_ctx = arg_ctx;
+                                    $(
+                                        #[allow(unused_variables)]
+                                        let $exit_to: &'static str = arg_to;
+                                    )?
+                                    $exit_block
+                                )?
+                            }
+                        )*
+                    }
+                }
+            }
+
+            $crate::__fsm_inline! { [$( $code_size )?] process [$( $strict_lints )?]
+                /// Internal: Determines the next state based on the event.
+                /// Returns a `Transition` enum.
+                fn on_process(
+                    &mut self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                    #[allow(unused_variables)] arg_instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                    #[allow(unused_variables)] arg_evt: &$event_type,
+                ) -> Transition<Self, $event_type> {
+                    // Instrumentation, bound under the name declared by the directive
+                    $(
+                        #[allow(unused_variables)]
+                        let $instr_var: &mut $instr_type = arg_instr;
+                    )?
+
+                    // Machine-wide prelude, before the state's own process
+                    $(
+                        {
+                            #[allow(unused_variables)]
+                            let $prelude_ctx: &mut $ctx_type = &mut *arg_ctx;
+                            #[allow(unused_variables)]
+                            let $prelude_evt: &$event_type = arg_evt;
+                            $prelude_block
+                        }
+                    )?
+
+                    match self {
+                        $(
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name $( { $($field_name),* } )? => {
+                                // The state's fields, which the user logic may not need
+                                // for this event
+                                $( $( let _ = &$field_name; )* )?
+
+                                // Mixins first, in `use` order: the first one that handles the
+                                // event (doesn't return `Unhandled`) decides the transition
+                                $($(
+                                    match $state_mixin::process_fragment(&mut *arg_ctx, arg_evt) {
+                                        Transition::Unhandled => {}
+                                        transition => return transition,
+                                    }
+                                )*)?
+
+                                // Bind context and event to user-defined names (e.g., |ctx, evt|)
+                                #[allow(unused_variables)]
+                                let $ctx_var = arg_ctx;
+
+                                // Readonly states only see a shared reborrow of the context
+                                $(
+                                    #[allow(unused_variables)]
+                                    let $ctx_var: &$ctx_type = $crate::__fsm_readonly!($readonly $ctx_var);
+                                )?
+
+                                #[allow(unused_variables)]
+                                let $evt_var = arg_evt;
+                                $(
+                                    #[allow(unused_variables)]
+                                    let $shared_var = arg_shared;
+                                )?
+
+                                // Execute user's process logic (a bare state or `()` is sugar,
+                                // see `IntoTransition`)
+                                $crate::IntoTransition::into_transition({ $($process_body)* })
+                            }
+                        )*
+                    }
+                }
+            }
+
+            $crate::__fsm_inline! { [$( $code_size )?] hot [$( $must_use )?]
+                /// Main Event Dispatcher.
+                ///
+                /// This is the primary function to call in your main loop.
+                /// It handles the full lifecycle: `Process` -> `Exit Old` -> `Update` -> `Entry New`.
+                ///
+                /// Events queued with `post()` while handling `event` are processed
+                /// afterwards, in order, before `dispatch()` returns.
+                ///
+                /// Returns whether `event` caused a transition (see `DispatchOutcome`), or
+                /// `DispatchOutcome::Frozen` without running any hook after `freeze()`.
+                ///
+                /// # Performance
+                /// Marked `#[inline(always)]` to allow the compiler to flatten the state machine
+                /// into a highly optimized jump table / switch-case structure.
+                pub fn dispatch(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: &$event_type,
+                ) -> $crate::DispatchOutcome {
+                    self.dispatch_ref(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event,
+                    )
+                }
+            }
+
+            $crate::__fsm_inline! { [$( $code_size )?] hot [$( $must_use )?]
+                /// Event Dispatcher taking the event by value.
+                ///
+                /// Equivalent to `dispatch(ctx, &event)`. Provided so code written for the
+                /// `concurrent` feature (where it avoids cloning queued events) also compiles
+                /// in the default build.
+                pub fn dispatch_owned(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: $event_type,
+                ) -> $crate::DispatchOutcome {
+                    self.dispatch_value(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event,
+                    )
+                }
+            }
+
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Converts `event` into the event type, then dispatches it like
+                /// `dispatch_owned()`.
+                ///
+                /// Saves the wrapping at call sites fed by parsers or drivers that produce
+                /// values convertible into the event (e.g., a raw byte with
+                /// `impl From<u8> for Event`).
+                #[inline]
+                pub fn dispatch_into(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: impl Into<$event_type>,
+                ) -> $crate::DispatchOutcome {
+                    self.dispatch_value(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event.into(),
+                    )
+                }
+            }
+
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Dispatches `event` like `dispatch()` and returns the outcome together with
+                /// the machine itself, so the resulting state can be read in the same
+                /// expression: `fsm.dispatch_and_peek(&mut ctx, &event).1.current_state_name()`.
+                pub fn dispatch_and_peek(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: &$event_type,
+                ) -> ($crate::DispatchOutcome, &Self) {
+                    let outcome = self.dispatch_ref(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event,
+                    );
+                    (outcome, self)
+                }
+            }
+
+            /// Simulation/test driver: pulls events from `next` and dispatches each one
+            /// until the machine is in the state named `stop` (see `current_state_name()`)
+            /// or `next` returns `None`.
+            ///
+            /// Returns the number of events pulled from `next` and dispatched (events
+            /// drained from the `post()` queue along the way are not counted). If the
+            /// machine is already in `stop`, `next` is never called and `0` is returned.
+            ///
+            /// ```rust
+            /// # use typed_fsm::{state_machine, Transition};
+            /// # struct Context {}
+            /// # #[derive(Debug, Clone)]
+            /// # enum Event { Step }
+            /// # state_machine! {
+            /// #     Name: FSM,
+            /// #     Context: Context,
+            /// #     Event: Event,
+            /// #     States: {
+            /// #         A => { process: |_ctx, _evt| { Transition::To(FSM::B) } },
+            /// #         B => { process: |_ctx, _evt| { Transition::To(FSM::Done) } },
+            /// #         Done => { process: |_ctx, _evt| { Transition::None } }
+            /// #     }
+            /// # }
+            /// let mut ctx = Context {};
+            /// let mut fsm = FSM::start_in(FSM::A, &mut ctx);
+            /// let processed = fsm.run_until(&mut ctx, "Done", || Some(Event::Step));
+            /// assert_eq!(processed, 2);
+            /// ```
+            pub fn run_until<F>(
+                &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                $(, instrumentation: &mut $instr_type)?,
+                stop: &str,
+                mut next: F,
+            ) -> usize
+            where
+                F: FnMut() -> Option<$event_type>,
+            {
+                let ctx: &mut $ctx_type = $ctx_arg;
+                let shared: &$crate::__fsm_shared!(type [$( $shared_type )?]) = $crate::__fsm_shared!(arg [$( $shared_type )?] shared);
+                let instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]) =
+                    $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation);
+                let mut processed = 0;
+                while self.current_state_name() != stop {
+                    let Some(event) = next() else {
+                        break;
+                    };
+                    self.dispatch_value(ctx, shared, instr, event);
+                    processed += 1;
+                }
+                processed
+            }
+
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Dispatches `event` like `dispatch()`, then calls `after` with the new state if
+                /// the event caused a `Transition::To`, for final fixups of the just-entered state's
+                /// fields (e.g., storing a handle obtained outside the state machine).
+                ///
+                /// `after` runs once the new state's `entry` has completed, before events queued
+                /// with `post()` are processed. It does not run for `Transition::None`,
+                /// `Transition::Unhandled` or `Transition::Reenter`.
+                ///
+                /// ```rust
+                /// # use typed_fsm::{state_machine, Transition};
+                /// # struct Context {}
+                /// # #[derive(Debug, Clone)]
+                /// # enum Event { Connect }
+                /// # state_machine! {
+                /// #     Name: Link,
+                /// #     Context: Context,
+                /// #     Event: Event,
+                /// #     States: {
+                /// #         Idle => { process: |_ctx, _evt| { Transition::To(Link::Connected { handle: 0 }) } },
+                /// #         Connected { handle: u32 } => { process: |_ctx, _evt| { Transition::None } }
+                /// #     }
+                /// # }
+                /// let mut ctx = Context {};
+                /// let mut fsm = Link::start_in(Link::Idle, &mut ctx);
+                ///
+                /// fsm.dispatch_then(&mut ctx, &Event::Connect, |state| {
+                ///     if let Link::Connected { handle } = state {
+                ///         *handle = 42;
+                ///     }
+                /// });
+                /// assert!(matches!(fsm, Link::Connected { handle: 42 }));
+                /// ```
+                pub fn dispatch_then<F>(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: &$event_type,
+                    after: F,
+                ) -> $crate::DispatchOutcome
+                where
+                    F: FnOnce(&mut Self),
+                {
+                    let ctx: &mut $ctx_type = $ctx_arg;
+                    let shared: &$crate::__fsm_shared!(type [$( $shared_type )?]) = $crate::__fsm_shared!(arg [$( $shared_type )?] shared);
+                    let instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]) =
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation);
+                    if Self::is_frozen() {
+                        return $crate::DispatchOutcome::Frozen;
+                    }
+                    $crate::__fsm_reentrancy_guard!(enter);
+                    $crate::__fsm_record!([$( $record )?] record event);
+                    let outcome = self.dispatch_one(ctx, shared, instr, event);
+                    if outcome == $crate::DispatchOutcome::Transitioned {
+                        after(self);
+                    }
+                    self.drain_posted(ctx, shared, instr);
+                    outcome
+                }
+            }
+
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Dispatches `event` like `dispatch()` unless it is stale: when `now > deadline`,
+                /// nothing runs and `DispatchOutcome::Expired` is returned.
+                ///
+                /// `now` and `deadline` are timestamps in any monotonic unit (ticks, microseconds,
+                /// ...), so stale events can be dropped without time checks in every state.
+                pub fn dispatch_before(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: &$event_type,
+                    now: u64,
+                    deadline: u64,
+                ) -> $crate::DispatchOutcome {
+                    if now > deadline {
+                        return $crate::DispatchOutcome::Expired;
+                    }
+                    self.dispatch_ref(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event,
+                    )
+                }
+            }
+
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Resumes a state parked with `Transition::Await(token)`: dispatches `event`
+                /// like `dispatch()` if the current state is still awaiting `token`.
+                ///
+                /// Call it from the completion callback of the operation the state started. A
+                /// stale completion (the machine left the state, or the token was already
+                /// completed or replaced) is dropped: nothing runs and
+                /// `DispatchOutcome::Stale` is returned. The pending token lives in the
+                /// `Bookkeeping` context field: without that directive, every completion is
+                /// stale.
+                ///
+                /// A completion that is not processed (`Frozen`, `Filtered`) leaves the
+                /// token pending, so it can be delivered again.
+                pub fn complete(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    token: usize,
+                    event: &$event_type,
+                ) -> $crate::DispatchOutcome {
+                    let ctx: &mut $ctx_type = $ctx_arg;
+                    if Self::is_frozen() {
+                        return $crate::DispatchOutcome::Frozen;
+                    }
+                    let state_index = self.state_index();
+                    if !Self::bookkeeping_mut(ctx).is_some_and(|book| book.unpark(state_index, token)) {
+                        return $crate::DispatchOutcome::Stale;
+                    }
+                    let shared: &$crate::__fsm_shared!(type [$( $shared_type )?]) = $crate::__fsm_shared!(arg [$( $shared_type )?] shared);
+                    let instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]) =
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation);
+                    $crate::__fsm_reentrancy_guard!(enter);
+                    $crate::__fsm_record!([$( $record )?] record event);
+                    let outcome = self.dispatch_one(ctx, shared, instr, event);
+                    if matches!(outcome, $crate::DispatchOutcome::Filtered) {
+                        // Rejected before any hook ran: the state still awaits the token
+                        if let Some(book) = Self::bookkeeping_mut(ctx) {
+                            book.park(state_index, token);
+                        }
+                    }
+                    self.drain_posted(ctx, shared, instr);
+                    outcome
+                }
+            }
+
+            $crate::__fsm_generation! {
+                [$( $bookkeeping )?] $ctx_type, $event_type, [$( $shared_type )?] [$( $instr_type )?] []
+            }
+
+            /// Internal: `dispatch()` body, shared with `dispatch_before()`.
+            #[inline(always)]
+            fn dispatch_ref(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                event: &$event_type,
+            ) -> $crate::DispatchOutcome {
+                if Self::is_frozen() {
+                    return $crate::DispatchOutcome::Frozen;
+                }
+                $crate::__fsm_reentrancy_guard!(enter);
+                $crate::__fsm_record!([$( $record )?] record event);
+                let outcome = self.dispatch_one(ctx, shared, instr, event);
+
+                // 3. Run-to-completion: process events posted while handling this one
+                self.drain_posted(ctx, shared, instr);
+                outcome
+            }
+
+            /// Internal: `dispatch_owned()` body, shared with `run_until()`.
+            #[inline(always)]
+            fn dispatch_value(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                event: $event_type,
+            ) -> $crate::DispatchOutcome {
+                if Self::is_frozen() {
+                    return $crate::DispatchOutcome::Frozen;
+                }
+                $crate::__fsm_reentrancy_guard!(enter);
+                $crate::__fsm_record!([$( $record )?] record &event);
+                let outcome = self.dispatch_one(ctx, shared, instr, &event);
+                self.drain_posted(ctx, shared, instr);
+                outcome
+            }
+
+            /// Internal: Processes a single event (no deferred events), timed by
+            /// `MaxLatency` if declared.
+            #[inline(always)]
+            fn dispatch_one(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                event: &$event_type,
+            ) -> $crate::DispatchOutcome {
+                let (outcome, mut follow_up) = $crate::__fsm_max_latency!(
+                    [$( $latency_now )?] time [$crate::atomic64]
+                    self.dispatch_one_untimed(ctx, shared, instr, event)
+                );
+                // `ToAndPost` follow-ups, each timed on its own. A loop rather than
+                // recursion, so a long chain of them does not grow the stack
+                while let Some(event) = follow_up.take() {
+                    follow_up = $crate::__fsm_max_latency!(
+                        [$( $latency_now )?] time [$crate::atomic64]
+                        self.dispatch_one_untimed(ctx, shared, instr, &event)
+                    )
+                    .1;
+                }
+                outcome
+            }
+
+            /// Internal: `dispatch_one()` body for one event.
+            /// Returns `Transitioned` if it applied a `Transition::To`, `Stayed` otherwise,
+            /// along with the `Transition::ToAndPost` follow-up event, if any.
+            #[inline(always)]
+            fn dispatch_one_untimed(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                event: &$event_type,
+            ) -> ($crate::DispatchOutcome, Option<$event_type>) {
+                // 0. Machine-wide gate: a rejected event never reaches any hook
+                if !Self::accepts(ctx, event) {
+                    return ($crate::DispatchOutcome::Filtered, None);
+                }
+                $crate::__fsm_metrics!([$( $metrics )?] count [$crate::atomic64]);
+
+                // 1. Calculate Transition
+                let transition = if self.is_locked_terminal() {
+                    Transition::None
+                } else {
+                    self.on_process(ctx, shared, instr, event)
+                };
+
+                // Resolve `Retry`, and split off a follow-up event processed once the
+                // transition is applied
+                let (transition, follow_up) = match transition {
+                    Transition::ToAndPost(new_state, follow_up) => (Transition::To(new_state), Some(follow_up)),
+                    Transition::Retry => (self.retry(ctx), None),
+                    transition => (transition, None),
+                };
+
+                // 2. Apply Transition (if any)
+                let outcome = match transition {
+                    Transition::To(mut new_state) => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] transition: $enum_name, self, event, new_state);
+
+                        // A. Exit current state (told where it is going). Hook-less states
+                        // skip the call altogether
+                        let from = self.current_state_name();
+                        let from_index = self.state_index();
+                        if self.has_exit_hook() {
+                            self.on_exit(ctx, new_state.current_state_name());
+                        }
+
+                        // B. Enter new state (told where it came from)
+                        if new_state.has_entry_hook() {
+                            new_state.on_entry(ctx, from, false);
+                        }
+
+                        // C. Update state (Move semantics - extremely fast)
+                        *self = new_state;
+                        self.record_transition(ctx, from, from_index);
+                        $crate::DispatchOutcome::Transitioned
+                    }
+                    Transition::ToFn(make) => {
+                        // A. Exit current state first: the target does not exist yet
+                        let from = self.current_state_name();
+                        let from_index = self.state_index();
+                        if self.has_exit_hook() {
+                            self.on_exit(ctx, "");
+                        }
+
+                        // B. Build the target only now that the transition is certain
+                        let mut new_state = make();
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] transition: $enum_name, self, event, new_state);
+                        if new_state.has_entry_hook() {
+                            new_state.on_entry(ctx, from, false);
+                        }
+
+                        // C. Update state
+                        *self = new_state;
+                        self.record_transition(ctx, from, from_index);
+                        $crate::DispatchOutcome::Transitioned
+                    }
+                    Transition::Reenter => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] transition: $enum_name, self, event, self);
+
+                        // Restart the current state's lifecycle, keeping its data
+                        let name = self.current_state_name();
+                        if self.has_exit_hook() {
+                            self.on_exit(ctx, name);
+                        }
+                        if self.has_entry_hook() {
+                            self.on_entry(ctx, name, false);
+                        }
+
+                        self.watchdog_step(ctx, true);
+                        self.check_invariant(ctx);
+                        $crate::DispatchOutcome::Stayed
+                    }
+                    Transition::None => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] stayed: $enum_name, self, event, "None");
+                        self.watchdog_step(ctx, false);
+                        $crate::DispatchOutcome::Stayed
+                    }
+                    Transition::Unhandled => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] stayed: $enum_name, self, event, "Unhandled");
+                        self.on_unhandled(ctx, event);
+                        self.watchdog_step(ctx, false);
+                        $crate::DispatchOutcome::Stayed
+                    }
+                    Transition::Await(token) => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] stayed: $enum_name, self, event, "Await");
+                        if !self.park(ctx, token) {
+                            // No completion can ever resume the state: the event was
+                            // not handled after all
+                            self.on_unhandled(ctx, event);
+                        }
+                        self.watchdog_step(ctx, false);
+                        $crate::DispatchOutcome::Stayed
+                    }
+                    // Resolved above
+                    Transition::ToAndPost(..) | Transition::Retry => unreachable!(),
+                };
+                (outcome, follow_up)
+            }
+
+            /// Freezes the state machine: until `thaw()`, every dispatch ignores its event
+            /// and returns `DispatchOutcome::Frozen` without running any hook.
+            ///
+            /// Use it for critical sections (e.g., a firmware update) instead of guarding
+            /// every state's `process`. The flag belongs to the state machine type, not to
+            /// a value: `Self::freeze()` freezes every machine of this type. Events already
+            /// queued with `post()` are kept and processed by the first dispatch after
+            /// `thaw()`.
+            pub fn freeze() {
+                Self::frozen_flag().store(true, core::sync::atomic::Ordering::Relaxed);
+            }
+
+            /// Resumes normal dispatching of every machine of this type after `freeze()`.
+            pub fn thaw() {
+                Self::frozen_flag().store(false, core::sync::atomic::Ordering::Relaxed);
+            }
+
+            /// Returns `true` between `freeze()` and `thaw()`.
+            pub fn is_frozen() -> bool {
+                Self::frozen_flag().load(core::sync::atomic::Ordering::Relaxed)
+            }
+
+            /// Internal: The `freeze()` flag of this state machine.
+            fn frozen_flag() -> &'static core::sync::atomic::AtomicBool {
+                static FROZEN: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+                &FROZEN
+            }
+
+            $crate::__fsm_reentrancy_guard! { api $enum_name }
+
+            /// Internal: Processes events queued with `post()` (only with `QueueCapacity`),
+            /// then runs the `OnQuiescent` hook.
+            #[allow(unused_variables)]
+            #[inline(always)]
+            fn drain_posted(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+            ) {
+                $(
+                    let queue: &$crate::DeferredQueue<$event_type, { $queue_capacity }> =
+                        Self::posted_queue();
+                    while let Some(evt) = queue.pop() {
+                        self.dispatch_one(ctx, shared, instr, &evt);
+                    }
+                )?
+
+                // Nothing left to process
+                self.on_quiescent(ctx);
+            }
+
+            $(
+                /// Internal: The deferred-event queue of this state machine.
+                fn posted_queue() -> &'static $crate::DeferredQueue<$event_type, { $queue_capacity }> {
+                    static QUEUE: $crate::DeferredQueue<$event_type, { $queue_capacity }> =
+                        $crate::DeferredQueue::new();
+                    &QUEUE
+                }
+
+                /// Queues an event to be processed after the current one (run-to-completion).
+                ///
+                /// Intended for `entry`, `process` and `exit` code that needs to chain
+                /// events: the event is handled by the running `dispatch()` once the current
+                /// event is complete. Events posted outside a dispatch wait for the next one.
+                ///
+                /// If the queue (capacity: `QueueCapacity`) is full, the event is handed back
+                /// in `Err` and `dropped_events_count()` is incremented.
+                pub fn post(event: $event_type) -> Result<(), $event_type> {
+                    Self::posted_queue().push(event)
+                }
+
+                /// Returns the number of posted events dropped because the queue was full.
+                pub fn dropped_events_count() -> usize {
+                    Self::posted_queue().dropped()
+                }
+
+                /// Resets the dropped events counter to zero.
+                pub fn reset_dropped_count() {
+                    Self::posted_queue().reset_dropped();
+                }
+            )?
+
+            $crate::__fsm_metrics! { [$( $metrics )?] api [$crate::atomic64] }
+            $crate::__fsm_max_latency! { [$( $latency_now )?] api [$crate::atomic64] }
+
+            $crate::__fsm_path_trace! { [$( $path_trace )?] api }
+
+            $crate::__fsm_test_log! { api }
+
+            $crate::__fsm_record! {
+                [$( $record )?] api $event_type,
+                [$($ctx_param)*] $ctx_arg, [$( $shared_type )?] [$( $instr_type )?]
+            }
+        }
+    };
+}
+
+// ============================================================================
+// IMPLEMENTATION WITH CONCURRENCY PROTECTION (feature = "concurrent")
+// ============================================================================
+#[cfg(feature = "concurrent")]
+#[macro_export]
+macro_rules! state_machine {
+    // Pattern 1: With Context
+    (
+        Name: $enum_name:ident,
+        Context: $ctx_type:ty,
+        Event: $event_type:ty,
+        $($rest:tt)*
+    ) => {
+        $crate::__fsm_missing_process! { $($rest)* }
+
+        $crate::state_machine! {
+            @queue
+            Name: $enum_name,
+            Context: $ctx_type => [, ctx: &mut $ctx_type] ctx,
+            Event: $event_type,
+            $($rest)*
+        }
+    };
+
+    // Pattern 2: Context-free (hooks take no context argument)
+    (
+        Name: $enum_name:ident,
+        Event: $event_type:ty,
+        $($rest:tt)*
+    ) => {
+        $crate::__fsm_missing_process! { $($rest)* }
+
+        $crate::__fsm_context_free! {
+            [
+                @queue
+                Name: $enum_name,
+                Context: () => [] &mut (),
+                Event: $event_type,
+            ]
+            $($rest)*
+        }
+    };
+
+    // With explicit QueueCapacity
+    (
+        @queue
+        Name: $enum_name:ident,
+        Context: $ctx_type:ty => [$($ctx_param:tt)*] $ctx_arg:expr,
+        Event: $event_type:ty,
+        QueueCapacity: $queue_capacity:expr,
+        $($rest:tt)*
+    ) => {
+        $crate::state_machine! {
+            @internal
+            Name: $enum_name,
+            Context: $ctx_type => [$($ctx_param)*] $ctx_arg,
+            Event: $event_type,
+            QueueCapacity: $queue_capacity,
+            $($rest)*
+        }
+    };
+
+    // Without QueueCapacity (default to 16)
+    (
+        @queue
+        Name: $enum_name:ident,
+        Context: $ctx_type:ty => [$($ctx_param:tt)*] $ctx_arg:expr,
+        Event: $event_type:ty,
+        $($rest:tt)*
+    ) => {
+        $crate::state_machine! {
+            @internal
+            Name: $enum_name,
+            Context: $ctx_type => [$($ctx_param)*] $ctx_arg,
+            Event: $event_type,
+            QueueCapacity: 16,
+            $($rest)*
+        }
+    };
+
+    // Internal implementation (actual code generation)
+    (
+        @internal
+        Name: $enum_name:ident,
+        // Public signatures take `$ctx_param` (empty when context-free) and
+        // hand `$ctx_arg` to the internal functions
+        Context: $ctx_type:ty => [$($ctx_param:tt)*] $ctx_arg:expr,
+        Event: $event_type:ty,
+        QueueCapacity: $queue_capacity:expr,
+        $( OverflowPolicy: $overflow_policy:ident, )?
+        $( DroppedBuffer: $dropped_buffer:expr, )?
+        $( SharedRef: $shared_type:ty, )?
+        $( Instrumentation: $instr_var:ident : $instr_type:ty, )?
+        $( Repr: $repr:ident, )?
+        $( MaxSize: $max_size:expr, )?
+        $( Attrs: [ $( #[$attr:meta] ),* $(,)? ], )?
+        $( Derives: [ $( $derive:path ),* $(,)? ], )?
+        $( DebugMode: $debug_mode:ident, )?
+        $( Default: $default_state:ident, )?
+        $( EmbedContext: $embed:ident, )?
+        $( Snapshot: $snap:ident { fields: [ $( $snap_field:ident : $snap_ty:ty ),* $(,)? ] $(,)? }, )?
+        $( CodeSize: $code_size:ident, )?
+        $( MustUse: $must_use:tt, )?
+        $( StrictLints: $strict_lints:tt, )?
+        $( Metrics: $metrics:ident, )?
+        $( MaxLatency: $latency_now:path, )?
+        $( PathTrace: $path_trace:expr, )?
+        $( Record: $record:expr, )?
+        $( LogLevels: { $( $log_hook:ident : $log_level:ident ),* $(,)? }, )?
+        $( TransitionTable: [ $( $tt_from:ident + $tt_event:ident => $tt_to:ident ),* $(,)? ], )?
+
+        // Optional Per-Edge Counters of the Transition Table: CountTransitions: true,
+        $( CountTransitions: $count_transitions:tt, )?
+        $( Initial: [ $( $initial:ident ),+ $(,)? ], )?
+        $( InitialValues: $init_state:ident $( { $($init_fields:tt)* } )?, )?
+        $( Terminal: [ $( $terminal:ident ),+ $(,)? ], )?
+        $( StrictTerminal: $strict_terminal:literal, )?
+        $( EventNames: [ $( $event_name:ident ),+ $(,)? ], )?
+        $( ExhaustiveEvents: $exhaustive:tt, )?
+        $( OnUnhandled: |$unhandled_ctx:ident, $unhandled_state:ident, $unhandled_evt:ident| $unhandled_block:block, )?
+        $( Watchdog: $watchdog_limit:expr, OnStuck: |$stuck_ctx:ident, $stuck_state:ident| $stuck_block:block, )?
+        $( OnQuiescent: |$quiescent_ctx:ident| $quiescent_block:block, )?
+        $( TransitionObserver: $observer:ident, )?
+
+        // Optional Per-Instance Counters (a `typed_fsm::Bookkeeping` context field): Bookkeeping: fsm,
+        $( Bookkeeping: $bookkeeping:ident, )?
+        $( AnyEntry: |$any_entry_ctx:ident, $any_entry_state:ident| $any_entry_block:block, )?
+        $( AnyExit: |$any_exit_ctx:ident, $any_exit_state:ident| $any_exit_block:block, )?
+        $( EventFilter: |$filter_ctx:ident, $filter_evt:ident| $( -> bool )? $filter_block:block, )?
+        $( ProcessPrelude: |$prelude_ctx:ident, $prelude_evt:ident| $prelude_block:block, )?
+        $( Invariant: |$inv_ctx:ident, $inv_state:ident| $( -> bool )? $inv_block:block, )?
+        $( OnInvariantViolation: |$violation_ctx:ident, $violation_state:ident| $violation_block:block, )?
+        $( Mixin: $mixin:ident => { process_fragment: |$mixin_ctx:ident, $mixin_evt:ident| $mixin_block:block $(,)? }, )*
+        States: {
+            $(
+                $( #[doc = $state_doc:expr] )*
+                $( #[cfg($state_cfg:meta)] )*
+                $state_name:ident $( { $($field_name:ident : $field_type:ty),* } )? => $( $readonly:ident )? {
+                    $( cost: $state_cost:expr, )?
+                    $( retry: { max: $retry_max:expr, on_exhausted: $retry_fail:ident $(,)? }, )?
+                    $( use: [ $( $state_mixin:ident ),* $(,)? ], )?
+                    $( entry: |$entry_ctx:ident $(, $entry_from:ident $(, $entry_is_init:ident )? )?| $entry_block:block )?
+                    process: |$ctx_var:ident, $evt_var:ident $(, $shared_var:ident )?| { $($process_body:tt)* }
+                    $( exit: |$exit_ctx:ident $(, $exit_to:ident )?| $exit_block:block )?
+                }
+            ),* $(,)?
+        }
+    ) => {
+        $crate::__fsm_debug! {
+            [$( $debug_mode )?] $enum_name;
+            /// Auto-generated State Machine Enum (with concurrency protection).
+            /// Holds the current state and its internal data.
+            ///
+            /// # Concurrency Safety
+            ///
+            /// When the `concurrent` feature is enabled, this state machine is safe to use with:
+            /// - **ISRs (Interrupt Service Routines)**: Can be called from interrupt handlers
+            /// - **Threads**: Can be called from multiple threads
+            /// - **ISRs + Threads**: Both simultaneously (e.g., RTOS environments)
+            ///
+            /// The implementation uses atomic operations and lock-free queues to prevent
+            /// re-entrancy while maintaining low latency for interrupt handlers.
+            //
+            // Keep this declaration identical to the default build's (doc comment aside):
+            // a state value must not change when the `concurrent` feature is toggled
+            $( #[repr($repr)] )?
+            $($( #[$attr] )*)?
+            $( #[derive($($derive),*)] )?
+            pub enum $enum_name {
+                $(
+                    $( #[doc = $state_doc] )*
+                    $( #[cfg($state_cfg)] )*
+                    $state_name $( { $($field_name : $field_type),* } )?,
+                )*
+            }
+        }
+
+        // `retry:` budgets count their attempts in the `Bookkeeping` field
+        $crate::__fsm_bookkeeping! {
+            require [$( $bookkeeping )?] $($( $retry_fail )?)*
+        }
+        $crate::__fsm_bookkeeping! {
+            require_watchdog [$( $bookkeeping )?] $( $watchdog_limit )?
+        }
+
+        $crate::__fsm_require_fieldless! {
+            [$( Repr: $repr )?]
+            $( $( #[cfg($state_cfg)] )* $state_name $( { $($field_name : $field_type),* } )? ; )*
+        }
+
+        // `ExhaustiveEvents: true` rejects catch-all arms in `process`
+        $crate::__fsm_exhaustive_events! {
+            [$( $exhaustive )?]
+            $( $state_name $evt_var { $($process_body)* } )*
+        }
+
+        // `MaxSize` is a compile-time budget for the size of the enum
+        $(
+            const _: () = assert!(
+                core::mem::size_of::<$enum_name>() <= $max_size,
+                concat!(
+                    "`", stringify!($enum_name), "` exceeds its `MaxSize: ",
+                    stringify!($max_size), "` byte budget"
+                )
+            );
+        )?
+
+        // `Default` names a fieldless state (a state with fields is not a value)
+        $(
+            impl core::default::Default for $enum_name {
+                fn default() -> Self {
+                    $enum_name::$default_state
+                }
+            }
+        )?
+
+        // Parsing a state name (the reverse of `current_state_name()`). `Self::Err` and
+        // `Self::Error` are avoided: they would be ambiguous with a state named `Error`.
+        impl core::str::FromStr for $enum_name {
+            type Err = $crate::ParseStateError;
+
+            fn from_str(name: &str) -> Result<Self, $crate::ParseStateError> {
+                let index = Self::STATE_NAMES
+                    .iter()
+                    .position(|state| *state == name)
+                    .ok_or($crate::ParseStateError::Unknown)?;
+                Self::from_index(index).ok_or($crate::ParseStateError::HasFields)
+            }
+        }
+
+        impl core::convert::TryFrom<&str> for $enum_name {
+            type Error = $crate::ParseStateError;
+
+            fn try_from(name: &str) -> Result<Self, $crate::ParseStateError> {
+                name.parse()
+            }
+        }
+
+        // Every state named in the TransitionTable must exist
+        $(
+            #[allow(unreachable_patterns)]
+            const _: fn(&$enum_name) = |state| match state {
+                $( $enum_name::$tt_from { .. } | $enum_name::$tt_to { .. } => {} )*
+                _ => {}
+            };
+        )?
+
+        // Concurrency control: unique statics per state machine
+        paste::paste! {
+            static [<DISPATCH_ACTIVE_ $enum_name:upper>]: portable_atomic::AtomicBool =
+                portable_atomic::AtomicBool::new(false);
+
+            static [<PENDING_QUEUE_ $enum_name:upper>]: critical_section::Mutex<
+                core::cell::RefCell<$crate::PendingQueue<$event_type, $queue_capacity>>
+            > = critical_section::Mutex::new(core::cell::RefCell::new($crate::PendingQueue::new()));
+
+            static [<DROPPED_EVENTS_ $enum_name:upper>]: portable_atomic::AtomicUsize =
+                portable_atomic::AtomicUsize::new(0);
+
+            static [<FROZEN_ $enum_name:upper>]: portable_atomic::AtomicBool =
+                portable_atomic::AtomicBool::new(false);
+
+            static [<QUEUE_PAUSED_ $enum_name:upper>]: portable_atomic::AtomicBool =
+                portable_atomic::AtomicBool::new(false);
+        }
+
+        // `process` may evaluate to a bare state (sugar for `Transition::To`)
+        impl $crate::IntoTransition<$enum_name, $event_type> for $enum_name {
+            #[inline(always)]
+            fn into_transition(self) -> $crate::Transition<$enum_name, $event_type> {
+                $crate::Transition::To(self)
+            }
+        }
+
+        // Generic dispatch (e.g., through `FsmCell`)
+        $crate::__fsm_shared! {
+            dispatch_impl [$( $shared_type )?] [$( $instr_type )?] $enum_name $ctx_type, $event_type,
+            [where for<'a> $event_type: Clone]
+        }
+
+        $crate::__fsm_embed_context! {
+            [$( $embed )?] [$( $shared_type )?] [$( $instr_type )?]
+            $enum_name, $ctx_type, $event_type,
+            [where for<'a> $event_type: Clone]
+        }
+
+        $crate::__fsm_snapshot! {
+            [$( $snap { fields: [ $( $snap_field: $snap_ty ),* ] } )?]
+            $enum_name, $ctx_type
+        }
+
+        // One zero-sized type per `Mixin`, holding its shared `process` arms
+        $crate::__fsm_mixins! {
+            [$( $strict_lints )?] $enum_name, $ctx_type, $event_type;
+            $( $mixin |$mixin_ctx, $mixin_evt| $mixin_block )*
+        }
+
+        impl $enum_name {
+            /// Initializes the state machine by executing the entry action of the initial state.
+            ///
+            /// # CRITICAL: Must be called before the event loop!
+            pub fn init(&mut self $($ctx_param)*) {
+                self.do_init($ctx_arg);
+            }
+
+            /// Internal: `init()` body, shared with `start_in()`.
+            #[allow(unused_variables)]
+            fn do_init(&mut self, ctx: &mut $ctx_type) {
+                $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] init: $enum_name, self);
+                // No predecessor on init: the initial state is its own `from_name`
+                let from = self.current_state_name();
+                self.on_entry(ctx, from, true);
+                $crate::__fsm_path_trace!([$( $path_trace )?] record from);
+
+                // A fresh start resets the watchdog, the retries and any `Await`, and
+                // makes the previous generation stale
+                self.watchdog_step(ctx, true);
+                if let Some(book) = Self::bookkeeping_mut(ctx) {
+                    book.leave_state();
+                    book.generation = Self::next_generation();
+                }
+            }
+
+            /// Re-runs the entry action of the **current** state against `ctx`
+            /// (no exit, no transition). Use after swapping the whole context.
+            #[allow(unused_variables)]
+            pub fn reentry(&mut self $($ctx_param)*) {
+                let ctx: &mut $ctx_type = $ctx_arg;
+                $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] reentry: $enum_name, self);
+                let from = self.current_state_name();
+                self.on_entry(ctx, from, false);
+            }
+
+            /// Creates a state machine in `state` and runs `init()` on it.
+            ///
+            /// Shorthand for `let mut fsm = state; fsm.init(ctx);`. If an `Initial` list is
+            /// declared, debug builds assert that `state` is one of the legal boot states.
+            ///
+            /// ```rust
+            /// # use typed_fsm::{state_machine, Transition};
+            /// # struct Context { booted: bool }
+            /// # #[derive(Debug, Clone)]
+            /// # enum Event { Tick }
+            /// # state_machine! {
+            /// #     Name: FSM,
+            /// #     Context: Context,
+            /// #     Event: Event,
+            /// #     States: {
+            /// #         Idle => {
+            /// #             entry: |ctx| { ctx.booted = true; }
+            /// #             process: |_ctx, _evt| { Transition::None }
+            /// #         }
+            /// #     }
+            /// # }
+            /// let mut ctx = Context { booted: false };
+            /// let fsm = FSM::start_in(FSM::Idle, &mut ctx);
+            /// assert!(ctx.booted);
+            /// ```
+            #[must_use]
+            pub fn start_in(state: Self $($ctx_param)*) -> Self {
+                Self::boot(state, $ctx_arg)
+            }
+
+            /// Internal: `start_in()` body, shared with `start_default()`.
+            fn boot(state: Self, ctx: &mut $ctx_type) -> Self {
+                $(
+                    debug_assert!(
+                        matches!(state, $( Self::$initial { .. } )|+),
+                        "[{}] start_in({}): not a declared Initial state {:?}",
+                        stringify!($enum_name),
+                        state.current_state_name(),
+                        Self::INITIAL_STATES
+                    );
+                )?
+                let mut state = state;
+                state.do_init(ctx);
+                state
+            }
+
+            /// Names of the declared `Initial` states, in declaration order.
+            /// Empty if no `Initial` list was declared.
+            pub const INITIAL_STATES: &'static [&'static str] = &[
+                $($( stringify!($initial), )+)?
+            ];
+
+            $(
+                /// Returns the name of the variant of `event`, as listed in `EventNames`.
+                ///
+                /// Useful to bucket logs or metrics by event kind. The list must name every
+                /// variant of the event enum: a missing or unknown name fails to compile.
+                pub fn event_name(event: &$event_type) -> &'static str {
+                    // Variant paths need a path, not a type fragment
+                    type Event = $event_type;
+                    match event {
+                        $( Event::$event_name { .. } => stringify!($event_name), )+
+                    }
+                }
+            )?
+
+            $crate::__fsm_start_default! {
+                $enum_name [$($ctx_param)*] $ctx_arg; [$( $($initial),+ )?]
+            }
+
+            $crate::__fsm_initial_values! {
+                $enum_name [$($ctx_param)*] $ctx_arg;
+                [$( $init_state $( { $($init_fields)* } )? )?]
+            }
+
+            /// Returns the name of the current state, exactly as declared in the macro.
+            #[must_use]
+            pub fn current_state_name(&self) -> &'static str {
+                match self {
+                    $(
+                        $( #[cfg($state_cfg)] )*
+                        Self::$state_name { .. } => stringify!($state_name),
+                    )*
+                }
+            }
+
+            /// Returns the declared `cost: N,` of the current state (`0` if none).
+            pub fn state_cost(&self) -> u32 {
+                match self {
+                    $(
+                        $( #[cfg($state_cfg)] )*
+                        Self::$state_name { .. } => 0 $( + ($state_cost) )?,
+                    )*
+                }
+            }
+
+            $(
+                /// Returns how many times the current state was retried with
+                /// `Transition::Retry` since it was entered (`0` after `init()` or a
+                /// transition), as counted in `ctx`'s `Bookkeeping` field.
+                #[must_use]
+                pub fn retry_count(&self, ctx: &$ctx_type) -> usize {
+                    ctx.$bookkeeping.retries
+                }
+            )?
+
+            /// Internal: Resolves a `Transition::Retry` of the current state: `Reenter`
+            /// while attempts remain, `To` its `on_exhausted` state once `max` is reached.
+            fn retry<E>(&self, ctx: &mut $ctx_type) -> Transition<Self, E> {
+                // Without `Bookkeeping` no state has a budget (see `require`): re-enter
+                let Some(book) = Self::bookkeeping_mut(ctx) else {
+                    return Transition::Reenter;
+                };
+                let retries = book.retries;
+                match self {
+                    $(
+                        $( #[cfg($state_cfg)] )*
+                        Self::$state_name { .. } => {
+                            $(
+                                // `max` attempts, the first being the entry into the state
+                                if retries + 1 >= $retry_max {
+                                    return Transition::To($enum_name::$retry_fail);
+                                }
+                            )?
+                        }
+                    )*
+                }
+                book.retries = retries + 1;
+                Transition::Reenter
+            }
+
+            /// Internal: The context's `Bookkeeping` field, `None` without the directive.
+            #[allow(unused_variables)]
+            #[inline(always)]
+            fn bookkeeping_mut(ctx: &mut $ctx_type) -> Option<&mut $crate::Bookkeeping> {
+                $crate::__fsm_bookkeeping!(get_mut [$( $bookkeeping )?] ctx)
+            }
+
+            $(
+                /// Returns the token the current state is parked on with
+                /// `Transition::Await`, as recorded in `ctx`'s `Bookkeeping` field, or `None`
+                /// if no completion is pending.
+                #[must_use]
+                pub fn pending_token(&self, ctx: &$ctx_type) -> Option<usize> {
+                    ctx.$bookkeeping.pending(self.state_index())
+                }
+            )?
+
+            /// Internal: Parks the current state on `token` (`Transition::Await`), in the
+            /// `Bookkeeping` field. Returns `false` if there is none to hold the token.
+            fn park(&self, ctx: &mut $ctx_type, token: usize) -> bool {
+                let state_index = self.state_index();
+                match Self::bookkeeping_mut(ctx) {
+                    Some(book) => {
+                        book.park(state_index, token);
+                        true
+                    }
+                    None => {
+                        // Without it the completion could never be delivered
+                        #[cfg(debug_assertions)]
+                        panic!(
+                            "[{}] Transition::Await in state {} needs the `Bookkeeping` directive",
+                            stringify!($enum_name),
+                            self.current_state_name()
+                        );
+                        #[cfg(not(debug_assertions))]
+                        false
+                    }
+                }
+            }
+
+            /// Internal: Issues the `generation()` of a starting machine, distinct from
+            /// every one issued before, even when machines start concurrently. `0` is
+            /// never issued, so a fresh `Bookkeeping` matches no started machine.
+            fn next_generation() -> u32 {
+                use portable_atomic::{AtomicU32, Ordering};
+
+                static LAST_ISSUED: AtomicU32 = AtomicU32::new(0);
+                let issued = LAST_ISSUED.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+                if issued == 0 {
+                    // Wrapped around: skip `0`
+                    LAST_ISSUED.fetch_add(1, Ordering::Relaxed).wrapping_add(1)
+                } else {
+                    issued
+                }
+            }
+
+            /// Returns `true` if `self` and `other` are the same state variant (fields ignored).
+            #[must_use]
+            pub fn same_variant(&self, other: &Self) -> bool {
+                core::mem::discriminant(self) == core::mem::discriminant(other)
+            }
+
+            /// Returns the position of the current state in declaration order (its index
+            /// in `STATE_NAMES`).
+            #[must_use]
+            pub fn state_index(&self) -> usize {
+                #[allow(non_camel_case_types)]
+                enum StateIndex { $( $( #[cfg($state_cfg)] )* $state_name, )* }
+
+                match self {
+                    $(
+                        $( #[cfg($state_cfg)] )*
+                        Self::$state_name { .. } => StateIndex::$state_name as usize,
+                    )*
+                }
+            }
+
+            /// Reverse of `state_index()`. `None` if `index` is out of range or that state
+            /// carries fields.
+            #[must_use]
+            pub fn from_index(index: usize) -> Option<Self> {
+                #[allow(non_camel_case_types)]
+                enum StateIndex { $( $( #[cfg($state_cfg)] )* $state_name, )* }
+
+                $(
+                    $( #[cfg($state_cfg)] )*
+                    if index == StateIndex::$state_name as usize {
+                        return $crate::__fsm_fieldless_value!(
+                            $state_name $( { $($field_name : $field_type),* } )?
+                        );
+                    }
+                )*
+                None
+            }
+
+            /// `state_index()` as a single byte, e.g., to log or persist the current state
+            /// cheaply. Machines with more than 256 states fail to compile.
+            pub fn state_byte(&self) -> u8 {
+                const _: () = assert!(
+                    $enum_name::STATE_NAMES.len() <= 256,
+                    "state_byte() needs at most 256 states"
+                );
+                self.state_index() as u8
+            }
+
+            /// Reverse of `state_byte()`, with the same rules as `from_index()`: `None` if
+            /// `byte` is out of range or names a state with fields.
+            #[must_use]
+            pub fn from_byte(byte: u8) -> Option<Self> {
+                Self::from_index(byte as usize)
+            }
+
+            /// The declared name of the `index`-th state (`STATE_NAMES[index]`), or `None`
+            /// if `index` is out of range. Pairs with `state_index()` and `state_byte()` for
+            /// tools that speak integer state IDs.
+            #[must_use]
+            pub fn name_of_index(index: usize) -> Option<&'static str> {
+                Self::STATE_NAMES.get(index).copied()
+            }
+
+            /// Reverse of `name_of_index()`: the index of the state declared as `name`, or
+            /// `None` if no state has that name.
+            #[must_use]
+            pub fn index_of_name(name: &str) -> Option<usize> {
+                Self::STATE_NAMES.iter().position(|declared| *declared == name)
+            }
+
+            /// Yields every fieldless state once, in declaration order, skipping states
+            /// with fields. Handy for table-driven tests that start the machine in each
+            /// state. No hook runs: call `init()` on each value as usual.
+            pub fn all_fieldless_states() -> impl Iterator<Item = Self> {
+                (0..Self::STATE_NAMES.len()).filter_map(Self::from_index)
+            }
+
+            $crate::__fsm_cycle_next! {
+                $( $( #[cfg($state_cfg)] )* $state_name $( { $($field_name : $field_type),* } )? ; )*
+            }
+
+            /// The declared `TransitionTable` as `(from, event, to)` name triples, in
+            /// declaration order. Empty if no `TransitionTable` was declared.
+            pub const TRANSITIONS: &'static [(&'static str, &'static str, &'static str)] = &[
+                $($(
+                    (stringify!($tt_from), stringify!($tt_event), stringify!($tt_to)),
+                )*)?
+            ];
+
+            /// Returns `true` if the current state is listed in `Terminal` (a "done" state
+            /// of a workflow). Always `false` without a `Terminal` directive.
+            #[allow(unreachable_patterns)]
+            #[must_use]
+            pub fn is_terminal(&self) -> bool {
+                match self {
+                    $($( Self::$terminal { .. } => true, )+)?
+                    _ => false,
+                }
+            }
+
+            /// Internal: `true` in a terminal state with `StrictTerminal: true`, where
+            /// `dispatch` skips `process` (the state can't be left).
+            #[inline(always)]
+            fn is_locked_terminal(&self) -> bool {
+                false $( || ($strict_terminal && self.is_terminal()) )?
+            }
+
+            /// Names of all declared states, in declaration order.
+            pub const STATE_NAMES: &'static [&'static str] = &[
+                $( $( #[cfg($state_cfg)] )* stringify!($state_name), )*
+            ];
+
+            /// Number of declared states (the length of `STATE_NAMES`).
+            pub const STATE_COUNT: usize = $enum_name::STATE_NAMES.len();
+
+            /// Debug-build self-check of the declared `TransitionTable`: panics if a source
+            /// or target name is not one of `STATE_NAMES`.
+            ///
+            /// Call it from a test or at startup. Only available with `debug_assertions`.
+            #[cfg(debug_assertions)]
+            pub fn validate() {
+                Self::validate_transition_table(Self::TRANSITIONS);
+            }
+
+            /// Checks any `(from, event, to)` name table against `STATE_NAMES` (e.g., a
+            /// hand-written copy kept in docs or tooling), which the compiler cannot
+            /// cross-reference. Panics with the list of unknown names.
+            ///
+            /// Only available with `debug_assertions`.
+            #[cfg(debug_assertions)]
+            pub fn validate_transition_table(table: &[(&str, &str, &str)]) {
+                $crate::validate_transition_table(stringify!($enum_name), Self::STATE_NAMES, table);
+            }
+
+            /// Mermaid `stateDiagram-v2` diagram built from the declared `Initial` states
+            /// and `TransitionTable`, for GitHub READMEs and other Markdown docs.
+            ///
+            /// Assembled at compile time with `concat!` (no runtime cost): one
+            /// `[*] --> State` line per `Initial` state, then one
+            /// `From --> To : Event` line per table entry, in declaration order.
+            pub fn to_mermaid() -> &'static str {
+                concat!(
+                    "stateDiagram-v2\n",
+                    $($( "    [*] --> ", stringify!($initial), "\n", )+)?
+                    $($(
+                        "    ", stringify!($tt_from), " --> ", stringify!($tt_to),
+                        " : ", stringify!($tt_event), "\n",
+                    )*)?
+                )
+            }
+
+            /// Names of the states reachable in one transition from the current state,
+            /// according to `TRANSITIONS`.
+            ///
+            /// Each target is yielded once, in table order, even if several events lead
+            /// to it. Allocation-free: this is a filtered iterator over `TRANSITIONS`.
+            #[must_use]
+            pub fn successors(&self) -> impl Iterator<Item = &'static str> {
+                let current = self.current_state_name();
+                Self::TRANSITIONS
+                    .iter()
+                    .enumerate()
+                    .filter(move |(i, (from, _, to))| {
+                        *from == current
+                            && !Self::TRANSITIONS[..*i]
+                                .iter()
+                                .any(|(f, _, t)| *f == current && t == to)
+                    })
+                    .map(|(_, (_, _, to))| *to)
+            }
+
+            /// The `(event, target)` name pairs that `TRANSITIONS` declares out of the
+            /// current state, in table order, e.g., to build help text ("Start: go to
+            /// Running") or to validate input against the current state.
+            ///
+            /// A slice grouped at compile time: no filtering or allocation at runtime.
+            /// Empty without a `TransitionTable`.
+            #[must_use]
+            pub fn available_transitions(&self) -> &'static [(&'static str, &'static str)] {
+                const STATES: usize = $enum_name::STATE_NAMES.len();
+                const EDGES: usize = $enum_name::TRANSITIONS.len();
+                static BY_STATE: $crate::TransitionsByState<STATES, EDGES> =
+                    $crate::transitions_by_state($enum_name::STATE_NAMES, $enum_name::TRANSITIONS);
+
+                let index = self.state_index();
+                &BY_STATE.0[index][..BY_STATE.1[index]]
+            }
+
+            /// `true` if `TRANSITIONS` declares an edge for `event_name` (a `TransitionTable`
+            /// event name, e.g., as returned by `event_name()`) out of the current state.
+            ///
+            /// Nothing is dispatched, so it can drive UIs (e.g., graying out buttons whose
+            /// event would be a no-op). Always `false` without a `TransitionTable`.
+            #[must_use]
+            pub fn can_handle(&self, event_name: &str) -> bool {
+                let current = self.current_state_name();
+                Self::TRANSITIONS
+                    .iter()
+                    .any(|(from, event, _)| *from == current && *event == event_name)
+            }
+
+            /// `true` if `TRANSITIONS` declares a path, over any number of edges, from the
+            /// state named `from` to the state named `to`. A state always reaches itself.
+            ///
+            /// Useful in tests to check that every terminal state can be reached from the
+            /// initial one. `false` if either name is not one of `STATE_NAMES`. A
+            /// breadth-first search with fixed-size arrays (one slot per state), so it
+            /// doesn't allocate.
+            #[must_use]
+            pub fn can_reach(from: &str, to: &str) -> bool {
+                const STATES: usize = $enum_name::STATE_NAMES.len();
+                let index_of = |name: &str| Self::STATE_NAMES.iter().position(|s| *s == name);
+                let (Some(start), Some(target)) = (index_of(from), index_of(to)) else {
+                    return false;
+                };
+
+                let mut visited = [false; STATES];
+                let mut pending = [0usize; STATES];
+                visited[start] = true;
+                pending[0] = start;
+                let (mut head, mut tail) = (0, 1);
+                while head < tail {
+                    let state = pending[head];
+                    head += 1;
+                    if state == target {
+                        return true;
+                    }
+                    for (edge_from, _, edge_to) in Self::TRANSITIONS {
+                        if *edge_from != Self::STATE_NAMES[state] {
+                            continue;
+                        }
+                        if let Some(next) = index_of(edge_to) {
+                            if !visited[next] {
+                                visited[next] = true;
+                                pending[tail] = next;
+                                tail += 1;
+                            }
+                        }
+                    }
+                }
+                false
+            }
+
+            /// Number of edges in `TRANSITIONS`.
+            pub const TRANSITION_COUNT: usize = $enum_name::TRANSITIONS.len();
+
+            $crate::__fsm_transition_counters! {
+                [$( $count_transitions )?] api $enum_name, portable_atomic
+            }
+
+            /// Internal: Bookkeeping once a `Transition::To` / `ToFn` has been applied
+            /// (edge counters, `PathTrace`, `TransitionObserver`, watchdog, retries,
+            /// `Invariant`).
+            #[inline(always)]
+            fn record_transition(&mut self, ctx: &mut $ctx_type, from: &'static str, from_index: usize) {
+                if let Some(book) = Self::bookkeeping_mut(ctx) {
+                    book.leave_state();
+                }
+                $crate::__fsm_transition_counters!([$( $count_transitions )?] count from_index, self.state_index());
+                $crate::__fsm_test_log!(record from, self.current_state_name());
+                $crate::__fsm_path_trace!([$( $path_trace )?] record self.current_state_name());
+                $( (ctx.$observer)(from, self.current_state_name()); )?
+                self.watchdog_step(ctx, true);
+                self.check_invariant(ctx);
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: The `EventFilter` predicate (`true` when none was declared).
+                #[inline(always)]
+                fn accepts(
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_evt: &$event_type,
+                ) -> bool {
+                    true $( && {
+                        // Rename the filter arguments to what the user chose
+                        #[allow(unused_variables)]
+                        let $filter_ctx = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $filter_evt = arg_evt;
+                        $filter_block
+                    } )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the `OnUnhandled` hook (if the user declared one).
+                fn on_unhandled(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_evt: &$event_type,
+                ) {
+                    $(
+                        #[allow(unused_variables)]
+                        let $unhandled_ctx = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $unhandled_state = self.current_state_name();
+                        #[allow(unused_variables)]
+                        let $unhandled_evt = arg_evt;
+                        $unhandled_block
+                    )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Evaluates the `Invariant` (if the user declared one) after a
+                /// transition. A violation runs `OnInvariantViolation`, or panics in debug
+                /// builds when no hook was declared.
+                #[inline(always)]
+                fn check_invariant(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                ) {
+                    $(
+                        let holds = {
+                            // Read-only: the invariant observes the context, it can't fix it
+                            #[allow(unused_variables)]
+                            let $inv_ctx: &$ctx_type = arg_ctx;
+                            #[allow(unused_variables)]
+                            let $inv_state = self.current_state_name();
+
+                            $inv_block
+                        };
+                        if !holds {
+                            self.on_invariant_violation(arg_ctx);
+                        }
+                    )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the `OnInvariantViolation` hook, or panics in debug
+                /// builds if the user declared none.
+                #[allow(dead_code)]
+                #[cold]
+                fn on_invariant_violation(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                ) {
+                    $(
+                        #[allow(unused_variables)]
+                        let $violation_ctx = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $violation_state = self.current_state_name();
+
+                        $violation_block
+                    )?
+                    #[cfg(debug_assertions)]
+                    if !$crate::__fsm_declared!([$( $violation_ctx )?]) {
+                        panic!(
+                            "[{}] Invariant violated in state {}",
+                            stringify!($enum_name),
+                            self.current_state_name()
+                        );
+                    }
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the `OnQuiescent` hook (if the user declared one).
+                #[inline(always)]
+                fn on_quiescent(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                ) {
+                    $(
+                        #[allow(unused_variables)]
+                        let $quiescent_ctx = arg_ctx;
+
+                        $quiescent_block
+                    )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Advances the `Watchdog` (if declared) after a dispatch step.
+                ///
+                /// `transitioned == true` resets the count; otherwise the `OnStuck` hook runs
+                /// when the count of consecutive stayed dispatches reaches the limit.
+                #[inline(always)]
+                fn watchdog_step(
+                    &self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    transitioned: bool,
+                ) {
+                    $(
+                        // Consecutive dispatches without a transition, counted per instance
+                        // (the `Watchdog` requires `Bookkeeping`)
+                        let Some(book) = Self::bookkeeping_mut(arg_ctx) else {
+                            return;
+                        };
+                        if transitioned {
+                            book.stayed = 0;
+                            return;
+                        }
+                        book.stayed = book.stayed.saturating_add(1);
+
+                        if book.stayed == $watchdog_limit {
+                            #[allow(unused_variables)]
+                            let $stuck_ctx = arg_ctx;
+                            #[allow(unused_variables)]
+                            let $stuck_state = self.current_state_name();
+
+                            $stuck_block
+                        }
+                    )?
+                }
+            }
+
+            /// Internal: `false` when entering the current state runs no code at all (no
+            /// `entry` hook, no `AnyEntry`, no logging), so `dispatch` skips the call.
+            #[inline(always)]
+            fn has_entry_hook(&self) -> bool {
+                $crate::__fsm_log!(enabled)
+                    || $crate::__fsm_declared!([$( $any_entry_ctx )?])
+                    || match self {
+                        $(
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name { .. } => $crate::__fsm_declared!([$( $entry_ctx )?]),
+                        )*
+                    }
+            }
+
+            /// Internal: `false` when leaving the current state runs no code at all (no
+            /// `exit` hook, no `AnyExit`, no logging), so `dispatch` skips the call.
+            #[inline(always)]
+            fn has_exit_hook(&self) -> bool {
+                $crate::__fsm_log!(enabled)
+                    || $crate::__fsm_declared!([$( $any_exit_ctx )?])
+                    || match self {
+                        $(
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name { .. } => $crate::__fsm_declared!([$( $exit_ctx )?]),
+                        )*
+                    }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the entry action for the current state.
+                fn on_entry(
+                    &mut self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_from: &'static str,
+                    #[allow(unused_variables)] arg_is_init: bool,
+                ) {
+                    $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] entry: $enum_name, self);
+                    match self {
+                        $(
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name $( { $($field_name),* } )? => {
+                                // Fields the hook may not use
+                                $( $( let _ = &$field_name; )* )?
+                                $(
+                                    #[allow(unused_variables)]
+                                    let $entry_ctx: &mut $ctx_type = arg_ctx;
+                                    $(
+                                        #[allow(unused_variables)]
+                                        let $entry_from: &'static str = arg_from;
+                                        $(
+                                            #[allow(unused_variables)]
+                                            let $entry_is_init: bool = arg_is_init;
+                                        )?
+                                    )?
+                                    $entry_block
+                                )?
+                            }
+                        )*
+                    }
+
+                    // Machine-wide entry hook, after the state's own entry
+                    $(
+                        #[allow(unused_variables)]
+                        let $any_entry_ctx = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $any_entry_state = self.current_state_name();
+                        $any_entry_block
+                    )?
+                }
+            }
+
+            $crate::__fsm_strict_lints! { [$( $strict_lints )?]
+                /// Internal: Executes the exit action for the current state.
+                fn on_exit(
+                    &mut self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_to: &'static str,
+                ) {
+                    $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] exit: $enum_name, self);
+
+                    // Machine-wide exit hook, before the state's own exit
+                    $(
+                        #[allow(unused_variables)]
+                        let $any_exit_ctx: &mut $ctx_type = arg_ctx;
+                        #[allow(unused_variables)]
+                        let $any_exit_state = self.current_state_name();
+                        $any_exit_block
+                    )?
+
+                    match self {
+                        $(
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name $( { $($field_name),* } )? => {
+                                // Fields the hook may not use
+                                $( $( let _ = &$field_name; )* )?
+                                $(
+                                    #[allow(unused_variables)]
+                                    let $exit_ctx = arg_ctx;
+                                    $(
+                                        #[allow(unused_variables)]
+                                        let $exit_to: &'static str = arg_to;
+                                    )?
+                                    $exit_block
+                                )?
+                            }
+                        )*
+                    }
+                }
+            }
+
+            $crate::__fsm_inline! { [$( $code_size )?] process [$( $strict_lints )?]
+                /// Internal: Determines the next state based on the event.
+                fn on_process(
+                    &mut self,
+                    #[allow(unused_variables)] arg_ctx: &mut $ctx_type,
+                    #[allow(unused_variables)] arg_shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                    #[allow(unused_variables)] arg_instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                    #[allow(unused_variables)] arg_evt: &$event_type,
+                ) -> Transition<Self, $event_type> {
+                    // Instrumentation, bound under the name declared by the directive
+                    $(
+                        #[allow(unused_variables)]
+                        let $instr_var: &mut $instr_type = arg_instr;
+                    )?
+
+                    // Machine-wide prelude, before the state's own process
+                    $(
+                        {
+                            #[allow(unused_variables)]
+                            let $prelude_ctx: &mut $ctx_type = &mut *arg_ctx;
+                            #[allow(unused_variables)]
+                            let $prelude_evt: &$event_type = arg_evt;
+                            $prelude_block
+                        }
+                    )?
+
+                    match self {
+                        $(
+                            $( #[cfg($state_cfg)] )*
+                            Self::$state_name $( { $($field_name),* } )? => {
+                                // The state's fields, which the user logic may not need
+                                // for this event
+                                $( $( let _ = &$field_name; )* )?
+                                $($(
+                                    match $state_mixin::process_fragment(&mut *arg_ctx, arg_evt) {
+                                        Transition::Unhandled => {}
+                                        transition => return transition,
+                                    }
+                                )*)?
+                                #[allow(unused_variables)]
+                                let $ctx_var = arg_ctx;
+                                $(
+                                    #[allow(unused_variables)]
+                                    let $ctx_var: &$ctx_type = $crate::__fsm_readonly!($readonly $ctx_var);
+                                )?
+                                #[allow(unused_variables)]
+                                let $evt_var = arg_evt;
+                                $(
+                                    #[allow(unused_variables)]
+                                    let $shared_var = arg_shared;
+                                )?
+                                $crate::IntoTransition::into_transition({ $($process_body)* })
+                            }
+                        )*
+                    }
+                }
+            }
+
+            /// Internal dispatch implementation (without concurrency protection).
+            ///
+            /// This is called by the public `dispatch()` method after acquiring the lock.
+            /// Timed by `MaxLatency` if declared.
+            #[inline(always)]
+            fn do_dispatch_internal(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                event: &$event_type,
+            ) -> $crate::DispatchOutcome {
+                let (outcome, mut follow_up) = $crate::__fsm_max_latency!(
+                    [$( $latency_now )?] time [portable_atomic]
+                    self.do_dispatch_untimed(ctx, shared, instr, event)
+                );
+                // `ToAndPost` follow-ups, each timed on its own. A loop rather than
+                // recursion, so a long chain of them does not grow the stack
+                while let Some(event) = follow_up.take() {
+                    follow_up = $crate::__fsm_max_latency!(
+                        [$( $latency_now )?] time [portable_atomic]
+                        self.do_dispatch_untimed(ctx, shared, instr, &event)
+                    )
+                    .1;
+                }
+                outcome
+            }
+
+            /// Internal: `do_dispatch_internal()` body for one event.
+            /// Returns `Transitioned` if it applied a `Transition::To`, `Stayed` otherwise,
+            /// along with the `Transition::ToAndPost` follow-up event, if any.
+            #[inline(always)]
+            fn do_dispatch_untimed(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                event: &$event_type,
+            ) -> ($crate::DispatchOutcome, Option<$event_type>) {
+                if !Self::accepts(ctx, event) {
+                    return ($crate::DispatchOutcome::Filtered, None);
+                }
+                $crate::__fsm_metrics!([$( $metrics )?] count [portable_atomic]);
+                let transition = if self.is_locked_terminal() {
+                    Transition::None
+                } else {
+                    self.on_process(ctx, shared, instr, event)
+                };
+                // Resolve `Retry`, and split off a follow-up event processed once the
+                // transition is applied
+                let (transition, follow_up) = match transition {
+                    Transition::ToAndPost(new_state, follow_up) => (Transition::To(new_state), Some(follow_up)),
+                    Transition::Retry => (self.retry(ctx), None),
+                    transition => (transition, None),
+                };
+                let outcome = match transition {
+                    Transition::To(mut new_state) => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] transition: $enum_name, self, event, new_state);
+                        let from = self.current_state_name();
+                        let from_index = self.state_index();
+                        if self.has_exit_hook() {
+                            self.on_exit(ctx, new_state.current_state_name());
+                        }
+                        if new_state.has_entry_hook() {
+                            new_state.on_entry(ctx, from, false);
+                        }
+                        *self = new_state;
+                        self.record_transition(ctx, from, from_index);
+                        $crate::DispatchOutcome::Transitioned
+                    }
+                    Transition::ToFn(make) => {
+                        let from = self.current_state_name();
+                        let from_index = self.state_index();
+                        if self.has_exit_hook() {
+                            self.on_exit(ctx, "");
+                        }
+                        let mut new_state = make();
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] transition: $enum_name, self, event, new_state);
+                        if new_state.has_entry_hook() {
+                            new_state.on_entry(ctx, from, false);
+                        }
+                        *self = new_state;
+                        self.record_transition(ctx, from, from_index);
+                        $crate::DispatchOutcome::Transitioned
+                    }
+                    Transition::Reenter => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] transition: $enum_name, self, event, self);
+
+                        // Restart the current state's lifecycle, keeping its data
+                        let name = self.current_state_name();
+                        if self.has_exit_hook() {
+                            self.on_exit(ctx, name);
+                        }
+                        if self.has_entry_hook() {
+                            self.on_entry(ctx, name, false);
+                        }
+
+                        self.watchdog_step(ctx, true);
+                        self.check_invariant(ctx);
+                        $crate::DispatchOutcome::Stayed
+                    }
+                    Transition::None => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] stayed: $enum_name, self, event, "None");
+                        self.watchdog_step(ctx, false);
+                        $crate::DispatchOutcome::Stayed
+                    }
+                    Transition::Unhandled => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] stayed: $enum_name, self, event, "Unhandled");
+                        self.on_unhandled(ctx, event);
+                        self.watchdog_step(ctx, false);
+                        $crate::DispatchOutcome::Stayed
+                    }
+                    Transition::Await(token) => {
+                        $crate::__fsm_log!([$($( $log_hook: $log_level ),*)?] stayed: $enum_name, self, event, "Await");
+                        if !self.park(ctx, token) {
+                            // No completion can ever resume the state: the event was
+                            // not handled after all
+                            self.on_unhandled(ctx, event);
+                        }
+                        self.watchdog_step(ctx, false);
+                        $crate::DispatchOutcome::Stayed
+                    }
+                    // Resolved above
+                    Transition::ToAndPost(..) | Transition::Retry => unreachable!(),
+                };
+                (outcome, follow_up)
+            }
+
+            /// Returns the number of events that were dropped due to queue overflow.
+            ///
+            /// When the event queue is full (capacity: $queue_capacity), new events are dropped
+            /// and this counter is incremented. Use this to detect if your queue capacity
+            /// is insufficient for your workload.
+            /// With the `alloc` feature the queue is unbounded, so this is always `0`.
+            ///
+            /// # Example
+            ///
+            /// ```rust,no_run
+            /// # use typed_fsm::state_machine;
+            /// # struct Context {}
+            /// # #[derive(Debug, Clone)]
+            /// # enum Event { Tick }
+            /// # state_machine! {
+            /// #     Name: MyFSM,
+            /// #     Context: Context,
+            /// #     Event: Event,
+            /// #     States: { Idle => { process: |_ctx, _evt| { typed_fsm::Transition::None } } }
+            /// # }
+            /// // Check if events were dropped
+            /// let dropped = MyFSM::dropped_events_count();
+            /// if dropped > 0 {
+            ///     eprintln!("Warning: {} events were dropped!", dropped);
+            ///     // Consider increasing QueueCapacity
+            /// }
+            /// ```
+            pub fn dropped_events_count() -> usize {
+                paste::paste! {
+                    use portable_atomic::Ordering;
+                    [<DROPPED_EVENTS_ $enum_name:upper>].load(Ordering::Relaxed)
+                }
+            }
+
+            /// Resets the dropped events counter to zero.
+            ///
+            /// Useful for monitoring event loss over specific time periods.
+            ///
+            /// # Example
+            ///
+            /// ```rust,no_run
+            /// # use typed_fsm::state_machine;
+            /// # struct Context {}
+            /// # #[derive(Debug, Clone)]
+            /// # enum Event { Tick }
+            /// # state_machine! {
+            /// #     Name: MyFSM,
+            /// #     Context: Context,
+            /// #     Event: Event,
+            /// #     States: { Idle => { process: |_ctx, _evt| { typed_fsm::Transition::None } } }
+            /// # }
+            /// // Reset counter for new monitoring period
+            /// MyFSM::reset_dropped_count();
+            ///
+            /// // ... run for some time ...
+            ///
+            /// // Check events dropped during this period
+            /// let dropped = MyFSM::dropped_events_count();
+            /// ```
+            pub fn reset_dropped_count() {
+                paste::paste! {
+                    use portable_atomic::Ordering;
+                    [<DROPPED_EVENTS_ $enum_name:upper>].store(0, Ordering::Relaxed);
+                }
+            }
+
+            $crate::__fsm_dropped_buffer! { [$( $dropped_buffer )?] api $event_type }
+
+            $crate::__fsm_metrics! { [$( $metrics )?] api [portable_atomic] }
+            $crate::__fsm_max_latency! { [$( $latency_now )?] api [portable_atomic] }
+
+            $crate::__fsm_path_trace! { [$( $path_trace )?] api }
+
+            $crate::__fsm_test_log! { api }
+
+            $crate::__fsm_record! {
+                [$( $record )?] api $event_type,
+                [$($ctx_param)*] $ctx_arg, [$( $shared_type )?] [$( $instr_type )?]
+            }
+
+            /// Returns `true` while a dispatch of this state machine type holds the lock.
+            ///
+            /// Useful for diagnostics, or for an ISR/thread to know that `dispatch()` would
+            /// only enqueue its event right now. The value can change immediately after it
+            /// is read, so use it as a hint, not for synchronization.
+            pub fn is_dispatch_active() -> bool {
+                paste::paste! {
+                    use portable_atomic::Ordering;
+                    [<DISPATCH_ACTIVE_ $enum_name:upper>].load(Ordering::Acquire)
+                }
+            }
+
+            /// Forcibly clears the dispatch lock, e.g., after a panic unwound out of a
+            /// `dispatch()` (caught by `catch_unwind()` or a panic handler) and left it held.
+            ///
+            /// A stuck lock makes every later dispatch only enqueue its event (returning
+            /// `DispatchOutcome::Queued`) until the queue overflows. After this call, the
+            /// next dispatch takes the lock again and drains the events queued meanwhile.
+            ///
+            /// # Danger
+            ///
+            /// Recovery and test tooling only. Called while a dispatch is really running
+            /// (in another thread, or in an interrupted context), a second dispatch can start
+            /// alongside it: events are then processed out of order and the hooks of one
+            /// run interleave with the other's, breaking run-to-completion. The state
+            /// machine value and context left by the panic may also be inconsistent.
+            #[doc(alias = "defuse")]
+            #[doc(alias = "disarm")]
+            pub fn force_release_lock() {
+                paste::paste! {
+                    use portable_atomic::Ordering;
+                    [<DISPATCH_ACTIVE_ $enum_name:upper>].store(false, Ordering::Release);
+                }
+            }
 
-                    // Mandatory Process Block: process: |ctx, evt| { ... }
-                    process: |$ctx_var:ident, $evt_var:ident| $process_block:block
+            /// Queues an event to be processed after the current one (run-to-completion).
+            ///
+            /// Intended for `entry`, `process` and `exit` code that needs to chain events:
+            /// the event joins the pending queue and is handled by the active `dispatch()`
+            /// before it releases the lock. Events posted outside a dispatch wait for the
+            /// next one.
+            ///
+            /// If the queue (capacity: `QueueCapacity`) is full, `dropped_events_count()` is
+            /// incremented and the event is handed back in `Err` (with `OverflowPolicy:
+            /// DropOldest`, the oldest queued event is dropped instead). Unlike an overflowing
+            /// `dispatch()`, this only panics with `OverflowPolicy: Panic`.
+            pub fn post(event: $event_type) -> Result<(), $event_type> {
+                Self::enqueue_pending(event)
+            }
 
-                    // Optional Exit Block: exit: |ctx| { ... }
-                    $( exit: |$exit_ctx:ident| $exit_block:block )?
-                }
-            ),* $(,)?
-        }
-    ) => {
-        /// Auto-generated State Machine Enum.
-        /// Holds the current state and its internal data.
-        #[derive(Debug)]
-        pub enum $enum_name {
-            $(
-                $state_name $( { $($field_name : $field_type),* } )?,
-            )*
-        }
+            /// What the event queue does when it is full (see `OverflowPolicy`), as selected
+            /// by the `OverflowPolicy` directive.
+            pub const OVERFLOW_POLICY: $crate::OverflowPolicy =
+                $crate::__fsm_overflow_policy!($( $overflow_policy )?);
 
-        impl $enum_name {
-            /// Initializes the state machine by executing the entry action of the initial state.
+            /// Static memory, in bytes, taken by the concurrent machinery of this state
+            /// machine type: the event queue (`QueueCapacity` events plus its bookkeeping),
+            /// the dispatch lock, the frozen flag, the queue pause flag, the dropped-events
+            /// counter and the `DroppedBuffer` store, if declared. Use it when budgeting
+            /// `.bss`; padding between the statics is not included.
             ///
-            /// # CRITICAL: Must be called before the event loop!
+            /// With the `alloc` feature the queued events live on the heap instead, so
+            /// only the `VecDeque` header is counted.
+            pub const CONCURRENT_STATIC_BYTES: usize = core::mem::size_of::<
+                critical_section::Mutex<core::cell::RefCell<$crate::PendingQueue<$event_type, $queue_capacity>>>,
+            >() + 3 * core::mem::size_of::<portable_atomic::AtomicBool>()
+                + core::mem::size_of::<portable_atomic::AtomicUsize>()
+                + $crate::__fsm_dropped_buffer!([$( $dropped_buffer )?] bytes $event_type);
+
+            /// Returns a consistent snapshot of the event queue and its overflow counter.
             ///
-            /// **Forgetting to call `init()` will cause silent failures:**
-            /// - The `entry` action of the initial state will NEVER execute
-            /// - State machine will still process events, but initialization is skipped
-            /// - This can lead to incorrect behavior that is difficult to debug
+            /// The dropped counter, queue capacity and current queue length are all read
+            /// inside a single critical section, so a monitor never observes a torn state
+            /// (e.g., a drop counted against a queue length from a later moment).
             ///
-            /// # Correct Usage
+            /// # Example
             ///
-            /// ```rust
-            /// # use typed_fsm::{state_machine, Transition};
-            /// # struct Context { count: u32 }
+            /// ```rust,no_run
+            /// # use typed_fsm::state_machine;
+            /// # struct Context {}
             /// # #[derive(Debug, Clone)]
             /// # enum Event { Tick }
             /// # state_machine! {
-            /// #     Name: FSM,
+            /// #     Name: MyFSM,
             /// #     Context: Context,
             /// #     Event: Event,
-            /// #     States: {
-            /// #         Idle => {
-            /// #             entry: |ctx| { ctx.count = 0; }
-            /// #             process: |_ctx, _evt| { Transition::None }
-            /// #         }
-            /// #     }
+            /// #     States: { Idle => { process: |_ctx, _evt| { typed_fsm::Transition::None } } }
             /// # }
-            /// let mut ctx = Context { count: 0 };
-            /// let mut fsm = FSM::Idle;
-            ///
-            /// // CORRECT: Call init() before event loop
-            /// fsm.init(&mut ctx);
-            ///
-            /// // Now safe to dispatch events
-            /// fsm.dispatch(&mut ctx, &Event::Tick);
+            /// let report = MyFSM::overflow_report();
+            /// if report.dropped > 0 {
+            ///     eprintln!("{} dropped, queue {}/{}", report.dropped, report.current_len, report.capacity);
+            /// }
             /// ```
+            pub fn overflow_report() -> $crate::OverflowReport {
+                paste::paste! {
+                    use portable_atomic::Ordering;
+                    critical_section::with(|cs| {
+                        let queue = [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow();
+                        $crate::OverflowReport {
+                            dropped: [<DROPPED_EVENTS_ $enum_name:upper>].load(Ordering::Relaxed),
+                            capacity: queue.capacity(),
+                            current_len: queue.len(),
+                        }
+                    })
+                }
+            }
+
+            /// Discards every event waiting in the queue without processing it, and
+            /// returns how many were discarded.
             ///
-            /// # Incorrect Usage (Common Mistake)
+            /// Use it when recovering into a safe state where stale events must be
+            /// abandoned. Discarded events are not counted by `dropped_events_count()`,
+            /// which only tracks overflow.
+            pub fn clear_queue() -> usize {
+                paste::paste! {
+                    critical_section::with(|cs| {
+                        let mut queue = [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow_mut();
+                        let cleared = queue.len();
+                        queue.clear();
+                        cleared
+                    })
+                }
+            }
+
+            /// Returns a clone of the next queued event (the one the active dispatch
+            /// will process next), without removing it. `None` if the queue is empty.
+            pub fn peek_front() -> Option<$event_type>
+            where
+                // Higher-ranked, as in `dispatch()`
+                for<'a> $event_type: Clone,
+            {
+                paste::paste! {
+                    critical_section::with(|cs| {
+                        [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow().front().cloned()
+                    })
+                }
+            }
+
+            $crate::__fsm_inline! { [$( $code_size )?] hot [$( $must_use )?]
+                /// Main Event Dispatcher with Concurrency Protection.
+                ///
+                /// This function is safe to call from:
+                /// - **Main loop**: Regular sequential execution
+                /// - **ISRs**: Interrupt service routines
+                /// - **Threads**: Multiple concurrent threads
+                /// - **ISRs + Threads**: Both simultaneously
+                ///
+                /// # Behavior
+                ///
+                /// - If no dispatch is active: Executes immediately and processes entire pending queue
+                /// - If dispatch is already active: Enqueues event for later processing
+                ///   (returns `DispatchOutcome::Queued`)
+                /// - If the machine is frozen (see `freeze()`): Ignores the event without
+                ///   enqueuing it (returns `DispatchOutcome::Frozen`)
+                ///
+                /// # Performance
+                ///
+                /// - **Without contention**: ~10-15% overhead vs non-concurrent version
+                /// - **ISR enqueue**: ~100 cycles (fast and deterministic)
+                /// - **Queue processing**: Automatic before releasing lock
+                ///
+                /// # Safety
+                ///
+                /// Uses atomic compare-exchange and lock-free queue to prevent:
+                /// - Re-entrant dispatch calls
+                /// - Data races on state machine state
+                /// - Data races on context
+                ///
+                /// # Example
+                ///
+                /// ```rust,no_run
+                /// // From ISR
+                /// #[interrupt]
+                /// fn TIMER_IRQ() {
+                ///     unsafe {
+                ///         FSM.as_mut().unwrap().dispatch(&mut CTX.as_mut().unwrap(), Event::Tick);
+                ///         // ✅ ISR-safe: Enqueues if main is active
+                ///     }
+                /// }
+                ///
+                /// // From main loop
+                /// fn main() {
+                ///     loop {
+                ///         fsm.dispatch(&mut ctx, Event::Button);
+                ///         // ✅ Processes event + all ISR-queued events
+                ///     }
+                /// }
+                /// ```
+                ///
+                /// # Clone Requirement
+                ///
+                /// An event that arrives while another dispatch is active is cloned into the
+                /// queue, so this method requires `Event: Clone` (checked where it is called).
+                /// Use `dispatch_owned()` to move the event instead.
+                pub fn dispatch(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: &$event_type,
+                ) -> $crate::DispatchOutcome
+                where
+                    // Higher-ranked so the bound is checked at call sites, keeping the
+                    // generated code valid for non-`Clone` events
+                    for<'a> $event_type: Clone
+                {
+                    self.dispatch_ref(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event,
+                    )
+                }
+            }
+
+            $crate::__fsm_inline! { [$( $code_size )?] hot [$( $must_use )?]
+                /// Event Dispatcher taking the event by value.
+                ///
+                /// Behaves exactly like `dispatch()`, but when another dispatch is active the
+                /// event is **moved** into the queue instead of cloned. This avoids the clone
+                /// for large events and works for events that don't implement `Clone`.
+                ///
+                /// # Example
+                ///
+                /// ```rust,no_run
+                /// # use typed_fsm::{state_machine, Transition};
+                /// # struct Context {}
+                /// // No Clone derive needed
+                /// #[derive(Debug)]
+                /// enum Event { Frame([u8; 256]) }
+                /// # state_machine! {
+                /// #     Name: MyFSM,
+                /// #     Context: Context,
+                /// #     Event: Event,
+                /// #     States: { Idle => { process: |_ctx, _evt| { Transition::None } } }
+                /// # }
+                /// # let mut fsm = MyFSM::Idle;
+                /// # let mut ctx = Context {};
+                /// fsm.dispatch_owned(&mut ctx, Event::Frame([0; 256]));
+                /// ```
+                pub fn dispatch_owned(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: $event_type,
+                ) -> $crate::DispatchOutcome {
+                    self.dispatch_value(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event,
+                    )
+                }
+            }
+
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Converts `event` into the event type, then dispatches it like
+                /// `dispatch_owned()`.
+                ///
+                /// Saves the wrapping at call sites fed by parsers or drivers that produce
+                /// values convertible into the event (e.g., a raw byte with
+                /// `impl From<u8> for Event`).
+                #[inline]
+                pub fn dispatch_into(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: impl Into<$event_type>,
+                ) -> $crate::DispatchOutcome {
+                    self.dispatch_value(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event.into(),
+                    )
+                }
+            }
+
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Dispatches `event` like `dispatch()` and returns the outcome together with
+                /// the machine itself, so the resulting state can be read in the same
+                /// expression: `fsm.dispatch_and_peek(&mut ctx, &event).1.current_state_name()`.
+                pub fn dispatch_and_peek(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: &$event_type,
+                ) -> ($crate::DispatchOutcome, &Self)
+                where
+                    // Higher-ranked, as in `dispatch()`
+                    for<'a> $event_type: Clone,
+                {
+                    let outcome = self.dispatch_ref(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event,
+                    );
+                    (outcome, self)
+                }
+            }
+
+            /// Simulation/test driver: pulls events from `next` and dispatches each one
+            /// until the machine is in the state named `stop` (see `current_state_name()`)
+            /// or `next` returns `None`.
             ///
-            /// ```rust,no_run
+            /// Returns the number of events pulled from `next` and dispatched (events
+            /// drained from the `post()` queue along the way are not counted). If the
+            /// machine is already in `stop`, `next` is never called and `0` is returned.
+            ///
+            /// ```rust
             /// # use typed_fsm::{state_machine, Transition};
-            /// # struct Context { count: u32 }
+            /// # struct Context {}
             /// # #[derive(Debug, Clone)]
-            /// # enum Event { Tick }
+            /// # enum Event { Step }
             /// # state_machine! {
             /// #     Name: FSM,
             /// #     Context: Context,
             /// #     Event: Event,
             /// #     States: {
-            /// #         Idle => {
-            /// #             entry: |ctx| { ctx.count = 0; }
-            /// #             process: |_ctx, _evt| { Transition::None }
-            /// #         }
+            /// #         A => { process: |_ctx, _evt| { Transition::To(FSM::B) } },
+            /// #         B => { process: |_ctx, _evt| { Transition::To(FSM::Done) } },
+            /// #         Done => { process: |_ctx, _evt| { Transition::None } }
             /// #     }
             /// # }
-            /// let mut ctx = Context { count: 0 };
-            /// let mut fsm = FSM::Idle;
-            ///
-            /// // WRONG: Forgot to call init()!
-            /// // The entry action will NEVER execute!
-            /// fsm.dispatch(&mut ctx, &Event::Tick);
+            /// let mut ctx = Context {};
+            /// let mut fsm = FSM::start_in(FSM::A, &mut ctx);
+            /// let processed = fsm.run_until(&mut ctx, "Done", || Some(Event::Step));
+            /// assert_eq!(processed, 2);
             /// ```
-            ///
-            /// # When to Call
-            ///
-            /// - Call exactly **once** after creating the state machine
-            /// - Call **before** entering the event loop
-            /// - Call **before** the first `dispatch()`
-            #[allow(unused_variables)]
-            pub fn init(&mut self, ctx: &mut $ctx_type) {
-                $crate::__fsm_log!("[{}] init() -> {:?}", stringify!($enum_name), self);
-                self.on_entry(ctx);
+            pub fn run_until<F>(
+                &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                $(, instrumentation: &mut $instr_type)?,
+                stop: &str,
+                mut next: F,
+            ) -> usize
+            where
+                F: FnMut() -> Option<$event_type>,
+            {
+                let ctx: &mut $ctx_type = $ctx_arg;
+                let shared: &$crate::__fsm_shared!(type [$( $shared_type )?]) = $crate::__fsm_shared!(arg [$( $shared_type )?] shared);
+                let instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]) =
+                    $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation);
+                let mut processed = 0;
+                while self.current_state_name() != stop {
+                    let Some(event) = next() else {
+                        break;
+                    };
+                    self.dispatch_value(ctx, shared, instr, event);
+                    processed += 1;
+                }
+                processed
             }
 
-            /// Internal: Executes the entry action for the current state.
-            #[allow(unused_variables)]
-            fn on_entry(&mut self, arg_ctx: &mut $ctx_type) {
-                $crate::__fsm_log!("[{}] {:?}.entry()", stringify!($enum_name), self);
-                match self {
-                    $(
-                        // Matches the current state and captures its fields (if any)
-                        Self::$state_name $( { $($field_name),* } )? => {
-                            // Only expands if the user defined an entry block
-                            $(
-                                // Rename the context variable to what the user chose (e.g., |ctx|)
-                                #[allow(unused_variables)]
-                                let $entry_ctx = arg_ctx;
-
-                                // Execute user code
-                                $entry_block
-                            )?
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Dispatches `event` like `dispatch()`, then calls `after` with the new state if
+                /// the event caused a `Transition::To` (before queued events are processed).
+                ///
+                /// If another dispatch is active, the event is queued like with `dispatch()` and
+                /// `after` does not run.
+                pub fn dispatch_then<F>(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: &$event_type,
+                    after: F,
+                ) -> $crate::DispatchOutcome
+                where
+                    F: FnOnce(&mut Self),
+                    // Higher-ranked, as in `dispatch()`
+                    for<'a> $event_type: Clone,
+                {
+                    let ctx: &mut $ctx_type = $ctx_arg;
+                    let shared: &$crate::__fsm_shared!(type [$( $shared_type )?]) = $crate::__fsm_shared!(arg [$( $shared_type )?] shared);
+                    let instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]) =
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation);
+                    if Self::is_frozen() {
+                        return $crate::DispatchOutcome::Frozen;
+                    }
+                    $crate::__fsm_record!([$( $record )?] record event);
+                    if Self::try_acquire_dispatch() {
+                        let outcome = self.do_dispatch_internal(ctx, shared, instr, event);
+                        if outcome == $crate::DispatchOutcome::Transitioned {
+                            after(self);
                         }
-                    )*
+                        self.finish_dispatch(ctx, shared, instr);
+                        outcome
+                    } else {
+                        Self::enqueue_or_overflow(event.clone());
+                        $crate::DispatchOutcome::Queued
+                    }
                 }
             }
 
-            /// Internal: Executes the exit action for the current state.
-            #[allow(unused_variables)]
-            fn on_exit(&mut self, arg_ctx: &mut $ctx_type) {
-                $crate::__fsm_log!("[{}] {:?}.exit()", stringify!($enum_name), self);
-                match self {
-                    $(
-                        Self::$state_name $( { $($field_name),* } )? => {
-                            $(
-                                #[allow(unused_variables)]
-                                let $exit_ctx = arg_ctx;
-                                $exit_block
-                            )?
-                        }
-                    )*
+            /// Dispatches every event of `events`, in order, under a single acquisition of
+            /// the dispatch lock, so no event from another thread or ISR is processed in
+            /// the middle of the batch.
+            ///
+            /// Events that other contexts dispatch meanwhile are queued and only processed
+            /// once the whole batch is done, which can delay ISR events by the length of
+            /// the batch. If another dispatch is active, the batch is appended to the queue
+            /// in one critical section, so it still runs without interleaving. When the
+            /// machine is frozen, the batch is ignored.
+            pub fn dispatch_batch(
+                &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                $(, instrumentation: &mut $instr_type)?,
+                events: &[$event_type],
+            )
+            where
+                // Higher-ranked, as in `dispatch()`
+                for<'a> $event_type: Clone,
+            {
+                let ctx: &mut $ctx_type = $ctx_arg;
+                let shared: &$crate::__fsm_shared!(type [$( $shared_type )?]) = $crate::__fsm_shared!(arg [$( $shared_type )?] shared);
+                let instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]) =
+                    $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation);
+                if Self::is_frozen() {
+                    return;
+                }
+                for event in events {
+                    $crate::__fsm_record!([$( $record )?] record event);
+                }
+                if Self::try_acquire_dispatch() {
+                    for event in events {
+                        self.do_dispatch_internal(ctx, shared, instr, event);
+                    }
+                    self.finish_dispatch(ctx, shared, instr);
+                } else {
+                    let overflowed = paste::paste! {
+                        critical_section::with(|cs| {
+                            let mut queue = [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow_mut();
+                            let mut overflowed = false;
+                            for event in events {
+                                overflowed |= Self::push_pending(cs, &mut queue, event.clone()).is_err();
+                            }
+                            overflowed
+                        })
+                    };
+                    if overflowed {
+                        Self::on_overflow();
+                    }
                 }
             }
 
-            /// Internal: Determines the next state based on the event.
-            /// Returns a `Transition` enum.
-            fn on_process(&mut self, arg_ctx: &mut $ctx_type, arg_evt: &$event_type) -> Transition<Self> {
-                match self {
-                    $(
-                        // We allow unused variables here because the state might have data
-                        // (like 'speed') that the user logic doesn't need to access in this specific event.
-                        #[allow(unused_variables)]
-                        Self::$state_name $( { $($field_name),* } )? => {
-
-                            // Bind context and event to user-defined names (e.g., |ctx, evt|)
-                            #[allow(unused_variables)]
-                            let $ctx_var = arg_ctx;
-
-                            #[allow(unused_variables)]
-                            let $evt_var = arg_evt;
-
-                            // Execute user's process logic
-                            $process_block
-                        }
-                    )*
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Dispatches `event` like `dispatch()` unless it is stale: when `now > deadline`,
+                /// nothing runs, nothing is queued and `DispatchOutcome::Expired` is returned.
+                ///
+                /// `now` and `deadline` are timestamps in any monotonic unit (ticks, microseconds,
+                /// ...). The check happens once, on entry: a fresh event that gets queued behind an
+                /// active dispatch is processed even if the deadline passes in the meantime.
+                pub fn dispatch_before(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    event: &$event_type,
+                    now: u64,
+                    deadline: u64,
+                ) -> $crate::DispatchOutcome
+                where
+                    // Higher-ranked, as in `dispatch()`
+                    for<'a> $event_type: Clone,
+                {
+                    if now > deadline {
+                        return $crate::DispatchOutcome::Expired;
+                    }
+                    self.dispatch_ref(
+                        $ctx_arg,
+                        $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                        event,
+                    )
                 }
             }
 
-            /// Main Event Dispatcher.
-            ///
-            /// This is the primary function to call in your main loop.
-            /// It handles the full lifecycle: `Process` -> `Exit Old` -> `Update` -> `Entry New`.
-            ///
-            /// # Performance
-            /// Marked `#[inline(always)]` to allow the compiler to flatten the state machine
-            /// into a highly optimized jump table / switch-case structure.
-            #[inline(always)]
-            pub fn dispatch(&mut self, ctx: &mut $ctx_type, event: &$event_type) {
-                // 1. Calculate Transition
-                let transition = self.on_process(ctx, event);
-
-                // 2. Apply Transition (if any)
-                match transition {
-                    Transition::To(mut new_state) => {
-                        $crate::__fsm_log!("[{}] {:?} + {:?} -> {:?}",
-                                           stringify!($enum_name), self, event, new_state);
-
-                        // A. Exit current state
-                        self.on_exit(ctx);
-
-                        // B. Enter new state
-                        new_state.on_entry(ctx);
-
-                        // C. Update state (Move semantics - extremely fast)
-                        *self = new_state;
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Resumes a state parked with `Transition::Await(token)`: dispatches `event`
+                /// like `dispatch()` if the current state is still awaiting `token`.
+                ///
+                /// Call it from the completion callback of the operation the state started. A
+                /// stale completion (the machine left the state, or the token was already
+                /// completed or replaced) is dropped: nothing runs and
+                /// `DispatchOutcome::Stale` is returned. The pending token lives in the
+                /// `Bookkeeping` context field: without that directive, every completion is
+                /// stale. The token is checked on entry: a completion queued behind an active
+                /// dispatch is processed even if that dispatch leaves the state.
+                ///
+                /// A completion that is not processed (`Frozen`, `Filtered`, or dropped by a
+                /// full queue) leaves the token pending, so it can be delivered again.
+                pub fn complete(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?,
+                    token: usize,
+                    event: &$event_type,
+                ) -> $crate::DispatchOutcome
+                where
+                    // Higher-ranked, as in `dispatch()`
+                    for<'a> $event_type: Clone,
+                {
+                    let ctx: &mut $ctx_type = $ctx_arg;
+                    if Self::is_frozen() {
+                        return $crate::DispatchOutcome::Frozen;
                     }
-                    Transition::None => {
-                        $crate::__fsm_log!("[{}] {:?} + {:?} -> None (stayed)",
-                                           stringify!($enum_name), self, event);
+                    let state_index = self.state_index();
+                    if !Self::bookkeeping_mut(ctx).is_some_and(|book| book.unpark(state_index, token)) {
+                        return $crate::DispatchOutcome::Stale;
+                    }
+                    let shared: &$crate::__fsm_shared!(type [$( $shared_type )?]) = $crate::__fsm_shared!(arg [$( $shared_type )?] shared);
+                    let instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]) =
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation);
+                    $crate::__fsm_record!([$( $record )?] record event);
+                    if Self::try_acquire_dispatch() {
+                        let outcome = self.do_dispatch_internal(ctx, shared, instr, event);
+                        if matches!(outcome, $crate::DispatchOutcome::Filtered) {
+                            // Rejected before any hook ran (and before the queue drains): the
+                            // state still awaits the token
+                            if let Some(book) = Self::bookkeeping_mut(ctx) {
+                                book.park(state_index, token);
+                            }
+                        }
+                        self.finish_dispatch(ctx, shared, instr);
+                        outcome
+                    } else {
+                        if Self::enqueue_pending(event.clone()).is_err() {
+                            // Dropped: the state still awaits the token
+                            if let Some(book) = Self::bookkeeping_mut(ctx) {
+                                book.park(state_index, token);
+                            }
+                            Self::on_overflow();
+                        }
+                        $crate::DispatchOutcome::Queued
                     }
                 }
             }
-        }
-    };
-}
 
-// ============================================================================
-// IMPLEMENTATION WITH CONCURRENCY PROTECTION (feature = "concurrent")
-// ============================================================================
-#[cfg(feature = "concurrent")]
-#[macro_export]
-macro_rules! state_machine {
-    // Pattern 1: With explicit QueueCapacity
-    (
-        Name: $enum_name:ident,
-        Context: $ctx_type:ty,
-        Event: $event_type:ty,
-        QueueCapacity: $queue_capacity:expr,
-        States: {
-            $(
-                $state_name:ident $( { $($field_name:ident : $field_type:ty),* } )? => {
-                    $( entry: |$entry_ctx:ident| $entry_block:block )?
-                    process: |$ctx_var:ident, $evt_var:ident| $process_block:block
-                    $( exit: |$exit_ctx:ident| $exit_block:block )?
+            // Higher-ranked `Clone` bound, as in `dispatch()`
+            $crate::__fsm_generation! {
+                [$( $bookkeeping )?] $ctx_type, $event_type, [$( $shared_type )?] [$( $instr_type )?]
+                [where for<'a> $event_type: Clone,]
+            }
+
+            /// Internal: `dispatch()` body, shared with `dispatch_before()`.
+            #[inline(always)]
+            fn dispatch_ref(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                event: &$event_type,
+            ) -> $crate::DispatchOutcome
+            where
+                for<'a> $event_type: Clone,
+            {
+                if Self::is_frozen() {
+                    // Frozen - ignore the event entirely (not even queued)
+                    return $crate::DispatchOutcome::Frozen;
+                }
+                $crate::__fsm_record!([$( $record )?] record event);
+                if Self::try_acquire_dispatch() {
+                    // ✅ Lock acquired - we are the active dispatch
+                    let outcome = self.do_dispatch_internal(ctx, shared, instr, event);
+                    self.finish_dispatch(ctx, shared, instr);
+                    outcome
+                } else {
+                    // ❌ Dispatch already active - clone the event into the queue
+                    Self::enqueue_or_overflow(event.clone());
+                    $crate::DispatchOutcome::Queued
                 }
-            ),* $(,)?
-        }
-    ) => {
-        $crate::state_machine! {
-            @internal
-            Name: $enum_name,
-            Context: $ctx_type,
-            Event: $event_type,
-            QueueCapacity: $queue_capacity,
-            States: {
-                $(
-                    $state_name $( { $($field_name : $field_type),* } )? => {
-                        $( entry: |$entry_ctx| $entry_block )?
-                        process: |$ctx_var, $evt_var| $process_block
-                        $( exit: |$exit_ctx| $exit_block )?
-                    }
-                ),*
             }
-        }
-    };
 
-    // Pattern 2: Without QueueCapacity (default to 16)
-    (
-        Name: $enum_name:ident,
-        Context: $ctx_type:ty,
-        Event: $event_type:ty,
-        States: {
-            $(
-                $state_name:ident $( { $($field_name:ident : $field_type:ty),* } )? => {
-                    $( entry: |$entry_ctx:ident| $entry_block:block )?
-                    process: |$ctx_var:ident, $evt_var:ident| $process_block:block
-                    $( exit: |$exit_ctx:ident| $exit_block:block )?
+            /// Internal: `dispatch_owned()` body, shared with `run_until()`.
+            #[inline(always)]
+            fn dispatch_value(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+                event: $event_type,
+            ) -> $crate::DispatchOutcome {
+                if Self::is_frozen() {
+                    return $crate::DispatchOutcome::Frozen;
+                }
+                $crate::__fsm_record!([$( $record )?] record &event);
+                if Self::try_acquire_dispatch() {
+                    let outcome = self.do_dispatch_internal(ctx, shared, instr, &event);
+                    self.finish_dispatch(ctx, shared, instr);
+                    outcome
+                } else {
+                    Self::enqueue_or_overflow(event);
+                    $crate::DispatchOutcome::Queued
                 }
-            ),* $(,)?
-        }
-    ) => {
-        $crate::state_machine! {
-            @internal
-            Name: $enum_name,
-            Context: $ctx_type,
-            Event: $event_type,
-            QueueCapacity: 16,
-            States: {
-                $(
-                    $state_name $( { $($field_name : $field_type),* } )? => {
-                        $( entry: |$entry_ctx| $entry_block )?
-                        process: |$ctx_var, $evt_var| $process_block
-                        $( exit: |$exit_ctx| $exit_block )?
-                    }
-                ),*
             }
-        }
-    };
 
-    // Internal implementation (actual code generation)
-    (
-        @internal
-        Name: $enum_name:ident,
-        Context: $ctx_type:ty,
-        Event: $event_type:ty,
-        QueueCapacity: $queue_capacity:expr,
-        States: {
-            $(
-                $state_name:ident $( { $($field_name:ident : $field_type:ty),* } )? => {
-                    $( entry: |$entry_ctx:ident| $entry_block:block )?
-                    process: |$ctx_var:ident, $evt_var:ident| $process_block:block
-                    $( exit: |$exit_ctx:ident| $exit_block:block )?
+            $crate::__fsm_must_use! { [$( $must_use )?]
+                /// Processes at most one queued event, then releases the dispatch lock.
+                ///
+                /// `dispatch()` drains the whole queue before returning. Cooperative schedulers
+                /// that must bound the work done per tick can instead call `step()` once per
+                /// tick. Whatever `step()` leaves in the queue is still drained by the next
+                /// `dispatch()`, and `OnQuiescent` runs when a step empties the queue.
+                ///
+                /// Returns the outcome of the processed event, or:
+                /// - `DispatchOutcome::Stayed` if the queue was empty (nothing processed)
+                /// - `DispatchOutcome::Queued` if another dispatch is active (it drains the queue)
+                ///   or the queue is paused (`pause_queue()`)
+                /// - `DispatchOutcome::Frozen` after `freeze()` (the queue is left untouched)
+                pub fn step(
+                    &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                    $(, instrumentation: &mut $instr_type)?
+                ) -> $crate::DispatchOutcome {
+                    let ctx: &mut $ctx_type = $ctx_arg;
+                    let shared: &$crate::__fsm_shared!(type [$( $shared_type )?]) = $crate::__fsm_shared!(arg [$( $shared_type )?] shared);
+                    let instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]) =
+                        $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation);
+                    if Self::is_frozen() {
+                        return $crate::DispatchOutcome::Frozen;
+                    }
+                    if !Self::try_acquire_dispatch() {
+                        return $crate::DispatchOutcome::Queued;
+                    }
+                    paste::paste! {
+                        use portable_atomic::Ordering;
+
+                        let pending = critical_section::with(|cs| {
+                            [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow_mut().pop_front()
+                        });
+                        let outcome = match pending {
+                            Some(evt) => {
+                                let outcome = self.do_dispatch_internal(ctx, shared, instr, &evt);
+                                let drained = critical_section::with(|cs| {
+                                    [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow().is_empty()
+                                });
+                                if drained {
+                                    self.on_quiescent(ctx);
+                                }
+                                outcome
+                            }
+                            None => $crate::DispatchOutcome::Stayed,
+                        };
+
+                        [<DISPATCH_ACTIVE_ $enum_name:upper>].store(false, Ordering::Release);
+                        outcome
+                    }
                 }
-            ),* $(,)?
-        }
-    ) => {
-        /// Auto-generated State Machine Enum (with concurrency protection).
-        /// Holds the current state and its internal data.
-        ///
-        /// # Concurrency Safety
-        ///
-        /// When the `concurrent` feature is enabled, this state machine is safe to use with:
-        /// - **ISRs (Interrupt Service Routines)**: Can be called from interrupt handlers
-        /// - **Threads**: Can be called from multiple threads
-        /// - **ISRs + Threads**: Both simultaneously (e.g., RTOS environments)
-        ///
-        /// The implementation uses atomic operations and lock-free queues to prevent
-        /// re-entrancy while maintaining low latency for interrupt handlers.
-        #[derive(Debug)]
-        pub enum $enum_name {
-            $(
-                $state_name $( { $($field_name : $field_type),* } )?,
-            )*
-        }
+            }
 
-        // Concurrency control: unique statics per state machine
-        paste::paste! {
-            static [<DISPATCH_ACTIVE_ $enum_name:upper>]: portable_atomic::AtomicBool =
-                portable_atomic::AtomicBool::new(false);
+            /// Processes every queued event, including those queued meanwhile, without a
+            /// new triggering event, then releases the dispatch lock.
+            ///
+            /// For a worker that should handle everything ISRs and other threads queued,
+            /// then sleep: when it returns, the queue was empty and `OnQuiescent` ran (if
+            /// at least one event was processed).
+            ///
+            /// Returns how many queued events were processed: `0` if the queue was empty,
+            /// if another dispatch is active (it drains the queue), or after `freeze()` or
+            /// `pause_queue()` (the queue is left untouched).
+            pub fn run_to_idle(
+                &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                $(, instrumentation: &mut $instr_type)?
+            ) -> usize {
+                self.drain_pending(
+                    $ctx_arg,
+                    $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                    $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                )
+            }
 
-            static [<PENDING_QUEUE_ $enum_name:upper>]: critical_section::Mutex<
-                core::cell::RefCell<heapless::Deque<$event_type, $queue_capacity>>
-            > = critical_section::Mutex::new(core::cell::RefCell::new(heapless::Deque::new()));
+            /// Internal: `run_to_idle()` body, shared with `resume_queue()`.
+            fn drain_pending(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+            ) -> usize {
+                if Self::is_frozen() || !Self::try_acquire_dispatch() {
+                    return 0;
+                }
+                paste::paste! {
+                    use portable_atomic::Ordering;
 
-            static [<DROPPED_EVENTS_ $enum_name:upper>]: portable_atomic::AtomicUsize =
-                portable_atomic::AtomicUsize::new(0);
-        }
+                    let mut processed = 0;
+                    while let Some(evt) = critical_section::with(|cs| {
+                        [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow_mut().pop_front()
+                    }) {
+                        self.do_dispatch_internal(ctx, shared, instr, &evt);
+                        processed += 1;
+                    }
+                    if processed > 0 {
+                        self.on_quiescent(ctx);
+                    }
 
-        impl $enum_name {
-            /// Initializes the state machine by executing the entry action of the initial state.
-            ///
-            /// # CRITICAL: Must be called before the event loop!
-            #[allow(unused_variables)]
-            pub fn init(&mut self, ctx: &mut $ctx_type) {
-                $crate::__fsm_log!("[{}] init() -> {:?}", stringify!($enum_name), self);
-                self.on_entry(ctx);
+                    [<DISPATCH_ACTIVE_ $enum_name:upper>].store(false, Ordering::Release);
+                    processed
+                }
             }
 
-            /// Internal: Executes the entry action for the current state.
-            #[allow(unused_variables)]
-            fn on_entry(&mut self, arg_ctx: &mut $ctx_type) {
-                $crate::__fsm_log!("[{}] {:?}.entry()", stringify!($enum_name), self);
-                match self {
-                    $(
-                        Self::$state_name $( { $($field_name),* } )? => {
-                            $(
-                                #[allow(unused_variables)]
-                                let $entry_ctx = arg_ctx;
-                                $entry_block
-                            )?
-                        }
-                    )*
+            /// Freezes the state machine: until `thaw()`, every dispatch ignores its event
+            /// and returns `DispatchOutcome::Frozen` without running any hook.
+            ///
+            /// Frozen dispatches do not enqueue their event, whichever context they come
+            /// from. The flag belongs to the state machine type, like its queue:
+            /// `Self::freeze()` freezes every machine of this type. Events queued before the
+            /// freeze are kept and processed by the first dispatch after `thaw()`.
+            pub fn freeze() {
+                paste::paste! {
+                    [<FROZEN_ $enum_name:upper>].store(true, portable_atomic::Ordering::Release);
                 }
             }
 
-            /// Internal: Executes the exit action for the current state.
-            #[allow(unused_variables)]
-            fn on_exit(&mut self, arg_ctx: &mut $ctx_type) {
-                $crate::__fsm_log!("[{}] {:?}.exit()", stringify!($enum_name), self);
-                match self {
-                    $(
-                        Self::$state_name $( { $($field_name),* } )? => {
-                            $(
-                                #[allow(unused_variables)]
-                                let $exit_ctx = arg_ctx;
-                                $exit_block
-                            )?
-                        }
-                    )*
+            /// Resumes normal dispatching of every machine of this type after `freeze()`.
+            pub fn thaw() {
+                paste::paste! {
+                    [<FROZEN_ $enum_name:upper>].store(false, portable_atomic::Ordering::Release);
                 }
             }
 
-            /// Internal: Determines the next state based on the event.
-            fn on_process(&mut self, arg_ctx: &mut $ctx_type, arg_evt: &$event_type) -> Transition<Self> {
-                match self {
-                    $(
-                        #[allow(unused_variables)]
-                        Self::$state_name $( { $($field_name),* } )? => {
-                            #[allow(unused_variables)]
-                            let $ctx_var = arg_ctx;
-                            #[allow(unused_variables)]
-                            let $evt_var = arg_evt;
-                            $process_block
-                        }
-                    )*
+            /// Returns `true` between `freeze()` and `thaw()`.
+            pub fn is_frozen() -> bool {
+                paste::paste! {
+                    [<FROZEN_ $enum_name:upper>].load(portable_atomic::Ordering::Acquire)
                 }
             }
 
-            /// Internal dispatch implementation (without concurrency protection).
+            /// Pauses the queue: until `resume_queue()`, every dispatch enqueues its event
+            /// (returning `DispatchOutcome::Queued`) instead of processing it, even when no
+            /// other dispatch is active.
             ///
-            /// This is called by the public `dispatch()` method after acquiring the lock.
-            #[inline(always)]
-            fn do_dispatch_internal(&mut self, ctx: &mut $ctx_type, event: &$event_type) {
-                let transition = self.on_process(ctx, event);
-                match transition {
-                    Transition::To(mut new_state) => {
-                        $crate::__fsm_log!("[{}] {:?} + {:?} -> {:?}",
-                                           stringify!($enum_name), self, event, new_state);
-                        self.on_exit(ctx);
-                        new_state.on_entry(ctx);
-                        *self = new_state;
-                    }
-                    Transition::None => {
-                        $crate::__fsm_log!("[{}] {:?} + {:?} -> None (stayed)",
-                                           stringify!($enum_name), self, event);
-                    }
+            /// Unlike `freeze()`, which ignores events, pausing keeps them and defers them:
+            /// use it around a reconfiguration that must not observe a transition. Events
+            /// beyond `QueueCapacity` still overflow. `step()` and `run_to_idle()` process
+            /// nothing while paused. The flag belongs to the state machine type, like its
+            /// queue: `Self::pause_queue()` pauses every machine of this type. A dispatch
+            /// already running when the queue is paused drains it as usual.
+            pub fn pause_queue() {
+                paste::paste! {
+                    [<QUEUE_PAUSED_ $enum_name:upper>].store(true, portable_atomic::Ordering::Release);
                 }
             }
 
-            /// Returns the number of events that were dropped due to queue overflow.
-            ///
-            /// When the event queue is full (capacity: $queue_capacity), new events are dropped
-            /// and this counter is incremented. Use this to detect if your queue capacity
-            /// is insufficient for your workload.
+            /// Resumes processing after `pause_queue()` and drains the events queued while
+            /// paused, in order (see `run_to_idle()`). It unpauses every machine of this
+            /// type, but only `self` drains the queue.
             ///
-            /// # Example
-            ///
-            /// ```rust,no_run
-            /// # use typed_fsm::state_machine;
-            /// # struct Context {}
-            /// # #[derive(Debug, Clone)]
-            /// # enum Event { Tick }
-            /// # state_machine! {
-            /// #     Name: MyFSM,
-            /// #     Context: Context,
-            /// #     Event: Event,
-            /// #     States: { Idle => { process: |_ctx, _evt| { typed_fsm::Transition::None } } }
-            /// # }
-            /// // Check if events were dropped
-            /// let dropped = MyFSM::dropped_events_count();
-            /// if dropped > 0 {
-            ///     eprintln!("Warning: {} events were dropped!", dropped);
-            ///     // Consider increasing QueueCapacity
-            /// }
-            /// ```
-            pub fn dropped_events_count() -> usize {
+            /// Returns how many queued events were processed.
+            pub fn resume_queue(
+                &mut self $($ctx_param)* $(, shared: &$shared_type)?
+                $(, instrumentation: &mut $instr_type)?
+            ) -> usize {
                 paste::paste! {
-                    use portable_atomic::Ordering;
-                    [<DROPPED_EVENTS_ $enum_name:upper>].load(Ordering::Relaxed)
+                    [<QUEUE_PAUSED_ $enum_name:upper>].store(false, portable_atomic::Ordering::Release);
                 }
+                self.drain_pending(
+                    $ctx_arg,
+                    $crate::__fsm_shared!(arg [$( $shared_type )?] shared),
+                    $crate::__fsm_instrumentation!(arg [$( $instr_type )?] instrumentation),
+                )
             }
 
-            /// Resets the dropped events counter to zero.
-            ///
-            /// Useful for monitoring event loss over specific time periods.
-            ///
-            /// # Example
-            ///
-            /// ```rust,no_run
-            /// # use typed_fsm::state_machine;
-            /// # struct Context {}
-            /// # #[derive(Debug, Clone)]
-            /// # enum Event { Tick }
-            /// # state_machine! {
-            /// #     Name: MyFSM,
-            /// #     Context: Context,
-            /// #     Event: Event,
-            /// #     States: { Idle => { process: |_ctx, _evt| { typed_fsm::Transition::None } } }
-            /// # }
-            /// // Reset counter for new monitoring period
-            /// MyFSM::reset_dropped_count();
-            ///
-            /// // ... run for some time ...
-            ///
-            /// // Check events dropped during this period
-            /// let dropped = MyFSM::dropped_events_count();
-            /// ```
-            pub fn reset_dropped_count() {
+            /// Returns `true` between `pause_queue()` and `resume_queue()`.
+            pub fn is_queue_paused() -> bool {
                 paste::paste! {
-                    use portable_atomic::Ordering;
-                    [<DROPPED_EVENTS_ $enum_name:upper>].store(0, Ordering::Relaxed);
+                    [<QUEUE_PAUSED_ $enum_name:upper>].load(portable_atomic::Ordering::Acquire)
                 }
             }
 
-            /// Main Event Dispatcher with Concurrency Protection.
-            ///
-            /// This function is safe to call from:
-            /// - **Main loop**: Regular sequential execution
-            /// - **ISRs**: Interrupt service routines
-            /// - **Threads**: Multiple concurrent threads
-            /// - **ISRs + Threads**: Both simultaneously
-            ///
-            /// # Behavior
-            ///
-            /// - If no dispatch is active: Executes immediately and processes entire pending queue
-            /// - If dispatch is already active: Enqueues event for later processing
-            ///
-            /// # Performance
-            ///
-            /// - **Without contention**: ~10-15% overhead vs non-concurrent version
-            /// - **ISR enqueue**: ~100 cycles (fast and deterministic)
-            /// - **Queue processing**: Automatic before releasing lock
-            ///
-            /// # Safety
-            ///
-            /// Uses atomic compare-exchange and lock-free queue to prevent:
-            /// - Re-entrant dispatch calls
-            /// - Data races on state machine state
-            /// - Data races on context
-            ///
-            /// # Example
-            ///
-            /// ```rust,no_run
-            /// // From ISR
-            /// #[interrupt]
-            /// fn TIMER_IRQ() {
-            ///     unsafe {
-            ///         FSM.as_mut().unwrap().dispatch(&mut CTX.as_mut().unwrap(), Event::Tick);
-            ///         // ✅ ISR-safe: Enqueues if main is active
-            ///     }
-            /// }
-            ///
-            /// // From main loop
-            /// fn main() {
-            ///     loop {
-            ///         fsm.dispatch(&mut ctx, Event::Button);
-            ///         // ✅ Processes event + all ISR-queued events
-            ///     }
-            /// }
-            /// ```
+            $crate::__fsm_test_internals! { $enum_name, $event_type }
+
+            /// Internal: Tries to become the active dispatch (atomic compare-exchange).
+            /// Always fails while the queue is paused, so callers enqueue their event.
             #[inline(always)]
-            pub fn dispatch(&mut self, ctx: &mut $ctx_type, event: &$event_type)
-            where
-                $event_type: Clone
-            {
+            fn try_acquire_dispatch() -> bool {
                 paste::paste! {
                     use portable_atomic::Ordering;
-
-                    // Try to acquire dispatch lock atomically
-                    if [<DISPATCH_ACTIVE_ $enum_name:upper>]
+                    if [<QUEUE_PAUSED_ $enum_name:upper>].load(Ordering::Acquire) {
+                        return false;
+                    }
+                    [<DISPATCH_ACTIVE_ $enum_name:upper>]
                         .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
                         .is_ok()
-                    {
-                        // ✅ Lock acquired - we are the active dispatch
-
-                        // Process the immediate event
-                        self.do_dispatch_internal(ctx, event);
-
-                        // Process ALL pending events from queue
-                        loop {
-                            let pending = critical_section::with(|cs| {
-                                [<PENDING_QUEUE_ $enum_name:upper>]
-                                    .borrow(cs)
-                                    .borrow_mut()
-                                    .pop_front()
-                            });
-
-                            match pending {
-                                Some(evt) => self.do_dispatch_internal(ctx, &evt),
-                                None => break,  // Queue empty - can release lock
-                            }
-                        }
+                }
+            }
 
-                        // Release dispatch lock
-                        [<DISPATCH_ACTIVE_ $enum_name:upper>].store(false, Ordering::Release);
-                    } else {
-                        // ❌ Dispatch already active - enqueue event for later
-                        // Clone the event to store in queue
-                        let enqueue_result = critical_section::with(|cs| {
+            /// Internal: Processes ALL pending events, runs `OnQuiescent`, then releases the
+            /// dispatch lock.
+            #[inline(always)]
+            fn finish_dispatch(
+                &mut self,
+                ctx: &mut $ctx_type,
+                shared: &$crate::__fsm_shared!(type [$( $shared_type )?]),
+                instr: &mut $crate::__fsm_instrumentation!(type [$( $instr_type )?]),
+            ) {
+                paste::paste! {
+                    use portable_atomic::Ordering;
+
+                    loop {
+                        let pending = critical_section::with(|cs| {
                             [<PENDING_QUEUE_ $enum_name:upper>]
                                 .borrow(cs)
                                 .borrow_mut()
-                                .push_back(event.clone())
+                                .pop_front()
                         });
 
-                        // Handle queue overflow
-                        if enqueue_result.is_err() {
-                            // Increment dropped events counter
-                            [<DROPPED_EVENTS_ $enum_name:upper>]
-                                .fetch_add(1, Ordering::Relaxed);
-
-                            // In debug builds, panic to help detect issues during development
-                            #[cfg(debug_assertions)]
-                            {
-                                panic!(
-                                    "[{}] Queue overflow! Event dropped. Queue capacity: {}. \
-                                     Consider increasing QueueCapacity or reducing event rate.",
-                                    stringify!($enum_name),
-                                    $queue_capacity
-                                );
-                            }
-
-                            // In release builds, silently drop (logged via counter)
-                            #[cfg(not(debug_assertions))]
-                            {
-                                // Event dropped silently - check dropped_events_count()
+                        match pending {
+                            Some(evt) => {
+                                self.do_dispatch_internal(ctx, shared, instr, &evt);
                             }
+                            None => break,  // Queue empty - can release lock
                         }
                     }
+
+                    // Quiescent: notify before another dispatch can take the lock
+                    self.on_quiescent(ctx);
+
+                    // Release dispatch lock
+                    [<DISPATCH_ACTIVE_ $enum_name:upper>].store(false, Ordering::Release);
+                }
+            }
+
+            /// Internal: Appends an event to the pending queue, applying the
+            /// `OverflowPolicy` if the queue is full.
+            fn enqueue_pending(event: $event_type) -> Result<(), $event_type> {
+                let result = paste::paste! {
+                    critical_section::with(|cs| {
+                        let mut queue = [<PENDING_QUEUE_ $enum_name:upper>].borrow(cs).borrow_mut();
+                        Self::push_pending(cs, &mut queue, event)
+                    })
+                };
+                if result.is_err() && matches!(Self::OVERFLOW_POLICY, $crate::OverflowPolicy::Panic) {
+                    Self::on_overflow();
+                }
+                result
+            }
+
+            /// Internal: Pushes `event` onto the (locked) queue. When it is full, counts
+            /// one dropped event and applies the `OverflowPolicy`: `DropOldest` makes room
+            /// by discarding the oldest event, the other policies hand `event` back. The
+            /// dropped event is kept by `DroppedBuffer`, if declared.
+            ///
+            /// Runs inside the caller's critical section, so overflow_report() always sees
+            /// the dropped counter consistent with the queue.
+            #[allow(unused_variables)]
+            fn push_pending(
+                cs: critical_section::CriticalSection<'_>,
+                queue: &mut $crate::PendingQueue<$event_type, $queue_capacity>,
+                event: $event_type,
+            ) -> Result<(), $event_type> {
+                let Err(event) = queue.push_back(event) else {
+                    return Ok(());
+                };
+                paste::paste! {
+                    [<DROPPED_EVENTS_ $enum_name:upper>]
+                        .fetch_add(1, portable_atomic::Ordering::Relaxed);
+                }
+                if matches!(Self::OVERFLOW_POLICY, $crate::OverflowPolicy::DropOldest) {
+                    if let Some(oldest) = queue.pop_front() {
+                        $crate::__fsm_dropped_buffer!([$( $dropped_buffer )?] record cs, oldest);
+                    }
+                    // Fits now, unless the queue has no capacity at all
+                    let _ = queue.push_back(event);
+                    Ok(())
+                } else {
+                    // Handed back to the caller, so the store keeps a clone
+                    $crate::__fsm_dropped_buffer!([$( $dropped_buffer )?] record cs, event.clone());
+                    Err(event)
                 }
             }
+
+            /// Internal: Enqueues an event for the active dispatch, handling overflow.
+            fn enqueue_or_overflow(event: $event_type) {
+                // Handle queue overflow
+                if Self::enqueue_pending(event).is_err() {
+                    Self::on_overflow();
+                }
+            }
+
+            /// Internal: Reacts to a new event dropped by a full queue (already counted).
+            fn on_overflow() {
+                // `OverflowPolicy: Panic` always panics; by default, panic in debug builds
+                // to help detect issues during development
+                if cfg!(debug_assertions)
+                    || matches!(Self::OVERFLOW_POLICY, $crate::OverflowPolicy::Panic)
+                {
+                    panic!(
+                        "[{}] Queue overflow! Event dropped. Queue capacity: {}. \
+                         Consider increasing QueueCapacity or reducing event rate.",
+                        stringify!($enum_name),
+                        $queue_capacity
+                    );
+                }
+
+                // Otherwise, silently drop (logged via counter)
+            }
+        }
+    };
+}
+
+/// Asserts that a state machine is in the given state, ignoring its fields.
+///
+/// Unlike `assert!(matches!(fsm, Machine::Idle))`, a failure names the actual state
+/// (from `current_state_name()`). An optional message is formatted like `assert!`'s.
+///
+/// ```rust
+/// # use typed_fsm::{assert_in_state, state_machine, Transition};
+/// # struct Context {}
+/// # #[derive(Debug, Clone)]
+/// # enum Event { Start }
+/// # state_machine! {
+/// #     Name: Motor,
+/// #     Context: Context,
+/// #     Event: Event,
+/// #     States: {
+/// #         Idle => {
+/// #             process: |_ctx, _evt| { Transition::To(Motor::Running { speed: 10 }) }
+/// #         },
+/// #         Running { speed: u32 } => {
+/// #             process: |_ctx, _evt| { Transition::None }
+/// #         }
+/// #     }
+/// # }
+/// let mut ctx = Context {};
+/// let mut fsm = Motor::start_in(Motor::Idle, &mut ctx);
+/// fsm.dispatch(&mut ctx, &Event::Start);
+///
+/// assert_in_state!(fsm, Running);
+/// assert_in_state!(fsm, Running, "after {} start event", 1);
+/// ```
+///
+/// A mismatch panics with, e.g.:
+///
+/// ```text
+/// assertion failed: `fsm` is in state `Idle`
+///   actual state: `Running`
+/// ```
+#[macro_export]
+macro_rules! assert_in_state {
+    ($fsm:expr, $state:ident $(,)?) => {{
+        let actual: &'static str = $fsm.current_state_name();
+        if actual != stringify!($state) {
+            panic!(
+                "assertion failed: `{}` is in state `{}`\n  actual state: `{}`",
+                stringify!($fsm),
+                stringify!($state),
+                actual
+            );
+        }
+    }};
+    ($fsm:expr, $state:ident, $($arg:tt)+) => {{
+        let actual: &'static str = $fsm.current_state_name();
+        if actual != stringify!($state) {
+            panic!(
+                "assertion failed: `{}` is in state `{}`\n  actual state: `{}`: {}",
+                stringify!($fsm),
+                stringify!($state),
+                actual,
+                format_args!($($arg)+)
+            );
+        }
+    }};
+}
+
+/// Composes several state machines that coordinate through one shared context.
+///
+/// Generates a struct owning the context and one value of each machine, with:
+/// - `new(context, machines...)` - takes the machines in declaration order
+/// - `init_all()` - calls `init()` on each machine, in declaration order
+/// - `dispatch_all(&event)` - dispatches the event to each machine, in declaration
+///   order, and returns their outcomes in the same order
+///
+/// Each machine must implement [`Fsm`](crate::Fsm) for the given `Context` and `Event`
+/// (i.e., use neither `SharedRef` nor `Instrumentation`). Context-free machines use
+/// `Context: ()`.
+///
+/// ```rust
+/// # use typed_fsm::{compose_fsms, state_machine, DispatchOutcome, Transition};
+/// struct Crossing { cars_waiting: u32 }
+/// #[derive(Debug, Clone)]
+/// enum Event { Tick }
+/// # state_machine! {
+/// #     Name: CarLight,
+/// #     Context: Crossing,
+/// #     Event: Event,
+/// #     States: {
+/// #         Green => { process: |ctx, _evt| { ctx.cars_waiting = 0; CarLight::Red } },
+/// #         Red => { process: |_ctx, _evt| { Transition::None } }
+/// #     }
+/// # }
+/// # state_machine! {
+/// #     Name: Pedestrian,
+/// #     Context: Crossing,
+/// #     Event: Event,
+/// #     States: {
+/// #         Wait => {
+/// #             process: |ctx, _evt| { Transition::to_if(ctx.cars_waiting == 0, Pedestrian::Walk) }
+/// #         },
+/// #         Walk => { process: |_ctx, _evt| { Transition::None } }
+/// #     }
+/// # }
+///
+/// compose_fsms! {
+///     Name: Intersection,
+///     Context: Crossing,
+///     Event: Event,
+///     Machines: {
+///         cars: CarLight,
+///         pedestrians: Pedestrian,
+///     }
+/// }
+///
+/// let mut crossing = Intersection::new(
+///     Crossing { cars_waiting: 3 },
+///     CarLight::Green,
+///     Pedestrian::Wait,
+/// );
+/// crossing.init_all();
+///
+/// // `cars` runs first, so `pedestrians` already sees the cleared crossing
+/// let outcomes = crossing.dispatch_all(&Event::Tick);
+/// assert_eq!(outcomes, [DispatchOutcome::Transitioned; 2]);
+/// assert!(matches!(crossing.pedestrians, Pedestrian::Walk));
+/// ```
+#[macro_export]
+macro_rules! compose_fsms {
+    (
+        Name: $name:ident,
+        Context: $ctx_type:ty,
+        Event: $event_type:ty,
+        Machines: {
+            $( $field:ident : $fsm_type:ty ),+ $(,)?
+        } $(,)?
+    ) => {
+        /// State machines composed by `compose_fsms!`, sharing one context.
+        pub struct $name {
+            /// The context shared by every machine.
+            pub context: $ctx_type,
+            $(
+                #[doc = concat!("The `", stringify!($field), "` machine.")]
+                pub $field: $fsm_type,
+            )+
+        }
+
+        impl $name {
+            /// Number of composed machines.
+            pub const MACHINE_COUNT: usize = [$( stringify!($field) ),+].len();
+
+            /// Composes `context` with the given machines (not initialized yet).
+            pub fn new(context: $ctx_type $(, $field: $fsm_type)+) -> Self {
+                Self { context $(, $field)+ }
+            }
+
+            /// Initializes every machine, in declaration order.
+            pub fn init_all(&mut self) {
+                $( $crate::Fsm::<$ctx_type, $event_type>::init(&mut self.$field, &mut self.context); )+
+            }
+
+            /// Dispatches `event` to every machine, in declaration order, and returns
+            /// their outcomes in the same order.
+            ///
+            /// Each machine sees the context as left by the previous ones.
+            pub fn dispatch_all(
+                &mut self,
+                event: &$event_type,
+            ) -> [$crate::DispatchOutcome; Self::MACHINE_COUNT] {
+                [$(
+                    $crate::Fsm::<$ctx_type, $event_type>::dispatch(
+                        &mut self.$field,
+                        &mut self.context,
+                        event,
+                    )
+                ),+]
+            }
         }
     };
 }
@@ -954,23 +8087,152 @@ macro_rules! state_machine {
 mod tests {
     use super::*;
 
+    #[test]
+    #[cfg(target_has_atomic = "8")]
+    fn test_deferred_queue_fifo_and_wraparound() {
+        let queue: DeferredQueue<u32, 2> = DeferredQueue::new();
+        assert!(queue.is_empty());
+
+        for round in 0..3 {
+            assert_eq!(queue.push(round * 10), Ok(()));
+            assert_eq!(queue.push(round * 10 + 1), Ok(()));
+            assert_eq!(queue.len(), 2);
+            assert_eq!(queue.pop(), Some(round * 10));
+            assert_eq!(queue.pop(), Some(round * 10 + 1));
+            assert_eq!(queue.pop(), None);
+        }
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    #[cfg(target_has_atomic = "8")]
+    fn test_deferred_queue_overflow_returns_event() {
+        let queue: DeferredQueue<u32, 1> = DeferredQueue::new();
+        assert_eq!(queue.push(1), Ok(()));
+        assert_eq!(queue.push(2), Err(2));
+        assert_eq!(queue.push(3), Err(3));
+        assert_eq!(queue.dropped(), 2);
+
+        queue.reset_dropped();
+        assert_eq!(queue.dropped(), 0);
+        assert_eq!(queue.pop(), Some(1));
+    }
+
+    #[test]
+    fn test_transition_to_if() {
+        // Condition true: transition to the given state
+        match Transition::<i32>::to_if(true, 7) {
+            Transition::To(value) => assert_eq!(value, 7),
+            _ => panic!("Expected To(7)"),
+        }
+
+        // Condition false: stay (None, not Unhandled)
+        match Transition::<i32>::to_if(false, 7) {
+            Transition::None => {} // Test passes if we reach this branch
+            _ => panic!("Expected None"),
+        }
+    }
+
+    #[test]
+    fn test_transition_to_or() {
+        match Transition::<&str>::to_or(true, "a", "b") {
+            Transition::To(value) => assert_eq!(value, "a"),
+            _ => panic!("Expected To(\"a\")"),
+        }
+
+        match Transition::<&str>::to_or(false, "a", "b") {
+            Transition::To(value) => assert_eq!(value, "b"),
+            _ => panic!("Expected To(\"b\")"),
+        }
+    }
+
+    #[test]
+    fn test_transition_to_or_drops_unused_state() {
+        use core::cell::Cell;
+
+        struct Tracked<'a>(&'static str, &'a Cell<u32>);
+        impl Drop for Tracked<'_> {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let next: Transition<Tracked> =
+            Transition::to_or(false, Tracked("yes", &drops), Tracked("no", &drops));
+        // Only the unused state was dropped, once
+        assert_eq!(drops.get(), 1);
+        match next {
+            Transition::To(Tracked(name, _)) => assert_eq!(name, "no"),
+            _ => panic!("Expected To(\"no\")"),
+        }
+        drop(next);
+        assert_eq!(drops.get(), 2);
+    }
+
     #[test]
     fn test_transition_none_is_none() {
         // Verify that Transition::None can be created and pattern matched
         let trans: Transition<i32> = Transition::None;
         match trans {
             Transition::None => {} // Test passes if we reach this branch
-            Transition::To(_) => panic!("Expected None"),
+            Transition::Unhandled => panic!("Expected None"),
+            Transition::Reenter => panic!("Expected None"),
+            Transition::To(_)
+            | Transition::ToFn(_)
+            | Transition::ToAndPost(..)
+            | Transition::Retry
+            | Transition::Await(_) => panic!("Expected None"),
         }
     }
 
     #[test]
     fn test_transition_to_carries_value() {
         // Verify that Transition::To carries the correct value
-        let trans = Transition::To(42);
+        let trans: Transition<i32> = Transition::To(42);
         match trans {
             Transition::To(value) => assert_eq!(value, 42),
-            Transition::None => panic!("Expected To"),
+            Transition::None
+            | Transition::Unhandled
+            | Transition::Reenter
+            | Transition::ToFn(_)
+            | Transition::ToAndPost(..)
+            | Transition::Retry
+            | Transition::Await(_) => {
+                panic!("Expected To")
+            }
+        }
+    }
+
+    #[test]
+    fn test_transition_unhandled_is_distinct_from_none() {
+        // Verify that Transition::Unhandled is its own variant
+        let trans: Transition<i32> = Transition::Unhandled;
+        match trans {
+            Transition::Unhandled => {} // Test passes if we reach this branch
+            Transition::None => panic!("Expected Unhandled, got None"),
+            Transition::Reenter => panic!("Expected Unhandled, got Reenter"),
+            Transition::To(_) => panic!("Expected Unhandled, got To"),
+            Transition::ToFn(_) => panic!("Expected Unhandled, got ToFn"),
+            Transition::ToAndPost(..) => panic!("Expected Unhandled, got ToAndPost"),
+            Transition::Retry => panic!("Expected Unhandled, got Retry"),
+            Transition::Await(_) => panic!("Expected Unhandled, got Await"),
+        }
+    }
+
+    #[test]
+    fn test_transition_reenter_is_distinct() {
+        // Verify that Transition::Reenter is its own variant
+        let trans: Transition<i32> = Transition::Reenter;
+        match trans {
+            Transition::Reenter => {} // Test passes if we reach this branch
+            Transition::None => panic!("Expected Reenter, got None"),
+            Transition::Unhandled => panic!("Expected Reenter, got Unhandled"),
+            Transition::To(_) => panic!("Expected Reenter, got To"),
+            Transition::ToFn(_) => panic!("Expected Reenter, got ToFn"),
+            Transition::ToAndPost(..) => panic!("Expected Reenter, got ToAndPost"),
+            Transition::Retry => panic!("Expected Reenter, got Retry"),
+            Transition::Await(_) => panic!("Expected Reenter, got Await"),
         }
     }
 
@@ -983,14 +8245,14 @@ mod tests {
         }
 
         // Test with simple variant
-        let trans = Transition::To(State::A);
+        let trans: Transition<State> = Transition::To(State::A);
         match trans {
             Transition::To(State::A) => {} // Test passes if we reach this branch
             _ => panic!("Expected State::A"),
         }
 
         // Test with variant carrying data
-        let trans = Transition::To(State::B { value: 100 });
+        let trans: Transition<State> = Transition::To(State::B { value: 100 });
         match trans {
             Transition::To(State::B { value }) => assert_eq!(value, 100),
             _ => panic!("Expected State::B"),
@@ -79,6 +79,9 @@
 //! assert_eq!(ctx.tick_count, 3); // Initial entry + 2 transitions
 //! ```
 //!
+//! `use typed_fsm::prelude::*;` imports the macros together with `Transition`,
+//! `DispatchOutcome` and the `Fsm` traits in one line (see [`prelude`]).
+//!
 //! ## More Complex Example: Light with Brightness
 //!
 //! States can handle multiple events and modify context:
@@ -263,6 +266,12 @@
 //! });
 //! ```
 //!
+//! Two locks must always be taken in the same order, or threads can deadlock. With the
+//! `std` feature, `FsmCell::new(fsm, ctx)` keeps both under a single lock and
+//! `cell.dispatch(event)` forwards to the machine. Unlike `lock().unwrap()`, it also
+//! survives a panicking hook: the poisoned lock is recovered, and
+//! `on_error_transition()` can move the machine to a safe state at that point.
+//!
 //! See `examples/traffic_intersection.rs` for a complete concurrent FSM example.
 //!
 //! **Note:** The core framework is `#![no_std]` compatible. Concurrency examples
@@ -301,14 +310,33 @@
 //! ### Important Limitations
 //!
 //! - **Queue capacity**: Fixed at 16 events. Events are silently dropped when queue is full.
+//!   On targets with an allocator, the `alloc` feature makes the queue unbounded instead.
 //! - **Shared statics**: All FSMs of the same type share global static variables (lock + queue).
 //!   This is normally not an issue as each FSM type has a unique name.
 //!
+//! ### Logging
+//!
+//! With `logging` or `defmt` also enabled, log lines are formatted while the dispatch is
+//! active, but never inside a critical section: those are held only to push to or pop
+//! from the queue. Interrupts stay enabled during formatting (events raised meanwhile
+//! are queued as usual), and a logger that takes a critical section of its own (e.g.,
+//! around an RTT or UART buffer) cannot deadlock against the dispatcher.
+//!
+//! ### Same State Values in Both Builds
+//!
+//! The feature only changes the generated methods and statics. The state machine enum
+//! itself is declared identically with and without `concurrent`: the same variants in
+//! the same order, the same fields, `Repr`, `Attrs` and `Derives`. A state value has the
+//! same size, alignment and fields in both builds, and with `Repr` the same
+//! discriminants, so code and stored values written against one build keep working
+//! when the feature is toggled. Rust leaves the layout of a non-`Repr` enum to the
+//! compiler, so persist states with `state_byte()` (or a `Repr` cast), not raw bytes.
+//!
 //! ### Usage
 //!
 //! ```toml
 //! [dependencies]
-//! typed-fsm = { version = "0.4", features = ["concurrent"] }
+//! typed-fsm = { version = "0.5", features = ["concurrent"] }
 //! ```
 //!
 //! ### Complete Examples
@@ -333,9 +361,100 @@
 
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 // The state_machine! macro is automatically available at the crate root
 // due to #[macro_export] in fsm.rs
 mod fsm;
 
 // Re-export the core types
-pub use fsm::Transition;
+pub use fsm::{
+    Bookkeeping, Dispatch, DispatchOutcome, Fsm, OverflowPolicy, ParseStateError, StaleGen,
+    Transition,
+};
+
+/// The items needed to define and drive state machines, for a single glob import.
+///
+/// ```rust
+/// use typed_fsm::prelude::*;
+///
+/// #[derive(Debug, Clone)]
+/// enum Event { Toggle }
+///
+/// state_machine! {
+///     Name: Switch,
+///     Event: Event,
+///     States: {
+///         Off => { process: |_evt| { Transition::To(Switch::On) } },
+///         On => { process: |_evt| { Switch::Off } }
+///     }
+/// }
+///
+/// let mut switch = Switch::start_in(Switch::Off);
+/// assert_eq!(switch.dispatch(&Event::Toggle), DispatchOutcome::Transitioned);
+/// assert_in_state!(switch, On);
+/// ```
+///
+/// Only the API used by nearly every state machine is included: the macros,
+/// `Transition` (which `process` blocks name unqualified), `DispatchOutcome`, the
+/// `Fsm` / `Dispatch` traits for generic drivers and, with `std`, `FsmCell`. Types
+/// for specific directives or features (e.g., `OverflowPolicy`, `OverflowReport`,
+/// `ParseStateError`) stay at the crate root. The traits' methods never shadow the
+/// generated inherent methods of the same name.
+pub mod prelude {
+    pub use crate::{assert_in_state, compose_fsms, state_machine};
+    pub use crate::{Dispatch, DispatchOutcome, Fsm, Transition};
+
+    #[cfg(feature = "std")]
+    pub use crate::FsmCell;
+}
+
+#[cfg(feature = "std")]
+pub use fsm::FsmCell;
+
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub use fsm::DispatchGuard;
+
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub use std::thread_local as __thread_local;
+
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub use std::vec::Vec as __Vec;
+
+#[doc(hidden)]
+#[cfg(target_has_atomic = "8")]
+pub use fsm::DeferredQueue;
+
+#[doc(hidden)]
+pub use fsm::validate_transition_table;
+
+#[doc(hidden)]
+pub use fsm::{edge_lookup, transitions_by_state, TransitionsByState};
+
+#[doc(hidden)]
+pub use fsm::IntoTransition;
+
+#[doc(hidden)]
+pub use fsm::atomic64;
+
+#[doc(hidden)]
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+pub use fsm::PathTrace;
+
+#[doc(hidden)]
+#[cfg(all(feature = "heapless", target_has_atomic = "8"))]
+pub use fsm::EventRecorder;
+
+#[cfg(feature = "concurrent")]
+pub use fsm::OverflowReport;
+
+#[doc(hidden)]
+#[cfg(feature = "concurrent")]
+pub use fsm::PendingQueue;
@@ -21,7 +21,7 @@
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
-use typed_fsm::{state_machine, Transition};
+use typed_fsm::{state_machine, DispatchOutcome, Transition};
 
 // ============================================================================
 // Test FSM Definition
@@ -1201,3 +1201,1196 @@ fn test_concurrent_custom_queue_capacity_small() {
 
     println!("Small queue (capacity 4) API verified");
 }
+
+// ============================================================================
+// Static memory footprint
+// ============================================================================
+
+struct FootprintContext {}
+
+state_machine! {
+    Name: FootprintFSM,
+    Context: FootprintContext,
+    Event: u32,
+    QueueCapacity: 8,
+    States: {
+        Idle => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_static_bytes() {
+    // At least the queued events plus three flags and the dropped counter
+    #[cfg(not(feature = "alloc"))]
+    assert!(
+        FootprintFSM::CONCURRENT_STATIC_BYTES
+            >= 8 * std::mem::size_of::<u32>() + 3 + std::mem::size_of::<usize>()
+    );
+
+    // 64-bit heapless queue: 8 * 4 (events) + 2 * 8 (front/back) + 1 (full), padded
+    // to 56, + 8 (RefCell flag) = 64; + 3 * 1 (AtomicBool: lock, frozen, paused)
+    // + 8 (AtomicUsize) = 75
+    #[cfg(all(target_pointer_width = "64", not(feature = "alloc")))]
+    assert_eq!(FootprintFSM::CONCURRENT_STATIC_BYTES, 75);
+}
+
+// ============================================================================
+// Overflow Report (consistent snapshot of queue + dropped counter)
+// ============================================================================
+
+// The unbounded `alloc` queue never overflows
+#[cfg(not(feature = "alloc"))]
+mod overflow_report {
+    use super::*;
+
+    struct ReportContext {
+        counter: u32,
+    }
+
+    #[derive(Debug, Clone)]
+    enum ReportEvent {
+        Inc,
+    }
+
+    state_machine! {
+        Name: OverflowReportFSM,
+        Context: ReportContext,
+        Event: ReportEvent,
+        QueueCapacity: 4,
+        States: {
+            Active => {
+                process: |ctx, evt| {
+                    match evt {
+                        ReportEvent::Inc => {
+                            ctx.counter += 1;
+                            Transition::None
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_overflow_report_consistent() {
+        use portable_atomic::Ordering;
+
+        let mut fsm = OverflowReportFSM::Active;
+        let mut ctx = ReportContext { counter: 0 };
+        fsm.init(&mut ctx);
+        OverflowReportFSM::reset_dropped_count();
+
+        let report = OverflowReportFSM::overflow_report();
+        assert_eq!(report.dropped, 0);
+        assert_eq!(report.capacity, 4);
+        assert_eq!(report.current_len, 0);
+
+        // Simulate an active dispatch (e.g., main loop interrupted by an ISR)
+        DISPATCH_ACTIVE_OVERFLOWREPORTFSM.store(true, Ordering::Release);
+
+        // Fill the queue, then overflow it twice
+        for _ in 0..4 {
+            fsm.dispatch(&mut ctx, &ReportEvent::Inc);
+        }
+        for _ in 0..2 {
+            // Debug builds panic on overflow (after counting the drop)
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                fsm.dispatch(&mut ctx, &ReportEvent::Inc);
+            }));
+        }
+
+        let report = OverflowReportFSM::overflow_report();
+        assert_eq!(report.dropped, 2);
+        assert_eq!(report.capacity, 4);
+        assert_eq!(report.current_len, 4);
+        assert_eq!(report.dropped, OverflowReportFSM::dropped_events_count());
+        assert!(report.current_len <= report.capacity);
+
+        // Release the simulated dispatch and drain the queue
+        DISPATCH_ACTIVE_OVERFLOWREPORTFSM.store(false, Ordering::Release);
+        fsm.dispatch(&mut ctx, &ReportEvent::Inc);
+        assert_eq!(ctx.counter, 5); // 1 immediate + 4 queued
+
+        let report = OverflowReportFSM::overflow_report();
+        assert_eq!(report.current_len, 0);
+        assert_eq!(report.dropped, 2);
+        OverflowReportFSM::reset_dropped_count();
+    }
+}
+
+// ============================================================================
+// Test: dispatch_owned() with a non-Clone event
+// ============================================================================
+
+struct OwnedContext {
+    received: Vec<u32>,
+}
+
+// Deliberately NOT Clone: only dispatch_owned() may be used
+#[derive(Debug)]
+enum OwnedEvent {
+    Payload(Box<[u32; 4]>),
+}
+
+state_machine! {
+    Name: OwnedEventFSM,
+    Context: OwnedContext,
+    Event: OwnedEvent,
+    QueueCapacity: 2,
+    States: {
+        Receiving => {
+            process: |ctx, evt| {
+                match evt {
+                    OwnedEvent::Payload(data) => {
+                        ctx.received.push(data[0]);
+                        Transition::None
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_dispatch_owned_moves_into_queue() {
+    use portable_atomic::Ordering;
+
+    let mut fsm = OwnedEventFSM::Receiving;
+    let mut ctx = OwnedContext {
+        received: Vec::new(),
+    };
+    fsm.init(&mut ctx);
+
+    // Simulate an active dispatch: owned events are moved into the queue
+    DISPATCH_ACTIVE_OWNEDEVENTFSM.store(true, Ordering::Release);
+    fsm.dispatch_owned(&mut ctx, OwnedEvent::Payload(Box::new([1; 4])));
+    fsm.dispatch_owned(&mut ctx, OwnedEvent::Payload(Box::new([2; 4])));
+    assert!(ctx.received.is_empty());
+    assert_eq!(OwnedEventFSM::overflow_report().current_len, 2);
+
+    // Release and dispatch again: immediate event first, then the queued ones
+    DISPATCH_ACTIVE_OWNEDEVENTFSM.store(false, Ordering::Release);
+    fsm.dispatch_owned(&mut ctx, OwnedEvent::Payload(Box::new([3; 4])));
+    assert_eq!(ctx.received, vec![3, 1, 2]);
+    assert_eq!(OwnedEventFSM::dropped_events_count(), 0);
+}
+
+// ============================================================================
+// Test: is_dispatch_active()
+// ============================================================================
+
+struct ActiveFlagContext {
+    seen_active: Vec<bool>,
+}
+
+#[derive(Debug, Clone)]
+enum ActiveFlagEvent {
+    Probe,
+}
+
+state_machine! {
+    Name: ActiveFlagFSM,
+    Context: ActiveFlagContext,
+    Event: ActiveFlagEvent,
+    States: {
+        Running => {
+            entry: |ctx| {
+                ctx.seen_active.push(ActiveFlagFSM::is_dispatch_active());
+            }
+            process: |ctx, evt| {
+                match evt {
+                    ActiveFlagEvent::Probe => {
+                        ctx.seen_active.push(ActiveFlagFSM::is_dispatch_active());
+                        Transition::None
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_is_dispatch_active() {
+    let mut fsm = ActiveFlagFSM::Running;
+    let mut ctx = ActiveFlagContext {
+        seen_active: Vec::new(),
+    };
+
+    // init() does not take the dispatch lock
+    fsm.init(&mut ctx);
+    assert!(!ActiveFlagFSM::is_dispatch_active());
+
+    // While process runs, the dispatch holds the lock
+    fsm.dispatch(&mut ctx, &ActiveFlagEvent::Probe);
+    fsm.dispatch_owned(&mut ctx, ActiveFlagEvent::Probe);
+    assert_eq!(ctx.seen_active, vec![false, true, true]);
+
+    // Released afterwards
+    assert!(!ActiveFlagFSM::is_dispatch_active());
+}
+
+// ============================================================================
+// Test: clear_queue() / peek_front()
+// ============================================================================
+
+struct StaleContext {
+    handled: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum StaleEvent {
+    Reading(u32),
+}
+
+state_machine! {
+    Name: StaleFSM,
+    Context: StaleContext,
+    Event: StaleEvent,
+    QueueCapacity: 8,
+    States: {
+        Sampling => {
+            process: |ctx, evt| {
+                match evt {
+                    StaleEvent::Reading(n) => {
+                        ctx.handled.push(*n);
+                        Transition::None
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_clear_queue_discards_pending_events() {
+    let mut fsm = StaleFSM::Sampling;
+    let mut ctx = StaleContext {
+        handled: Vec::new(),
+    };
+    fsm.init(&mut ctx);
+    StaleFSM::reset_dropped_count();
+
+    assert_eq!(StaleFSM::peek_front(), None);
+    for n in 1..=3 {
+        StaleFSM::post(StaleEvent::Reading(n)).unwrap();
+    }
+
+    // Peeking does not consume
+    assert_eq!(StaleFSM::peek_front(), Some(StaleEvent::Reading(1)));
+    assert_eq!(StaleFSM::overflow_report().current_len, 3);
+
+    assert_eq!(StaleFSM::clear_queue(), 3);
+    assert_eq!(StaleFSM::overflow_report().current_len, 0);
+    assert_eq!(StaleFSM::peek_front(), None);
+    assert_eq!(StaleFSM::clear_queue(), 0);
+
+    // Discarding is not an overflow
+    assert_eq!(StaleFSM::dropped_events_count(), 0);
+
+    // The stale readings are never processed
+    fsm.dispatch(&mut ctx, &StaleEvent::Reading(9));
+    assert_eq!(ctx.handled, vec![9]);
+}
+
+// ============================================================================
+// Test: freeze() / thaw()
+// ============================================================================
+
+struct IceContext {
+    handled: Vec<u32>,
+    // A second value of the same machine, dispatched from inside `process`
+    nested: Option<IceFSM>,
+    nested_outcome: Option<DispatchOutcome>,
+}
+
+#[derive(Debug, Clone)]
+enum IceEvent {
+    Reading(u32),
+    Nested(u32),
+}
+
+state_machine! {
+    Name: IceFSM,
+    Context: IceContext,
+    Event: IceEvent,
+    QueueCapacity: 4,
+    States: {
+        Sampling => {
+            process: |ctx, evt| {
+                match evt {
+                    IceEvent::Reading(n) => ctx.handled.push(*n),
+                    IceEvent::Nested(n) => {
+                        ctx.handled.push(*n);
+                        let mut nested = ctx.nested.take().unwrap();
+                        ctx.nested_outcome = Some(nested.dispatch(ctx, &IceEvent::Reading(*n + 1)));
+                    }
+                }
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_freeze_does_not_enqueue() {
+    let mut fsm = IceFSM::Sampling;
+    let mut ctx = IceContext {
+        handled: Vec::new(),
+        nested: Some(IceFSM::Sampling),
+        nested_outcome: None,
+    };
+    fsm.init(&mut ctx);
+
+    // Unfrozen, a dispatch during an active one is queued
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &IceEvent::Nested(1)),
+        DispatchOutcome::Stayed
+    );
+    assert_eq!(ctx.nested_outcome, Some(DispatchOutcome::Queued));
+    assert_eq!(ctx.handled, vec![1, 2]);
+
+    // Frozen, events are neither processed nor queued
+    IceFSM::freeze();
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &IceEvent::Reading(3)),
+        DispatchOutcome::Frozen
+    );
+    assert_eq!(
+        fsm.dispatch_owned(&mut ctx, IceEvent::Reading(4)),
+        DispatchOutcome::Frozen
+    );
+    assert_eq!(IceFSM::overflow_report().current_len, 0);
+    assert_eq!(ctx.handled, vec![1, 2]);
+
+    IceFSM::thaw();
+    fsm.dispatch(&mut ctx, &IceEvent::Reading(5));
+    assert_eq!(ctx.handled, vec![1, 2, 5]);
+}
+
+// ============================================================================
+// Test: step()
+// ============================================================================
+
+struct StepContext {
+    handled: Vec<u32>,
+    quiescent: u32,
+}
+
+#[derive(Debug, Clone)]
+enum StepEvent {
+    Job(u32),
+    Finish,
+}
+
+state_machine! {
+    Name: StepFSM,
+    Context: StepContext,
+    Event: StepEvent,
+    QueueCapacity: 8,
+    OnQuiescent: |ctx| { ctx.quiescent += 1; },
+    States: {
+        Working => {
+            process: |ctx, evt| {
+                match evt {
+                    StepEvent::Job(n) => {
+                        ctx.handled.push(*n);
+                        Transition::None
+                    }
+                    StepEvent::Finish => Transition::To(StepFSM::Done),
+                }
+            }
+        },
+        Done => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_step_processes_one_event_at_a_time() {
+    let mut fsm = StepFSM::Working;
+    let mut ctx = StepContext {
+        handled: Vec::new(),
+        quiescent: 0,
+    };
+    fsm.init(&mut ctx);
+
+    // Nothing queued: nothing to do
+    assert_eq!(fsm.step(&mut ctx), DispatchOutcome::Stayed);
+    assert_eq!(ctx.quiescent, 0);
+
+    for n in 1..=3 {
+        StepFSM::post(StepEvent::Job(n)).unwrap();
+    }
+    StepFSM::post(StepEvent::Finish).unwrap();
+
+    assert_eq!(fsm.step(&mut ctx), DispatchOutcome::Stayed);
+    assert_eq!(ctx.handled, vec![1]);
+    assert_eq!(StepFSM::overflow_report().current_len, 3);
+    assert!(!StepFSM::is_dispatch_active());
+
+    assert_eq!(fsm.step(&mut ctx), DispatchOutcome::Stayed);
+    assert_eq!(fsm.step(&mut ctx), DispatchOutcome::Stayed);
+    assert_eq!(ctx.handled, vec![1, 2, 3]);
+    assert_eq!(ctx.quiescent, 0);
+
+    // The last queued event empties the queue
+    assert_eq!(fsm.step(&mut ctx), DispatchOutcome::Transitioned);
+    assert!(matches!(fsm, StepFSM::Done));
+    assert_eq!(ctx.quiescent, 1);
+    assert_eq!(StepFSM::overflow_report().current_len, 0);
+}
+
+// ============================================================================
+// Test: dispatch_batch()
+// ============================================================================
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static BATCH_LOG: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+static BATCH_STARTED: AtomicBool = AtomicBool::new(false);
+
+struct BatchContext {}
+
+#[derive(Debug, Clone)]
+enum BatchEvent {
+    Write(u32),
+}
+
+state_machine! {
+    Name: BatchFSM,
+    Context: BatchContext,
+    Event: BatchEvent,
+    QueueCapacity: 8,
+    States: {
+        Ready => {
+            process: |_ctx, evt| {
+                let BatchEvent::Write(n) = evt;
+                BATCH_STARTED.store(true, Ordering::SeqCst);
+                // Leave time for the other thread to try to slip in
+                thread::sleep(Duration::from_millis(10));
+                BATCH_LOG.lock().unwrap().push(*n);
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_dispatch_batch_is_not_interleaved() {
+    let writer = thread::spawn(|| {
+        let mut ctx = BatchContext {};
+        let mut fsm = BatchFSM::Ready;
+        fsm.init(&mut ctx);
+        fsm.dispatch_batch(
+            &mut ctx,
+            &[
+                BatchEvent::Write(1),
+                BatchEvent::Write(2),
+                BatchEvent::Write(3),
+            ],
+        );
+    });
+
+    // Dispatch from another thread while the batch is running
+    while !BATCH_STARTED.load(Ordering::SeqCst) {
+        thread::yield_now();
+    }
+    let mut ctx = BatchContext {};
+    let mut fsm = BatchFSM::Ready;
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &BatchEvent::Write(99)),
+        DispatchOutcome::Queued
+    );
+
+    writer.join().unwrap();
+    assert_eq!(*BATCH_LOG.lock().unwrap(), vec![1, 2, 3, 99]);
+}
+
+// ============================================================================
+// Test: Unbounded queue (feature `alloc`)
+// ============================================================================
+
+#[cfg(feature = "alloc")]
+mod unbounded_queue {
+    use super::*;
+    use portable_atomic::Ordering;
+
+    struct UnboundedContext {
+        handled: u32,
+    }
+
+    #[derive(Debug, Clone)]
+    enum UnboundedEvent {
+        Tick,
+    }
+
+    state_machine! {
+        Name: UnboundedFSM,
+        Context: UnboundedContext,
+        Event: UnboundedEvent,
+        QueueCapacity: 4,  // Ignored by the `alloc` queue
+
+        States: {
+            Active => {
+                process: |ctx, _evt| {
+                    ctx.handled += 1;
+                    Transition::None
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_alloc_queue_drops_nothing_under_contention() {
+        const THREADS: usize = 8;
+        const EVENTS: usize = 500;
+
+        UnboundedFSM::reset_dropped_count();
+
+        // Keep a dispatch active so every event from the threads is queued
+        DISPATCH_ACTIVE_UNBOUNDEDFSM.store(true, Ordering::Release);
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                thread::spawn(|| {
+                    let mut ctx = UnboundedContext { handled: 0 };
+                    let mut fsm = UnboundedFSM::Active;
+                    for _ in 0..EVENTS {
+                        assert_eq!(
+                            fsm.dispatch(&mut ctx, &UnboundedEvent::Tick),
+                            DispatchOutcome::Queued
+                        );
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let report = UnboundedFSM::overflow_report();
+        assert_eq!(report.dropped, 0);
+        assert_eq!(report.current_len, THREADS * EVENTS);
+        assert_eq!(report.capacity, usize::MAX);
+
+        // Release the simulated dispatch: the next one drains everything
+        DISPATCH_ACTIVE_UNBOUNDEDFSM.store(false, Ordering::Release);
+        let mut ctx = UnboundedContext { handled: 0 };
+        let mut fsm = UnboundedFSM::Active;
+        fsm.dispatch(&mut ctx, &UnboundedEvent::Tick);
+
+        assert_eq!(ctx.handled as usize, THREADS * EVENTS + 1);
+        assert_eq!(UnboundedFSM::dropped_events_count(), 0);
+    }
+}
+
+// ============================================================================
+// Test: OverflowPolicy directive (which events survive a full queue)
+// ============================================================================
+
+// The unbounded `alloc` queue never overflows
+#[cfg(not(feature = "alloc"))]
+mod overflow_policy {
+    use super::*;
+    use portable_atomic::Ordering;
+    use typed_fsm::OverflowPolicy;
+
+    struct SeenContext {
+        seen: Vec<u32>,
+    }
+
+    #[derive(Debug, Clone)]
+    enum SeenEvent {
+        Num(u32),
+    }
+
+    state_machine! {
+        Name: DropNewestFSM,
+        Context: SeenContext,
+        Event: SeenEvent,
+        QueueCapacity: 3,
+        States: {
+            Active => {
+                process: |ctx, evt| {
+                    match evt {
+                        SeenEvent::Num(n) => {
+                            ctx.seen.push(*n);
+                            Transition::None
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    state_machine! {
+        Name: DropOldestFSM,
+        Context: SeenContext,
+        Event: SeenEvent,
+        QueueCapacity: 3,
+        OverflowPolicy: DropOldest,
+        States: {
+            Active => {
+                process: |ctx, evt| {
+                    match evt {
+                        SeenEvent::Num(n) => {
+                            ctx.seen.push(*n);
+                            Transition::None
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    state_machine! {
+        Name: PanicPolicyFSM,
+        Context: SeenContext,
+        Event: SeenEvent,
+        QueueCapacity: 3,
+        OverflowPolicy: Panic,
+        States: {
+            Active => {
+                process: |ctx, evt| {
+                    match evt {
+                        SeenEvent::Num(n) => {
+                            ctx.seen.push(*n);
+                            Transition::None
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_overflow_policy_drop_newest_keeps_first_events() {
+        assert_eq!(DropNewestFSM::OVERFLOW_POLICY, OverflowPolicy::DropNewest);
+
+        let mut fsm = DropNewestFSM::Active;
+        let mut ctx = SeenContext { seen: Vec::new() };
+        fsm.init(&mut ctx);
+        DropNewestFSM::reset_dropped_count();
+
+        DISPATCH_ACTIVE_DROPNEWESTFSM.store(true, Ordering::Release);
+        for n in 1..=5 {
+            // Debug builds panic on overflow (after dropping the new event)
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                fsm.dispatch(&mut ctx, &SeenEvent::Num(n));
+            }));
+        }
+        assert_eq!(DropNewestFSM::dropped_events_count(), 2);
+
+        DISPATCH_ACTIVE_DROPNEWESTFSM.store(false, Ordering::Release);
+        fsm.dispatch(&mut ctx, &SeenEvent::Num(6));
+        assert_eq!(ctx.seen, vec![6, 1, 2, 3]);
+        DropNewestFSM::reset_dropped_count();
+    }
+
+    #[test]
+    fn test_overflow_policy_drop_oldest_keeps_latest_events() {
+        assert_eq!(DropOldestFSM::OVERFLOW_POLICY, OverflowPolicy::DropOldest);
+
+        let mut fsm = DropOldestFSM::Active;
+        let mut ctx = SeenContext { seen: Vec::new() };
+        fsm.init(&mut ctx);
+        DropOldestFSM::reset_dropped_count();
+
+        // Never panics, not even in debug builds
+        DISPATCH_ACTIVE_DROPOLDESTFSM.store(true, Ordering::Release);
+        for n in 1..=5 {
+            assert_eq!(
+                fsm.dispatch(&mut ctx, &SeenEvent::Num(n)),
+                DispatchOutcome::Queued
+            );
+        }
+        assert_eq!(DropOldestFSM::dropped_events_count(), 2);
+        assert_eq!(DropOldestFSM::overflow_report().current_len, 3);
+
+        DISPATCH_ACTIVE_DROPOLDESTFSM.store(false, Ordering::Release);
+        fsm.dispatch(&mut ctx, &SeenEvent::Num(6));
+        assert_eq!(ctx.seen, vec![6, 3, 4, 5]);
+        DropOldestFSM::reset_dropped_count();
+    }
+
+    #[test]
+    fn test_overflow_policy_panic_panics_on_overflow() {
+        assert_eq!(PanicPolicyFSM::OVERFLOW_POLICY, OverflowPolicy::Panic);
+
+        let mut fsm = PanicPolicyFSM::Active;
+        let mut ctx = SeenContext { seen: Vec::new() };
+        fsm.init(&mut ctx);
+        PanicPolicyFSM::reset_dropped_count();
+
+        DISPATCH_ACTIVE_PANICPOLICYFSM.store(true, Ordering::Release);
+        for n in 1..=3 {
+            fsm.dispatch(&mut ctx, &SeenEvent::Num(n));
+        }
+        // Panics in every build, also through post()
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            fsm.dispatch(&mut ctx, &SeenEvent::Num(4));
+        }));
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(|| {
+            let _ = PanicPolicyFSM::post(SeenEvent::Num(5));
+        });
+        assert!(result.is_err());
+        assert_eq!(PanicPolicyFSM::dropped_events_count(), 2);
+
+        DISPATCH_ACTIVE_PANICPOLICYFSM.store(false, Ordering::Release);
+        fsm.dispatch(&mut ctx, &SeenEvent::Num(6));
+        assert_eq!(ctx.seen, vec![6, 1, 2, 3]);
+        PanicPolicyFSM::reset_dropped_count();
+    }
+}
+
+// ============================================================================
+// Test: DroppedBuffer directive (which events a full queue dropped)
+// ============================================================================
+
+// The unbounded `alloc` queue never overflows
+#[cfg(not(feature = "alloc"))]
+mod dropped_buffer {
+    use super::*;
+    use portable_atomic::Ordering;
+
+    struct LossContext {
+        seen: Vec<u32>,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum LossEvent {
+        Num(u32),
+    }
+
+    state_machine! {
+        Name: LossNewestFSM,
+        Context: LossContext,
+        Event: LossEvent,
+        QueueCapacity: 3,
+        DroppedBuffer: 2,
+        States: {
+            Active => {
+                process: |ctx, evt| {
+                    match evt {
+                        LossEvent::Num(n) => {
+                            ctx.seen.push(*n);
+                            Transition::None
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    state_machine! {
+        Name: LossOldestFSM,
+        Context: LossContext,
+        Event: LossEvent,
+        QueueCapacity: 3,
+        OverflowPolicy: DropOldest,
+        DroppedBuffer: 4,
+        States: {
+            Active => {
+                process: |ctx, evt| {
+                    match evt {
+                        LossEvent::Num(n) => {
+                            ctx.seen.push(*n);
+                            Transition::None
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_dropped_buffer_keeps_latest_overflowed_events() {
+        let mut fsm = LossNewestFSM::Active;
+        let mut ctx = LossContext { seen: Vec::new() };
+        fsm.init(&mut ctx);
+        LossNewestFSM::reset_dropped_count();
+        assert!(LossNewestFSM::drained_dropped_events().is_empty());
+
+        // 1..=3 fill the queue; 4, 5 and 6 are handed back, only the last 2 are kept
+        for n in 1..=6 {
+            let _ = LossNewestFSM::post(LossEvent::Num(n));
+        }
+        assert_eq!(LossNewestFSM::dropped_events_count(), 3);
+        let dropped: Vec<_> = LossNewestFSM::drained_dropped_events()
+            .into_iter()
+            .collect();
+        assert_eq!(dropped, vec![LossEvent::Num(5), LossEvent::Num(6)]);
+
+        // Draining empties the store, not the counter
+        assert!(LossNewestFSM::drained_dropped_events().is_empty());
+        assert_eq!(LossNewestFSM::dropped_events_count(), 3);
+
+        fsm.dispatch(&mut ctx, &LossEvent::Num(7));
+        assert_eq!(ctx.seen, vec![7, 1, 2, 3]);
+        LossNewestFSM::reset_dropped_count();
+    }
+
+    #[test]
+    fn test_dropped_buffer_with_drop_oldest() {
+        let mut fsm = LossOldestFSM::Active;
+        let mut ctx = LossContext { seen: Vec::new() };
+        fsm.init(&mut ctx);
+        LossOldestFSM::reset_dropped_count();
+
+        // The evicted oldest events are the ones stored
+        DISPATCH_ACTIVE_LOSSOLDESTFSM.store(true, Ordering::Release);
+        for n in 1..=5 {
+            assert_eq!(
+                fsm.dispatch(&mut ctx, &LossEvent::Num(n)),
+                DispatchOutcome::Queued
+            );
+        }
+        let dropped: Vec<_> = LossOldestFSM::drained_dropped_events()
+            .into_iter()
+            .collect();
+        assert_eq!(dropped, vec![LossEvent::Num(1), LossEvent::Num(2)]);
+
+        DISPATCH_ACTIVE_LOSSOLDESTFSM.store(false, Ordering::Release);
+        fsm.dispatch(&mut ctx, &LossEvent::Num(6));
+        assert_eq!(ctx.seen, vec![6, 3, 4, 5]);
+        LossOldestFSM::reset_dropped_count();
+    }
+
+    #[test]
+    fn test_dropped_buffer_counts_in_static_bytes() {
+        type Store<const N: usize> =
+            critical_section::Mutex<std::cell::RefCell<heapless::Deque<LossEvent, N>>>;
+
+        // Same queue, so only the stores differ
+        assert_eq!(
+            LossNewestFSM::CONCURRENT_STATIC_BYTES - std::mem::size_of::<Store<2>>(),
+            LossOldestFSM::CONCURRENT_STATIC_BYTES - std::mem::size_of::<Store<4>>()
+        );
+    }
+}
+
+// ============================================================================
+// Test: force_release_lock() after a panic left the dispatch lock held
+// ============================================================================
+
+struct RecoveryContext {
+    handled: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+enum RecoveryEvent {
+    Boom,
+    Num(u32),
+}
+
+state_machine! {
+    Name: RecoveryFSM,
+    Context: RecoveryContext,
+    Event: RecoveryEvent,
+    States: {
+        Active => {
+            process: |ctx, evt| {
+                match evt {
+                    RecoveryEvent::Boom => panic!("hook failure"),
+                    RecoveryEvent::Num(n) => {
+                        ctx.handled.push(*n);
+                        Transition::None
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_force_release_lock_recovers_after_panic() {
+    let mut fsm = RecoveryFSM::Active;
+    let mut ctx = RecoveryContext {
+        handled: Vec::new(),
+    };
+    fsm.init(&mut ctx);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        fsm.dispatch(&mut ctx, &RecoveryEvent::Boom);
+    }));
+    assert!(result.is_err());
+
+    // The unwinding dispatch never released the lock: events only queue up
+    assert!(RecoveryFSM::is_dispatch_active());
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &RecoveryEvent::Num(1)),
+        DispatchOutcome::Queued
+    );
+    assert!(ctx.handled.is_empty());
+
+    RecoveryFSM::force_release_lock();
+    assert!(!RecoveryFSM::is_dispatch_active());
+
+    // Normal dispatch again, draining the event queued meanwhile
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &RecoveryEvent::Num(2)),
+        DispatchOutcome::Stayed
+    );
+    assert_eq!(ctx.handled, vec![2, 1]);
+    assert!(!RecoveryFSM::is_dispatch_active());
+}
+
+// ============================================================================
+// Test: run_to_idle()
+// ============================================================================
+
+struct IdleContext {
+    handled: Vec<u32>,
+    quiescent: u32,
+}
+
+#[derive(Debug, Clone)]
+enum IdleEvent {
+    // Countdown: `Job(n)` posts `Job(n - 1)` until 0
+    Job(u32),
+}
+
+state_machine! {
+    Name: IdleFSM,
+    Context: IdleContext,
+    Event: IdleEvent,
+    QueueCapacity: 8,
+    OnQuiescent: |ctx| { ctx.quiescent += 1; },
+    States: {
+        Working => {
+            process: |ctx, evt| {
+                let IdleEvent::Job(n) = evt;
+                ctx.handled.push(*n);
+                if *n > 0 {
+                    IdleFSM::post(IdleEvent::Job(n - 1)).unwrap();
+                }
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_run_to_idle_drains_queue_and_counts() {
+    let mut fsm = IdleFSM::Working;
+    let mut ctx = IdleContext {
+        handled: Vec::new(),
+        quiescent: 0,
+    };
+    fsm.init(&mut ctx);
+
+    // Nothing queued: nothing to do, and no quiescent notification
+    assert_eq!(fsm.run_to_idle(&mut ctx), 0);
+    assert_eq!(ctx.quiescent, 0);
+
+    IdleFSM::post(IdleEvent::Job(2)).unwrap();
+    IdleFSM::post(IdleEvent::Job(0)).unwrap();
+
+    // Two queued events, plus the two posted while processing them
+    assert_eq!(fsm.run_to_idle(&mut ctx), 4);
+    assert_eq!(ctx.handled, vec![2, 0, 1, 0]);
+    assert_eq!(ctx.quiescent, 1);
+    assert_eq!(IdleFSM::overflow_report().current_len, 0);
+    assert!(!IdleFSM::is_dispatch_active());
+
+    // Frozen: the queue is left untouched
+    IdleFSM::post(IdleEvent::Job(0)).unwrap();
+    IdleFSM::freeze();
+    assert_eq!(fsm.run_to_idle(&mut ctx), 0);
+    IdleFSM::thaw();
+    assert_eq!(fsm.run_to_idle(&mut ctx), 1);
+    assert_eq!(ctx.quiescent, 2);
+}
+
+// ============================================================================
+// Test: pause_queue() / resume_queue()
+// ============================================================================
+
+struct ReconfigContext {
+    applied: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+enum ReconfigEvent {
+    Apply(u32),
+}
+
+state_machine! {
+    Name: ReconfigFSM,
+    Context: ReconfigContext,
+    Event: ReconfigEvent,
+    QueueCapacity: 8,
+    States: {
+        Running => {
+            process: |ctx, evt| {
+                let ReconfigEvent::Apply(n) = evt;
+                ctx.applied.push(*n);
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_concurrent_pause_queue_defers_events_until_resume() {
+    let mut fsm = ReconfigFSM::Running;
+    let mut ctx = ReconfigContext {
+        applied: Vec::new(),
+    };
+    fsm.init(&mut ctx);
+
+    ReconfigFSM::pause_queue();
+    assert!(ReconfigFSM::is_queue_paused());
+
+    // The lock is free, but every dispatch only enqueues its event
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &ReconfigEvent::Apply(1)),
+        DispatchOutcome::Queued
+    );
+    assert_eq!(
+        fsm.dispatch_owned(&mut ctx, ReconfigEvent::Apply(2)),
+        DispatchOutcome::Queued
+    );
+    fsm.dispatch_batch(
+        &mut ctx,
+        &[ReconfigEvent::Apply(3), ReconfigEvent::Apply(4)],
+    );
+    assert_eq!(fsm.step(&mut ctx), DispatchOutcome::Queued);
+    assert_eq!(fsm.run_to_idle(&mut ctx), 0);
+    assert!(ctx.applied.is_empty());
+    assert_eq!(ReconfigFSM::overflow_report().current_len, 4);
+    assert_eq!(ReconfigFSM::dropped_events_count(), 0);
+
+    // Resuming drains everything, in dispatch order
+    assert_eq!(fsm.resume_queue(&mut ctx), 4);
+    assert!(!ReconfigFSM::is_queue_paused());
+    assert_eq!(ctx.applied, vec![1, 2, 3, 4]);
+    assert!(!ReconfigFSM::is_dispatch_active());
+
+    fsm.dispatch(&mut ctx, &ReconfigEvent::Apply(5));
+    assert_eq!(ctx.applied, vec![1, 2, 3, 4, 5]);
+}
+
+// ============================================================================
+// Test: Deterministic queue tests through the `test-internals` hooks
+// ============================================================================
+
+#[cfg(feature = "test-internals")]
+mod queue_internals {
+    use super::*;
+
+    struct LineContext {
+        handled: Vec<u32>,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum LineEvent {
+        Byte(u32),
+    }
+
+    state_machine! {
+        Name: LineFSM,
+        Context: LineContext,
+        Event: LineEvent,
+        QueueCapacity: 3,
+        States: {
+            Receiving => {
+                process: |ctx, evt| {
+                    let LineEvent::Byte(byte) = evt;
+                    ctx.handled.push(*byte);
+                    Transition::None
+                }
+            }
+        }
+    }
+
+    state_machine! {
+        Name: LatestLineFSM,
+        Context: LineContext,
+        Event: LineEvent,
+        QueueCapacity: 3,
+        OverflowPolicy: DropOldest,
+        States: {
+            Receiving => {
+                process: |ctx, evt| {
+                    let LineEvent::Byte(byte) = evt;
+                    ctx.handled.push(*byte);
+                    Transition::None
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_internals_queue_while_dispatch_active_then_drain_fifo() {
+        LineFSM::__test_reset();
+        let mut ctx = LineContext {
+            handled: Vec::new(),
+        };
+        let mut fsm = LineFSM::start_in(LineFSM::Receiving, &mut ctx);
+
+        // Without threads: pretend an ISR interrupted an active dispatch
+        LineFSM::__test_set_dispatch_active(true);
+        for byte in [1, 2, 3] {
+            assert_eq!(
+                fsm.dispatch(&mut ctx, &LineEvent::Byte(byte)),
+                DispatchOutcome::Queued
+            );
+        }
+        assert_eq!(LineFSM::__test_queue_len(), 3);
+        assert!(ctx.handled.is_empty());
+
+        // The interrupted dispatch finishes: the queue drains in FIFO order
+        LineFSM::__test_set_dispatch_active(false);
+        assert_eq!(fsm.run_to_idle(&mut ctx), 3);
+        assert_eq!(ctx.handled, vec![1, 2, 3]);
+        assert_eq!(LineFSM::__test_queue_len(), 0);
+        assert!(!LineFSM::is_dispatch_active());
+    }
+
+    // The unbounded `alloc` queue never overflows
+    #[cfg(not(feature = "alloc"))]
+    #[test]
+    fn test_internals_overflow_drop_newest() {
+        LineFSM::__test_reset();
+        for byte in [1, 2, 3] {
+            assert!(LineFSM::__test_enqueue(LineEvent::Byte(byte)).is_ok());
+        }
+        assert_eq!(
+            LineFSM::__test_enqueue(LineEvent::Byte(4)),
+            Err(LineEvent::Byte(4))
+        );
+        assert_eq!(LineFSM::dropped_events_count(), 1);
+
+        // The oldest events survived, in order
+        assert_eq!(LineFSM::__test_pop(), Some(LineEvent::Byte(1)));
+        assert_eq!(LineFSM::__test_pop(), Some(LineEvent::Byte(2)));
+        assert_eq!(LineFSM::__test_pop(), Some(LineEvent::Byte(3)));
+        assert_eq!(LineFSM::__test_pop(), None);
+
+        LineFSM::__test_reset();
+        assert_eq!(LineFSM::dropped_events_count(), 0);
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    #[test]
+    fn test_internals_overflow_drop_oldest() {
+        LatestLineFSM::__test_reset();
+        for byte in [1, 2, 3, 4, 5] {
+            assert!(LatestLineFSM::__test_enqueue(LineEvent::Byte(byte)).is_ok());
+        }
+        assert_eq!(LatestLineFSM::dropped_events_count(), 2);
+        assert_eq!(LatestLineFSM::__test_queue_len(), 3);
+
+        // The newest events survived, and drain in order
+        let mut ctx = LineContext {
+            handled: Vec::new(),
+        };
+        let mut fsm = LatestLineFSM::start_in(LatestLineFSM::Receiving, &mut ctx);
+        assert_eq!(fsm.run_to_idle(&mut ctx), 3);
+        assert_eq!(ctx.handled, vec![3, 4, 5]);
+    }
+}
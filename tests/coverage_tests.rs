@@ -732,3 +732,80 @@ fn test_minimal_state_only_process() {
     fsm.dispatch(&mut ctx, &MinimalEvent::Trigger);
     assert!(ctx.processed);
 }
+
+// ============================================================================
+// Test 11: Readonly state (process receives a shared context reference)
+// ============================================================================
+
+#[derive(Debug, Clone)]
+struct ReadonlyContext {
+    failures: u32,
+    max_failures: u32,
+}
+
+#[derive(Debug, Clone)]
+enum ReadonlyEvent {
+    Fail,
+    Retry,
+}
+
+state_machine! {
+    Name: ReadonlyFSM,
+    Context: ReadonlyContext,
+    Event: ReadonlyEvent,
+
+    States: {
+        Working => {
+            process: |ctx, evt| {
+                match evt {
+                    ReadonlyEvent::Fail => {
+                        ctx.failures += 1;
+                        Transition::To(ReadonlyFSM::Failed)
+                    }
+                    ReadonlyEvent::Retry => Transition::None
+                }
+            }
+        },
+
+        Failed => readonly {
+            entry: |ctx| {
+                // Entry still receives a mutable context
+                ctx.max_failures = ctx.max_failures.max(1);
+            }
+
+            process: |ctx, evt| {
+                // Explicit type annotation proves the binding is immutable
+                let shared: &ReadonlyContext = ctx;
+                match evt {
+                    ReadonlyEvent::Retry if shared.failures < shared.max_failures => {
+                        Transition::To(ReadonlyFSM::Working)
+                    }
+                    _ => Transition::None
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_readonly_state_reads_context() {
+    let mut ctx = ReadonlyContext {
+        failures: 0,
+        max_failures: 2,
+    };
+    let mut fsm = ReadonlyFSM::Working;
+    fsm.init(&mut ctx);
+
+    // First failure: retry allowed
+    fsm.dispatch(&mut ctx, &ReadonlyEvent::Fail);
+    assert!(matches!(fsm, ReadonlyFSM::Failed));
+    fsm.dispatch(&mut ctx, &ReadonlyEvent::Retry);
+    assert!(matches!(fsm, ReadonlyFSM::Working));
+
+    // Second failure: retry refused, context untouched by the readonly state
+    fsm.dispatch(&mut ctx, &ReadonlyEvent::Fail);
+    fsm.dispatch(&mut ctx, &ReadonlyEvent::Retry);
+    fsm.dispatch(&mut ctx, &ReadonlyEvent::Fail);
+    assert!(matches!(fsm, ReadonlyFSM::Failed));
+    assert_eq!(ctx.failures, 2);
+}
@@ -0,0 +1,237 @@
+//! Tests for posted (deferred) events
+//!
+//! This test suite validates `post()` and run-to-completion semantics, in both
+//! the default build and with the `concurrent` feature:
+//! - Events posted during a dispatch are processed afterwards, in FIFO order
+//! - Events posted outside a dispatch wait for the next dispatch
+//! - A full queue hands the event back and increments `dropped_events_count()`
+//! - With the `alloc` feature the concurrent queue is unbounded and never hands events back
+//! - `OnQuiescent` fires once per dispatch, after the queue is fully drained
+
+use typed_fsm::{state_machine, Transition};
+
+// ============================================================================
+// Test 1: Event Chaining
+// ============================================================================
+
+struct ChainContext {
+    log: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum ChainEvent {
+    Start,
+    Step(u32),
+}
+
+state_machine! {
+    Name: ChainFSM,
+    Context: ChainContext,
+    Event: ChainEvent,
+    QueueCapacity: 4,
+
+    States: {
+        Idle => {
+            process: |ctx, evt| {
+                match evt {
+                    ChainEvent::Start => {
+                        ChainFSM::post(ChainEvent::Step(1)).unwrap();
+                        ChainFSM::post(ChainEvent::Step(2)).unwrap();
+                        ctx.log.push("start".to_string());
+                        Transition::To(ChainFSM::Working)
+                    }
+                    ChainEvent::Step(n) => {
+                        ctx.log.push(format!("idle step {}", n));
+                        Transition::None
+                    }
+                }
+            }
+        },
+
+        Working => {
+            entry: |ctx| {
+                ctx.log.push("working".to_string());
+            }
+
+            process: |ctx, evt| {
+                match evt {
+                    ChainEvent::Step(n) => {
+                        ctx.log.push(format!("step {}", n));
+                        Transition::None
+                    }
+                    ChainEvent::Start => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_posted_events_run_after_current_event() {
+    let mut ctx = ChainContext { log: Vec::new() };
+    let mut fsm = ChainFSM::Idle;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &ChainEvent::Start);
+
+    // The transition to Working completes before the posted steps are handled
+    assert!(matches!(fsm, ChainFSM::Working));
+    assert_eq!(ctx.log, vec!["start", "working", "step 1", "step 2"]);
+    assert_eq!(ChainFSM::dropped_events_count(), 0);
+
+    // Posted outside a dispatch: waits for (and runs after) the next dispatched event
+    // (kept in this test because the queue is shared by every ChainFSM instance)
+    ChainFSM::post(ChainEvent::Step(7)).unwrap();
+    assert_eq!(ctx.log.len(), 4);
+
+    fsm.dispatch(&mut ctx, &ChainEvent::Step(8));
+    assert_eq!(ctx.log[4..], ["step 8", "step 7"]);
+}
+
+// ============================================================================
+// Test 2: Deferred Queue Overflow
+// ============================================================================
+
+struct BurstContext {
+    rejected: Vec<u32>,
+    handled: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+enum BurstEvent {
+    Burst(u32),
+    Item(u32),
+}
+
+state_machine! {
+    Name: BurstFSM,
+    Context: BurstContext,
+    Event: BurstEvent,
+    QueueCapacity: 2,
+
+    States: {
+        Ready => {
+            process: |ctx, evt| {
+                match evt {
+                    BurstEvent::Burst(count) => {
+                        for i in 0..*count {
+                            if let Err(BurstEvent::Item(n)) = BurstFSM::post(BurstEvent::Item(i)) {
+                                ctx.rejected.push(n);
+                            }
+                        }
+                    }
+                    BurstEvent::Item(n) => ctx.handled.push(*n),
+                }
+                Transition::None
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+#[test]
+fn test_deferred_queue_overflow() {
+    let mut ctx = BurstContext {
+        rejected: Vec::new(),
+        handled: Vec::new(),
+    };
+    let mut fsm = BurstFSM::Ready;
+    fsm.init(&mut ctx);
+    BurstFSM::reset_dropped_count();
+
+    fsm.dispatch(&mut ctx, &BurstEvent::Burst(5));
+
+    // Capacity 2: the first two are processed, the remaining three are handed back
+    assert_eq!(ctx.handled, vec![0, 1]);
+    assert_eq!(ctx.rejected, vec![2, 3, 4]);
+    assert_eq!(BurstFSM::dropped_events_count(), 3);
+
+    // The queue drained, so there is room again
+    fsm.dispatch(&mut ctx, &BurstEvent::Burst(2));
+    assert_eq!(ctx.handled, vec![0, 1, 0, 1]);
+    assert_eq!(BurstFSM::dropped_events_count(), 3);
+
+    BurstFSM::reset_dropped_count();
+    assert_eq!(BurstFSM::dropped_events_count(), 0);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_deferred_queue_unbounded() {
+    let mut ctx = BurstContext {
+        rejected: Vec::new(),
+        handled: Vec::new(),
+    };
+    let mut fsm = BurstFSM::Ready;
+    fsm.init(&mut ctx);
+    BurstFSM::reset_dropped_count();
+
+    // `QueueCapacity: 2` is ignored: every posted event is processed
+    fsm.dispatch(&mut ctx, &BurstEvent::Burst(5));
+    assert_eq!(ctx.handled, vec![0, 1, 2, 3, 4]);
+    assert!(ctx.rejected.is_empty());
+    assert_eq!(BurstFSM::dropped_events_count(), 0);
+}
+
+// ============================================================================
+// Test 3: OnQuiescent
+// ============================================================================
+
+struct QuietContext {
+    handled: u32,
+    // `handled` as seen by each OnQuiescent call
+    quiescent_at: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+enum QuietEvent {
+    Burst(u32),
+    Item,
+}
+
+state_machine! {
+    Name: QuietFSM,
+    Context: QuietContext,
+    Event: QuietEvent,
+    QueueCapacity: 8,
+    OnQuiescent: |ctx| {
+        ctx.quiescent_at.push(ctx.handled);
+    },
+
+    States: {
+        Ready => {
+            process: |ctx, evt| {
+                match evt {
+                    QuietEvent::Burst(count) => {
+                        for _ in 0..*count {
+                            QuietFSM::post(QuietEvent::Item).unwrap();
+                        }
+                    }
+                    QuietEvent::Item => ctx.handled += 1,
+                }
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_on_quiescent_fires_once_after_drain() {
+    let mut ctx = QuietContext {
+        handled: 0,
+        quiescent_at: Vec::new(),
+    };
+    let mut fsm = QuietFSM::Ready;
+
+    // init() does not dispatch
+    fsm.init(&mut ctx);
+    assert!(ctx.quiescent_at.is_empty());
+
+    // Once, after all five queued items
+    fsm.dispatch(&mut ctx, &QuietEvent::Burst(5));
+    assert_eq!(ctx.quiescent_at, vec![5]);
+
+    // Once per dispatch, even with nothing queued
+    fsm.dispatch(&mut ctx, &QuietEvent::Item);
+    assert_eq!(ctx.quiescent_at, vec![5, 6]);
+}
@@ -0,0 +1,133 @@
+//! Tests for the `defmt` logging backend
+//!
+//! This test suite validates that the `defmt` feature routes FSM logging through
+//! `defmt::info!` (capturing the encoded frames with a test global logger):
+//! - Logging compiles with the 'defmt' feature
+//! - `init()`, transitions and "stayed" dispatches emit defmt frames
+//! - Nothing is emitted when `DEFMT_LOG` filters out the `info` level
+//!
+//! defmt requires a global logger in every binary, and `logging` takes precedence
+//! when both features are enabled, so run this suite on its own:
+//!
+//! ```bash
+//! DEFMT_LOG=info cargo test --features defmt --test defmt_tests
+//! ```
+
+#![cfg(all(feature = "defmt", not(feature = "logging")))]
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use typed_fsm::{state_machine, Transition};
+
+// ============================================================================
+// defmt Capture Logger
+// ============================================================================
+
+static CAPTURED: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+static FRAMES: AtomicUsize = AtomicUsize::new(0);
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+#[defmt::global_logger]
+struct CaptureLogger;
+
+unsafe impl defmt::Logger for CaptureLogger {
+    fn acquire() {
+        while TAKEN.swap(true, Ordering::Acquire) {
+            core::hint::spin_loop();
+        }
+        FRAMES.fetch_add(1, Ordering::SeqCst);
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        TAKEN.store(false, Ordering::Release);
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        CAPTURED.lock().unwrap().extend_from_slice(bytes);
+    }
+}
+
+defmt::timestamp!("{=u32}", 0);
+
+/// Whether `defmt::info!` frames survive the compile-time `DEFMT_LOG` filter.
+fn info_enabled() -> bool {
+    matches!(option_env!("DEFMT_LOG"), Some("trace" | "debug" | "info"))
+}
+
+/// Number of frames expected for `n` FSM log calls under the current filter.
+fn frames(n: usize) -> usize {
+    if info_enabled() {
+        n
+    } else {
+        0
+    }
+}
+
+// ============================================================================
+// Test FSM Definition
+// ============================================================================
+
+struct DefmtContext {
+    ticks: u32,
+}
+
+#[derive(Debug, Clone)]
+enum DefmtEvent {
+    Toggle,
+    Ignore,
+}
+
+state_machine! {
+    Name: DefmtSwitch,
+    Context: DefmtContext,
+    Event: DefmtEvent,
+
+    States: {
+        Off => {
+            process: |ctx, evt| {
+                ctx.ticks += 1;
+                match evt {
+                    DefmtEvent::Toggle => Transition::To(DefmtSwitch::On),
+                    DefmtEvent::Ignore => Transition::None,
+                }
+            }
+        },
+
+        On => {
+            process: |ctx, evt| {
+                ctx.ticks += 1;
+                match evt {
+                    DefmtEvent::Toggle => Transition::To(DefmtSwitch::Off),
+                    DefmtEvent::Ignore => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_defmt_backend_emits_frames() {
+    let mut ctx = DefmtContext { ticks: 0 };
+    let mut fsm = DefmtSwitch::Off;
+
+    let before = FRAMES.load(Ordering::SeqCst);
+    fsm.init(&mut ctx);
+    let after_init = FRAMES.load(Ordering::SeqCst);
+    // init() -> Off, Off.entry()
+    assert_eq!(after_init - before, frames(2));
+
+    fsm.dispatch(&mut ctx, &DefmtEvent::Toggle);
+    let after_transition = FRAMES.load(Ordering::SeqCst);
+    // Off -> On, Off.exit(), On.entry()
+    assert_eq!(after_transition - after_init, frames(3));
+
+    fsm.dispatch(&mut ctx, &DefmtEvent::Ignore);
+    // On -> None (stayed)
+    assert_eq!(FRAMES.load(Ordering::SeqCst) - after_transition, frames(1));
+
+    assert!(matches!(fsm, DefmtSwitch::On));
+    assert_eq!(ctx.ticks, 2);
+    assert_eq!(CAPTURED.lock().unwrap().is_empty(), !info_enabled());
+}
@@ -0,0 +1,1558 @@
+//! Tests for optional macro directives that shape the generated enum
+//!
+//! This test suite validates directives declared between `Event` and `States`:
+//! - `Repr: u8` produces a one-byte enum for fieldless state machines
+//! - `CodeSize: small` compiles and behaves like the default (inlining only)
+//! - `MaxSize: N` compiles when the enum fits (overflow is a doc `compile_fail` test)
+//! - `Attrs: [...]` attributes are applied to the generated enum
+//! - `Default: State` implements `Default` for the generated enum
+//! - `PathTrace: N` records the recent path of entered states (feature `path-trace`)
+//! - `Terminal: [...]` drives `is_terminal()`; `StrictTerminal: true` ignores events there
+//! - `ExhaustiveEvents: true` accepts `process` blocks that name every event variant
+//! - `Metrics: events` counts every processed event, including queued ones
+//!   (also on targets without 64-bit atomics, through a counter split in two words)
+//! - `EmbedContext: Name` generates a wrapper owning the context, so `dispatch()` takes none
+//! - `Record: N` records dispatched events and `replay()` reproduces the run (feature `record`)
+//! - `InitialValues: State { field: value }` drives `start()` and `reset()` for a stateful state
+//! - `DebugMode: NamesOnly` prints only the state name with `{:?}`, never field values
+//! - `MustUse: true` compiles with outcomes consumed (dropping one is a doc `compile_fail` test)
+//! - `Derives: [...]` derives `Hash` and `Eq` so fieldless states can key a `HashMap`
+//! - `EventFilter: |ctx, evt| -> bool` drops rejected events before any hook runs
+//! - `Snapshot: Name { fields: [...] }` rolls back the state and the nominated fields
+//! - `MaxLatency: now_fn` keeps the slowest dispatch measured with a mock clock
+//!   (saturating at `u32::MAX` on targets without 64-bit atomics)
+//! - `Invariant` is checked after each transition; a violation runs `OnInvariantViolation`
+//! - `StrictLints: true` keeps unused hook arguments and fields quiet under `deny(warnings)`
+
+use typed_fsm::{state_machine, DispatchOutcome, Transition};
+
+// ============================================================================
+// Test 1: Repr: u8
+// ============================================================================
+
+struct ReprContext {
+    transitions: u32,
+}
+
+#[derive(Debug, Clone)]
+enum ReprEvent {
+    Next,
+}
+
+state_machine! {
+    Name: ReprLight,
+    Context: ReprContext,
+    Event: ReprEvent,
+    Repr: u8,
+
+    States: {
+        Red => {
+            entry: |ctx| { ctx.transitions += 1; }
+            process: |_ctx, evt| {
+                match evt {
+                    ReprEvent::Next => Transition::To(ReprLight::Green),
+                }
+            }
+        },
+
+        Green => {
+            entry: |ctx| { ctx.transitions += 1; }
+            process: |_ctx, evt| {
+                match evt {
+                    ReprEvent::Next => Transition::To(ReprLight::Yellow),
+                }
+            }
+        },
+
+        Yellow => {
+            entry: |ctx| { ctx.transitions += 1; }
+            process: |_ctx, evt| {
+                match evt {
+                    ReprEvent::Next => Transition::To(ReprLight::Red),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_repr_u8_is_one_byte() {
+    assert_eq!(core::mem::size_of::<ReprLight>(), 1);
+    assert_eq!(core::mem::size_of::<[ReprLight; 16]>(), 16);
+}
+
+#[test]
+fn test_repr_u8_discriminants_follow_declaration_order() {
+    assert_eq!(ReprLight::Red as u8, 0);
+    assert_eq!(ReprLight::Green as u8, 1);
+    assert_eq!(ReprLight::Yellow as u8, 2);
+}
+
+#[test]
+fn test_repr_u8_machine_still_dispatches() {
+    let mut ctx = ReprContext { transitions: 0 };
+    let mut fsm = ReprLight::Red;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &ReprEvent::Next);
+    fsm.dispatch(&mut ctx, &ReprEvent::Next);
+
+    assert!(matches!(fsm, ReprLight::Yellow));
+    assert_eq!(ctx.transitions, 3);
+}
+
+// ============================================================================
+// Test 2: CodeSize: small
+// ============================================================================
+
+struct SizeContext {
+    entries: u32,
+}
+
+#[derive(Debug, Clone)]
+enum SizeEvent {
+    Toggle,
+    Hold,
+}
+
+state_machine! {
+    Name: SmallBlinker,
+    Context: SizeContext,
+    Event: SizeEvent,
+    CodeSize: small,
+
+    States: {
+        On => {
+            entry: |ctx| { ctx.entries += 1; }
+            process: |_ctx, evt| {
+                match evt {
+                    SizeEvent::Toggle => Transition::To(SmallBlinker::Off),
+                    SizeEvent::Hold => Transition::None,
+                }
+            }
+        },
+
+        Off => {
+            entry: |ctx| { ctx.entries += 1; }
+            process: |_ctx, evt| {
+                match evt {
+                    SizeEvent::Toggle => Transition::To(SmallBlinker::On),
+                    SizeEvent::Hold => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_code_size_small_compiles_and_dispatches() {
+    let mut ctx = SizeContext { entries: 0 };
+    let mut fsm = SmallBlinker::On;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &SizeEvent::Toggle);
+    fsm.dispatch(&mut ctx, &SizeEvent::Hold);
+    fsm.dispatch_owned(&mut ctx, SizeEvent::Toggle);
+
+    assert!(matches!(fsm, SmallBlinker::On));
+    assert_eq!(ctx.entries, 3);
+}
+
+// ============================================================================
+// Test 3: Metrics: events
+// ============================================================================
+
+struct MeteredContext {
+    echoes: u32,
+}
+
+#[derive(Debug, Clone)]
+enum MeteredEvent {
+    Ping,
+    Echo,
+    Switch,
+}
+
+state_machine! {
+    Name: MeteredFSM,
+    Context: MeteredContext,
+    Event: MeteredEvent,
+    QueueCapacity: 4,
+    Metrics: events,
+
+    States: {
+        Left => {
+            process: |ctx, evt| {
+                match evt {
+                    MeteredEvent::Ping => {
+                        // Two follow-up events, processed in the same dispatch
+                        MeteredFSM::post(MeteredEvent::Echo).unwrap();
+                        MeteredFSM::post(MeteredEvent::Echo).unwrap();
+                        Transition::None
+                    }
+                    MeteredEvent::Echo => { ctx.echoes += 1; Transition::None }
+                    MeteredEvent::Switch => Transition::To(MeteredFSM::Right),
+                }
+            }
+        },
+
+        Right => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, MeteredEvent::Switch), MeteredFSM::Left)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_metrics_counts_every_processed_event() {
+    let mut ctx = MeteredContext { echoes: 0 };
+    let mut fsm = MeteredFSM::Left;
+    fsm.init(&mut ctx);
+    MeteredFSM::reset_events_processed();
+    assert_eq!(MeteredFSM::events_processed(), 0);
+
+    // Ping + its two queued echoes
+    fsm.dispatch(&mut ctx, &MeteredEvent::Ping);
+    assert_eq!(ctx.echoes, 2);
+    assert_eq!(MeteredFSM::events_processed(), 3);
+
+    // Transitions and stays both count
+    fsm.dispatch(&mut ctx, &MeteredEvent::Switch);
+    fsm.dispatch(&mut ctx, &MeteredEvent::Echo);
+    fsm.dispatch_owned(&mut ctx, MeteredEvent::Switch);
+    assert!(matches!(fsm, MeteredFSM::Left));
+    assert_eq!(MeteredFSM::events_processed(), 6);
+
+    MeteredFSM::reset_events_processed();
+    assert_eq!(MeteredFSM::events_processed(), 0);
+}
+
+#[test]
+fn test_metrics_split_counter_carries_into_high_word() {
+    use std::sync::atomic::Ordering;
+    use typed_fsm::atomic64::SplitU64;
+
+    // The counter of targets without 64-bit atomics
+    let counter = SplitU64::new(u64::from(u32::MAX) - 1);
+    counter.fetch_add(1, Ordering::Relaxed);
+    assert_eq!(counter.load(Ordering::Relaxed), u64::from(u32::MAX));
+    counter.fetch_add(2, Ordering::Relaxed);
+    assert_eq!(counter.load(Ordering::Relaxed), u64::from(u32::MAX) + 2);
+    counter.fetch_add(1 << 32, Ordering::Relaxed);
+    assert_eq!(counter.load(Ordering::Relaxed), (2 << 32) + 1);
+
+    counter.store(u64::MAX, Ordering::Relaxed);
+    counter.fetch_add(1, Ordering::Relaxed);
+    assert_eq!(counter.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn test_max_latency_split_maximum_saturates() {
+    use std::sync::atomic::Ordering;
+    use typed_fsm::atomic64::SplitU64;
+
+    // The `MaxLatency` worst case of targets without 64-bit atomics
+    let worst = SplitU64::new(0);
+    worst.fetch_max(40, Ordering::Relaxed);
+    worst.fetch_max(12, Ordering::Relaxed);
+    assert_eq!(worst.load(Ordering::Relaxed), 40);
+    worst.fetch_max(u64::MAX, Ordering::Relaxed);
+    assert_eq!(worst.load(Ordering::Relaxed), u64::from(u32::MAX));
+}
+
+// ============================================================================
+// Test 4: MaxSize
+// ============================================================================
+
+struct BudgetContext {}
+
+#[derive(Debug, Clone)]
+enum BudgetEvent {
+    Sample(u32),
+    Stop,
+}
+
+state_machine! {
+    Name: BudgetSensor,
+    Context: BudgetContext,
+    Event: BudgetEvent,
+    MaxSize: 8,
+
+    States: {
+        Idle => {
+            process: |_ctx, evt| {
+                match evt {
+                    BudgetEvent::Sample(v) => Transition::To(BudgetSensor::Sampling { last: *v }),
+                    BudgetEvent::Stop => Transition::None,
+                }
+            }
+        },
+
+        Sampling { last: u32 } => {
+            process: |_ctx, evt| {
+                match evt {
+                    BudgetEvent::Sample(v) => Transition::To(BudgetSensor::Sampling { last: *v }),
+                    BudgetEvent::Stop => Transition::To(BudgetSensor::Idle),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_max_size_within_budget_compiles() {
+    assert!(core::mem::size_of::<BudgetSensor>() <= 8);
+
+    let mut ctx = BudgetContext {};
+    let mut fsm = BudgetSensor::start_in(BudgetSensor::Idle, &mut ctx);
+    fsm.dispatch(&mut ctx, &BudgetEvent::Sample(42));
+    assert!(matches!(fsm, BudgetSensor::Sampling { last: 42 }));
+    fsm.dispatch(&mut ctx, &BudgetEvent::Stop);
+    assert!(matches!(fsm, BudgetSensor::Idle));
+}
+
+// ============================================================================
+// Test 5: Attrs
+// ============================================================================
+
+struct AttrsContext {}
+
+#[derive(Debug, Clone)]
+enum AttrsEvent {
+    Open,
+    Close,
+}
+
+state_machine! {
+    Name: AttrsValve,
+    Context: AttrsContext,
+    Event: AttrsEvent,
+    Attrs: [
+        #[non_exhaustive],
+        #[derive(Clone, PartialEq, Eq, Hash)],
+    ],
+
+    States: {
+        Closed => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, AttrsEvent::Open), AttrsValve::Open { percent: 100 })
+            }
+        },
+
+        Open { percent: u8 } => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, AttrsEvent::Close), AttrsValve::Closed)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_attrs_are_applied_to_the_enum() {
+    // Derives from `Attrs` sit alongside the built-in `Debug`
+    let mut ctx = AttrsContext {};
+    let mut fsm = AttrsValve::start_in(AttrsValve::Closed, &mut ctx);
+    let snapshot = fsm.clone();
+
+    fsm.dispatch(&mut ctx, &AttrsEvent::Open);
+    assert_eq!(fsm, AttrsValve::Open { percent: 100 });
+    assert_ne!(fsm, snapshot);
+
+    let mut seen = std::collections::HashSet::new();
+    seen.insert(fsm.clone());
+    assert!(seen.contains(&AttrsValve::Open { percent: 100 }));
+
+    // `#[non_exhaustive]` only restricts other crates; here it must simply compile
+    fsm.dispatch(&mut ctx, &AttrsEvent::Close);
+    assert_eq!(format!("{:?}", fsm), "Closed");
+}
+
+// ============================================================================
+// Test 6: Default
+// ============================================================================
+
+struct DefaultContext {
+    entries: u32,
+}
+
+#[derive(Debug, Clone)]
+enum DefaultEvent {
+    Arm,
+}
+
+state_machine! {
+    Name: DefaultAlarm,
+    Context: DefaultContext,
+    Event: DefaultEvent,
+    Default: Disarmed,
+
+    States: {
+        Armed { zone: u8 } => {
+            process: |_ctx, _evt| { Transition::None }
+        },
+
+        Disarmed => {
+            entry: |ctx| { ctx.entries += 1; }
+            process: |_ctx, evt| {
+                match evt {
+                    DefaultEvent::Arm => Transition::To(DefaultAlarm::Armed { zone: 1 }),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_default_yields_declared_state() {
+    let mut ctx = DefaultContext { entries: 0 };
+    let mut fsm = DefaultAlarm::default();
+    assert!(matches!(fsm, DefaultAlarm::Disarmed));
+
+    fsm.init(&mut ctx);
+    assert_eq!(ctx.entries, 1);
+
+    fsm.dispatch(&mut ctx, &DefaultEvent::Arm);
+    assert!(matches!(fsm, DefaultAlarm::Armed { zone: 1 }));
+
+    // Usable wherever `Default` is expected
+    let slots: [DefaultAlarm; 2] = Default::default();
+    assert!(slots.iter().all(|s| matches!(s, DefaultAlarm::Disarmed)));
+}
+
+// ============================================================================
+// Test 7: Terminal / StrictTerminal
+// ============================================================================
+
+struct JobContext {
+    processed: u32,
+}
+
+#[derive(Debug, Clone)]
+enum JobEvent {
+    Finish,
+    Cancel,
+    Restart,
+}
+
+state_machine! {
+    Name: LenientJob,
+    Context: JobContext,
+    Event: JobEvent,
+    Terminal: [Completed, Cancelled],
+
+    States: {
+        Running => {
+            process: |ctx, evt| {
+                ctx.processed += 1;
+                match evt {
+                    JobEvent::Finish => Transition::To(LenientJob::Completed),
+                    JobEvent::Cancel => Transition::To(LenientJob::Cancelled),
+                    JobEvent::Restart => Transition::None,
+                }
+            }
+        },
+
+        Completed => {
+            process: |ctx, evt| {
+                ctx.processed += 1;
+                Transition::to_if(matches!(evt, JobEvent::Restart), LenientJob::Running)
+            }
+        },
+
+        Cancelled => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+state_machine! {
+    Name: StrictJob,
+    Context: JobContext,
+    Event: JobEvent,
+    Terminal: [Completed, Cancelled],
+    StrictTerminal: true,
+
+    States: {
+        Running => {
+            process: |ctx, evt| {
+                ctx.processed += 1;
+                match evt {
+                    JobEvent::Finish => Transition::To(StrictJob::Completed),
+                    JobEvent::Cancel => Transition::To(StrictJob::Cancelled),
+                    JobEvent::Restart => Transition::None,
+                }
+            }
+        },
+
+        Completed => {
+            process: |ctx, evt| {
+                ctx.processed += 1;
+                Transition::to_if(matches!(evt, JobEvent::Restart), StrictJob::Running)
+            }
+        },
+
+        Cancelled => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_is_terminal_follows_declared_states() {
+    assert!(!LenientJob::Running.is_terminal());
+    assert!(LenientJob::Completed.is_terminal());
+    assert!(LenientJob::Cancelled.is_terminal());
+
+    // Without `StrictTerminal`, `process` still decides
+    let mut ctx = JobContext { processed: 0 };
+    let mut fsm = LenientJob::start_in(LenientJob::Running, &mut ctx);
+    fsm.dispatch(&mut ctx, &JobEvent::Finish);
+    assert!(fsm.is_terminal());
+    fsm.dispatch(&mut ctx, &JobEvent::Restart);
+    assert!(matches!(fsm, LenientJob::Running));
+    assert_eq!(ctx.processed, 2);
+}
+
+#[test]
+fn test_strict_terminal_ignores_events() {
+    let mut ctx = JobContext { processed: 0 };
+    let mut fsm = StrictJob::start_in(StrictJob::Running, &mut ctx);
+    fsm.dispatch(&mut ctx, &JobEvent::Finish);
+    assert!(matches!(fsm, StrictJob::Completed));
+    assert_eq!(ctx.processed, 1);
+
+    // `process` of the terminal state never runs
+    fsm.dispatch(&mut ctx, &JobEvent::Restart);
+    fsm.dispatch(&mut ctx, &JobEvent::Cancel);
+    assert!(matches!(fsm, StrictJob::Completed));
+    assert_eq!(ctx.processed, 1);
+
+    // Non-terminal states are unaffected
+    assert!(!StrictJob::Running.is_terminal());
+    assert!(StrictJob::Cancelled.is_terminal());
+}
+
+// ============================================================================
+// Test 8: PathTrace
+// ============================================================================
+
+#[cfg(feature = "path-trace")]
+struct TraceContext {}
+
+#[cfg(feature = "path-trace")]
+#[derive(Debug, Clone)]
+enum TraceEvent {
+    Next,
+    Retry,
+    Stay,
+}
+
+#[cfg(feature = "path-trace")]
+state_machine! {
+    Name: TraceLink,
+    Context: TraceContext,
+    Event: TraceEvent,
+    PathTrace: 24,
+
+    States: {
+        Idle => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, TraceEvent::Next), TraceLink::Connecting)
+            }
+        },
+
+        Connecting => {
+            process: |_ctx, evt| {
+                match evt {
+                    TraceEvent::Next => Transition::To(TraceLink::Online),
+                    TraceEvent::Retry => Transition::To(TraceLink::Retrying),
+                    TraceEvent::Stay => Transition::Reenter,
+                }
+            }
+        },
+
+        Retrying => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, TraceEvent::Next), TraceLink::Online)
+            }
+        },
+
+        Online => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, TraceEvent::Next), TraceLink::Idle)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "path-trace")]
+#[test]
+fn test_path_trace_records_and_rotates() {
+    let mut ctx = TraceContext {};
+    assert_eq!(TraceLink::path_trace(), "");
+
+    let mut fsm = TraceLink::start_in(TraceLink::Idle, &mut ctx);
+    assert_eq!(TraceLink::path_trace(), "Idle");
+
+    fsm.dispatch(&mut ctx, &TraceEvent::Next);
+    // Neither Reenter nor None is a new state in the path
+    fsm.dispatch(&mut ctx, &TraceEvent::Stay);
+    fsm.dispatch(&mut ctx, &TraceEvent::Retry);
+    assert_eq!(TraceLink::path_trace(), "Idle>Connecting>Retrying");
+    assert_eq!(TraceLink::path_trace().len(), 24);
+
+    // Full: the oldest states are dropped until the new one fits
+    fsm.dispatch(&mut ctx, &TraceEvent::Next);
+    assert_eq!(TraceLink::path_trace(), "Retrying>Online");
+
+    fsm.dispatch(&mut ctx, &TraceEvent::Next);
+    assert_eq!(TraceLink::path_trace(), "Retrying>Online>Idle");
+}
+
+// ============================================================================
+// Test 9: ExhaustiveEvents
+// ============================================================================
+// Catch-all arms are rejected at compile time (see the doc `compile_fail` test);
+// this machine covers the arm shapes that must still be accepted.
+
+struct PumpContext {
+    primed: bool,
+}
+
+#[derive(Debug, Clone)]
+enum PumpEvent {
+    Prime,
+    Run(u8),
+    Fault { code: u16 },
+}
+
+state_machine! {
+    Name: Pump,
+    Context: PumpContext,
+    Event: PumpEvent,
+    ExhaustiveEvents: true,
+
+    States: {
+        Idle => {
+            process: |ctx, evt| {
+                match evt {
+                    PumpEvent::Prime => {
+                        ctx.primed = true;
+                        Transition::None
+                    }
+                    PumpEvent::Run(speed) if *speed > 0 => Transition::to_if(ctx.primed, Pump::Running),
+                    PumpEvent::Run(_) => if ctx.primed { Transition::None } else { Transition::Unhandled }
+                    PumpEvent::Fault { .. } => Transition::To(Pump::Failed),
+                }
+            }
+        },
+
+        Running => {
+            process: |_ctx, evt| {
+                match *evt {
+                    PumpEvent::Prime | PumpEvent::Run(_) => Transition::None,
+                    PumpEvent::Fault { code } => Transition::to_if(code != 0, Pump::Failed)
+                }
+            }
+        },
+
+        Failed => {
+            process: |_ctx, evt| {
+                match evt {
+                    PumpEvent::Prime | PumpEvent::Run(_) | PumpEvent::Fault { .. } => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_exhaustive_events_accepts_explicit_arms() {
+    let mut ctx = PumpContext { primed: false };
+    let mut fsm = Pump::start_in(Pump::Idle, &mut ctx);
+
+    fsm.dispatch(&mut ctx, &PumpEvent::Run(3));
+    assert!(matches!(fsm, Pump::Idle));
+
+    fsm.dispatch(&mut ctx, &PumpEvent::Prime);
+    fsm.dispatch(&mut ctx, &PumpEvent::Run(3));
+    assert!(matches!(fsm, Pump::Running));
+
+    fsm.dispatch(&mut ctx, &PumpEvent::Fault { code: 0 });
+    assert!(matches!(fsm, Pump::Running));
+    fsm.dispatch(&mut ctx, &PumpEvent::Fault { code: 7 });
+    assert!(matches!(fsm, Pump::Failed));
+}
+
+// ============================================================================
+// Test 10: EmbedContext
+// ============================================================================
+
+struct KettleContext {
+    heats: u32,
+    log: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+enum KettleEvent {
+    Heat,
+    Boiled,
+}
+
+state_machine! {
+    Name: Kettle,
+    Context: KettleContext,
+    Event: KettleEvent,
+    EmbedContext: KettleMachine,
+
+    States: {
+        Off => {
+            entry: |ctx| { ctx.log.push("Off"); }
+
+            process: |_ctx, evt| {
+                match evt {
+                    KettleEvent::Heat => Transition::To(Kettle::Heating),
+                    KettleEvent::Boiled => Transition::None,
+                }
+            }
+        },
+
+        Heating => {
+            entry: |ctx| {
+                ctx.heats += 1;
+                ctx.log.push("Heating");
+            }
+
+            process: |_ctx, evt| {
+                match evt {
+                    KettleEvent::Boiled => Transition::To(Kettle::Off),
+                    KettleEvent::Heat => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_embed_context_dispatches_without_ctx() {
+    let mut kettle = KettleMachine::new(
+        Kettle::Off,
+        KettleContext {
+            heats: 0,
+            log: Vec::new(),
+        },
+    );
+    kettle.init();
+
+    assert_eq!(
+        kettle.dispatch(&KettleEvent::Heat),
+        typed_fsm::DispatchOutcome::Transitioned
+    );
+    assert!(matches!(kettle.state(), Kettle::Heating));
+    assert_eq!(kettle.context().heats, 1);
+
+    kettle.context_mut().log.clear();
+    kettle.dispatch(&KettleEvent::Boiled);
+    kettle.dispatch(&KettleEvent::Heat);
+
+    let (state, ctx) = kettle.into_parts();
+    assert!(matches!(state, Kettle::Heating));
+    assert_eq!(ctx.heats, 2);
+    assert_eq!(ctx.log, ["Off", "Heating"]);
+}
+
+// ============================================================================
+// Test 11: Record
+// ============================================================================
+
+#[cfg(feature = "record")]
+struct LockContext {
+    attempts: u32,
+}
+
+#[cfg(feature = "record")]
+#[derive(Debug, Clone, PartialEq)]
+enum LockEvent {
+    Digit(u8),
+    Reset,
+}
+
+#[cfg(feature = "record")]
+state_machine! {
+    Name: CodeLock,
+    Context: LockContext,
+    Event: LockEvent,
+    Record: 8,
+
+    States: {
+        Locked { entered: u8 } => {
+            process: |ctx, evt| {
+                match evt {
+                    LockEvent::Digit(d) if *entered == 1 && *d == 2 => CodeLock::Open,
+                    LockEvent::Digit(d) => {
+                        ctx.attempts += 1;
+                        CodeLock::Locked { entered: *d }
+                    }
+                    LockEvent::Reset => CodeLock::Locked { entered: 0 },
+                }
+            }
+        },
+
+        Open => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, LockEvent::Reset), CodeLock::Locked { entered: 0 })
+            }
+        }
+    }
+}
+
+#[cfg(feature = "record")]
+#[test]
+fn test_record_and_replay_reproduces_final_state() {
+    let mut ctx = LockContext { attempts: 0 };
+    let mut lock = CodeLock::start_in(CodeLock::Locked { entered: 0 }, &mut ctx);
+    CodeLock::clear_recorded_events();
+
+    let events = [
+        LockEvent::Digit(7),
+        LockEvent::Digit(1),
+        LockEvent::Digit(2),
+    ];
+    for event in &events {
+        lock.dispatch(&mut ctx, event);
+    }
+    lock.dispatch_owned(&mut ctx, LockEvent::Reset);
+    lock.dispatch(&mut ctx, &LockEvent::Digit(1));
+    assert_eq!(CodeLock::recorded_events().len(), 5);
+    assert_eq!(CodeLock::recorded_events()[3], LockEvent::Reset);
+
+    // Replay against a fresh machine and context
+    let mut fresh_ctx = LockContext { attempts: 0 };
+    let mut fresh = CodeLock::start_in(CodeLock::Locked { entered: 0 }, &mut fresh_ctx);
+    assert_eq!(fresh.replay(&mut fresh_ctx), 5);
+
+    assert!(matches!(fresh, CodeLock::Locked { entered: 1 }));
+    assert!(lock.same_variant(&fresh));
+    assert_eq!(fresh_ctx.attempts, ctx.attempts);
+
+    // Replayed events are not recorded again
+    assert_eq!(CodeLock::recorded_events().len(), 5);
+
+    // Recording stops once `Record` events are stored
+    for _ in 0..10 {
+        lock.dispatch(&mut ctx, &LockEvent::Reset);
+    }
+    assert_eq!(CodeLock::recorded_events().len(), 8);
+}
+
+// ============================================================================
+// Test 12: InitialValues
+// ============================================================================
+
+struct FanContext {
+    entries: Vec<(u32, bool)>,
+    exits: u32,
+}
+
+#[derive(Debug, Clone)]
+enum FanEvent {
+    Faster,
+    Off,
+}
+
+state_machine! {
+    Name: Fan,
+    Context: FanContext,
+    Event: FanEvent,
+    InitialValues: Spinning { rpm: 600 },
+
+    States: {
+        Spinning { rpm: u32 } => {
+            entry: |ctx, _from, is_init| {
+                ctx.entries.push((*rpm, is_init));
+            }
+            process: |_ctx, evt| {
+                match evt {
+                    FanEvent::Faster => Fan::Spinning { rpm: *rpm + 300 },
+                    FanEvent::Off => Fan::Stopped,
+                }
+            }
+            exit: |ctx| {
+                ctx.exits += 1;
+            }
+        },
+
+        Stopped => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_initial_values_start_and_reset_with_declared_fields() {
+    assert!(matches!(Fan::initial_state(), Fan::Spinning { rpm: 600 }));
+
+    let mut ctx = FanContext {
+        entries: Vec::new(),
+        exits: 0,
+    };
+    let mut fan = Fan::start(&mut ctx);
+    assert!(matches!(fan, Fan::Spinning { rpm: 600 }));
+    assert_eq!(ctx.entries, [(600, true)]);
+
+    fan.dispatch(&mut ctx, &FanEvent::Faster);
+    fan.dispatch(&mut ctx, &FanEvent::Faster);
+    assert!(matches!(fan, Fan::Spinning { rpm: 1200 }));
+
+    // Back to the declared field values, entering as on `init()`, without `exit`
+    let exits = ctx.exits;
+    fan.reset(&mut ctx);
+    assert!(matches!(fan, Fan::Spinning { rpm: 600 }));
+    assert_eq!(ctx.entries.last(), Some(&(600, true)));
+    assert_eq!(ctx.exits, exits);
+
+    fan.dispatch(&mut ctx, &FanEvent::Off);
+    assert!(matches!(fan, Fan::Stopped));
+    fan.reset(&mut ctx);
+    assert!(matches!(fan, Fan::Spinning { rpm: 600 }));
+}
+
+// ============================================================================
+// Test 13: DebugMode
+// ============================================================================
+
+struct SessionContext {}
+
+#[derive(Debug, Clone)]
+enum SessionEvent {
+    Login(&'static str),
+    Logout,
+}
+
+state_machine! {
+    Name: Session,
+    Context: SessionContext,
+    Event: SessionEvent,
+    DebugMode: NamesOnly,
+
+    States: {
+        Anonymous => {
+            process: |_ctx, evt| {
+                match evt {
+                    SessionEvent::Login(token) => Transition::To(Session::Authenticated { token }),
+                    SessionEvent::Logout => Transition::None,
+                }
+            }
+        },
+
+        Authenticated { token: &'static str } => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, SessionEvent::Logout), Session::Anonymous)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_debug_mode_names_only_hides_field_values() {
+    let mut ctx = SessionContext {};
+    let mut session = Session::start_in(Session::Anonymous, &mut ctx);
+    assert_eq!(format!("{:?}", session), "Anonymous");
+
+    session.dispatch(&mut ctx, &SessionEvent::Login("s3cr3t-token"));
+    let printed = format!("{:?}", session);
+    assert_eq!(printed, "Authenticated");
+    assert!(!printed.contains("s3cr3t"));
+    assert_eq!(format!("{:#?}", session), "Authenticated");
+
+    session.dispatch(&mut ctx, &SessionEvent::Logout);
+    assert_eq!(format!("{:?}", session), "Anonymous");
+
+    // The default derived `Debug` still prints fields
+    assert_eq!(
+        format!("{:?}", Fan::Spinning { rpm: 600 }),
+        "Spinning { rpm: 600 }"
+    );
+}
+
+// ============================================================================
+// Test 14: MustUse
+// ============================================================================
+
+/// `unused_must_use` is denied, so this module only compiles if every outcome is used.
+#[deny(unused_must_use)]
+mod must_use {
+    use typed_fsm::{state_machine, DispatchOutcome, Transition};
+
+    struct LatchContext {}
+
+    #[derive(Debug, Clone)]
+    enum LatchEvent {
+        Set,
+    }
+
+    state_machine! {
+        Name: Latch,
+        Context: LatchContext,
+        Event: LatchEvent,
+        MustUse: true,
+
+        States: {
+            Open => {
+                process: |_ctx, _evt| { Latch::Closed }
+            },
+
+            Closed => {
+                process: |_ctx, _evt| { Transition::None }
+            }
+        }
+    }
+
+    #[test]
+    fn test_must_use_outcomes_are_consumed() {
+        let mut ctx = LatchContext {};
+        let mut latch = Latch::start_in(Latch::Open, &mut ctx);
+
+        assert_eq!(
+            latch.dispatch(&mut ctx, &LatchEvent::Set),
+            DispatchOutcome::Transitioned
+        );
+        // Explicitly ignoring an outcome is still allowed
+        let _ = latch.dispatch_owned(&mut ctx, LatchEvent::Set);
+        assert_eq!(latch.current_state_name(), "Closed");
+    }
+}
+
+// ============================================================================
+// Test 15: Derives
+// ============================================================================
+
+struct GearboxContext {}
+
+#[derive(Debug, Clone)]
+enum GearboxEvent {
+    Up,
+}
+
+state_machine! {
+    Name: Gear,
+    Context: GearboxContext,
+    Event: GearboxEvent,
+    Derives: [Clone, Copy, PartialEq, Eq, Hash],
+
+    States: {
+        First => {
+            process: |_ctx, _evt| { Gear::Second }
+        },
+
+        Second => {
+            process: |_ctx, _evt| { Gear::Third }
+        },
+
+        Third => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_derives_allow_states_as_map_keys() {
+    use std::collections::HashMap;
+
+    // Per-state configuration: maximum speed in km/h
+    let max_speed: HashMap<Gear, u32> = [(Gear::First, 20), (Gear::Second, 40), (Gear::Third, 60)]
+        .into_iter()
+        .collect();
+
+    let mut ctx = GearboxContext {};
+    let mut gear = Gear::start_in(Gear::First, &mut ctx);
+    assert_eq!(max_speed[&gear], 20);
+
+    gear.dispatch(&mut ctx, &GearboxEvent::Up);
+    assert_eq!(max_speed.get(&gear), Some(&40));
+    assert_eq!(gear, Gear::Second);
+
+    // `Copy`: the state is a plain value
+    let previous = gear;
+    gear.dispatch(&mut ctx, &GearboxEvent::Up);
+    assert_ne!(previous, gear);
+    assert_eq!(max_speed[&gear], 60);
+}
+
+// ============================================================================
+// Test 16: EventFilter
+// ============================================================================
+
+struct ThermostatContext {
+    processed: Vec<i32>,
+    rejected: u32,
+}
+
+#[derive(Debug, Clone)]
+enum ThermostatEvent {
+    Reading(i32),
+}
+
+state_machine! {
+    Name: Thermostat,
+    Context: ThermostatContext,
+    Event: ThermostatEvent,
+    EventFilter: |ctx, evt| -> bool {
+        // Discard readings outside the sensor's physical range
+        let ThermostatEvent::Reading(celsius) = evt;
+        let valid = (-40..=125).contains(celsius);
+        if !valid {
+            ctx.rejected += 1;
+        }
+        valid
+    },
+
+    States: {
+        Idle => {
+            process: |ctx, evt| {
+                let ThermostatEvent::Reading(celsius) = evt;
+                ctx.processed.push(*celsius);
+                if *celsius > 30 {
+                    Transition::To(Thermostat::Cooling)
+                } else {
+                    Transition::None
+                }
+            }
+        },
+
+        Cooling => {
+            process: |ctx, evt| {
+                let ThermostatEvent::Reading(celsius) = evt;
+                ctx.processed.push(*celsius);
+                if *celsius < 25 {
+                    Transition::To(Thermostat::Idle)
+                } else {
+                    Transition::None
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_event_filter_drops_rejected_events() {
+    let mut ctx = ThermostatContext {
+        processed: Vec::new(),
+        rejected: 0,
+    };
+    let mut thermostat = Thermostat::Idle;
+    thermostat.init(&mut ctx);
+
+    // A glitched reading never reaches `process`
+    let outcome = thermostat.dispatch(&mut ctx, &ThermostatEvent::Reading(900));
+    assert_eq!(outcome, DispatchOutcome::Filtered);
+    assert!(matches!(thermostat, Thermostat::Idle));
+    assert!(ctx.processed.is_empty());
+    assert_eq!(ctx.rejected, 1);
+
+    // Valid readings are processed as usual
+    let outcome = thermostat.dispatch(&mut ctx, &ThermostatEvent::Reading(35));
+    assert_eq!(outcome, DispatchOutcome::Transitioned);
+    assert!(matches!(thermostat, Thermostat::Cooling));
+
+    let outcome = thermostat.dispatch(&mut ctx, &ThermostatEvent::Reading(-300));
+    assert_eq!(outcome, DispatchOutcome::Filtered);
+    assert!(matches!(thermostat, Thermostat::Cooling));
+
+    let outcome = thermostat.dispatch(&mut ctx, &ThermostatEvent::Reading(20));
+    assert_eq!(outcome, DispatchOutcome::Transitioned);
+    assert!(matches!(thermostat, Thermostat::Idle));
+
+    assert_eq!(ctx.processed, vec![35, 20]);
+    assert_eq!(ctx.rejected, 2);
+}
+
+// ============================================================================
+// Test 17: Snapshot
+// ============================================================================
+
+/// Not `Clone`: only the nominated fields are captured
+struct AuditLog {
+    entries: u32,
+}
+
+struct WalletContext {
+    balance: i64,
+    pending: Vec<i64>,
+    audit: AuditLog,
+}
+
+#[derive(Debug, Clone)]
+enum WalletEvent {
+    Charge(i64),
+    Settle,
+}
+
+state_machine! {
+    Name: Wallet,
+    Context: WalletContext,
+    Event: WalletEvent,
+    Derives: [Clone],
+    Snapshot: WalletSnapshot { fields: [balance: i64, pending: Vec<i64>] },
+
+    States: {
+        Open => {
+            process: |ctx, evt| {
+                ctx.audit.entries += 1;
+                match evt {
+                    WalletEvent::Charge(amount) => {
+                        ctx.balance -= amount;
+                        ctx.pending.push(*amount);
+                        if ctx.balance < 0 {
+                            Transition::To(Wallet::Overdrawn { deficit: -ctx.balance })
+                        } else {
+                            Transition::None
+                        }
+                    }
+                    WalletEvent::Settle => {
+                        ctx.pending.clear();
+                        Transition::None
+                    }
+                }
+            }
+        },
+
+        Overdrawn { deficit: i64 } => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_snapshot_restore_rolls_back_state_and_fields() {
+    let mut ctx = WalletContext {
+        balance: 100,
+        pending: Vec::new(),
+        audit: AuditLog { entries: 0 },
+    };
+    let mut wallet = Wallet::Open;
+    wallet.init(&mut ctx);
+    wallet.dispatch(&mut ctx, &WalletEvent::Charge(30));
+
+    // Speculative dispatch: try a charge, roll back if it overdraws
+    let snapshot = wallet.snapshot(&ctx);
+    assert_eq!(snapshot.balance, 70);
+    assert_eq!(snapshot.pending, vec![30]);
+
+    wallet.dispatch(&mut ctx, &WalletEvent::Charge(120));
+    assert!(matches!(wallet, Wallet::Overdrawn { deficit: 50 }));
+    assert_eq!(ctx.balance, -50);
+
+    wallet.restore(&mut ctx, snapshot);
+    assert!(matches!(wallet, Wallet::Open));
+    assert_eq!(ctx.balance, 70);
+    assert_eq!(ctx.pending, vec![30]);
+    // Fields that weren't nominated keep their current values
+    assert_eq!(ctx.audit.entries, 2);
+
+    // The restored machine keeps working
+    wallet.dispatch(&mut ctx, &WalletEvent::Settle);
+    assert!(matches!(wallet, Wallet::Open));
+    assert!(ctx.pending.is_empty());
+}
+
+// ============================================================================
+// Test 18: MaxLatency
+// ============================================================================
+
+mod max_latency {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Mock cycle counter: hooks advance it to simulate work
+    static CYCLES: AtomicU64 = AtomicU64::new(0);
+
+    fn cycles() -> u64 {
+        CYCLES.load(Ordering::Relaxed)
+    }
+
+    fn spend(cycles: u64) {
+        CYCLES.fetch_add(cycles, Ordering::Relaxed);
+    }
+
+    struct ControlContext {}
+
+    #[derive(Debug, Clone)]
+    enum ControlEvent {
+        Compute(u64),
+        Arm,
+    }
+
+    state_machine! {
+        Name: ControlLoop,
+        Context: ControlContext,
+        Event: ControlEvent,
+        MaxLatency: cycles,
+
+        States: {
+            Idle => {
+                process: |_ctx, evt| {
+                    match evt {
+                        ControlEvent::Compute(work) => {
+                            spend(*work);
+                            Transition::None
+                        }
+                        ControlEvent::Arm => Transition::To(ControlLoop::Armed),
+                    }
+                }
+
+                exit: |_ctx| { spend(7); }
+            },
+
+            Armed => {
+                entry: |_ctx| { spend(50); }
+
+                process: |_ctx, _evt| { Transition::None }
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_latency_tracks_slowest_dispatch() {
+        // Starts at an arbitrary counter value, as a free-running clock would
+        CYCLES.store(u64::MAX - 10, Ordering::Relaxed);
+        let mut ctx = ControlContext {};
+        let mut control = ControlLoop::start_in(ControlLoop::Idle, &mut ctx);
+        assert_eq!(ControlLoop::max_dispatch_cycles(), 0);
+
+        // The worst case so far, across the counter's wraparound
+        control.dispatch(&mut ctx, &ControlEvent::Compute(5));
+        control.dispatch(&mut ctx, &ControlEvent::Compute(40));
+        control.dispatch(&mut ctx, &ControlEvent::Compute(12));
+        assert_eq!(ControlLoop::max_dispatch_cycles(), 40);
+
+        ControlLoop::reset_max_dispatch_cycles();
+        assert_eq!(ControlLoop::max_dispatch_cycles(), 0);
+        control.dispatch(&mut ctx, &ControlEvent::Compute(3));
+        assert_eq!(ControlLoop::max_dispatch_cycles(), 3);
+
+        // A transition is timed with its exit and entry hooks
+        control.dispatch(&mut ctx, &ControlEvent::Arm);
+        assert_eq!(ControlLoop::max_dispatch_cycles(), 57);
+    }
+}
+
+// ============================================================================
+// Test 19: Invariant / OnInvariantViolation
+// ============================================================================
+
+struct CrossingContext {
+    ns_green: bool,
+    ew_green: bool,
+    violations: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+enum CrossingEvent {
+    Go,
+    Stop,
+}
+
+state_machine! {
+    Name: Crossing,
+    Context: CrossingContext,
+    Event: CrossingEvent,
+    Invariant: |ctx, _state_name| -> bool { !(ctx.ns_green && ctx.ew_green) },
+    OnInvariantViolation: |ctx, state_name| {
+        ctx.violations.push(state_name);
+    },
+
+    States: {
+        AllRed => {
+            entry: |ctx| {
+                ctx.ns_green = false;
+            }
+
+            process: |_ctx, evt| {
+                match evt {
+                    CrossingEvent::Go => Transition::To(Crossing::NorthSouth),
+                    CrossingEvent::Stop => Transition::None,
+                }
+            }
+        },
+
+        NorthSouth => {
+            // Bug under test: the other direction is left green
+            entry: |ctx| {
+                ctx.ns_green = true;
+            }
+
+            process: |_ctx, evt| {
+                match evt {
+                    CrossingEvent::Go => Transition::Reenter,
+                    CrossingEvent::Stop => Transition::To(Crossing::AllRed),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_invariant_violation_runs_hook() {
+    let mut ctx = CrossingContext {
+        ns_green: false,
+        ew_green: false,
+        violations: Vec::new(),
+    };
+    let mut crossing = Crossing::start_in(Crossing::AllRed, &mut ctx);
+
+    // Satisfied: no hook
+    crossing.dispatch(&mut ctx, &CrossingEvent::Go);
+    crossing.dispatch(&mut ctx, &CrossingEvent::Stop);
+    assert!(ctx.violations.is_empty());
+
+    // Violated once the transition's entry has run
+    ctx.ew_green = true;
+    crossing.dispatch(&mut ctx, &CrossingEvent::Go);
+    assert_eq!(ctx.violations, vec!["NorthSouth"]);
+
+    // Reenter is checked too, staying put is not
+    crossing.dispatch(&mut ctx, &CrossingEvent::Go);
+    assert_eq!(ctx.violations, vec!["NorthSouth", "NorthSouth"]);
+    crossing.dispatch(&mut ctx, &CrossingEvent::Stop);
+    crossing.dispatch(&mut ctx, &CrossingEvent::Stop);
+    assert_eq!(ctx.violations.len(), 2);
+}
+
+struct TankContext {
+    level: u32,
+}
+
+#[derive(Debug, Clone)]
+enum TankEvent {
+    Fill(u32),
+}
+
+state_machine! {
+    Name: Tank,
+    Context: TankContext,
+    Event: TankEvent,
+    Invariant: |ctx, _state_name| { ctx.level <= 100 },
+
+    States: {
+        Idle => {
+            process: |ctx, evt| {
+                match evt {
+                    TankEvent::Fill(amount) => {
+                        ctx.level += amount;
+                        Transition::To(Tank::Filling)
+                    }
+                }
+            }
+        },
+
+        Filling => {
+            process: |_ctx, _evt| { Transition::To(Tank::Idle) }
+        }
+    }
+}
+
+#[test]
+fn test_invariant_satisfied_does_not_panic() {
+    let mut ctx = TankContext { level: 0 };
+    let mut tank = Tank::start_in(Tank::Idle, &mut ctx);
+
+    tank.dispatch(&mut ctx, &TankEvent::Fill(60));
+    tank.dispatch(&mut ctx, &TankEvent::Fill(0));
+    tank.dispatch(&mut ctx, &TankEvent::Fill(40));
+    assert_eq!(ctx.level, 100);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "[Tank] Invariant violated in state Filling")]
+fn test_invariant_violation_panics_without_hook() {
+    let mut ctx = TankContext { level: 90 };
+    let mut tank = Tank::start_in(Tank::Idle, &mut ctx);
+
+    tank.dispatch(&mut ctx, &TankEvent::Fill(20));
+}
+
+// ============================================================================
+// Test 20: StrictLints
+// ============================================================================
+
+// User code that leaves a variable unused fails to compile under `StrictLints`
+// (a doc `compile_fail` test); the bindings the macro generates must stay quiet.
+#[deny(warnings)]
+mod strict_lints {
+    use typed_fsm::{state_machine, Transition};
+
+    pub struct GateContext {
+        pub opened: u32,
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum GateEvent {
+        Open,
+        Close,
+        Ping,
+    }
+
+    state_machine! {
+        Name: Gate,
+        Context: GateContext,
+        Event: GateEvent,
+        StrictLints: true,
+        OnUnhandled: |ctx, state_name, evt| {},
+        EventFilter: |ctx, evt| -> bool { true },
+        Mixin: Pinging => {
+            process_fragment: |ctx, evt| {
+                match evt {
+                    GateEvent::Ping => Transition::None,
+                    _ => Transition::Unhandled,
+                }
+            }
+        },
+
+        States: {
+            Closed { since: u32 } => {
+                use: [Pinging],
+                entry: |ctx| {}
+                process: |ctx, evt| {
+                    match evt {
+                        GateEvent::Open => {
+                            ctx.opened += 1;
+                            Transition::To(Gate::Open { at: ctx.opened })
+                        }
+                        _ => Transition::None,
+                    }
+                }
+                exit: |ctx| {}
+            },
+
+            Open { at: u32 } => {
+                process: |ctx, evt| {
+                    match evt {
+                        GateEvent::Close => Transition::To(Gate::Closed { since: 0 }),
+                        _ => Transition::Unhandled,
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_strict_lints_machine_runs() {
+    use strict_lints::{Gate, GateContext, GateEvent};
+
+    let mut ctx = GateContext { opened: 0 };
+    let mut gate = Gate::start_in(Gate::Closed { since: 0 }, &mut ctx);
+
+    gate.dispatch(&mut ctx, &GateEvent::Ping);
+    assert!(matches!(gate, Gate::Closed { .. }));
+
+    gate.dispatch(&mut ctx, &GateEvent::Open);
+    assert!(matches!(gate, Gate::Open { at: 1 }));
+
+    gate.dispatch(&mut ctx, &GateEvent::Ping);
+    gate.dispatch(&mut ctx, &GateEvent::Close);
+    assert!(matches!(gate, Gate::Closed { since: 0 }));
+}
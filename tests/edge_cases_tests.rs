@@ -615,3 +615,60 @@ fn test_if_let_patterns() {
     assert_eq!(ctx.some_count, 15);
     assert_eq!(ctx.none_count, 2);
 }
+
+// ============================================================================
+// Test 9: Re-entrant dispatch (debug builds of the default implementation)
+// ============================================================================
+
+#[cfg(all(feature = "std", debug_assertions, not(feature = "concurrent")))]
+mod reentrant_dispatch {
+    use super::*;
+
+    struct EchoContext {
+        // A second machine of the same type, dispatched from inside `process`
+        peer: Option<Echo>,
+    }
+
+    #[derive(Debug, Clone)]
+    enum EchoEvent {
+        Ping,
+        Forward,
+    }
+
+    state_machine! {
+        Name: Echo,
+        Context: EchoContext,
+        Event: EchoEvent,
+
+        States: {
+            Listening => {
+                process: |ctx, evt| {
+                    if let EchoEvent::Forward = evt {
+                        let mut peer = ctx.peer.take().unwrap();
+                        peer.dispatch(ctx, &EchoEvent::Ping);
+                    }
+                    Transition::None
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "[Echo] dispatch() re-entered")]
+    fn test_reentrant_dispatch_panics() {
+        let mut ctx = EchoContext {
+            peer: Some(Echo::Listening),
+        };
+        let mut echo = Echo::start_in(Echo::Listening, &mut ctx);
+        echo.dispatch(&mut ctx, &EchoEvent::Forward);
+    }
+
+    #[test]
+    fn test_sequential_dispatches_are_not_reentrant() {
+        let mut ctx = EchoContext { peer: None };
+        let mut echo = Echo::start_in(Echo::Listening, &mut ctx);
+        echo.dispatch(&mut ctx, &EchoEvent::Ping);
+        echo.dispatch_owned(&mut ctx, EchoEvent::Ping);
+        echo.dispatch_then(&mut ctx, &EchoEvent::Ping, |_| {});
+    }
+}
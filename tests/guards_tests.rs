@@ -5,6 +5,7 @@
 //! - Guards allow valid transitions
 //! - Multiple guard conditions (AND/OR logic)
 //! - Context mutations in guard checks
+//! - Match guards (`Event::X if ctx.flag =>`) reading and mutating the context
 
 use typed_fsm::{state_machine, Transition};
 
@@ -480,3 +481,144 @@ fn test_guard_list_unauthorized_user() {
 
     assert!(matches!(access, AccessControl::Denied));
 }
+
+// ============================================================================
+// Test 5: Match Guards Referencing the Context
+// ============================================================================
+
+struct TurnstileContext {
+    coins: u32,
+    maintenance: bool,
+    passages: u32,
+}
+
+impl TurnstileContext {
+    /// Spends a coin if there is one (a guard with a `&mut` borrow)
+    fn take_coin(&mut self) -> bool {
+        if self.coins > 0 {
+            self.coins -= 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum TurnstileEvent {
+    Push,
+    Coin,
+    Service,
+}
+
+state_machine! {
+    Name: Turnstile,
+    Context: TurnstileContext,
+    Event: TurnstileEvent,
+    ExhaustiveEvents: true,
+
+    States: {
+        Locked => {
+            process: |ctx, evt| {
+                match evt {
+                    TurnstileEvent::Push if ctx.maintenance => {
+                        // The guard's shared borrow has ended: the arm may mutate
+                        ctx.passages += 1;
+                        Transition::None
+                    }
+                    TurnstileEvent::Push if ctx.take_coin() => Transition::To(Turnstile::Open { pushes: 0 }),
+                    TurnstileEvent::Push => Transition::None,
+                    TurnstileEvent::Coin => {
+                        ctx.coins += 1;
+                        Transition::None
+                    }
+                    _ if ctx.maintenance => Transition::None,
+                    TurnstileEvent::Service => Transition::To(Turnstile::OutOfService),
+                }
+            }
+        },
+
+        Open { pushes: u32 } => {
+            process: |ctx, evt| {
+                match evt {
+                    // Guards on the state's fields and the context together
+                    TurnstileEvent::Push if *pushes == 0 && !ctx.maintenance => {
+                        *pushes += 1;
+                        ctx.passages += 1;
+                        Transition::To(Turnstile::Locked)
+                    }
+                    TurnstileEvent::Push | TurnstileEvent::Service => Transition::None,
+                    TurnstileEvent::Coin => {
+                        ctx.coins += 1;
+                        Transition::None
+                    }
+                }
+            }
+        },
+
+        OutOfService => readonly {
+            process: |ctx, evt| {
+                match evt {
+                    TurnstileEvent::Service if ctx.coins == 0 => Transition::To(Turnstile::Locked),
+                    TurnstileEvent::Service | TurnstileEvent::Push | TurnstileEvent::Coin => {
+                        Transition::None
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_match_guards_reference_context() {
+    let mut ctx = TurnstileContext {
+        coins: 0,
+        maintenance: false,
+        passages: 0,
+    };
+    let mut turnstile = Turnstile::start_in(Turnstile::Locked, &mut ctx);
+
+    // No coin: `take_coin()` guard fails, the next arm handles the push
+    turnstile.dispatch(&mut ctx, &TurnstileEvent::Push);
+    assert!(matches!(turnstile, Turnstile::Locked));
+
+    // The guard spends the coin and the event transitions
+    turnstile.dispatch(&mut ctx, &TurnstileEvent::Coin);
+    turnstile.dispatch(&mut ctx, &TurnstileEvent::Push);
+    assert!(matches!(turnstile, Turnstile::Open { pushes: 0 }));
+    assert_eq!(ctx.coins, 0);
+
+    turnstile.dispatch(&mut ctx, &TurnstileEvent::Push);
+    assert!(matches!(turnstile, Turnstile::Locked));
+    assert_eq!(ctx.passages, 1);
+}
+
+#[test]
+fn test_match_guards_pick_arm_by_context_flag() {
+    let mut ctx = TurnstileContext {
+        coins: 1,
+        maintenance: true,
+        passages: 0,
+    };
+    let mut turnstile = Turnstile::start_in(Turnstile::Locked, &mut ctx);
+
+    // Same event, other arm: maintenance lets people through for free
+    turnstile.dispatch(&mut ctx, &TurnstileEvent::Push);
+    assert!(matches!(turnstile, Turnstile::Locked));
+    assert_eq!((ctx.passages, ctx.coins), (1, 1));
+
+    // Guarded `_` arm holds `Service` back during maintenance
+    turnstile.dispatch(&mut ctx, &TurnstileEvent::Service);
+    assert!(matches!(turnstile, Turnstile::Locked));
+
+    ctx.maintenance = false;
+    turnstile.dispatch(&mut ctx, &TurnstileEvent::Service);
+    assert!(matches!(turnstile, Turnstile::OutOfService));
+
+    // Readonly state: the guard reads the shared context
+    turnstile.dispatch(&mut ctx, &TurnstileEvent::Service);
+    assert!(matches!(turnstile, Turnstile::OutOfService));
+    ctx.coins = 0;
+    turnstile.dispatch(&mut ctx, &TurnstileEvent::Service);
+    assert!(matches!(turnstile, Turnstile::Locked));
+}
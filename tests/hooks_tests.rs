@@ -0,0 +1,643 @@
+//! Tests for machine-level hooks
+//!
+//! This test suite validates the optional hooks declared at the machine level:
+//! - `OnUnhandled` runs when `process` returns `Transition::Unhandled`
+//! - `OnUnhandled` does NOT run for an explicit `Transition::None`
+//! - `entry`/`exit` optionally receive the name of the counterpart state
+//! - `Watchdog`/`OnStuck` fires after N consecutive dispatches without a transition
+//! - `entry` optionally receives `is_init`, true only for the entry run by `init()`
+//! - `AnyEntry`/`AnyExit` wrap the per-state `entry`/`exit` of every state
+//! - `TransitionObserver` calls a swappable `fn` pointer stored in the context
+//! - `ProcessPrelude` runs once before `process` for every event, in every state
+
+use typed_fsm::{state_machine, Bookkeeping, Transition};
+
+// ============================================================================
+// Test 1: OnUnhandled Hook
+// ============================================================================
+
+struct UnhandledContext {
+    unhandled: Vec<(&'static str, UnhandledEvent)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum UnhandledEvent {
+    Start,
+    Stop,
+    Ignore,
+    Bogus,
+}
+
+state_machine! {
+    Name: UnhandledMachine,
+    Context: UnhandledContext,
+    Event: UnhandledEvent,
+
+    OnUnhandled: |ctx, state_name, evt| {
+        ctx.unhandled.push((state_name, evt.clone()));
+    },
+
+    States: {
+        Idle => {
+            process: |_ctx, evt| {
+                match evt {
+                    UnhandledEvent::Start => Transition::To(UnhandledMachine::Running),
+                    UnhandledEvent::Ignore => Transition::None,
+                    _ => Transition::Unhandled
+                }
+            }
+        },
+
+        Running => {
+            process: |_ctx, evt| {
+                match evt {
+                    UnhandledEvent::Stop => Transition::To(UnhandledMachine::Idle),
+                    UnhandledEvent::Ignore => Transition::None,
+                    _ => Transition::Unhandled
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_unhandled_triggers_hook() {
+    let mut ctx = UnhandledContext {
+        unhandled: Vec::new(),
+    };
+    let mut fsm = UnhandledMachine::Idle;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &UnhandledEvent::Bogus);
+    assert!(matches!(fsm, UnhandledMachine::Idle));
+    assert_eq!(ctx.unhandled, vec![("Idle", UnhandledEvent::Bogus)]);
+
+    fsm.dispatch(&mut ctx, &UnhandledEvent::Start);
+    fsm.dispatch(&mut ctx, &UnhandledEvent::Start);
+    assert!(matches!(fsm, UnhandledMachine::Running));
+    assert_eq!(
+        ctx.unhandled,
+        vec![
+            ("Idle", UnhandledEvent::Bogus),
+            ("Running", UnhandledEvent::Start)
+        ]
+    );
+}
+
+#[test]
+fn test_explicit_none_does_not_trigger_hook() {
+    let mut ctx = UnhandledContext {
+        unhandled: Vec::new(),
+    };
+    let mut fsm = UnhandledMachine::Idle;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &UnhandledEvent::Ignore);
+    fsm.dispatch(&mut ctx, &UnhandledEvent::Start);
+    fsm.dispatch(&mut ctx, &UnhandledEvent::Ignore);
+    assert!(matches!(fsm, UnhandledMachine::Running));
+
+    fsm.dispatch(&mut ctx, &UnhandledEvent::Stop);
+    assert!(matches!(fsm, UnhandledMachine::Idle));
+    assert!(ctx.unhandled.is_empty());
+}
+
+// ============================================================================
+// Test 2: Unhandled without an OnUnhandled hook
+// ============================================================================
+
+struct PlainContext {
+    processed: u32,
+}
+
+#[derive(Debug, Clone)]
+enum PlainEvent {
+    Tick,
+}
+
+state_machine! {
+    Name: PlainMachine,
+    Context: PlainContext,
+    Event: PlainEvent,
+
+    States: {
+        Only => {
+            process: |ctx, _evt| {
+                ctx.processed += 1;
+                Transition::Unhandled
+            }
+        }
+    }
+}
+
+#[test]
+fn test_unhandled_without_hook_stays() {
+    let mut ctx = PlainContext { processed: 0 };
+    let mut fsm = PlainMachine::Only;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &PlainEvent::Tick);
+    fsm.dispatch(&mut ctx, &PlainEvent::Tick);
+
+    assert!(matches!(fsm, PlainMachine::Only));
+    assert_eq!(ctx.processed, 2);
+    assert_eq!(fsm.current_state_name(), "Only");
+}
+
+// ============================================================================
+// Test 3: Two-argument entry/exit (counterpart state names)
+// ============================================================================
+
+struct RouteContext {
+    log: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum RouteEvent {
+    Next,
+}
+
+state_machine! {
+    Name: RouteMachine,
+    Context: RouteContext,
+    Event: RouteEvent,
+
+    States: {
+        A => {
+            entry: |ctx, from_name| {
+                ctx.log.push(format!("A.entry from {}", from_name));
+            }
+
+            process: |_ctx, _evt| {
+                Transition::To(RouteMachine::B)
+            }
+
+            exit: |ctx, to_name| {
+                ctx.log.push(format!("A.exit to {}", to_name));
+            }
+        },
+
+        B => {
+            entry: |ctx, from_name| {
+                ctx.log.push(format!("B.entry from {}", from_name));
+            }
+
+            process: |_ctx, _evt| {
+                Transition::To(RouteMachine::C)
+            }
+
+            exit: |ctx| {
+                ctx.log.push("B.exit".to_string());
+            }
+        },
+
+        C => {
+            entry: |ctx| {
+                ctx.log.push("C.entry".to_string());
+            }
+
+            process: |_ctx, evt| {
+                match evt {
+                    RouteEvent::Next => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_entry_exit_receive_counterpart_names() {
+    let mut ctx = RouteContext { log: Vec::new() };
+    let mut fsm = RouteMachine::A;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &RouteEvent::Next);
+    assert!(matches!(fsm, RouteMachine::B));
+    assert_eq!(
+        ctx.log,
+        vec!["A.entry from A", "A.exit to B", "B.entry from A"]
+    );
+}
+
+#[test]
+fn test_one_and_two_arg_hooks_mix() {
+    let mut ctx = RouteContext { log: Vec::new() };
+    let mut fsm = RouteMachine::B;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &RouteEvent::Next);
+    fsm.dispatch(&mut ctx, &RouteEvent::Next);
+    assert!(matches!(fsm, RouteMachine::C));
+    assert_eq!(ctx.log, vec!["B.entry from B", "B.exit", "C.entry"]);
+}
+
+// ============================================================================
+// Test 4: Watchdog / OnStuck
+// ============================================================================
+
+struct WatchdogContext {
+    stuck: Vec<&'static str>,
+    fsm: Bookkeeping,
+}
+
+impl WatchdogContext {
+    fn new() -> Self {
+        Self {
+            stuck: Vec::new(),
+            fsm: Bookkeeping::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum WatchdogEvent {
+    Poll,
+    Bogus,
+    Advance,
+}
+
+state_machine! {
+    Name: WatchdogMachine,
+    Context: WatchdogContext,
+    Event: WatchdogEvent,
+
+    Watchdog: 3,
+    OnStuck: |ctx, state_name| {
+        ctx.stuck.push(state_name);
+    },
+    Bookkeeping: fsm,
+
+    States: {
+        Waiting => {
+            process: |_ctx, evt| {
+                match evt {
+                    WatchdogEvent::Poll => Transition::None,
+                    WatchdogEvent::Bogus => Transition::Unhandled,
+                    WatchdogEvent::Advance => Transition::To(WatchdogMachine::Done),
+                }
+            }
+        },
+
+        Done => {
+            process: |_ctx, evt| {
+                match evt {
+                    WatchdogEvent::Advance => Transition::To(WatchdogMachine::Waiting),
+                    _ => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_watchdog_fires_once_and_resets_on_transition() {
+    let mut ctx = WatchdogContext::new();
+    let mut fsm = WatchdogMachine::Waiting;
+    fsm.init(&mut ctx);
+
+    // Two no-op dispatches: below the limit
+    fsm.dispatch(&mut ctx, &WatchdogEvent::Poll);
+    fsm.dispatch(&mut ctx, &WatchdogEvent::Poll);
+    assert!(ctx.stuck.is_empty());
+
+    // Third consecutive no-op (Unhandled counts too): fires once
+    fsm.dispatch(&mut ctx, &WatchdogEvent::Bogus);
+    assert_eq!(ctx.stuck, vec!["Waiting"]);
+
+    // Staying longer does not fire again
+    for _ in 0..5 {
+        fsm.dispatch(&mut ctx, &WatchdogEvent::Poll);
+    }
+    assert_eq!(ctx.stuck, vec!["Waiting"]);
+
+    // A real transition resets the count
+    fsm.dispatch(&mut ctx, &WatchdogEvent::Advance);
+    fsm.dispatch(&mut ctx, &WatchdogEvent::Poll);
+    fsm.dispatch(&mut ctx, &WatchdogEvent::Poll);
+    assert_eq!(ctx.stuck, vec!["Waiting"]);
+
+    fsm.dispatch(&mut ctx, &WatchdogEvent::Poll);
+    assert_eq!(ctx.stuck, vec!["Waiting", "Done"]);
+}
+
+#[test]
+fn test_watchdog_counts_per_instance() {
+    let mut stuck_ctx = WatchdogContext::new();
+    let mut stuck = WatchdogMachine::start_in(WatchdogMachine::Waiting, &mut stuck_ctx);
+    let mut busy_ctx = WatchdogContext::new();
+    let mut busy = WatchdogMachine::start_in(WatchdogMachine::Waiting, &mut busy_ctx);
+
+    // The other machine's transitions neither reset nor add to the stuck one's count
+    for _ in 0..2 {
+        stuck.dispatch(&mut stuck_ctx, &WatchdogEvent::Poll);
+        busy.dispatch(&mut busy_ctx, &WatchdogEvent::Advance);
+    }
+    assert!(stuck_ctx.stuck.is_empty());
+
+    stuck.dispatch(&mut stuck_ctx, &WatchdogEvent::Poll);
+    assert_eq!(stuck_ctx.stuck, vec!["Waiting"]);
+    assert!(busy_ctx.stuck.is_empty());
+}
+
+// ============================================================================
+// Test 5: AnyEntry / AnyExit
+// ============================================================================
+
+struct AnyContext {
+    trace: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum AnyEvent {
+    Next,
+    Restart,
+}
+
+state_machine! {
+    Name: AnyMachine,
+    Context: AnyContext,
+    Event: AnyEvent,
+    AnyEntry: |ctx, state_name| {
+        ctx.trace.push(format!("any entry {}", state_name));
+    },
+    AnyExit: |ctx, state_name| {
+        ctx.trace.push(format!("any exit {}", state_name));
+    },
+
+    States: {
+        Green => {
+            entry: |ctx| { ctx.trace.push("entry Green".to_string()); }
+            process: |_ctx, evt| {
+                match evt {
+                    AnyEvent::Next => Transition::To(AnyMachine::Red),
+                    AnyEvent::Restart => Transition::Reenter,
+                }
+            }
+            exit: |ctx| { ctx.trace.push("exit Green".to_string()); }
+        },
+
+        Red => {
+            process: |_ctx, _evt| {
+                Transition::To(AnyMachine::Green)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_any_hooks_wrap_per_state_hooks() {
+    let mut ctx = AnyContext { trace: Vec::new() };
+    let mut fsm = AnyMachine::Green;
+
+    fsm.init(&mut ctx);
+    assert_eq!(ctx.trace, vec!["entry Green", "any entry Green"]);
+    ctx.trace.clear();
+
+    // Red has no per-state hooks: the any-hooks still fire
+    fsm.dispatch(&mut ctx, &AnyEvent::Next);
+    assert_eq!(
+        ctx.trace,
+        vec!["any exit Green", "exit Green", "any entry Red"]
+    );
+    ctx.trace.clear();
+
+    fsm.dispatch(&mut ctx, &AnyEvent::Next);
+    assert_eq!(
+        ctx.trace,
+        vec!["any exit Red", "entry Green", "any entry Green"]
+    );
+    ctx.trace.clear();
+
+    // Reenter runs the whole exit/entry sequence for the same state
+    fsm.dispatch(&mut ctx, &AnyEvent::Restart);
+    assert_eq!(
+        ctx.trace,
+        vec![
+            "any exit Green",
+            "exit Green",
+            "entry Green",
+            "any entry Green"
+        ]
+    );
+}
+
+// ============================================================================
+// Test 6: is_init entry argument
+// ============================================================================
+
+struct InitFlagContext {
+    entries: Vec<(&'static str, bool)>,
+}
+
+#[derive(Debug, Clone)]
+enum InitFlagEvent {
+    Toggle,
+    Restart,
+}
+
+state_machine! {
+    Name: InitFlagMachine,
+    Context: InitFlagContext,
+    Event: InitFlagEvent,
+
+    States: {
+        Idle => {
+            entry: |ctx, _from, is_init| {
+                ctx.entries.push(("Idle", is_init));
+            }
+            process: |_ctx, evt| {
+                match evt {
+                    InitFlagEvent::Toggle => Transition::To(InitFlagMachine::Busy),
+                    InitFlagEvent::Restart => Transition::Reenter,
+                }
+            }
+        },
+
+        Busy => {
+            entry: |ctx, _from, is_init| {
+                ctx.entries.push(("Busy", is_init));
+            }
+            process: |_ctx, _evt| {
+                Transition::To(InitFlagMachine::Idle)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_is_init_true_only_on_init() {
+    let mut ctx = InitFlagContext {
+        entries: Vec::new(),
+    };
+    let mut fsm = InitFlagMachine::Idle;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &InitFlagEvent::Toggle);
+    fsm.dispatch(&mut ctx, &InitFlagEvent::Toggle);
+    fsm.dispatch(&mut ctx, &InitFlagEvent::Restart);
+    fsm.reentry(&mut ctx);
+
+    assert_eq!(
+        ctx.entries,
+        vec![
+            ("Idle", true),
+            ("Busy", false),
+            ("Idle", false),
+            ("Idle", false),
+            ("Idle", false),
+        ]
+    );
+
+    // start_in() runs init() on the new machine
+    let _busy = InitFlagMachine::start_in(InitFlagMachine::Busy, &mut ctx);
+    assert_eq!(ctx.entries.last(), Some(&("Busy", true)));
+}
+
+// ============================================================================
+// Test 7: TransitionObserver
+// ============================================================================
+
+use std::sync::Mutex;
+
+static OBSERVED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn record_arrow(from: &str, to: &str) {
+    OBSERVED.lock().unwrap().push(format!("{}->{}", from, to));
+}
+
+fn record_target(_from: &str, to: &str) {
+    OBSERVED.lock().unwrap().push(to.to_string());
+}
+
+struct ObservedContext {
+    observer: fn(&str, &str),
+}
+
+#[derive(Debug, Clone)]
+enum ObservedEvent {
+    Next,
+    Stay,
+}
+
+state_machine! {
+    Name: ObservedMachine,
+    Context: ObservedContext,
+    Event: ObservedEvent,
+    TransitionObserver: observer,
+
+    States: {
+        Green => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, ObservedEvent::Next), ObservedMachine::Yellow)
+            }
+        },
+
+        Yellow => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, ObservedEvent::Next), ObservedMachine::Red)
+            }
+        },
+
+        Red => {
+            process: |_ctx, evt| {
+                match evt {
+                    ObservedEvent::Next => Transition::To(ObservedMachine::Green),
+                    ObservedEvent::Stay => Transition::Reenter,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_transition_observer_can_be_swapped() {
+    let mut ctx = ObservedContext {
+        observer: record_arrow,
+    };
+    let mut fsm = ObservedMachine::start_in(ObservedMachine::Green, &mut ctx);
+
+    fsm.dispatch(&mut ctx, &ObservedEvent::Next);
+    // Only `Transition::To` is observed (not None or Reenter)
+    fsm.dispatch(&mut ctx, &ObservedEvent::Stay);
+    fsm.dispatch(&mut ctx, &ObservedEvent::Next);
+    fsm.dispatch(&mut ctx, &ObservedEvent::Stay);
+
+    ctx.observer = record_target;
+    fsm.dispatch(&mut ctx, &ObservedEvent::Next);
+
+    assert_eq!(
+        *OBSERVED.lock().unwrap(),
+        vec!["Green->Yellow", "Yellow->Red", "Green"]
+    );
+}
+
+// ============================================================================
+// Test 8: ProcessPrelude
+// ============================================================================
+
+struct PreludeContext {
+    trace: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum PreludeEvent {
+    Ping,
+    Toggle,
+}
+
+state_machine! {
+    Name: PreludeMachine,
+    Context: PreludeContext,
+    Event: PreludeEvent,
+    ProcessPrelude: |ctx, evt| {
+        ctx.trace.push(format!("prelude {:?}", evt));
+    },
+
+    States: {
+        Off => {
+            process: |ctx, evt| {
+                ctx.trace.push("process Off".to_string());
+                match evt {
+                    PreludeEvent::Toggle => Transition::To(PreludeMachine::On),
+                    PreludeEvent::Ping => Transition::None,
+                }
+            }
+        },
+
+        On => {
+            process: |ctx, evt| {
+                ctx.trace.push("process On".to_string());
+                match evt {
+                    PreludeEvent::Toggle => Transition::To(PreludeMachine::Off),
+                    PreludeEvent::Ping => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_process_prelude_runs_once_per_event_in_every_state() {
+    let mut ctx = PreludeContext { trace: Vec::new() };
+    let mut fsm = PreludeMachine::start_in(PreludeMachine::Off, &mut ctx);
+    assert!(ctx.trace.is_empty()); // init() processes no event
+
+    fsm.dispatch(&mut ctx, &PreludeEvent::Ping);
+    fsm.dispatch(&mut ctx, &PreludeEvent::Toggle);
+    fsm.dispatch(&mut ctx, &PreludeEvent::Ping);
+    fsm.dispatch(&mut ctx, &PreludeEvent::Toggle);
+
+    assert_eq!(
+        ctx.trace,
+        vec![
+            "prelude Ping",
+            "process Off",
+            "prelude Toggle",
+            "process Off",
+            "prelude Ping",
+            "process On",
+            "prelude Toggle",
+            "process On",
+        ]
+    );
+}
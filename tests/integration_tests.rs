@@ -1,6 +1,8 @@
 //! Integration tests for the finite_state_machine library
 
-use typed_fsm::{state_machine, Transition};
+use typed_fsm::{
+    compose_fsms, state_machine, Bookkeeping, DispatchOutcome, Fsm, StaleGen, Transition,
+};
 
 // ============================================================================
 // Test 1: Simple Toggle State Machine
@@ -415,6 +417,52 @@ fn test_init_not_called_skips_initial_entry() {
     assert_eq!(ctx.entry_call_count, 1); // Only Active entry was called
 }
 
+#[test]
+fn test_reentry_applies_entry_to_new_context() {
+    let mut ctx = InitContext {
+        entry_called: false,
+        entry_call_count: 0,
+    };
+    let mut fsm = InitFSM::Initial;
+    fsm.init(&mut ctx);
+    assert_eq!(ctx.entry_call_count, 1);
+
+    // Swap the whole context: the new one has never seen the entry action
+    let mut new_ctx = InitContext {
+        entry_called: false,
+        entry_call_count: 0,
+    };
+    fsm.reentry(&mut new_ctx);
+
+    assert!(matches!(fsm, InitFSM::Initial));
+    assert!(new_ctx.entry_called);
+    assert_eq!(new_ctx.entry_call_count, 1);
+    assert_eq!(ctx.entry_call_count, 1); // Old context untouched
+}
+
+#[test]
+fn test_reentry_runs_only_current_state_entry() {
+    let mut ctx = InitContext {
+        entry_called: false,
+        entry_call_count: 0,
+    };
+    let mut fsm = InitFSM::Initial;
+    fsm.init(&mut ctx);
+    fsm.dispatch(&mut ctx, &InitEvent::Trigger);
+    assert!(matches!(fsm, InitFSM::Active));
+
+    let mut new_ctx = InitContext {
+        entry_called: false,
+        entry_call_count: 0,
+    };
+    fsm.reentry(&mut new_ctx);
+
+    // Only Active's entry ran: Initial's entry (which sets entry_called) did not
+    assert!(matches!(fsm, InitFSM::Active));
+    assert!(!new_ctx.entry_called);
+    assert_eq!(new_ctx.entry_call_count, 1);
+}
+
 // ============================================================================
 // Test 7: Blink Pattern (like the blink.rs example)
 // ============================================================================
@@ -832,3 +880,2641 @@ fn test_concurrent_fsm_synchronization() {
     let ctx_lock = ctx.lock().unwrap();
     assert_eq!(ctx_lock.counter, 30); // 3 threads * 10 increments = 30
 }
+
+// ============================================================================
+// Test 12: Transition::Reenter (restart lifecycle, keep fields)
+// ============================================================================
+
+struct SessionContext {
+    lifecycle: Vec<String>,
+    elapsed: u32,
+}
+
+#[derive(Debug, Clone)]
+enum SessionEvent {
+    Activity,
+    Tick(u32),
+}
+
+state_machine! {
+    Name: SessionFSM,
+    Context: SessionContext,
+    Event: SessionEvent,
+
+    States: {
+        Active { user: u32, logins: u32 } => {
+            entry: |ctx, from_name| {
+                ctx.elapsed = 0;
+                ctx.lifecycle.push(format!("entry from {}", from_name));
+            }
+
+            process: |ctx, evt| {
+                match evt {
+                    SessionEvent::Activity => Transition::Reenter,
+                    SessionEvent::Tick(ms) => {
+                        ctx.elapsed += ms;
+                        Transition::None
+                    }
+                }
+            }
+
+            exit: |ctx, to_name| {
+                ctx.lifecycle.push(format!("exit to {}", to_name));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_reenter_runs_exit_and_entry_keeping_fields() {
+    let mut ctx = SessionContext {
+        lifecycle: Vec::new(),
+        elapsed: 0,
+    };
+    let mut fsm = SessionFSM::Active { user: 7, logins: 3 };
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &SessionEvent::Tick(250));
+    assert_eq!(ctx.elapsed, 250);
+
+    fsm.dispatch(&mut ctx, &SessionEvent::Activity);
+
+    // exit + entry ran (entry reset the timer)...
+    assert_eq!(
+        ctx.lifecycle,
+        vec!["entry from Active", "exit to Active", "entry from Active"]
+    );
+    assert_eq!(ctx.elapsed, 0);
+
+    // ...and the carried fields are unchanged
+    match fsm {
+        SessionFSM::Active { user, logins } => {
+            assert_eq!(user, 7);
+            assert_eq!(logins, 3);
+        }
+    }
+}
+
+// ============================================================================
+// Test 13: Declared Initial states (start_in / start_default)
+// ============================================================================
+
+struct BootContext {
+    booted_into: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+enum BootEvent {
+    Fault,
+}
+
+state_machine! {
+    Name: BootFSM,
+    Context: BootContext,
+    Event: BootEvent,
+    Initial: [Idle, SafeMode],
+
+    States: {
+        Idle => {
+            entry: |ctx| { ctx.booted_into.push("Idle"); }
+            process: |_ctx, evt| {
+                match evt {
+                    BootEvent::Fault => Transition::To(BootFSM::Faulted { code: 1 }),
+                }
+            }
+        },
+
+        SafeMode => {
+            entry: |ctx| { ctx.booted_into.push("SafeMode"); }
+            process: |_ctx, _evt| {
+                Transition::None
+            }
+        },
+
+        Faulted { code: u8 } => {
+            process: |_ctx, _evt| {
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_initial_states_declared_in_order() {
+    assert_eq!(BootFSM::INITIAL_STATES, ["Idle", "SafeMode"]);
+    assert!(InitFSM::INITIAL_STATES.is_empty());
+}
+
+#[test]
+fn test_start_default_in_each_initial_state() {
+    let mut ctx = BootContext {
+        booted_into: Vec::new(),
+    };
+
+    let fsm = BootFSM::start_default(0, &mut ctx);
+    assert!(matches!(fsm, BootFSM::Idle));
+
+    let fsm = BootFSM::start_default(1, &mut ctx);
+    assert!(matches!(fsm, BootFSM::SafeMode));
+
+    // Entry fired for each boot
+    assert_eq!(ctx.booted_into, vec!["Idle", "SafeMode"]);
+}
+
+#[test]
+fn test_start_in_runs_entry() {
+    let mut ctx = BootContext {
+        booted_into: Vec::new(),
+    };
+    let mut fsm = BootFSM::start_in(BootFSM::SafeMode, &mut ctx);
+    assert_eq!(ctx.booted_into, vec!["SafeMode"]);
+
+    fsm.dispatch(&mut ctx, &BootEvent::Fault);
+    assert!(matches!(fsm, BootFSM::SafeMode));
+}
+
+#[test]
+#[should_panic(expected = "only 2 Initial states declared")]
+fn test_start_default_out_of_range_panics() {
+    let mut ctx = BootContext {
+        booted_into: Vec::new(),
+    };
+    let _ = BootFSM::start_default(2, &mut ctx);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "not a declared Initial state")]
+fn test_start_in_undeclared_state_panics_in_debug() {
+    let mut ctx = BootContext {
+        booted_into: Vec::new(),
+    };
+    let _ = BootFSM::start_in(BootFSM::Faulted { code: 9 }, &mut ctx);
+}
+
+// ============================================================================
+// Test 14: Context-free State Machine (no `Context:` section)
+// ============================================================================
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+static LAMP_ENTRIES: AtomicU32 = AtomicU32::new(0);
+static LAMP_UNHANDLED: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Clone)]
+enum LampEvent {
+    Toggle,
+    Dim,
+}
+
+state_machine! {
+    Name: LampFSM,
+    Event: LampEvent,
+    OnUnhandled: |_state, _evt| {
+        LAMP_UNHANDLED.fetch_add(1, Ordering::SeqCst);
+    },
+
+    States: {
+        Off => {
+            entry: || {
+                LAMP_ENTRIES.fetch_add(1, Ordering::SeqCst);
+            }
+
+            process: |evt| {
+                match evt {
+                    LampEvent::Toggle => Transition::To(LampFSM::On),
+                    LampEvent::Dim => Transition::Unhandled,
+                }
+            }
+        },
+
+        On => {
+            entry: |from| {
+                assert_eq!(from, "Off");
+                LAMP_ENTRIES.fetch_add(1, Ordering::SeqCst);
+            }
+
+            process: |evt| {
+                match evt {
+                    LampEvent::Toggle => Transition::To(LampFSM::Off),
+                    LampEvent::Dim => Transition::None,
+                }
+            }
+
+            exit: |to| {
+                assert_eq!(to, "Off");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_context_free_machine() {
+    let mut fsm = LampFSM::Off;
+    fsm.init();
+    assert_eq!(LAMP_ENTRIES.load(Ordering::SeqCst), 1);
+
+    fsm.dispatch(&LampEvent::Toggle);
+    assert!(matches!(fsm, LampFSM::On));
+    assert_eq!(LAMP_ENTRIES.load(Ordering::SeqCst), 2);
+
+    fsm.dispatch(&LampEvent::Dim);
+    fsm.dispatch_owned(LampEvent::Toggle);
+    assert!(matches!(fsm, LampFSM::Off));
+    assert_eq!(LAMP_ENTRIES.load(Ordering::SeqCst), 3);
+
+    fsm.dispatch(&LampEvent::Dim);
+    assert_eq!(LAMP_UNHANDLED.load(Ordering::SeqCst), 1);
+}
+
+// ============================================================================
+// Test 15: run_until() simulation driver
+// ============================================================================
+
+struct PipelineContext {
+    advanced: u32,
+}
+
+#[derive(Debug, Clone)]
+enum PipelineEvent {
+    Advance,
+    Noise,
+}
+
+state_machine! {
+    Name: PipelineFSM,
+    Context: PipelineContext,
+    Event: PipelineEvent,
+
+    States: {
+        Fetch => {
+            process: |ctx, evt| {
+                match evt {
+                    PipelineEvent::Advance => { ctx.advanced += 1; Transition::To(PipelineFSM::Decode) }
+                    PipelineEvent::Noise => Transition::None,
+                }
+            }
+        },
+
+        Decode => {
+            process: |ctx, evt| {
+                match evt {
+                    PipelineEvent::Advance => { ctx.advanced += 1; Transition::To(PipelineFSM::Done) }
+                    PipelineEvent::Noise => Transition::None,
+                }
+            }
+        },
+
+        Done => {
+            process: |_ctx, _evt| {
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_run_until_stops_at_terminal_state() {
+    let mut ctx = PipelineContext { advanced: 0 };
+    let mut fsm = PipelineFSM::start_in(PipelineFSM::Fetch, &mut ctx);
+
+    let mut events = [
+        PipelineEvent::Noise,
+        PipelineEvent::Advance,
+        PipelineEvent::Noise,
+        PipelineEvent::Advance,
+        PipelineEvent::Advance,
+    ]
+    .into_iter();
+
+    let processed = fsm.run_until(&mut ctx, "Done", || events.next());
+    assert_eq!(processed, 4);
+    assert!(matches!(fsm, PipelineFSM::Done));
+    assert_eq!(ctx.advanced, 2);
+
+    // The event after the terminal state was never pulled
+    assert_eq!(events.len(), 1);
+
+    // Already in the stop state: nothing is pulled
+    assert_eq!(fsm.run_until(&mut ctx, "Done", || events.next()), 0);
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+fn test_run_until_stops_when_events_run_out() {
+    let mut ctx = PipelineContext { advanced: 0 };
+    let mut fsm = PipelineFSM::start_in(PipelineFSM::Fetch, &mut ctx);
+
+    let mut events = vec![PipelineEvent::Noise, PipelineEvent::Advance].into_iter();
+    let processed = fsm.run_until(&mut ctx, "Done", || events.next());
+
+    assert_eq!(processed, 2);
+    assert!(matches!(fsm, PipelineFSM::Decode));
+}
+
+// ============================================================================
+// Test 16: SharedRef (read-only data passed to process)
+// ============================================================================
+
+struct ThermostatConfig {
+    heat_below: i32,
+    cool_above: i32,
+}
+
+struct ThermostatContext {
+    readings: u32,
+    switches: u32,
+}
+
+#[derive(Debug, Clone)]
+enum ThermostatEvent {
+    Reading(i32),
+}
+
+state_machine! {
+    Name: ThermostatFSM,
+    Context: ThermostatContext,
+    Event: ThermostatEvent,
+    SharedRef: ThermostatConfig,
+
+    States: {
+        Idle => {
+            process: |ctx, evt, config| {
+                let ThermostatEvent::Reading(t) = evt;
+                ctx.readings += 1;
+                if *t < config.heat_below {
+                    Transition::To(ThermostatFSM::Heating)
+                } else if *t > config.cool_above {
+                    Transition::To(ThermostatFSM::Cooling)
+                } else {
+                    Transition::None
+                }
+            }
+        },
+
+        Heating => {
+            entry: |ctx| { ctx.switches += 1; }
+            process: |ctx, evt, config| {
+                let ThermostatEvent::Reading(t) = evt;
+                ctx.readings += 1;
+                Transition::to_if(*t >= config.heat_below, ThermostatFSM::Idle)
+            }
+        },
+
+        // The shared argument is optional per state
+        Cooling => {
+            entry: |ctx| { ctx.switches += 1; }
+            process: |ctx, _evt| {
+                ctx.readings += 1;
+                Transition::To(ThermostatFSM::Idle)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_shared_ref_reads_config_while_mutating_context() {
+    let config = ThermostatConfig {
+        heat_below: 18,
+        cool_above: 26,
+    };
+    let mut ctx = ThermostatContext {
+        readings: 0,
+        switches: 0,
+    };
+    let mut fsm = ThermostatFSM::start_in(ThermostatFSM::Idle, &mut ctx);
+
+    fsm.dispatch(&mut ctx, &config, &ThermostatEvent::Reading(21));
+    assert!(matches!(fsm, ThermostatFSM::Idle));
+
+    fsm.dispatch(&mut ctx, &config, &ThermostatEvent::Reading(15));
+    assert!(matches!(fsm, ThermostatFSM::Heating));
+
+    fsm.dispatch_owned(&mut ctx, &config, ThermostatEvent::Reading(19));
+    assert!(matches!(fsm, ThermostatFSM::Idle));
+
+    // A different config changes the decisions without touching the context type
+    let strict = ThermostatConfig {
+        heat_below: 10,
+        cool_above: 20,
+    };
+    fsm.dispatch(&mut ctx, &strict, &ThermostatEvent::Reading(21));
+    assert!(matches!(fsm, ThermostatFSM::Cooling));
+
+    assert_eq!(ctx.readings, 4);
+    assert_eq!(ctx.switches, 2);
+}
+
+// ============================================================================
+// Test 17: dispatch_then() fixups of the new state
+// ============================================================================
+
+struct LinkContext {
+    entered_with: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+enum LinkEvent {
+    Connect,
+    Ping,
+    Drop,
+}
+
+state_machine! {
+    Name: LinkFSM,
+    Context: LinkContext,
+    Event: LinkEvent,
+
+    States: {
+        Offline => {
+            process: |_ctx, evt| {
+                match evt {
+                    LinkEvent::Connect => Transition::To(LinkFSM::Online { handle: 0 }),
+                    _ => Transition::None,
+                }
+            }
+        },
+
+        Online { handle: u32 } => {
+            entry: |ctx| {
+                ctx.entered_with.push(*handle);
+            }
+            process: |_ctx, evt| {
+                match evt {
+                    LinkEvent::Drop => Transition::To(LinkFSM::Offline),
+                    LinkEvent::Ping => Transition::None,
+                    LinkEvent::Connect => Transition::Reenter,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_dispatch_then_runs_only_on_transition() {
+    let mut ctx = LinkContext {
+        entered_with: Vec::new(),
+    };
+    let mut fsm = LinkFSM::start_in(LinkFSM::Offline, &mut ctx);
+    let mut calls = 0;
+
+    // Transition::To: `after` sees the new state (entry has already run)
+    fsm.dispatch_then(&mut ctx, &LinkEvent::Connect, |state| {
+        calls += 1;
+        if let LinkFSM::Online { handle } = state {
+            assert_eq!(*handle, 0);
+            *handle = 7;
+        }
+    });
+    assert_eq!(calls, 1);
+    assert!(matches!(fsm, LinkFSM::Online { handle: 7 }));
+    assert_eq!(ctx.entered_with, vec![0]);
+
+    // Transition::None and Transition::Reenter: `after` is skipped
+    fsm.dispatch_then(&mut ctx, &LinkEvent::Ping, |_| calls += 1);
+    fsm.dispatch_then(&mut ctx, &LinkEvent::Connect, |_| calls += 1);
+    assert_eq!(calls, 1);
+    assert!(matches!(fsm, LinkFSM::Online { handle: 7 }));
+    assert_eq!(ctx.entered_with, vec![0, 7]);
+
+    fsm.dispatch_then(&mut ctx, &LinkEvent::Drop, |state| {
+        calls += 1;
+        assert!(matches!(state, LinkFSM::Offline));
+    });
+    assert_eq!(calls, 2);
+}
+
+// ============================================================================
+// Test 18: freeze() / thaw()
+// ============================================================================
+
+struct GateContext {
+    processed: u32,
+    entries: u32,
+}
+
+#[derive(Debug, Clone)]
+enum GateEvent {
+    Toggle,
+}
+
+state_machine! {
+    Name: GateFSM,
+    Context: GateContext,
+    Event: GateEvent,
+
+    States: {
+        Closed => {
+            entry: |ctx| { ctx.entries += 1; }
+            process: |ctx, evt| {
+                ctx.processed += 1;
+                Transition::to_if(matches!(evt, GateEvent::Toggle), GateFSM::Open)
+            }
+        },
+
+        Open => {
+            entry: |ctx| { ctx.entries += 1; }
+            process: |ctx, evt| {
+                ctx.processed += 1;
+                Transition::to_if(matches!(evt, GateEvent::Toggle), GateFSM::Closed)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_freeze_ignores_events_until_thaw() {
+    let mut ctx = GateContext {
+        processed: 0,
+        entries: 0,
+    };
+    let mut fsm = GateFSM::start_in(GateFSM::Closed, &mut ctx);
+    assert!(!GateFSM::is_frozen());
+
+    GateFSM::freeze();
+    assert!(GateFSM::is_frozen());
+
+    // The flag belongs to the type: every gate is frozen
+    let mut other_ctx = GateContext {
+        processed: 0,
+        entries: 0,
+    };
+    let mut other = GateFSM::start_in(GateFSM::Closed, &mut other_ctx);
+    assert_eq!(
+        other.dispatch(&mut other_ctx, &GateEvent::Toggle),
+        DispatchOutcome::Frozen
+    );
+
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &GateEvent::Toggle),
+        DispatchOutcome::Frozen
+    );
+    assert_eq!(
+        fsm.dispatch_owned(&mut ctx, GateEvent::Toggle),
+        DispatchOutcome::Frozen
+    );
+    assert_eq!(
+        fsm.dispatch_then(&mut ctx, &GateEvent::Toggle, |_| panic!("frozen")),
+        DispatchOutcome::Frozen
+    );
+
+    // Nothing ran: no process, no exit/entry
+    assert!(matches!(fsm, GateFSM::Closed));
+    assert_eq!(ctx.processed, 0);
+    assert_eq!(ctx.entries, 1);
+
+    GateFSM::thaw();
+    assert!(!GateFSM::is_frozen());
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &GateEvent::Toggle),
+        DispatchOutcome::Transitioned
+    );
+    assert!(matches!(fsm, GateFSM::Open));
+    assert_eq!(ctx.processed, 1);
+    assert_eq!(ctx.entries, 2);
+}
+
+// ============================================================================
+// Test 19: dispatch_into() with a convertible event
+// ============================================================================
+
+struct ParserContext {
+    digits: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+enum ParserEvent {
+    Digit(u8),
+    End,
+    Other(u8),
+}
+
+impl From<u8> for ParserEvent {
+    fn from(byte: u8) -> Self {
+        match byte {
+            b'0'..=b'9' => ParserEvent::Digit(byte - b'0'),
+            b'\n' => ParserEvent::End,
+            _ => ParserEvent::Other(byte),
+        }
+    }
+}
+
+state_machine! {
+    Name: ParserFSM,
+    Context: ParserContext,
+    Event: ParserEvent,
+
+    States: {
+        Reading => {
+            process: |ctx, evt| {
+                match evt {
+                    ParserEvent::Digit(d) => { ctx.digits.push(*d); Transition::None }
+                    ParserEvent::End => Transition::To(ParserFSM::Done),
+                    ParserEvent::Other(byte) => Transition::to_if(*byte == 0, ParserFSM::Done),
+                }
+            }
+        },
+
+        Done => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_dispatch_into_converts_raw_bytes() {
+    let mut ctx = ParserContext { digits: Vec::new() };
+    let mut fsm = ParserFSM::start_in(ParserFSM::Reading, &mut ctx);
+
+    for byte in *b"4x2" {
+        assert_eq!(fsm.dispatch_into(&mut ctx, byte), DispatchOutcome::Stayed);
+    }
+    assert_eq!(ctx.digits, vec![4, 2]);
+
+    // Values that already are events work too
+    assert_eq!(
+        fsm.dispatch_into(&mut ctx, ParserEvent::End),
+        DispatchOutcome::Transitioned
+    );
+    assert!(matches!(fsm, ParserFSM::Done));
+}
+
+// ============================================================================
+// Test 20: cfg attributes on individual states
+// ============================================================================
+
+struct FirmwareContext {
+    calibrations: u32,
+}
+
+#[derive(Debug, Clone)]
+enum FirmwareEvent {
+    Calibrate,
+    Run,
+}
+
+state_machine! {
+    Name: FirmwareFSM,
+    Context: FirmwareContext,
+    Event: FirmwareEvent,
+
+    States: {
+        Booting => {
+            process: |_ctx, evt| {
+                match evt {
+                    // Only dev builds (here: `concurrent` ones) can calibrate
+                    #[cfg(feature = "concurrent")]
+                    FirmwareEvent::Calibrate => Transition::To(FirmwareFSM::Calibration { step: 0 }),
+                    #[cfg(not(feature = "concurrent"))]
+                    FirmwareEvent::Calibrate => Transition::None,
+                    FirmwareEvent::Run => Transition::To(FirmwareFSM::Running),
+                }
+            }
+        },
+
+        #[cfg(feature = "concurrent")]
+        Calibration { step: u8 } => {
+            entry: |ctx| { ctx.calibrations += 1; }
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, FirmwareEvent::Run), FirmwareFSM::Running)
+            }
+        },
+
+        // Never compiled
+        #[cfg(any())]
+        Factory => {
+            process: |_ctx, _evt| { Transition::None }
+        },
+
+        Running => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_cfg_states_follow_the_build() {
+    let mut ctx = FirmwareContext { calibrations: 0 };
+    let mut fsm = FirmwareFSM::start_in(FirmwareFSM::Booting, &mut ctx);
+    fsm.dispatch(&mut ctx, &FirmwareEvent::Calibrate);
+
+    if cfg!(feature = "concurrent") {
+        assert_eq!(
+            FirmwareFSM::STATE_NAMES,
+            ["Booting", "Calibration", "Running"]
+        );
+        assert_eq!(fsm.current_state_name(), "Calibration");
+        assert_eq!(ctx.calibrations, 1);
+        assert!(FirmwareFSM::from_index(1).is_none());
+    } else {
+        assert_eq!(FirmwareFSM::STATE_NAMES, ["Booting", "Running"]);
+        assert_eq!(fsm.current_state_name(), "Booting");
+        assert_eq!(ctx.calibrations, 0);
+    }
+
+    fsm.dispatch(&mut ctx, &FirmwareEvent::Run);
+    assert!(matches!(fsm, FirmwareFSM::Running));
+    assert_eq!(fsm.state_index(), FirmwareFSM::STATE_NAMES.len() - 1);
+    assert!(matches!(
+        FirmwareFSM::from_index(fsm.state_index()),
+        Some(FirmwareFSM::Running)
+    ));
+}
+
+// ============================================================================
+// Test 21: Lazily built target states (Transition::ToFn)
+// ============================================================================
+
+static BUFFERS_BUILT: AtomicU32 = AtomicU32::new(0);
+
+fn build_buffer() -> BufferFSM {
+    BUFFERS_BUILT.fetch_add(1, Ordering::SeqCst);
+    BufferFSM::Filled { data: [0xAA; 32] }
+}
+
+struct BufferContext {
+    log: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+enum BufferEvent {
+    Fill,
+    Ignore,
+}
+
+state_machine! {
+    Name: BufferFSM,
+    Context: BufferContext,
+    Event: BufferEvent,
+    Terminal: [Filled],
+    StrictTerminal: true,
+
+    States: {
+        Empty => {
+            process: |_ctx, evt| {
+                match evt {
+                    BufferEvent::Fill => Transition::ToFn(build_buffer),
+                    BufferEvent::Ignore => Transition::None,
+                }
+            }
+
+            exit: |ctx, to_name| {
+                assert_eq!(to_name, "");
+                assert_eq!(BUFFERS_BUILT.load(Ordering::SeqCst), 0);
+                ctx.log.push("exit Empty");
+            }
+        },
+
+        Filled { data: [u8; 32] } => {
+            entry: |ctx| {
+                ctx.log.push("entry Filled");
+            }
+
+            process: |_ctx, _evt| {
+                // Never reached: `Filled` is a strict terminal state
+                Transition::ToFn(build_buffer)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_to_fn_builds_target_once_when_applied() {
+    let mut ctx = BufferContext { log: Vec::new() };
+    let mut fsm = BufferFSM::start_in(BufferFSM::Empty, &mut ctx);
+
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &BufferEvent::Ignore),
+        DispatchOutcome::Stayed
+    );
+    assert_eq!(BUFFERS_BUILT.load(Ordering::SeqCst), 0);
+
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &BufferEvent::Fill),
+        DispatchOutcome::Transitioned
+    );
+    assert_eq!(BUFFERS_BUILT.load(Ordering::SeqCst), 1);
+    assert_eq!(ctx.log, ["exit Empty", "entry Filled"]);
+    assert!(matches!(fsm, BufferFSM::Filled { data } if data == [0xAA; 32]));
+
+    // The locked terminal state never asks for its target
+    assert_eq!(
+        fsm.dispatch(&mut ctx, &BufferEvent::Fill),
+        DispatchOutcome::Stayed
+    );
+    assert_eq!(BUFFERS_BUILT.load(Ordering::SeqCst), 1);
+}
+
+// ============================================================================
+// Test 22: FsmCell (single lock for the machine and its context)
+// ============================================================================
+
+#[cfg(feature = "std")]
+mod fsm_cell {
+    use super::*;
+    use typed_fsm::FsmCell;
+
+    struct TurnstileContext {
+        coins: u32,
+        passes: u32,
+    }
+
+    #[derive(Debug, Clone)]
+    enum TurnstileEvent {
+        Coin,
+        Push,
+    }
+
+    state_machine! {
+        Name: Turnstile,
+        Context: TurnstileContext,
+        Event: TurnstileEvent,
+
+        States: {
+            Locked => {
+                process: |ctx, evt| {
+                    match evt {
+                        TurnstileEvent::Coin => {
+                            ctx.coins += 1;
+                            Transition::To(Turnstile::Unlocked)
+                        }
+                        TurnstileEvent::Push => Transition::None,
+                    }
+                }
+            },
+
+            Unlocked => {
+                process: |ctx, evt| {
+                    match evt {
+                        TurnstileEvent::Coin => {
+                            ctx.coins += 1;
+                            Transition::None
+                        }
+                        TurnstileEvent::Push => {
+                            ctx.passes += 1;
+                            Transition::To(Turnstile::Locked)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fsm_cell_dispatch_from_threads() {
+        let mut ctx = TurnstileContext {
+            coins: 0,
+            passes: 0,
+        };
+        let fsm = Turnstile::start_in(Turnstile::Locked, &mut ctx);
+        let cell = Arc::new(FsmCell::new(fsm, ctx));
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let cell = Arc::clone(&cell);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        // Coin and Push dispatched under one lock each, in any interleaving
+                        cell.dispatch(TurnstileEvent::Coin);
+                        cell.dispatch(TurnstileEvent::Push);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(cell.with(|_fsm, ctx| ctx.coins), 400);
+        let (fsm, ctx) = Arc::try_unwrap(cell).ok().unwrap().into_inner();
+        assert!(ctx.passes >= 1 && ctx.passes <= 400);
+        assert!(fsm.same_variant(&Turnstile::Locked));
+    }
+}
+
+// ============================================================================
+// Test 23: process sugar (bare state => To, unit => None)
+// ============================================================================
+
+struct DimmerContext {
+    toggles: u32,
+}
+
+#[derive(Debug, Clone)]
+enum DimmerEvent {
+    Toggle,
+    Touch,
+}
+
+state_machine! {
+    Name: SugarLamp,
+    Context: DimmerContext,
+    Event: DimmerEvent,
+
+    States: {
+        Off => {
+            process: |ctx, evt| {
+                if let DimmerEvent::Toggle = evt {
+                    ctx.toggles += 1;
+                    return Transition::To(SugarLamp::On);
+                }
+            }
+        },
+
+        On => {
+            process: |ctx, evt| {
+                match evt {
+                    DimmerEvent::Toggle => {
+                        ctx.toggles += 1;
+                        SugarLamp::Off
+                    }
+                    DimmerEvent::Touch => SugarLamp::On,
+                }
+            }
+        }
+    }
+}
+
+state_machine! {
+    Name: PlainLamp,
+    Context: DimmerContext,
+    Event: DimmerEvent,
+
+    States: {
+        Off => {
+            process: |ctx, evt| {
+                match evt {
+                    DimmerEvent::Toggle => {
+                        ctx.toggles += 1;
+                        Transition::To(PlainLamp::On)
+                    }
+                    DimmerEvent::Touch => Transition::None,
+                }
+            }
+        },
+
+        On => {
+            process: |ctx, evt| {
+                match evt {
+                    DimmerEvent::Toggle => {
+                        ctx.toggles += 1;
+                        Transition::To(PlainLamp::Off)
+                    }
+                    DimmerEvent::Touch => Transition::To(PlainLamp::On),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_process_sugar_matches_explicit_transitions() {
+    let mut sugar_ctx = DimmerContext { toggles: 0 };
+    let mut plain_ctx = DimmerContext { toggles: 0 };
+    let mut sugar = SugarLamp::start_in(SugarLamp::Off, &mut sugar_ctx);
+    let mut plain = PlainLamp::start_in(PlainLamp::Off, &mut plain_ctx);
+
+    let events = [
+        DimmerEvent::Touch,
+        DimmerEvent::Toggle,
+        DimmerEvent::Touch,
+        DimmerEvent::Toggle,
+        DimmerEvent::Toggle,
+    ];
+    for event in &events {
+        assert_eq!(
+            sugar.dispatch(&mut sugar_ctx, event),
+            plain.dispatch(&mut plain_ctx, event)
+        );
+        assert_eq!(sugar.current_state_name(), plain.current_state_name());
+    }
+    assert_eq!(sugar_ctx.toggles, 3);
+    assert_eq!(plain_ctx.toggles, 3);
+}
+
+// ============================================================================
+// Test 24: dispatch_before() drops stale events
+// ============================================================================
+
+struct ValveContext {
+    commands: u32,
+}
+
+#[derive(Debug, Clone)]
+enum ValveEvent {
+    Open,
+}
+
+state_machine! {
+    Name: Valve,
+    Context: ValveContext,
+    Event: ValveEvent,
+
+    States: {
+        Closed => {
+            process: |ctx, _evt| {
+                ctx.commands += 1;
+                Transition::To(Valve::Opened)
+            }
+        },
+
+        Opened => {
+            process: |ctx, _evt| {
+                ctx.commands += 1;
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_dispatch_before_skips_expired_events() {
+    let mut ctx = ValveContext { commands: 0 };
+    let mut valve = Valve::start_in(Valve::Closed, &mut ctx);
+
+    // Past the deadline: nothing runs
+    assert_eq!(
+        valve.dispatch_before(&mut ctx, &ValveEvent::Open, 1_001, 1_000),
+        DispatchOutcome::Expired
+    );
+    assert!(matches!(valve, Valve::Closed));
+    assert_eq!(ctx.commands, 0);
+
+    // At or before the deadline: processed like dispatch()
+    assert_eq!(
+        valve.dispatch_before(&mut ctx, &ValveEvent::Open, 1_000, 1_000),
+        DispatchOutcome::Transitioned
+    );
+    assert_eq!(
+        valve.dispatch_before(&mut ctx, &ValveEvent::Open, 10, 1_000),
+        DispatchOutcome::Stayed
+    );
+    assert!(matches!(valve, Valve::Opened));
+    assert_eq!(ctx.commands, 2);
+}
+
+// ============================================================================
+// Test 25: dispatch_and_peek() returns the resulting state
+// ============================================================================
+
+#[test]
+fn test_dispatch_and_peek_reflects_new_state() {
+    let mut ctx = ValveContext { commands: 0 };
+    let mut valve = Valve::start_in(Valve::Closed, &mut ctx);
+
+    let (outcome, state) = valve.dispatch_and_peek(&mut ctx, &ValveEvent::Open);
+    assert_eq!(outcome, DispatchOutcome::Transitioned);
+    assert!(matches!(state, Valve::Opened));
+
+    assert_eq!(
+        valve
+            .dispatch_and_peek(&mut ctx, &ValveEvent::Open)
+            .1
+            .current_state_name(),
+        "Opened"
+    );
+    assert_eq!(ctx.commands, 2);
+}
+
+// ============================================================================
+// Test 26: Transition log in test builds (feature `std`)
+// ============================================================================
+
+#[cfg(feature = "std")]
+mod test_transition_log {
+    use typed_fsm::{state_machine, Transition};
+
+    #[derive(Debug, Clone)]
+    enum CycleEvent {
+        Next,
+        Hold,
+        Restart,
+    }
+
+    state_machine! {
+        Name: Cycle,
+        Event: CycleEvent,
+
+        States: {
+            Red => {
+                process: |evt| {
+                    match evt {
+                        CycleEvent::Next => Transition::To(Cycle::Green),
+                        CycleEvent::Hold => Transition::None,
+                        CycleEvent::Restart => Transition::Reenter,
+                    }
+                }
+            },
+
+            Green => {
+                process: |evt| {
+                    Transition::to_if(matches!(evt, CycleEvent::Next), Cycle::Yellow)
+                }
+            },
+
+            Yellow => {
+                process: |_evt| { Transition::ToFn(|| Cycle::Red) }
+            }
+        }
+    }
+
+    #[test]
+    fn test_transitions_are_logged_in_order() {
+        let mut light = Cycle::start_in(Cycle::Red);
+        assert!(Cycle::test_transitions().is_empty());
+
+        for event in [
+            CycleEvent::Next,
+            CycleEvent::Hold,
+            CycleEvent::Next,
+            CycleEvent::Hold,
+            CycleEvent::Restart,
+        ] {
+            light.dispatch(&event);
+        }
+
+        // Yellow leaves on any event (through `ToFn`). The first `Hold` stays and
+        // `Restart` re-enters: neither is logged
+        assert_eq!(
+            Cycle::test_transitions(),
+            [("Red", "Green"), ("Green", "Yellow"), ("Yellow", "Red")]
+        );
+
+        Cycle::clear_test_transitions();
+        assert!(Cycle::test_transitions().is_empty());
+    }
+}
+
+// ============================================================================
+// Test 27: Generic drivers through the Fsm trait
+// ============================================================================
+
+/// Drives any state machine through a script, returning how many events transitioned.
+fn run<C, E, F: Fsm<C, E>>(fsm: &mut F, ctx: &mut C, script: &[E]) -> usize {
+    fsm.init(ctx);
+    script
+        .iter()
+        .filter(|event| fsm.dispatch(ctx, event) == DispatchOutcome::Transitioned)
+        .count()
+}
+
+#[test]
+fn test_fsm_trait_drives_different_machines() {
+    let mut toggle_ctx = ToggleContext { toggle_count: 0 };
+    let mut toggle = ToggleFSM::Off;
+    let transitions = run(
+        &mut toggle,
+        &mut toggle_ctx,
+        &[
+            ToggleEvent::Toggle,
+            ToggleEvent::Toggle,
+            ToggleEvent::Toggle,
+        ],
+    );
+    assert_eq!(transitions, 3);
+    assert!(matches!(toggle, ToggleFSM::On));
+    // init() entered Off, then each toggle entered a state
+    assert_eq!(toggle_ctx.toggle_count, 4);
+
+    let mut valve_ctx = ValveContext { commands: 0 };
+    let mut valve = Valve::Closed;
+    let transitions = run(
+        &mut valve,
+        &mut valve_ctx,
+        &[ValveEvent::Open, ValveEvent::Open],
+    );
+    assert_eq!(transitions, 1);
+    assert!(matches!(valve, Valve::Opened));
+    assert_eq!(valve_ctx.commands, 2);
+}
+
+// ============================================================================
+// Test 28: Instrumentation (mutable data passed to process, kept out of the context)
+// ============================================================================
+
+#[derive(Default)]
+struct PumpStats {
+    events: u32,
+    starts: u32,
+    stops: u32,
+}
+
+struct PumpContext {
+    liters: u32,
+}
+
+#[derive(Debug, Clone)]
+enum PumpEvent {
+    Start,
+    Stop,
+    Flow(u32),
+}
+
+state_machine! {
+    Name: Pump,
+    Context: PumpContext,
+    Event: PumpEvent,
+    Instrumentation: stats: PumpStats,
+    ProcessPrelude: |_ctx, _evt| {
+        stats.events += 1;
+    },
+
+    States: {
+        Stopped => {
+            process: |_ctx, evt| {
+                match evt {
+                    PumpEvent::Start => {
+                        stats.starts += 1;
+                        Transition::To(Pump::Running)
+                    }
+                    PumpEvent::Stop | PumpEvent::Flow(_) => Transition::None,
+                }
+            }
+        },
+
+        Running => {
+            process: |ctx, evt| {
+                match evt {
+                    PumpEvent::Flow(liters) => {
+                        ctx.liters += liters;
+                        Transition::None
+                    }
+                    PumpEvent::Stop => {
+                        stats.stops += 1;
+                        Transition::To(Pump::Stopped)
+                    }
+                    PumpEvent::Start => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_instrumentation_counts_transitions_outside_context() {
+    let mut ctx = PumpContext { liters: 0 };
+    let mut stats = PumpStats::default();
+    let mut pump = Pump::start_in(Pump::Stopped, &mut ctx);
+
+    pump.dispatch(&mut ctx, &mut stats, &PumpEvent::Start);
+    pump.dispatch(&mut ctx, &mut stats, &PumpEvent::Flow(5));
+    pump.dispatch_owned(&mut ctx, &mut stats, PumpEvent::Stop);
+    pump.dispatch(&mut ctx, &mut stats, &PumpEvent::Start);
+    pump.dispatch(&mut ctx, &mut stats, &PumpEvent::Flow(2));
+    assert!(matches!(pump, Pump::Running));
+
+    // The context only holds the domain data
+    assert_eq!(ctx.liters, 7);
+    assert_eq!(stats.events, 5);
+    assert_eq!(stats.starts, 2);
+    assert_eq!(stats.stops, 1);
+
+    // Separate instrumentation per run, e.g., one per test or per reporting period
+    let mut period = PumpStats::default();
+    pump.dispatch(&mut ctx, &mut period, &PumpEvent::Stop);
+    assert_eq!((period.events, period.stops), (1, 1));
+    assert_eq!(stats.stops, 1);
+}
+
+// ============================================================================
+// Test 29: Doc comments on states document the generated variants
+// ============================================================================
+
+/// `missing_docs` rejects any undocumented variant of the (public) generated enum.
+#[deny(missing_docs)]
+pub mod documented_states {
+    use typed_fsm::{state_machine, Transition};
+
+    #[derive(Debug, Clone)]
+    enum CoinEvent {
+        Coin,
+        Push,
+    }
+
+    state_machine! {
+        Name: Turnstile,
+        Event: CoinEvent,
+
+        States: {
+            /// Waiting for a coin.
+            Locked => {
+                process: |evt| {
+                    Transition::to_if(matches!(evt, CoinEvent::Coin), Turnstile::Unlocked)
+                }
+            },
+
+            /// Paid: the next push lets one person through.
+            #[cfg(not(any()))]
+            Unlocked => {
+                process: |evt| {
+                    Transition::to_if(matches!(evt, CoinEvent::Push), Turnstile::Locked)
+                }
+            },
+
+            /// Out of service (never compiled).
+            ///
+            /// Multi-line docs are forwarded too.
+            #[cfg(any())]
+            Maintenance => {
+                process: |_evt| { Transition::None }
+            }
+        }
+    }
+
+    #[test]
+    fn test_documented_states_dispatch_normally() {
+        let mut gate = Turnstile::start_in(Turnstile::Locked);
+        gate.dispatch(&CoinEvent::Coin);
+        assert!(matches!(gate, Turnstile::Unlocked));
+        gate.dispatch(&CoinEvent::Push);
+        assert!(matches!(gate, Turnstile::Locked));
+        assert_eq!(Turnstile::STATE_NAMES, ["Locked", "Unlocked"]);
+    }
+}
+
+// ============================================================================
+// Test 30: Several machines sharing one context (compose_fsms!)
+// ============================================================================
+
+struct PlantContext {
+    level: u32,
+    entered: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+enum PlantEvent {
+    Tick,
+}
+
+state_machine! {
+    Name: Feeder,
+    Context: PlantContext,
+    Event: PlantEvent,
+
+    States: {
+        Stopped => {
+            process: |_ctx, _evt| { Feeder::Feeding }
+        },
+
+        Feeding => {
+            entry: |ctx| { ctx.entered.push("Feeding"); }
+
+            process: |ctx, _evt| {
+                ctx.level += 10;
+                Transition::None
+            }
+        }
+    }
+}
+
+state_machine! {
+    Name: LevelAlarm,
+    Context: PlantContext,
+    Event: PlantEvent,
+
+    States: {
+        Quiet => {
+            process: |ctx, _evt| {
+                Transition::to_if(ctx.entered.contains(&"Feeding"), LevelAlarm::Armed)
+            }
+        },
+
+        Armed => {
+            entry: |ctx| { ctx.entered.push("Armed"); }
+
+            process: |ctx, _evt| {
+                Transition::to_if(ctx.level >= 10, LevelAlarm::Ringing)
+            }
+        },
+
+        Ringing => {
+            entry: |ctx| { ctx.entered.push("Ringing"); }
+
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+compose_fsms! {
+    Name: Plant,
+    Context: PlantContext,
+    Event: PlantEvent,
+    Machines: {
+        feeder: Feeder,
+        alarm: LevelAlarm,
+    }
+}
+
+#[test]
+fn test_composed_machines_advance_on_one_event() {
+    let mut plant = Plant::new(
+        PlantContext {
+            level: 0,
+            entered: Vec::new(),
+        },
+        Feeder::Stopped,
+        LevelAlarm::Quiet,
+    );
+    plant.init_all();
+    assert_eq!(Plant::MACHINE_COUNT, 2);
+
+    // One event advances both; the alarm sees the feeder's entry
+    assert_eq!(
+        plant.dispatch_all(&PlantEvent::Tick),
+        [DispatchOutcome::Transitioned, DispatchOutcome::Transitioned]
+    );
+    assert!(matches!(plant.feeder, Feeder::Feeding));
+    assert!(matches!(plant.alarm, LevelAlarm::Armed));
+
+    // Declaration order: the feeder raises the level before the alarm checks it
+    assert_eq!(
+        plant.dispatch_all(&PlantEvent::Tick),
+        [DispatchOutcome::Stayed, DispatchOutcome::Transitioned]
+    );
+    assert!(matches!(plant.alarm, LevelAlarm::Ringing));
+    assert_eq!(plant.context.level, 10);
+    assert_eq!(plant.context.entered, vec!["Feeding", "Armed", "Ringing"]);
+}
+
+// ============================================================================
+// Test 31: Transition::ToAndPost (transition, then a follow-up event)
+// ============================================================================
+
+struct ModemContext {
+    log: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+enum ModemEvent {
+    Dial,
+    Handshake,
+}
+
+state_machine! {
+    Name: Modem,
+    Context: ModemContext,
+    Event: ModemEvent,
+
+    States: {
+        OnHook => {
+            process: |_ctx, evt| {
+                match evt {
+                    ModemEvent::Dial => Transition::ToAndPost(Modem::Connecting, ModemEvent::Handshake),
+                    ModemEvent::Handshake => Transition::Unhandled,
+                }
+            }
+        },
+
+        Connecting => {
+            entry: |ctx| { ctx.log.push("entry Connecting"); }
+
+            process: |ctx, evt| {
+                ctx.log.push("process Connecting");
+                match evt {
+                    ModemEvent::Handshake => Modem::Online,
+                    ModemEvent::Dial => Modem::Connecting,
+                }
+            }
+
+            exit: |ctx| { ctx.log.push("exit Connecting"); }
+        },
+
+        Online => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_to_and_post_feeds_the_new_state_before_returning() {
+    let mut ctx = ModemContext { log: Vec::new() };
+    let mut modem = Modem::OnHook;
+    modem.init(&mut ctx);
+
+    // The outcome is that of `Dial`; `Handshake` already ran in `Connecting`
+    assert_eq!(
+        modem.dispatch(&mut ctx, &ModemEvent::Dial),
+        DispatchOutcome::Transitioned
+    );
+    assert!(matches!(modem, Modem::Online));
+    assert_eq!(
+        ctx.log,
+        vec!["entry Connecting", "process Connecting", "exit Connecting"]
+    );
+}
+
+#[derive(Debug, Clone)]
+enum RelayEvent {
+    Pass(u32),
+}
+
+// Each hop posts the next one, until the count runs out
+state_machine! {
+    Name: Relay,
+    Event: RelayEvent,
+
+    States: {
+        Left => {
+            process: |evt| {
+                let RelayEvent::Pass(hops) = evt;
+                match hops {
+                    0 => Transition::None,
+                    _ => Transition::ToAndPost(Relay::Right, RelayEvent::Pass(hops - 1)),
+                }
+            }
+        },
+
+        Right => {
+            process: |evt| {
+                let RelayEvent::Pass(hops) = evt;
+                match hops {
+                    0 => Transition::None,
+                    _ => Transition::ToAndPost(Relay::Left, RelayEvent::Pass(hops - 1)),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_to_and_post_chain_does_not_grow_the_stack() {
+    let mut relay = Relay::start_in(Relay::Left);
+
+    // Far deeper than the test thread's stack could take as recursion
+    assert_eq!(
+        relay.dispatch(&RelayEvent::Pass(1_000_001)),
+        DispatchOutcome::Transitioned
+    );
+    assert!(matches!(relay, Relay::Right));
+}
+
+// ============================================================================
+// Test 32: Transition::Retry with a per-state retry budget
+// ============================================================================
+
+struct DialerContext {
+    attempts: u32,
+    link_up: bool,
+    fsm: Bookkeeping,
+}
+
+#[derive(Debug, Clone)]
+enum DialerEvent {
+    Timeout,
+    Reset,
+}
+
+state_machine! {
+    Name: Dialer,
+    Context: DialerContext,
+    Event: DialerEvent,
+    Bookkeeping: fsm,
+
+    States: {
+        Dialing => {
+            retry: { max: 3, on_exhausted: Failed },
+
+            entry: |ctx| { ctx.attempts += 1; }
+
+            process: |ctx, evt| {
+                match evt {
+                    DialerEvent::Timeout if ctx.link_up => Transition::To(Dialer::Connected),
+                    DialerEvent::Timeout => Transition::Retry,
+                    DialerEvent::Reset => Transition::None,
+                }
+            }
+        },
+
+        Connected => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, DialerEvent::Reset), Dialer::Dialing)
+            }
+        },
+
+        Failed => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, DialerEvent::Reset), Dialer::Dialing)
+            }
+        }
+    }
+}
+
+// Context-free, and without a `retry:` block: `Retry` always re-enters
+state_machine! {
+    Name: Poller,
+    Event: DialerEvent,
+
+    States: {
+        Polling => {
+            cost: 5,
+
+            process: |_evt| { Transition::Retry }
+        }
+    }
+}
+
+#[test]
+fn test_retry_exhausts_to_fail_state_after_max_attempts() {
+    let mut ctx = DialerContext {
+        attempts: 0,
+        link_up: false,
+        fsm: Bookkeeping::new(),
+    };
+    let mut dialer = Dialer::start_in(Dialer::Dialing, &mut ctx);
+    assert_eq!(ctx.attempts, 1);
+
+    // Attempts 1 and 2 fail: the state is re-entered
+    assert_eq!(
+        dialer.dispatch(&mut ctx, &DialerEvent::Timeout),
+        DispatchOutcome::Stayed
+    );
+    assert_eq!(dialer.retry_count(&ctx), 1);
+    dialer.dispatch(&mut ctx, &DialerEvent::Timeout);
+    assert_eq!(dialer.retry_count(&ctx), 2);
+    assert!(matches!(dialer, Dialer::Dialing));
+    assert_eq!(ctx.attempts, 3);
+
+    // Attempt 3 fails: the budget is spent
+    assert_eq!(
+        dialer.dispatch(&mut ctx, &DialerEvent::Timeout),
+        DispatchOutcome::Transitioned
+    );
+    assert!(matches!(dialer, Dialer::Failed));
+    assert_eq!(ctx.attempts, 3);
+    assert_eq!(dialer.retry_count(&ctx), 0);
+
+    // A fresh entry gets a fresh budget; leaving normally resets the count too
+    dialer.dispatch(&mut ctx, &DialerEvent::Reset);
+    dialer.dispatch(&mut ctx, &DialerEvent::Timeout);
+    assert_eq!(dialer.retry_count(&ctx), 1);
+    ctx.link_up = true;
+    dialer.dispatch(&mut ctx, &DialerEvent::Timeout);
+    assert!(matches!(dialer, Dialer::Connected));
+    assert_eq!(dialer.retry_count(&ctx), 0);
+}
+
+#[test]
+fn test_retry_without_budget_always_reenters() {
+    let mut poller = Poller::start_in(Poller::Polling);
+    for _ in 0..5 {
+        assert_eq!(
+            poller.dispatch(&DialerEvent::Timeout),
+            DispatchOutcome::Stayed
+        );
+    }
+    assert_eq!(poller.state_cost(), 5);
+}
+
+#[test]
+fn test_retry_budgets_are_per_instance() {
+    let mut ctx_a = DialerContext {
+        attempts: 0,
+        link_up: false,
+        fsm: Bookkeeping::new(),
+    };
+    let mut ctx_b = DialerContext {
+        attempts: 0,
+        link_up: false,
+        fsm: Bookkeeping::new(),
+    };
+    let mut a = Dialer::start_in(Dialer::Dialing, &mut ctx_a);
+    let mut b = Dialer::start_in(Dialer::Dialing, &mut ctx_b);
+
+    a.dispatch(&mut ctx_a, &DialerEvent::Timeout);
+    a.dispatch(&mut ctx_a, &DialerEvent::Timeout);
+    assert_eq!(a.retry_count(&ctx_a), 2);
+
+    // `a`'s retries neither spend `b`'s budget nor get reset by `b`'s transitions
+    assert_eq!(b.retry_count(&ctx_b), 0);
+    b.dispatch(&mut ctx_b, &DialerEvent::Timeout);
+    assert_eq!(b.retry_count(&ctx_b), 1);
+    ctx_b.link_up = true;
+    b.dispatch(&mut ctx_b, &DialerEvent::Timeout);
+    assert!(matches!(b, Dialer::Connected));
+    assert_eq!(a.retry_count(&ctx_a), 2);
+
+    a.dispatch(&mut ctx_a, &DialerEvent::Timeout);
+    assert!(matches!(a, Dialer::Failed));
+}
+
+// ============================================================================
+// Test 33: One glob import through the prelude
+// ============================================================================
+
+/// Nothing but the prelude is imported here.
+mod prelude_glob {
+    use typed_fsm::prelude::*;
+
+    struct DoorContext {
+        opened: u32,
+    }
+
+    #[derive(Debug, Clone)]
+    enum DoorEvent {
+        Push,
+    }
+
+    state_machine! {
+        Name: Door,
+        Context: DoorContext,
+        Event: DoorEvent,
+
+        States: {
+            Shut => {
+                process: |_ctx, _evt| { Transition::To(Door::Ajar) }
+            },
+
+            Ajar => {
+                entry: |ctx| { ctx.opened += 1; }
+
+                process: |_ctx, _evt| { Transition::None }
+            }
+        }
+    }
+
+    /// Generic over any machine, through the prelude's `Fsm` trait.
+    fn push_twice<F: Fsm<DoorContext, DoorEvent>>(fsm: &mut F, ctx: &mut DoorContext) {
+        fsm.dispatch(ctx, &DoorEvent::Push);
+        fsm.dispatch(ctx, &DoorEvent::Push);
+    }
+
+    #[test]
+    fn test_prelude_brings_macro_and_types_into_scope() {
+        let mut ctx = DoorContext { opened: 0 };
+        let mut door = Door::start_in(Door::Shut, &mut ctx);
+        push_twice(&mut door, &mut ctx);
+        assert_in_state!(door, Ajar);
+        assert_eq!(ctx.opened, 1);
+        assert_eq!(
+            door.dispatch(&mut ctx, &DoorEvent::Push),
+            DispatchOutcome::Stayed
+        );
+    }
+}
+
+// ============================================================================
+// Test 34: Hook-less states next to states with entry/exit hooks
+// ============================================================================
+
+struct StageContext {
+    log: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum StageEvent {
+    Next,
+    Again,
+}
+
+state_machine! {
+    Name: Stage,
+    Context: StageContext,
+    Event: StageEvent,
+
+    States: {
+        // No hooks: transitions in and out of here skip the exit/entry calls
+        Fetch => {
+            process: |_ctx, _evt| { Transition::To(Stage::Decode) }
+        },
+
+        Decode => {
+            entry: |ctx, from| { ctx.log.push(format!("enter Decode from {}", from)); }
+
+            process: |_ctx, evt| {
+                match evt {
+                    StageEvent::Next => Transition::To(Stage::Execute { cycles: 0 }),
+                    StageEvent::Again => Transition::Reenter,
+                }
+            }
+
+            exit: |ctx, to| { ctx.log.push(format!("exit Decode to {}", to)); }
+        },
+
+        // Hook-less state carrying data
+        Execute { cycles: u32 } => {
+            process: |_ctx, evt| {
+                match evt {
+                    StageEvent::Next => Transition::To(Stage::Retire),
+                    StageEvent::Again => Transition::Reenter,
+                }
+            }
+        },
+
+        Retire => {
+            process: |_ctx, _evt| { Transition::To(Stage::Fetch) }
+
+            exit: |ctx| { ctx.log.push("exit Retire".to_string()); }
+        }
+    }
+}
+
+#[test]
+fn test_hookless_states_keep_lifecycle_of_hooked_ones() {
+    let mut ctx = StageContext { log: Vec::new() };
+    let mut cpu = Stage::start_in(Stage::Fetch, &mut ctx);
+    assert!(ctx.log.is_empty());
+
+    // Hook-less -> hooked: only the entry of the target runs
+    cpu.dispatch(&mut ctx, &StageEvent::Next);
+    assert_eq!(ctx.log, vec!["enter Decode from Fetch"]);
+
+    // Re-entering a hooked state runs both of its hooks
+    cpu.dispatch(&mut ctx, &StageEvent::Again);
+    assert_eq!(ctx.log.len(), 3);
+    assert_eq!(ctx.log[1], "exit Decode to Decode");
+    assert_eq!(ctx.log[2], "enter Decode from Decode");
+
+    // Hooked -> hook-less: only the exit of the source runs
+    cpu.dispatch(&mut ctx, &StageEvent::Next);
+    assert_eq!(ctx.log[3], "exit Decode to Execute");
+    assert!(matches!(cpu, Stage::Execute { cycles: 0 }));
+
+    // Hook-less throughout: nothing is logged, the state still changes
+    assert_eq!(
+        cpu.dispatch(&mut ctx, &StageEvent::Again),
+        DispatchOutcome::Stayed
+    );
+    assert_eq!(
+        cpu.dispatch(&mut ctx, &StageEvent::Next),
+        DispatchOutcome::Transitioned
+    );
+    assert!(matches!(cpu, Stage::Retire));
+    assert_eq!(ctx.log.len(), 4);
+
+    // Exit-only state -> hook-less state
+    cpu.dispatch(&mut ctx, &StageEvent::Next);
+    assert!(matches!(cpu, Stage::Fetch));
+    assert_eq!(ctx.log[4], "exit Retire");
+    assert_eq!(ctx.log.len(), 5);
+}
+
+// ============================================================================
+// Test 35: FsmCell recovers from a panicking hook (lock poisoning)
+// ============================================================================
+
+// With `concurrent`, a panicking dispatch also leaves the type's dispatch lock held
+#[cfg(all(feature = "std", not(feature = "concurrent")))]
+mod fsm_cell_poisoning {
+    use super::*;
+    use typed_fsm::FsmCell;
+
+    struct BoilerContext {
+        pressure: u32,
+        safe_mode_entries: u32,
+    }
+
+    #[derive(Debug, Clone)]
+    enum BoilerEvent {
+        Heat,
+        Vent,
+    }
+
+    state_machine! {
+        Name: Boiler,
+        Context: BoilerContext,
+        Event: BoilerEvent,
+
+        States: {
+            Heating => {
+                process: |ctx, evt| {
+                    match evt {
+                        BoilerEvent::Heat => {
+                            ctx.pressure += 10;
+                            // A buggy hook: panics while holding the cell's lock
+                            assert!(ctx.pressure < 30, "pressure sensor out of range");
+                            Transition::None
+                        }
+                        BoilerEvent::Vent => Transition::None,
+                    }
+                }
+            },
+
+            SafeMode => {
+                entry: |ctx| { ctx.safe_mode_entries += 1; }
+
+                process: |ctx, evt| {
+                    if let BoilerEvent::Vent = evt {
+                        ctx.pressure = 0;
+                    }
+                    Transition::None
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fsm_cell_recovers_poisoned_lock_into_safe_state() {
+        let mut ctx = BoilerContext {
+            pressure: 0,
+            safe_mode_entries: 0,
+        };
+        let fsm = Boiler::start_in(Boiler::Heating, &mut ctx);
+        let cell = Arc::new(
+            FsmCell::new(fsm, ctx)
+                .on_error_transition(|ctx| Boiler::start_in(Boiler::SafeMode, ctx)),
+        );
+
+        // One worker panics mid-dispatch, poisoning the lock
+        let worker = Arc::clone(&cell);
+        let crashed = thread::spawn(move || {
+            for _ in 0..3 {
+                worker.dispatch(BoilerEvent::Heat);
+            }
+        })
+        .join();
+        assert!(crashed.is_err());
+
+        // Other threads keep using the machine, now in the safe state
+        let worker = Arc::clone(&cell);
+        let outcome = thread::spawn(move || worker.dispatch(BoilerEvent::Vent))
+            .join()
+            .unwrap();
+        assert_eq!(outcome, DispatchOutcome::Stayed);
+
+        // Recovery ran exactly once
+        cell.with(|fsm, ctx| {
+            assert!(matches!(fsm, Boiler::SafeMode));
+            assert_eq!(ctx.safe_mode_entries, 1);
+            assert_eq!(ctx.pressure, 0);
+        });
+        cell.dispatch(BoilerEvent::Heat);
+        let (fsm, ctx) = Arc::try_unwrap(cell).ok().unwrap().into_inner();
+        assert!(matches!(fsm, Boiler::SafeMode));
+        assert_eq!(ctx.safe_mode_entries, 1);
+    }
+
+    #[test]
+    fn test_fsm_cell_recovers_poisoned_lock_without_safe_state() {
+        let mut ctx = BoilerContext {
+            pressure: 20,
+            safe_mode_entries: 0,
+        };
+        let fsm = Boiler::start_in(Boiler::Heating, &mut ctx);
+        let cell = FsmCell::new(fsm, ctx);
+
+        let crashed = std::panic::catch_unwind(|| cell.dispatch(BoilerEvent::Heat));
+        assert!(crashed.is_err());
+
+        // No recovery transition: the lock is usable and the machine is left as it was
+        assert_eq!(cell.dispatch(BoilerEvent::Vent), DispatchOutcome::Stayed);
+        cell.with(|fsm, ctx| {
+            assert!(matches!(fsm, Boiler::Heating));
+            assert_eq!(ctx.pressure, 30);
+        });
+    }
+}
+
+// ============================================================================
+// Test 36: Mixins (process arms shared by several states)
+// ============================================================================
+
+struct CheckoutContext {
+    cancellations: u32,
+    audit: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+enum CheckoutEvent {
+    Cancel,
+    Ping,
+    AddressEntered,
+    PaymentAuthorized,
+}
+
+state_machine! {
+    Name: Checkout,
+    Context: CheckoutContext,
+    Event: CheckoutEvent,
+
+    Mixin: Auditing => {
+        process_fragment: |ctx, evt| {
+            // Records and passes every event on
+            if let CheckoutEvent::Ping = evt {
+                ctx.audit.push("ping");
+            }
+            Transition::Unhandled
+        }
+    },
+
+    Mixin: CancelHandling => {
+        process_fragment: |ctx, evt| {
+            match evt {
+                CheckoutEvent::Cancel => {
+                    ctx.cancellations += 1;
+                    Transition::To(Checkout::Cancelled)
+                }
+                _ => Transition::Unhandled,
+            }
+        }
+    },
+
+    States: {
+        WaitingForAddress => {
+            use: [Auditing, CancelHandling],
+
+            process: |_ctx, evt| {
+                match evt {
+                    CheckoutEvent::AddressEntered => Transition::To(Checkout::WaitingForPayment),
+                    // Never reached: handled by the mixin first
+                    CheckoutEvent::Cancel => Transition::To(Checkout::Done),
+                    _ => Transition::None,
+                }
+            }
+        },
+
+        WaitingForPayment => {
+            use: [CancelHandling],
+
+            process: |_ctx, evt| {
+                match evt {
+                    CheckoutEvent::PaymentAuthorized => Transition::To(Checkout::Done),
+                    _ => Transition::None,
+                }
+            }
+        },
+
+        // Without the mixin, `Cancel` is just another event
+        Done => {
+            process: |_ctx, _evt| { Transition::None }
+        },
+
+        Cancelled => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_mixin_shared_by_two_states() {
+    let new_context = || CheckoutContext {
+        cancellations: 0,
+        audit: Vec::new(),
+    };
+
+    // Cancel while waiting for the address: the mixin wins over the state's own arm
+    let mut ctx = new_context();
+    let mut checkout = Checkout::start_in(Checkout::WaitingForAddress, &mut ctx);
+    checkout.dispatch(&mut ctx, &CheckoutEvent::Ping);
+    checkout.dispatch(&mut ctx, &CheckoutEvent::Cancel);
+    assert!(matches!(checkout, Checkout::Cancelled));
+    assert_eq!(ctx.cancellations, 1);
+    assert_eq!(ctx.audit, vec!["ping"]);
+
+    // Cancel while waiting for the payment: same shared arm
+    let mut ctx = new_context();
+    let mut checkout = Checkout::start_in(Checkout::WaitingForAddress, &mut ctx);
+    checkout.dispatch(&mut ctx, &CheckoutEvent::AddressEntered);
+    assert!(matches!(checkout, Checkout::WaitingForPayment));
+    checkout.dispatch(&mut ctx, &CheckoutEvent::Ping);
+    checkout.dispatch(&mut ctx, &CheckoutEvent::Cancel);
+    assert!(matches!(checkout, Checkout::Cancelled));
+    assert_eq!(ctx.cancellations, 1);
+    // Auditing is only mixed into WaitingForAddress
+    assert!(ctx.audit.is_empty());
+
+    // Unique arms still work, and states without the mixin ignore Cancel
+    let mut ctx = new_context();
+    let mut checkout = Checkout::start_in(Checkout::WaitingForPayment, &mut ctx);
+    checkout.dispatch(&mut ctx, &CheckoutEvent::PaymentAuthorized);
+    assert!(matches!(checkout, Checkout::Done));
+    checkout.dispatch(&mut ctx, &CheckoutEvent::Cancel);
+    assert!(matches!(checkout, Checkout::Done));
+    assert_eq!(ctx.cancellations, 0);
+}
+
+mod context_free_mixin {
+    use typed_fsm::{state_machine, Transition};
+
+    #[derive(Debug, Clone)]
+    enum Key {
+        Escape,
+        Char(char),
+    }
+
+    state_machine! {
+        Name: Prompt,
+        Event: Key,
+
+        Mixin: EscapeToIdle => {
+            process_fragment: |evt| {
+                match evt {
+                    Key::Escape => Transition::To(Prompt::Idle),
+                    _ => Transition::Unhandled,
+                }
+            }
+        },
+
+        States: {
+            Idle => {
+                process: |evt| {
+                    match evt {
+                        Key::Char(':') => Transition::To(Prompt::Command),
+                        _ => Transition::None,
+                    }
+                }
+            },
+
+            Command => {
+                use: [EscapeToIdle],
+
+                process: |_evt| { Transition::None }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mixin_in_context_free_machine() {
+        let mut prompt = Prompt::Idle;
+        prompt.init();
+        prompt.dispatch(&Key::Char(':'));
+        assert!(matches!(prompt, Prompt::Command));
+        prompt.dispatch(&Key::Char('w'));
+        assert!(matches!(prompt, Prompt::Command));
+        prompt.dispatch(&Key::Escape);
+        assert!(matches!(prompt, Prompt::Idle));
+    }
+}
+
+// ============================================================================
+// Test 37: Transition::Await parks a state until complete() resumes it
+// ============================================================================
+
+struct ProbeContext {
+    next_token: usize,
+    started: Vec<usize>,
+    fsm: Bookkeeping,
+}
+
+impl ProbeContext {
+    fn new() -> Self {
+        ProbeContext {
+            next_token: 0,
+            started: Vec::new(),
+            fsm: Bookkeeping::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ProbeEvent {
+    Start,
+    Measure,
+    Sample(u16),
+    Cancel,
+}
+
+state_machine! {
+    Name: Probe,
+    Context: ProbeContext,
+    Event: ProbeEvent,
+    Bookkeeping: fsm,
+
+    States: {
+        Idle => {
+            process: |_ctx, evt| {
+                match evt {
+                    ProbeEvent::Start => Transition::To(Probe::Measuring),
+                    // Must never be reached through a stale completion
+                    ProbeEvent::Sample(value) => Transition::To(Probe::Ready { value: *value }),
+                    _ => Transition::None,
+                }
+            }
+        },
+
+        Measuring => {
+            process: |ctx, evt| {
+                match evt {
+                    ProbeEvent::Measure => {
+                        // Start the conversion; its callback calls `complete()`
+                        ctx.next_token += 1;
+                        ctx.started.push(ctx.next_token);
+                        Transition::Await(ctx.next_token)
+                    }
+                    ProbeEvent::Sample(value) => Transition::To(Probe::Ready { value: *value }),
+                    ProbeEvent::Cancel => Transition::To(Probe::Idle),
+                    ProbeEvent::Start => Transition::None,
+                }
+            }
+        },
+
+        Ready { value: u16 } => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_await_parks_until_complete_resumes() {
+    let mut ctx = ProbeContext::new();
+    let mut probe = Probe::start_in(Probe::Idle, &mut ctx);
+    assert_eq!(probe.pending_token(&ctx), None);
+
+    probe.dispatch(&mut ctx, &ProbeEvent::Start);
+    assert_eq!(
+        probe.dispatch(&mut ctx, &ProbeEvent::Measure),
+        DispatchOutcome::Stayed
+    );
+    assert!(matches!(probe, Probe::Measuring));
+    assert_eq!(probe.pending_token(&ctx), Some(1));
+
+    // A completion for another token is dropped and keeps the state parked
+    assert_eq!(
+        probe.complete(&mut ctx, 7, &ProbeEvent::Sample(1)),
+        DispatchOutcome::Stale
+    );
+    assert!(matches!(probe, Probe::Measuring));
+    assert_eq!(probe.pending_token(&ctx), Some(1));
+
+    assert_eq!(
+        probe.complete(&mut ctx, 1, &ProbeEvent::Sample(512)),
+        DispatchOutcome::Transitioned
+    );
+    assert!(matches!(probe, Probe::Ready { value: 512 }));
+    assert_eq!(probe.pending_token(&ctx), None);
+
+    // Each completion resumes the state at most once
+    assert_eq!(
+        probe.complete(&mut ctx, 1, &ProbeEvent::Sample(512)),
+        DispatchOutcome::Stale
+    );
+    assert_eq!(ctx.started, vec![1]);
+
+    // Awaiting again replaces the pending token
+    let mut probe = Probe::start_in(Probe::Measuring, &mut ctx);
+    probe.dispatch(&mut ctx, &ProbeEvent::Measure);
+    probe.dispatch(&mut ctx, &ProbeEvent::Measure);
+    assert_eq!(probe.pending_token(&ctx), Some(3));
+    assert_eq!(
+        probe.complete(&mut ctx, 2, &ProbeEvent::Sample(3)),
+        DispatchOutcome::Stale
+    );
+
+    // Leaving the state discards the pending completion: it never reaches `Idle`
+    probe.dispatch(&mut ctx, &ProbeEvent::Cancel);
+    assert!(matches!(probe, Probe::Idle));
+    assert_eq!(probe.pending_token(&ctx), None);
+    assert_eq!(
+        probe.complete(&mut ctx, 3, &ProbeEvent::Sample(3)),
+        DispatchOutcome::Stale
+    );
+    assert!(matches!(probe, Probe::Idle));
+}
+
+#[test]
+fn test_await_tokens_are_per_instance() {
+    let mut ctx_a = ProbeContext::new();
+    let mut ctx_b = ProbeContext::new();
+    let mut a = Probe::start_in(Probe::Measuring, &mut ctx_a);
+    let mut b = Probe::start_in(Probe::Measuring, &mut ctx_b);
+
+    a.dispatch(&mut ctx_a, &ProbeEvent::Measure);
+    assert_eq!(a.pending_token(&ctx_a), Some(1));
+    assert_eq!(b.pending_token(&ctx_b), None);
+
+    // `a`'s completion can't resume `b`, and `b`'s own wait doesn't replace `a`'s
+    assert_eq!(
+        b.complete(&mut ctx_b, 1, &ProbeEvent::Sample(9)),
+        DispatchOutcome::Stale
+    );
+    assert!(matches!(b, Probe::Measuring));
+    b.dispatch(&mut ctx_b, &ProbeEvent::Measure);
+    assert_eq!(b.pending_token(&ctx_b), Some(1));
+
+    assert_eq!(
+        a.complete(&mut ctx_a, 1, &ProbeEvent::Sample(5)),
+        DispatchOutcome::Transitioned
+    );
+    assert!(matches!(a, Probe::Ready { value: 5 }));
+    assert_eq!(b.pending_token(&ctx_b), Some(1));
+}
+
+// Without `Bookkeeping`, nowhere holds the token
+state_machine! {
+    Name: Unbooked,
+    Event: ProbeEvent,
+
+    States: {
+        Waiting => {
+            process: |_evt| { Transition::Await(1) }
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(
+    expected = "[Unbooked] Transition::Await in state Waiting needs the `Bookkeeping` directive"
+)]
+fn test_await_without_bookkeeping_panics() {
+    let mut waiting = Unbooked::start_in(Unbooked::Waiting);
+    waiting.dispatch(&ProbeEvent::Measure);
+}
+
+struct SamplerContext {
+    fsm: Bookkeeping,
+}
+
+state_machine! {
+    Name: Sampler,
+    Context: SamplerContext,
+    Event: ProbeEvent,
+    Bookkeeping: fsm,
+    // A zero sample means the conversion failed
+    EventFilter: |_ctx, evt| -> bool { !matches!(evt, ProbeEvent::Sample(0)) },
+
+    States: {
+        Converting => {
+            process: |_ctx, evt| {
+                match evt {
+                    ProbeEvent::Measure => Transition::Await(1),
+                    ProbeEvent::Sample(value) => Transition::To(Sampler::Done { value: *value }),
+                    _ => Transition::None,
+                }
+            }
+        },
+
+        Done { value: u16 } => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_unprocessed_completion_keeps_its_token() {
+    let mut ctx = SamplerContext {
+        fsm: Bookkeeping::new(),
+    };
+    let mut sampler = Sampler::start_in(Sampler::Converting, &mut ctx);
+    sampler.dispatch(&mut ctx, &ProbeEvent::Measure);
+
+    // A completion arriving while frozen can be delivered again after `thaw()`
+    Sampler::freeze();
+    assert_eq!(
+        sampler.complete(&mut ctx, 1, &ProbeEvent::Sample(7)),
+        DispatchOutcome::Frozen
+    );
+    Sampler::thaw();
+    assert_eq!(sampler.pending_token(&ctx), Some(1));
+
+    // So can one rejected by the `EventFilter`
+    assert_eq!(
+        sampler.complete(&mut ctx, 1, &ProbeEvent::Sample(0)),
+        DispatchOutcome::Filtered
+    );
+    assert_eq!(sampler.pending_token(&ctx), Some(1));
+
+    assert_eq!(
+        sampler.complete(&mut ctx, 1, &ProbeEvent::Sample(7)),
+        DispatchOutcome::Transitioned
+    );
+    assert!(matches!(sampler, Sampler::Done { value: 7 }));
+}
+
+// ============================================================================
+// Test 38: dispatch_gen() refuses events from stale generations
+// ============================================================================
+
+struct UplinkContext {
+    sent: u32,
+    fsm: Bookkeeping,
+}
+
+impl UplinkContext {
+    fn new() -> Self {
+        Self {
+            sent: 0,
+            fsm: Bookkeeping::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum UplinkEvent {
+    Send,
+}
+
+state_machine! {
+    Name: Uplink,
+    Context: UplinkContext,
+    Event: UplinkEvent,
+    Bookkeeping: fsm,
+
+    States: {
+        Up => {
+            process: |ctx, evt| {
+                match evt {
+                    UplinkEvent::Send => {
+                        ctx.sent += 1;
+                        Transition::None
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_dispatch_gen_rejects_stale_generation() {
+    let mut ctx = UplinkContext::new();
+    let mut uplink = Uplink::start_in(Uplink::Up, &mut ctx);
+    let old_gen = uplink.generation(&ctx);
+
+    assert_eq!(
+        uplink.dispatch_gen(&mut ctx, &UplinkEvent::Send, old_gen),
+        Ok(DispatchOutcome::Stayed)
+    );
+    assert_eq!(ctx.sent, 1);
+
+    // Reconnection: recreating the uplink starts a new generation
+    uplink = Uplink::start_in(Uplink::Up, &mut ctx);
+    let new_gen = uplink.generation(&ctx);
+    assert_ne!(new_gen, old_gen);
+
+    assert_eq!(
+        uplink.dispatch_gen(&mut ctx, &UplinkEvent::Send, old_gen),
+        Err(StaleGen {
+            expected: old_gen,
+            current: new_gen,
+        })
+    );
+    assert_eq!(ctx.sent, 1);
+
+    assert_eq!(
+        uplink.dispatch_gen(&mut ctx, &UplinkEvent::Send, new_gen),
+        Ok(DispatchOutcome::Stayed)
+    );
+    assert_eq!(ctx.sent, 2);
+}
+
+#[test]
+fn test_generations_are_per_instance() {
+    let mut ctx_a = UplinkContext::new();
+    let mut a = Uplink::start_in(Uplink::Up, &mut ctx_a);
+    let gen_a = a.generation(&ctx_a);
+
+    // Starting another uplink leaves the first one's handles valid
+    let mut ctx_b = UplinkContext::new();
+    let mut b = Uplink::start_in(Uplink::Up, &mut ctx_b);
+    let gen_b = b.generation(&ctx_b);
+    assert_ne!(gen_b, gen_a);
+
+    assert_eq!(
+        a.dispatch_gen(&mut ctx_a, &UplinkEvent::Send, gen_a),
+        Ok(DispatchOutcome::Stayed)
+    );
+    assert_eq!(ctx_a.sent, 1);
+
+    // A handle to the first uplink is stale for the second one
+    assert_eq!(
+        b.dispatch_gen(&mut ctx_b, &UplinkEvent::Send, gen_a),
+        Err(StaleGen {
+            expected: gen_a,
+            current: gen_b,
+        })
+    );
+    assert_eq!(ctx_b.sent, 0);
+}
+
+#[test]
+fn test_generations_are_unique_across_threads() {
+    // Machines of one type started in parallel never share a generation
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            std::thread::spawn(|| {
+                let mut ctx = UplinkContext::new();
+                (0..1000)
+                    .map(|_| Uplink::start_in(Uplink::Up, &mut ctx).generation(&ctx))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    let mut issued = std::collections::HashSet::new();
+    for worker in workers {
+        for generation in worker.join().unwrap() {
+            assert!(
+                issued.insert(generation),
+                "generation {generation} issued twice"
+            );
+        }
+    }
+    assert_eq!(issued.len(), 4000);
+}
+
+// ============================================================================
+// Test 39: The state enum is declared identically with and without `concurrent`
+// ============================================================================
+
+// Runs in both builds: each time, the generated enum must match the same plain
+// declarations below
+const BUILD: &str = if cfg!(feature = "concurrent") {
+    "concurrent"
+} else {
+    "default"
+};
+
+struct GaugeContext {}
+
+#[derive(Debug, Clone)]
+enum GaugeEvent {
+    Tick,
+}
+
+state_machine! {
+    Name: Gauge,
+    Context: GaugeContext,
+    Event: GaugeEvent,
+
+    States: {
+        Off => {
+            process: |_ctx, _evt| { Transition::To(Gauge::Reading { value: 0, scale: -1 }) }
+        },
+
+        Reading { value: u16, scale: i8 } => {
+            process: |_ctx, _evt| { Transition::To(Gauge::Fault { code: 7 }) }
+        },
+
+        Fault { code: u32 } => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[allow(dead_code)]
+enum GaugeMirror {
+    Off,
+    Reading { value: u16, scale: i8 },
+    Fault { code: u32 },
+}
+
+state_machine! {
+    Name: Speed,
+    Context: GaugeContext,
+    Event: GaugeEvent,
+    Repr: u8,
+
+    States: {
+        Slow => {
+            process: |_ctx, _evt| { Transition::To(Speed::Medium) }
+        },
+
+        Medium => {
+            process: |_ctx, _evt| { Transition::To(Speed::Fast) }
+        },
+
+        Fast => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[repr(u8)]
+enum SpeedMirror {
+    Slow,
+    Medium,
+    Fast,
+}
+
+#[test]
+fn test_state_enum_layout_matches_plain_declaration() {
+    use core::mem::{align_of, size_of};
+
+    assert_eq!(
+        size_of::<Gauge>(),
+        size_of::<GaugeMirror>(),
+        "{BUILD} build"
+    );
+    assert_eq!(
+        align_of::<Gauge>(),
+        align_of::<GaugeMirror>(),
+        "{BUILD} build"
+    );
+    // The niche left for `Option` is the same too
+    assert_eq!(
+        size_of::<Option<Gauge>>(),
+        size_of::<Option<GaugeMirror>>(),
+        "{BUILD} build"
+    );
+
+    assert_eq!(
+        size_of::<Speed>(),
+        size_of::<SpeedMirror>(),
+        "{BUILD} build"
+    );
+    assert_eq!(Speed::Slow as u8, SpeedMirror::Slow as u8);
+    assert_eq!(Speed::Medium as u8, SpeedMirror::Medium as u8);
+    assert_eq!(Speed::Fast as u8, SpeedMirror::Fast as u8);
+    assert_eq!(Speed::Fast.state_byte(), SpeedMirror::Fast as u8);
+
+    // Values built in either build carry the same fields
+    let mut ctx = GaugeContext {};
+    let mut gauge = Gauge::start_in(Gauge::Off, &mut ctx);
+    gauge.dispatch(&mut ctx, &GaugeEvent::Tick);
+    assert!(matches!(
+        gauge,
+        Gauge::Reading {
+            value: 0,
+            scale: -1
+        }
+    ));
+}
@@ -0,0 +1,809 @@
+//! Tests for introspection helpers
+//!
+//! This test suite validates the generated helpers that inspect an FSM value
+//! without dispatching events:
+//! - `current_state_name()` returns the declared state name
+//! - `same_variant()` compares variants while ignoring field values
+//! - `TRANSITIONS`, `successors()`, `can_handle()` and `available_transitions()` expose the
+//!   declarative `TransitionTable`
+//! - `can_reach()` follows the `TransitionTable` transitively
+//! - `to_mermaid()` renders the table as a Mermaid `stateDiagram-v2`
+//! - `STATE_NAMES` lists the states, and `validate()` checks table names against it
+//! - `transition_count()` counts how often each `TransitionTable` edge fired
+//! - `state_index()` and `from_index()` map states to and from their declaration index
+//! - `state_byte()` and `from_byte()` do the same with a `u8`
+//! - `name_of_index()` and `index_of_name()` map declaration indices to and from names
+//! - `all_fieldless_states()` iterates over the states without fields
+//! - `STATE_COUNT` and `cycle_next()` step through fieldless states in declaration order
+//! - `FromStr` / `TryFrom<&str>` build fieldless states from their names
+//! - `EventNames` generates `event_name()` for unit, tuple and struct event variants
+//! - `state_cost()` reports each state's declared `cost: N` (0 when undeclared)
+//! - `assert_in_state!` names the actual state when the assertion fails
+
+use typed_fsm::{assert_in_state, state_machine, ParseStateError, Transition};
+
+// ============================================================================
+// Test FSM Definition
+// ============================================================================
+
+struct MotorContext {}
+
+#[derive(Debug, Clone)]
+enum MotorEvent {
+    Start(u32),
+    Stop,
+}
+
+state_machine! {
+    Name: Motor,
+    Context: MotorContext,
+    Event: MotorEvent,
+
+    States: {
+        Idle => {
+            process: |_ctx, evt| {
+                match evt {
+                    MotorEvent::Start(speed) => Transition::To(Motor::Running { speed: *speed }),
+                    MotorEvent::Stop => Transition::None
+                }
+            }
+        },
+
+        Running { speed: u32 } => {
+            process: |_ctx, evt| {
+                match evt {
+                    MotorEvent::Stop => Transition::To(Motor::Idle),
+                    MotorEvent::Start(_) => Transition::None
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// same_variant()
+// ============================================================================
+
+#[test]
+fn test_same_variant_ignores_fields() {
+    let a = Motor::Running { speed: 1 };
+    let b = Motor::Running { speed: 2 };
+
+    assert!(a.same_variant(&b));
+    assert!(b.same_variant(&a));
+}
+
+#[test]
+fn test_same_variant_different_states() {
+    let running = Motor::Running { speed: 1 };
+    let idle = Motor::Idle;
+
+    assert!(!running.same_variant(&idle));
+    assert!(!idle.same_variant(&running));
+    assert!(idle.same_variant(&Motor::Idle));
+}
+
+#[test]
+fn test_same_variant_after_dispatch() {
+    let mut ctx = MotorContext {};
+    let mut fsm = Motor::Idle;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &MotorEvent::Start(1500));
+    assert!(fsm.same_variant(&Motor::Running { speed: 0 }));
+    assert_eq!(fsm.current_state_name(), "Running");
+
+    fsm.dispatch(&mut ctx, &MotorEvent::Stop);
+    assert!(fsm.same_variant(&Motor::Idle));
+    assert_eq!(fsm.current_state_name(), "Idle");
+}
+
+// ============================================================================
+// TransitionTable / successors()
+// ============================================================================
+
+struct DoorContext {}
+
+#[derive(Debug, Clone)]
+enum DoorEvent {
+    Open,
+    Close,
+    Lock,
+    Unlock,
+    Alarm,
+}
+
+state_machine! {
+    Name: Door,
+    Context: DoorContext,
+    Event: DoorEvent,
+    TransitionTable: [
+        Closed + Open => Opened,
+        Closed + Lock => Locked,
+        Opened + Close => Closed,
+        Locked + Unlock => Closed,
+        Locked + Alarm => Broken,
+        Closed + Alarm => Broken,
+        Opened + Alarm => Broken,
+        Locked + Open => Broken,
+    ],
+
+    States: {
+        Closed => {
+            process: |_ctx, evt| {
+                match evt {
+                    DoorEvent::Open => Transition::To(Door::Opened),
+                    DoorEvent::Lock => Transition::To(Door::Locked),
+                    DoorEvent::Alarm => Transition::To(Door::Broken),
+                    _ => Transition::None
+                }
+            }
+        },
+
+        Opened => {
+            process: |_ctx, evt| {
+                match evt {
+                    DoorEvent::Close => Transition::To(Door::Closed),
+                    DoorEvent::Alarm => Transition::To(Door::Broken),
+                    _ => Transition::None
+                }
+            }
+        },
+
+        Locked => {
+            process: |_ctx, evt| {
+                match evt {
+                    DoorEvent::Unlock => Transition::To(Door::Closed),
+                    DoorEvent::Alarm | DoorEvent::Open => Transition::To(Door::Broken),
+                    _ => Transition::None
+                }
+            }
+        },
+
+        Broken => {
+            process: |_ctx, _evt| {
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_transitions_table_in_declaration_order() {
+    assert_eq!(Door::TRANSITIONS.len(), 8);
+    assert_eq!(Door::TRANSITIONS[0], ("Closed", "Open", "Opened"));
+    assert_eq!(Door::TRANSITIONS[7], ("Locked", "Open", "Broken"));
+}
+
+#[test]
+fn test_successors_lists_declared_targets() {
+    let closed: Vec<_> = Door::Closed.successors().collect();
+    assert_eq!(closed, vec!["Opened", "Locked", "Broken"]);
+
+    let opened: Vec<_> = Door::Opened.successors().collect();
+    assert_eq!(opened, vec!["Closed", "Broken"]);
+
+    // Two events lead Locked to Broken: listed once
+    let locked: Vec<_> = Door::Locked.successors().collect();
+    assert_eq!(locked, vec!["Closed", "Broken"]);
+
+    assert_eq!(Door::Broken.successors().count(), 0);
+}
+
+#[test]
+fn test_successors_follow_current_state() {
+    let mut ctx = DoorContext {};
+    let mut fsm = Door::Closed;
+    fsm.init(&mut ctx);
+
+    fsm.dispatch(&mut ctx, &DoorEvent::Open);
+    assert_eq!(
+        fsm.successors().collect::<Vec<_>>(),
+        vec!["Closed", "Broken"]
+    );
+
+    fsm.dispatch(&mut ctx, &DoorEvent::Close);
+    fsm.dispatch(&mut ctx, &DoorEvent::Lock);
+    fsm.dispatch(&mut ctx, &DoorEvent::Unlock);
+    assert!(matches!(fsm, Door::Closed));
+
+    fsm.dispatch(&mut ctx, &DoorEvent::Alarm);
+    assert!(matches!(fsm, Door::Broken));
+    assert_eq!(fsm.successors().count(), 0);
+}
+
+#[test]
+fn test_no_transition_table_means_no_successors() {
+    assert!(Motor::TRANSITIONS.is_empty());
+    assert_eq!(Motor::Idle.successors().count(), 0);
+}
+
+#[test]
+fn test_can_handle_follows_declared_events() {
+    assert!(Door::Closed.can_handle("Open"));
+    assert!(Door::Closed.can_handle("Lock"));
+    assert!(Door::Closed.can_handle("Alarm"));
+    assert!(!Door::Closed.can_handle("Close"));
+    assert!(!Door::Closed.can_handle("Unlock"));
+
+    assert!(Door::Locked.can_handle("Open"));
+    assert!(!Door::Locked.can_handle("Lock"));
+
+    // No outgoing edges, unknown events and machines without a table
+    assert!(!Door::Broken.can_handle("Open"));
+    assert!(!Door::Closed.can_handle("Knock"));
+    assert!(!Motor::Idle.can_handle("Start"));
+}
+
+#[test]
+fn test_available_transitions_lists_event_target_pairs() {
+    assert_eq!(
+        Door::Closed.available_transitions(),
+        &[("Open", "Opened"), ("Lock", "Locked"), ("Alarm", "Broken")]
+    );
+    assert_eq!(
+        Door::Opened.available_transitions(),
+        &[("Close", "Closed"), ("Alarm", "Broken")]
+    );
+    // Both events leading to Broken are listed, unlike successors()
+    assert_eq!(
+        Door::Locked.available_transitions(),
+        &[
+            ("Unlock", "Closed"),
+            ("Alarm", "Broken"),
+            ("Open", "Broken")
+        ]
+    );
+    assert!(Door::Broken.available_transitions().is_empty());
+    assert!(Motor::Running { speed: 1 }
+        .available_transitions()
+        .is_empty());
+
+    // Help text for the current state
+    let help: Vec<String> = Door::Opened
+        .available_transitions()
+        .iter()
+        .map(|(event, to)| format!("{event}: go to {to}"))
+        .collect();
+    assert_eq!(help, vec!["Close: go to Closed", "Alarm: go to Broken"]);
+
+    // Consistent with can_handle()
+    for state in Door::all_fieldless_states() {
+        for (event, _) in state.available_transitions() {
+            assert!(state.can_handle(event));
+        }
+    }
+}
+
+// ============================================================================
+// can_reach()
+// ============================================================================
+
+struct ArticleContext {}
+
+#[derive(Debug, Clone)]
+enum ArticleEvent {
+    Submit,
+    Approve,
+}
+
+state_machine! {
+    Name: Article,
+    Context: ArticleContext,
+    Event: ArticleEvent,
+    TransitionTable: [
+        Draft + Submit => Review,
+        Review + Approve => Published,
+    ],
+
+    States: {
+        Draft => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, ArticleEvent::Submit), Article::Review)
+            }
+        },
+
+        Review => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, ArticleEvent::Approve), Article::Published)
+            }
+        },
+
+        Published => {
+            process: |_ctx, _evt| { Transition::None }
+        },
+
+        // Declared but not wired into the table
+        Archived => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+#[test]
+fn test_can_reach_follows_chain_transitively() {
+    assert!(Article::can_reach("Draft", "Review"));
+    assert!(Article::can_reach("Draft", "Published"));
+    assert!(Article::can_reach("Draft", "Draft"));
+
+    // Every state visited by a run is reachable from where it started
+    let mut ctx = ArticleContext {};
+    let mut article = Article::start_in(Article::Draft, &mut ctx);
+    for event in [ArticleEvent::Submit, ArticleEvent::Approve] {
+        article.dispatch(&mut ctx, &event);
+        assert!(Article::can_reach("Draft", article.current_state_name()));
+    }
+    assert!(matches!(article, Article::Published));
+
+    // Edges are directed
+    assert!(!Article::can_reach("Published", "Draft"));
+    assert!(!Article::can_reach("Review", "Draft"));
+
+    // Door cycles between Closed and Opened, and everything ends up Broken
+    assert!(Door::can_reach("Opened", "Locked"));
+    assert!(Door::can_reach("Opened", "Broken"));
+    assert!(!Door::can_reach("Broken", "Closed"));
+}
+
+#[test]
+fn test_can_reach_rejects_disconnected_and_unknown_states() {
+    assert!(!Article::can_reach("Draft", "Archived"));
+    assert!(!Article::can_reach("Archived", "Published"));
+    assert!(Article::can_reach("Archived", "Archived"));
+
+    assert!(!Article::can_reach("Draft", "Deleted"));
+    assert!(!Article::can_reach("Deleted", "Deleted"));
+    // No TransitionTable: only the trivial path
+    assert!(!Motor::can_reach("Idle", "Running"));
+}
+
+// ============================================================================
+// to_mermaid()
+// ============================================================================
+
+struct PumpContext {}
+
+#[derive(Debug, Clone)]
+enum PumpEvent {
+    Start,
+    Stop,
+}
+
+state_machine! {
+    Name: Pump,
+    Context: PumpContext,
+    Event: PumpEvent,
+    TransitionTable: [
+        Off + Start => On,
+        On + Stop => Off,
+    ],
+    Initial: [Off],
+
+    States: {
+        Off => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, PumpEvent::Start), Pump::On)
+            }
+        },
+
+        On => {
+            process: |_ctx, evt| {
+                Transition::to_if(matches!(evt, PumpEvent::Stop), Pump::Off)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_to_mermaid_small_machine() {
+    assert_eq!(
+        Pump::to_mermaid(),
+        "stateDiagram-v2\n\
+         \x20   [*] --> Off\n\
+         \x20   Off --> On : Start\n\
+         \x20   On --> Off : Stop\n"
+    );
+
+    // Same source data as TRANSITIONS / INITIAL_STATES
+    for (from, event, to) in Pump::TRANSITIONS {
+        let line = format!("    {} --> {} : {}\n", from, to, event);
+        assert!(Pump::to_mermaid().contains(&line));
+    }
+    for initial in Pump::INITIAL_STATES {
+        assert!(Pump::to_mermaid().contains(&format!("[*] --> {}\n", initial)));
+    }
+}
+
+#[test]
+fn test_to_mermaid_without_table() {
+    assert_eq!(Motor::to_mermaid(), "stateDiagram-v2\n");
+}
+
+#[test]
+fn test_mermaid_matches_dispatch() {
+    let mut ctx = PumpContext {};
+    let mut fsm = Pump::start_default(0, &mut ctx);
+    fsm.dispatch(&mut ctx, &PumpEvent::Start);
+    assert!(matches!(fsm, Pump::On));
+    fsm.dispatch(&mut ctx, &PumpEvent::Stop);
+    assert!(matches!(fsm, Pump::Off));
+}
+
+// ============================================================================
+// STATE_NAMES / validate()
+// ============================================================================
+
+#[test]
+fn test_state_names_in_declaration_order() {
+    assert_eq!(Door::STATE_NAMES, ["Closed", "Opened", "Locked", "Broken"]);
+    assert_eq!(Motor::STATE_NAMES, ["Idle", "Running"]);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn test_validate_accepts_declared_table() {
+    Door::validate();
+    Pump::validate();
+    // No table: nothing to check
+    Motor::validate();
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "[Door] TransitionTable names unknown states: Opne, Brokn")]
+fn test_validate_transition_table_lists_unknown_names() {
+    Door::validate_transition_table(&[
+        ("Closed", "Open", "Opne"),
+        ("Opne", "Close", "Closed"),
+        ("Locked", "Alarm", "Brokn"),
+    ]);
+}
+
+// ============================================================================
+// EventNames / event_name()
+// ============================================================================
+
+struct PanelContext {
+    input_sum: u32,
+}
+
+#[derive(Debug, Clone)]
+enum PanelEvent {
+    Tick,
+    Button(u8),
+    Touch { x: u16, y: u16 },
+}
+
+state_machine! {
+    Name: Panel,
+    Context: PanelContext,
+    Event: PanelEvent,
+    EventNames: [Tick, Button, Touch],
+
+    States: {
+        Showing => {
+            process: |ctx, evt| {
+                match evt {
+                    PanelEvent::Tick => {}
+                    PanelEvent::Button(n) => ctx.input_sum += u32::from(*n),
+                    PanelEvent::Touch { x, y } => ctx.input_sum += u32::from(*x + *y),
+                }
+                Transition::None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_event_name_maps_every_variant() {
+    assert_eq!(Panel::event_name(&PanelEvent::Tick), "Tick");
+    assert_eq!(Panel::event_name(&PanelEvent::Button(3)), "Button");
+    assert_eq!(
+        Panel::event_name(&PanelEvent::Touch { x: 1, y: 2 }),
+        "Touch"
+    );
+
+    let mut ctx = PanelContext { input_sum: 0 };
+    let mut fsm = Panel::start_in(Panel::Showing, &mut ctx);
+    for event in [
+        PanelEvent::Tick,
+        PanelEvent::Button(1),
+        PanelEvent::Touch { x: 2, y: 3 },
+    ] {
+        fsm.dispatch(&mut ctx, &event);
+    }
+    assert_eq!(ctx.input_sum, 6);
+}
+
+// ============================================================================
+// state_index() / from_index() / state_byte() / from_byte()
+// ============================================================================
+
+#[test]
+fn test_from_index_round_trips_fieldless_states() {
+    for (index, name) in Door::STATE_NAMES.iter().enumerate() {
+        let state = Door::from_index(index).unwrap();
+        assert_eq!(state.state_index(), index);
+        assert_eq!(state.current_state_name(), *name);
+    }
+    assert!(Door::from_index(Door::STATE_NAMES.len()).is_none());
+
+    // As a byte over the wire
+    let byte = Door::Locked.state_index() as u8;
+    assert!(matches!(Door::from_index(byte.into()), Some(Door::Locked)));
+}
+
+#[test]
+fn test_state_byte_round_trips_fieldless_states() {
+    for (index, name) in Door::STATE_NAMES.iter().enumerate() {
+        let state = Door::from_byte(index as u8).unwrap();
+        assert_eq!(state.state_byte(), index as u8);
+        assert_eq!(state.current_state_name(), *name);
+    }
+    // The byte is the declaration index, so it is stable across builds
+    assert_eq!(
+        Door::Locked.state_byte() as usize,
+        Door::Locked.state_index()
+    );
+    assert!(Door::from_byte(u8::MAX).is_none());
+
+    assert_eq!(Motor::Running { speed: 10 }.state_byte(), 1);
+    assert!(matches!(Motor::from_byte(0), Some(Motor::Idle)));
+    assert!(Motor::from_byte(1).is_none());
+}
+
+#[test]
+fn test_name_index_and_byte_round_trip() {
+    for name in Door::STATE_NAMES {
+        let index = Door::index_of_name(name).unwrap();
+        let state = Door::from_index(index).unwrap();
+        assert_eq!(state.current_state_name(), *name);
+
+        // name -> index -> byte -> index -> name
+        let byte = state.state_byte();
+        assert_eq!(byte as usize, index);
+        assert_eq!(Door::name_of_index(byte as usize), Some(*name));
+        assert_eq!(Door::from_byte(byte).unwrap().state_index(), index);
+    }
+    assert_eq!(Door::name_of_index(Door::STATE_COUNT), None);
+    assert_eq!(Door::index_of_name("Ajar"), None);
+
+    // Names work for states with fields too, unlike `from_index()`
+    let running = Motor::Running { speed: 10 };
+    assert_eq!(Motor::index_of_name("Running"), Some(running.state_index()));
+    assert_eq!(
+        Motor::name_of_index(running.state_index()),
+        Some(running.current_state_name())
+    );
+}
+
+#[test]
+fn test_from_index_rejects_states_with_fields() {
+    assert_eq!(Motor::Idle.state_index(), 0);
+    assert_eq!(Motor::Running { speed: 10 }.state_index(), 1);
+
+    assert!(matches!(Motor::from_index(0), Some(Motor::Idle)));
+    // The speed of `Running` is unknown
+    assert!(Motor::from_index(1).is_none());
+    assert!(Motor::from_index(2).is_none());
+}
+
+#[test]
+fn test_all_fieldless_states_skips_states_with_fields() {
+    let names: Vec<_> = Door::all_fieldless_states()
+        .map(|state| state.current_state_name())
+        .collect();
+    assert_eq!(names, Door::STATE_NAMES);
+
+    // `Running` carries a speed, so only `Idle` is yielded
+    let motors: Vec<_> = Motor::all_fieldless_states().collect();
+    assert_eq!(motors.len(), 1);
+    assert!(matches!(motors[0], Motor::Idle));
+}
+
+#[test]
+fn test_cycle_next_visits_every_state_then_wraps() {
+    assert_eq!(Door::STATE_COUNT, Door::STATE_NAMES.len());
+    assert_eq!(Motor::STATE_COUNT, 2);
+
+    let mut door = Door::from_index(0).unwrap();
+    let mut visited = Vec::new();
+    for _ in 0..Door::STATE_COUNT {
+        visited.push(door.current_state_name());
+        door = door.cycle_next();
+    }
+    assert_eq!(visited, Door::STATE_NAMES);
+
+    // Back to the first state
+    assert_eq!(door.state_index(), 0);
+}
+
+#[test]
+fn test_parse_state_names() {
+    for name in Door::STATE_NAMES {
+        let state: Door = name.parse().unwrap();
+        assert_eq!(state.current_state_name(), *name);
+    }
+    assert!(matches!(Door::try_from("Locked"), Ok(Door::Locked)));
+
+    // Unknown names, case-sensitive
+    assert_eq!(
+        "Ajar".parse::<Door>().unwrap_err(),
+        ParseStateError::Unknown
+    );
+    assert_eq!(
+        "locked".parse::<Door>().unwrap_err(),
+        ParseStateError::Unknown
+    );
+    assert_eq!("".parse::<Door>().unwrap_err(), ParseStateError::Unknown);
+
+    // A state with fields has no value without them
+    assert!(matches!("Idle".parse::<Motor>(), Ok(Motor::Idle)));
+    assert_eq!(
+        "Running".parse::<Motor>().unwrap_err(),
+        ParseStateError::HasFields
+    );
+}
+
+// ============================================================================
+// transition_count() / transition_counts()
+// ============================================================================
+
+struct LiftContext {}
+
+#[derive(Debug, Clone)]
+enum LiftEvent {
+    Up,
+    Down,
+    Fault,
+}
+
+state_machine! {
+    Name: Lift,
+    Context: LiftContext,
+    Event: LiftEvent,
+    TransitionTable: [
+        Ground + Up => Upper,
+        Upper + Down => Ground,
+        Upper + Fault => Halted,
+        Ground + Fault => Halted,
+    ],
+    CountTransitions: true,
+
+    States: {
+        Ground => {
+            process: |_ctx, evt| {
+                match evt {
+                    LiftEvent::Up => Transition::To(Lift::Upper),
+                    LiftEvent::Down => Transition::None,
+                    LiftEvent::Fault => Transition::To(Lift::Halted),
+                }
+            }
+        },
+
+        Upper => {
+            process: |_ctx, evt| {
+                match evt {
+                    LiftEvent::Down => Transition::To(Lift::Ground),
+                    LiftEvent::Up => Transition::Reenter,
+                    LiftEvent::Fault => Transition::To(Lift::Halted),
+                }
+            }
+        },
+
+        Halted => {
+            process: |_ctx, evt| {
+                // Not in the table: fires, but is not counted
+                Transition::to_if(matches!(evt, LiftEvent::Up), Lift::Ground)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_transition_count_per_edge() {
+    assert_eq!(Lift::TRANSITION_COUNT, 4);
+    assert_eq!(Lift::transition_counts(), [0; 4]);
+
+    let mut ctx = LiftContext {};
+    let mut fsm = Lift::start_in(Lift::Ground, &mut ctx);
+    for event in [
+        LiftEvent::Up,
+        LiftEvent::Down,
+        LiftEvent::Up,
+        LiftEvent::Up, // Reenter: not an edge
+        LiftEvent::Down,
+        LiftEvent::Down, // None: not an edge
+        LiftEvent::Up,
+        LiftEvent::Fault,
+        LiftEvent::Up, // Halted -> Ground is undeclared
+    ] {
+        fsm.dispatch(&mut ctx, &event);
+    }
+
+    assert_eq!(Lift::transition_count(0), 3);
+    assert_eq!(Lift::transition_count(1), 2);
+    assert_eq!(Lift::transition_count(2), 1);
+    assert_eq!(Lift::transition_count(3), 0);
+    assert_eq!(Lift::transition_count(4), 0);
+    assert_eq!(Lift::transition_counts(), [3, 2, 1, 0]);
+
+    // No table: no edges
+    assert_eq!(Motor::TRANSITION_COUNT, 0);
+}
+
+// ============================================================================
+// Per-state cost / state_cost()
+// ============================================================================
+
+state_machine! {
+    Name: Codec,
+    Event: u8,
+
+    States: {
+        Idle => {
+            process: |_byte| { Transition::To(Codec::Decoding { left: 4 }) }
+        },
+
+        Decoding { left: u8 } => {
+            cost: 120,
+            entry: || {}
+            process: |_byte| { Transition::None }
+        },
+
+        Flushing => {
+            cost: 2 * 40,
+            process: |_byte| { Transition::To(Codec::Idle) }
+        }
+    }
+}
+
+#[test]
+fn test_state_cost_reports_declared_cost() {
+    assert_eq!(Codec::Idle.state_cost(), 0);
+    assert_eq!(Codec::Decoding { left: 1 }.state_cost(), 120);
+    assert_eq!(Codec::Flushing.state_cost(), 80);
+
+    let mut codec = Codec::start_in(Codec::Idle);
+    codec.dispatch(&0x7f);
+    assert_eq!(codec.state_cost(), 120);
+}
+
+// ============================================================================
+// assert_in_state!
+// ============================================================================
+
+#[test]
+fn test_assert_in_state_passes_on_match() {
+    let mut ctx = MotorContext {};
+    let mut motor = Motor::start_in(Motor::Idle, &mut ctx);
+    assert_in_state!(motor, Idle);
+
+    // Fields are ignored
+    motor.dispatch(&mut ctx, &MotorEvent::Start(40));
+    assert_in_state!(motor, Running);
+    assert_in_state!(motor, Running, "speed {}", 40);
+}
+
+#[test]
+#[should_panic(
+    expected = "assertion failed: `motor` is in state `Idle`\n  actual state: `Running`"
+)]
+fn test_assert_in_state_names_actual_state() {
+    let motor = Motor::Running { speed: 40 };
+    assert_in_state!(motor, Idle);
+}
+
+#[test]
+fn test_assert_in_state_appends_custom_message() {
+    let result = std::panic::catch_unwind(|| {
+        assert_in_state!(Motor::Idle, Running, "after {} starts", 2);
+    });
+    let payload = result.unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert_eq!(
+        message,
+        "assertion failed: `Motor::Idle` is in state `Running`\n  \
+         actual state: `Idle`: after 2 starts"
+    );
+}
@@ -5,6 +5,9 @@
 //! - Logging compiles with 'logging' feature
 //! - All state transitions are logged
 //! - No runtime errors with logging enabled
+//! - `LogLevels` picks the level of each hook (checked with a capturing logger)
+//! - With `concurrent`, log lines are formatted outside critical sections: a logger may
+//!   enter one itself, and events dispatched from several threads are all processed
 
 use typed_fsm::{state_machine, Transition};
 
@@ -347,3 +350,208 @@ fn test_logging_multiple_self_transitions() {
 
     assert_eq!(ctx.resets, 4); // init + 3 resets
 }
+
+// ============================================================================
+// Test 4: LogLevels directive
+// ============================================================================
+
+struct LeveledContext;
+
+#[derive(Debug, Clone)]
+enum LeveledEvent {
+    Go,
+    Poke,
+}
+
+state_machine! {
+    Name: Leveled,
+    Context: LeveledContext,
+    Event: LeveledEvent,
+    LogLevels: { entry: debug, transition: warn, process: trace },
+
+    States: {
+        Idle => {
+            process: |_ctx, evt| {
+                match evt {
+                    LeveledEvent::Go => Transition::To(Leveled::Busy),
+                    LeveledEvent::Poke => Transition::None,
+                }
+            }
+        },
+
+        Busy => {
+            process: |_ctx, _evt| { Transition::None }
+        }
+    }
+}
+
+// With `logging`, `log_levels` below owns the `[Leveled]` log lines
+#[cfg(not(feature = "logging"))]
+#[test]
+fn test_log_levels_without_logging_feature() {
+    // The directive is accepted (and ignored) without a logging backend
+    let mut ctx = LeveledContext;
+    let mut machine = Leveled::Idle;
+    machine.init(&mut ctx);
+    machine.dispatch(&mut ctx, &LeveledEvent::Poke);
+    machine.dispatch(&mut ctx, &LeveledEvent::Go);
+    assert!(matches!(machine, Leveled::Busy));
+}
+
+#[cfg(feature = "logging")]
+mod log_levels {
+    use super::*;
+    use log::{Level, Log, Metadata, Record};
+    use std::sync::Mutex;
+
+    /// Records `(level, message)` for every `[Leveled]` and `[Relay]` log line.
+    struct CaptureLogger;
+
+    static CAPTURED: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let message = record.args().to_string();
+            if message.starts_with("[Relay]") {
+                // Like an RTT or UART logger guarding its buffer: would deadlock on a
+                // non-reentrant critical section if the line were logged inside one
+                #[cfg(feature = "concurrent")]
+                {
+                    let _ = relay::Relay::overflow_report();
+                }
+                CAPTURED.lock().unwrap().push((record.level(), message));
+            } else if message.starts_with("[Leveled]") {
+                CAPTURED.lock().unwrap().push((record.level(), message));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger;
+
+    /// Installs `LOGGER` once for every test of the binary.
+    fn install_logger() {
+        static INSTALL: std::sync::Once = std::sync::Once::new();
+        INSTALL.call_once(|| {
+            log::set_logger(&LOGGER).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    #[test]
+    fn test_log_levels_per_hook() {
+        install_logger();
+
+        let mut ctx = LeveledContext;
+        let mut machine = Leveled::Idle;
+        machine.init(&mut ctx);
+        machine.dispatch(&mut ctx, &LeveledEvent::Poke);
+        machine.dispatch(&mut ctx, &LeveledEvent::Go);
+
+        let captured: Vec<(Level, String)> = CAPTURED
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, message)| message.starts_with("[Leveled]"))
+            .cloned()
+            .collect();
+        let levels: Vec<Level> = captured.iter().map(|(level, _)| *level).collect();
+        assert_eq!(
+            levels,
+            [
+                Level::Debug, // init()
+                Level::Debug, // Idle.entry()
+                Level::Trace, // Poke: stayed
+                Level::Warn,  // Idle + Go -> Busy
+                Level::Info,  // Idle.exit(): not listed
+                Level::Debug, // Busy.entry()
+            ]
+        );
+        assert!(captured[3].1.contains("Idle + Go -> Busy"));
+    }
+
+    // ========================================================================
+    // Test 5: Logging under `concurrent`
+    // ========================================================================
+
+    #[cfg(feature = "concurrent")]
+    mod relay {
+        use super::*;
+        use std::sync::Arc;
+        use std::thread;
+
+        struct RelayContext {
+            relayed: u32,
+        }
+
+        #[derive(Debug, Clone)]
+        enum RelayEvent {
+            Frame,
+        }
+
+        state_machine! {
+            Name: Relay,
+            Context: RelayContext,
+            Event: RelayEvent,
+
+            States: {
+                Listening => {
+                    process: |ctx, _evt| {
+                        ctx.relayed += 1;
+                        Transition::To(Relay::Forwarding)
+                    }
+                },
+
+                Forwarding => {
+                    process: |ctx, _evt| {
+                        ctx.relayed += 1;
+                        Transition::To(Relay::Listening)
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn test_logging_with_concurrent_dispatch_from_threads() {
+            install_logger();
+            let mut ctx = RelayContext { relayed: 0 };
+            let fsm = Relay::start_in(Relay::Listening, &mut ctx);
+            let fsm = Arc::new(Mutex::new(fsm));
+            let ctx = Arc::new(Mutex::new(ctx));
+
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    let fsm = Arc::clone(&fsm);
+                    let ctx = Arc::clone(&ctx);
+                    thread::spawn(move || {
+                        for _ in 0..25 {
+                            let mut fsm = fsm.lock().unwrap();
+                            let mut ctx = ctx.lock().unwrap();
+                            let _ = fsm.dispatch(&mut ctx, &RelayEvent::Frame);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+
+            // Every event was processed, and logged, without deadlocking
+            assert_eq!(ctx.lock().unwrap().relayed, 100);
+            assert!(fsm.lock().unwrap().same_variant(&Relay::Listening));
+            let transitions = CAPTURED
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, message)| message.starts_with("[Relay]") && message.contains(" -> "))
+                .count();
+            assert!(transitions >= 100);
+            assert_eq!(Relay::overflow_report().dropped, 0);
+        }
+    }
+}
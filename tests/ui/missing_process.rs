@@ -0,0 +1,28 @@
+use typed_fsm::{state_machine, Transition};
+
+struct Context {}
+
+#[derive(Debug, Clone)]
+enum Event {
+    Open,
+}
+
+state_machine! {
+    Name: Door,
+    Context: Context,
+    Event: Event,
+    States: {
+        Closed => {
+            process: |_ctx, evt| {
+                match evt {
+                    Event::Open => Transition::To(Door::Open),
+                }
+            }
+        },
+        Open => {
+            entry: |_ctx| { }
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,27 @@
+use typed_fsm::{state_machine, Transition};
+
+struct Context {}
+
+#[derive(Debug, Clone)]
+enum Event {
+    Poll,
+}
+
+state_machine! {
+    Name: Poller,
+    Context: Context,
+    Event: Event,
+    Watchdog: 3,
+    OnStuck: |_ctx, _state_name| {},
+    States: {
+        Waiting => {
+            process: |_ctx, evt| {
+                match evt {
+                    Event::Poll => Transition::None,
+                }
+            }
+        }
+    }
+}
+
+fn main() {}
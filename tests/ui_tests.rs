@@ -0,0 +1,18 @@
+//! Compile-fail tests for the error messages of `state_machine!`
+//!
+//! This test suite compiles the invalid machines in `tests/ui` and compares the
+//! compiler output with the `.stderr` file next to each of them:
+//! - A state without a `process` block is rejected with an error naming the state
+//! - A `Watchdog` without the `Bookkeeping` directive it counts in is rejected
+//!
+//! After an intended change to a message, regenerate the expected output with
+//! `TRYBUILD=overwrite cargo test --test ui_tests` and review the diff.
+
+// The concurrent build expands to code naming the user's `paste` and `portable_atomic`
+// dependencies, which the cases don't have: only the default build pins the messages
+#[cfg(not(feature = "concurrent"))]
+#[test]
+fn test_ui() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}